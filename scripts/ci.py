@@ -130,7 +130,7 @@ def build() -> None:
     )
 
 
-# TODO: remove unless crate is no_std
+# @if no_std
 # def build_nostd() -> None:
 #     """Build on no_std target."""
 #     print_header("Building on no_std target...")
@@ -138,6 +138,7 @@ def build() -> None:
 #         ["cargo", "+stable", "build", "--target", "thumbv6m-none-eabi"],
 #         env={"RUSTFLAGS": "-D warnings"}
 #     )
+# @endif
 
 
 def run_tests_stable() -> None:
@@ -160,28 +161,34 @@ def run_tests_msrv() -> None:
 
 def run_tests_leak_sanitizer() -> None:
     """Run tests with leak sanitizer."""
-    # TODO: remove loom workaround unless we're actually using loom
+    print_header("Running tests with leak sanitizer...")
+    # @if loom
     # NOTE: loom seems to make the leak sanitizer unhappy. I don't think that
     # combination of tests is important, so we just skip loom tests here.
-
-    print_header("Running tests with leak sanitizer...")
     run_command(
         ["cargo", "+nightly", "test", "--", "--skip", "loom"],
         env={"RUSTFLAGS": "-D warnings -Z sanitizer=leak"},
     )
-
-
-# TODO: remove if unsafe_code is forbidden
-def run_tests_miri() -> None:
-    """Run tests with MIRI."""
-    print_header("Running tests with MIRI...")
-    run_command(
-        ["cargo", "+nightly", "miri", "test"],
-        env={
-            "RUSTFLAGS": "-D warnings -C opt-level=0",
-            "MIRIFLAGS": "-Zmiri-strict-provenance",
-        },
-    )
+    # @else
+    # run_command(
+    #     ["cargo", "+nightly", "test"],
+    #     env={"RUSTFLAGS": "-D warnings -Z sanitizer=leak"},
+    # )
+    # @endif
+
+
+# @if unsafe
+# def run_tests_miri() -> None:
+#     """Run tests with MIRI."""
+#     print_header("Running tests with MIRI...")
+#     run_command(
+#         ["cargo", "+nightly", "miri", "test"],
+#         env={
+#             "RUSTFLAGS": "-D warnings -C opt-level=0",
+#             "MIRIFLAGS": "-Zmiri-strict-provenance",
+#         },
+#     )
+# @endif
 
 
 # All CI stages, in execution order
@@ -189,13 +196,17 @@ CI_STAGES: List[tuple[str, Callable[[], None]]] = [
     ("check_fmt", check_fmt),
     ("check_docs", check_docs),
     ("build", build),
+    # @if no_std
     # ("build_nostd", build_nostd),
+    # @endif
     ("lint", lint),
     ("run_tests_stable", run_tests_stable),
     ("run_tests_beta", run_tests_beta),
     ("run_tests_msrv", run_tests_msrv),
     ("run_tests_leak_sanitizer", run_tests_leak_sanitizer),
-    ("run_tests_miri", run_tests_miri),
+    # @if unsafe
+    # ("run_tests_miri", run_tests_miri),
+    # @endif
 ]
 
 
@@ -0,0 +1,456 @@
+//! The answers a crate is instantiated with.
+//!
+//! Every question instantiating asks (the template's variables, keywords,
+//! categories, MSRV, license, variants and conditions) can be answered in an
+//! answers file, with command-line flags or through the interactive wizard,
+//! and [`Answers::merge`] combines them. An answers file is TOML or JSON (by
+//! its extension), with one top-level key per question:
+//!
+//! ```toml
+//! name = "my-crate"
+//! owner = "me"
+//! keywords = ["parsing"]
+//! msrv = "1.85"
+//! license = "MIT"
+//! variants = ["no_std"]
+//!
+//! [conditions]
+//! unsafe = false
+//! ```
+//!
+//! Instantiating records the answers in an `[answers]` table of the crate's
+//! [`STAMP_PATH`], in the same format, so that upgrades can replay them (see
+//! [`recorded`]).
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    Error, Result,
+    changes::Files,
+    conditional::Condition,
+    fs,
+    instantiate::License,
+    json,
+    msrv::RustVersion,
+    placeholder::{Values, Variable},
+    toml::{self, Document},
+    upgrade::{self, STAMP_PATH},
+};
+
+/// The table of [`STAMP_PATH`] recording the answers.
+const TABLE: &str = "answers";
+
+/// The answers to the questions asked when instantiating the template.
+/// Questions without an answer are left to be dealt with by hand.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Answers {
+    /// The values of the template's variables.
+    pub values: Values,
+    /// The crates.io keywords.
+    pub keywords: Vec<String>,
+    /// The crates.io category slugs.
+    pub categories: Vec<String>,
+    /// The minimum supported Rust version.
+    pub msrv: Option<RustVersion>,
+    /// The license.
+    pub license: Option<License>,
+    /// The names of the variants to layer over the template.
+    pub variants: Vec<String>,
+    /// Whether each named condition holds, overriding the chosen variants.
+    pub conditions: BTreeMap<String, bool>,
+}
+
+/// The value of one answer, in a file.
+enum Field {
+    /// A string (or number).
+    Text(String),
+    /// An array of strings.
+    List(Vec<String>),
+    /// A table of booleans.
+    Flags(Vec<(String, bool)>),
+}
+
+impl Answers {
+    /// Reads the answers file at `path`, as JSON if its extension is `.json`
+    /// and as TOML otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or an answer is
+    /// invalid.
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read(path)?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::parse_json(path, &json::Value::parse(path, &contents)?)
+        } else {
+            Self::parse_toml(path, &Document::parse(path, &contents)?, "")
+        }
+    }
+
+    /// Reads the answers in `table` (`""` for the top-level table) of the TOML
+    /// `document`, and its `conditions` subtable. `path` is only used for
+    /// error messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key is unknown or an answer is invalid.
+    pub fn parse_toml(path: &Path, document: &Document, table: &str) -> Result<Self> {
+        let field = |key: &str, value: &toml::Value| -> Result<Field> {
+            let invalid = || {
+                Error::malformed(
+                    path,
+                    format!("`{key}` is not a string, an array of strings or a table of booleans"),
+                )
+            };
+            Ok(match value {
+                toml::Value::String(text) => Field::Text(text.clone()),
+                toml::Value::Integer(number) => Field::Text(number.to_string()),
+                toml::Value::Array(items) => Field::List(
+                    items
+                        .iter()
+                        .map(|item| item.as_str().map(str::to_owned))
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?,
+                ),
+                toml::Value::Table(entries) => Field::Flags(
+                    entries
+                        .iter()
+                        .map(|(name, value)| match value {
+                            toml::Value::Boolean(holds) => Some((name.clone(), *holds)),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?,
+                ),
+                _ => return Err(invalid()),
+            })
+        };
+
+        let mut fields = Vec::new();
+        for entry in document.entries(table) {
+            fields.push((entry.key.to_owned(), field(entry.key, &entry.value)?));
+        }
+        let conditions = if table.is_empty() {
+            "conditions".to_owned()
+        } else {
+            format!("{table}.conditions")
+        };
+        if document.tables().any(|name| name == conditions) {
+            let entries = document
+                .entries(&conditions)
+                .into_iter()
+                .map(|entry| (entry.key.to_owned(), entry.value))
+                .collect();
+            fields.push((
+                "conditions".to_owned(),
+                field("conditions", &toml::Value::Table(entries))?,
+            ));
+        }
+        Self::from_fields(path, fields)
+    }
+
+    /// Reads the answers in the JSON object `value`. `path` is only used for
+    /// error messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` isn't an object, a key is unknown or an
+    /// answer is invalid.
+    pub fn parse_json(path: &Path, value: &json::Value) -> Result<Self> {
+        let json::Value::Object(entries) = value else {
+            return Err(Error::malformed(path, "the answers are not an object"));
+        };
+        let fields = entries
+            .iter()
+            .map(|(key, value)| {
+                let invalid = || {
+                    Error::malformed(
+                        path,
+                        format!(
+                            "`{key}` is not a string, an array of strings or an object of \
+                             booleans"
+                        ),
+                    )
+                };
+                let field = match value {
+                    json::Value::String(text) | json::Value::Number(text) => {
+                        Field::Text(text.clone())
+                    }
+                    json::Value::Array(items) => Field::List(
+                        items
+                            .iter()
+                            .map(|item| match item {
+                                json::Value::String(text) => Some(text.clone()),
+                                _ => None,
+                            })
+                            .collect::<Option<_>>()
+                            .ok_or_else(invalid)?,
+                    ),
+                    json::Value::Object(entries) => Field::Flags(
+                        entries
+                            .iter()
+                            .map(|(name, value)| match value {
+                                json::Value::Bool(holds) => Some((name.clone(), *holds)),
+                                _ => None,
+                            })
+                            .collect::<Option<_>>()
+                            .ok_or_else(invalid)?,
+                    ),
+                    json::Value::Null | json::Value::Bool(_) => return Err(invalid()),
+                };
+                Ok((key.clone(), field))
+            })
+            .collect::<Result<_>>()?;
+        Self::from_fields(path, fields)
+    }
+
+    /// Interprets the answers read from the file at `path`.
+    fn from_fields(path: &Path, fields: Vec<(String, Field)>) -> Result<Self> {
+        let malformed =
+            |key: &str, message: &str| Error::malformed(path, format!("answer `{key}`: {message}"));
+        let mut answers = Self::default();
+        for (key, field) in fields {
+            match (Variable::from_name(&key), key.as_str(), field) {
+                (Some(variable), _, Field::Text(text)) => {
+                    answers
+                        .values
+                        .set(variable, text)
+                        .map_err(|error| malformed(&key, &error.to_string()))?;
+                }
+                (None, "keywords", Field::List(items)) => answers.keywords = items,
+                (None, "categories", Field::List(items)) => answers.categories = items,
+                (None, "variants", Field::List(items)) => answers.variants = items,
+                (None, "msrv", Field::Text(text)) => {
+                    answers.msrv = Some(
+                        text.parse()
+                            .map_err(|error: Error| malformed(&key, &error.to_string()))?,
+                    );
+                }
+                (None, "license", Field::Text(text)) => {
+                    answers.license = Some(
+                        parse_license(&text)
+                            .map_err(|error| malformed(&key, &error.to_string()))?,
+                    );
+                }
+                (None, "conditions", Field::Flags(flags)) => {
+                    for (name, holds) in flags {
+                        Condition::find(&name)
+                            .map_err(|error| malformed(&key, &error.to_string()))?;
+                        answers.conditions.insert(name, holds);
+                    }
+                }
+                (Some(_), ..) => return Err(malformed(&key, "has the wrong type")),
+                (None, key, _) if KEYS.contains(&key) => {
+                    return Err(malformed(key, "has the wrong type"));
+                }
+                (None, key, _) => return Err(malformed(key, "unknown question")),
+            }
+        }
+        Ok(answers)
+    }
+
+    /// Adds `other` to the answers, replacing those it also answers.
+    pub fn merge(&mut self, other: Self) {
+        for (variable, value) in other.values.iter() {
+            // The value was already validated, so this can't fail
+            let _ = self.values.set(variable, value);
+        }
+        for (mine, theirs) in [
+            (&mut self.keywords, other.keywords),
+            (&mut self.categories, other.categories),
+            (&mut self.variants, other.variants),
+        ] {
+            if !theirs.is_empty() {
+                *mine = theirs;
+            }
+        }
+        self.msrv = other.msrv.or(self.msrv);
+        self.license = other.license.or(self.license);
+        self.conditions.extend(other.conditions);
+    }
+
+    /// Records the answers in the `[answers]` table of [`STAMP_PATH`] (creating
+    /// it if needed), among the crate's `files`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`STAMP_PATH`] cannot be read or parsed.
+    pub fn record(&self, files: &mut Files<'_>) -> Result<()> {
+        let path = Path::new(STAMP_PATH);
+        let contents = files
+            .get(path)?
+            .map_or_else(|| upgrade::STAMP_HEADER.to_owned(), str::to_owned);
+        let mut document = Document::parse(path, &contents)?;
+
+        for (variable, value) in self.values.iter() {
+            document.set(TABLE, variable.name(), &value.into());
+        }
+        for (key, items) in [
+            ("keywords", &self.keywords),
+            ("categories", &self.categories),
+            ("variants", &self.variants),
+        ] {
+            if !items.is_empty() {
+                document.set(TABLE, key, &items.clone().into());
+            }
+        }
+        if let Some(msrv) = self.msrv {
+            document.set(TABLE, "msrv", &msrv.to_string().into());
+        }
+        if let Some(license) = self.license {
+            document.set(TABLE, "license", &license.expression().into());
+        }
+        for (name, &holds) in &self.conditions {
+            document.set(&format!("{TABLE}.conditions"), name, &holds.into());
+        }
+
+        files.set(path, document.to_string())
+    }
+}
+
+/// The questions other than the template's variables.
+const KEYS: &[&str] = &[
+    "keywords",
+    "categories",
+    "msrv",
+    "license",
+    "variants",
+    "conditions",
+];
+
+/// Parses a license from its SPDX expression.
+///
+/// # Errors
+///
+/// Returns an error if the license isn't one the template offers.
+pub fn parse_license(expression: &str) -> Result<License> {
+    License::from_expression(expression).ok_or_else(|| {
+        let known: Vec<&str> = License::ALL
+            .iter()
+            .map(|license| license.expression())
+            .collect();
+        Error::InvalidInput(format!(
+            "unknown license `{expression}` (expected one of {})",
+            known.join(", ")
+        ))
+    })
+}
+
+/// Reads the answers recorded in the crate at `root`, if any.
+///
+/// # Errors
+///
+/// Returns an error if [`STAMP_PATH`] exists but cannot be read or parsed, or
+/// a recorded answer is invalid.
+pub fn recorded(root: &Path) -> Result<Option<Answers>> {
+    let path = root.join(STAMP_PATH);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let document = Document::read(&path)?;
+    if !document.tables().any(|table| table == TABLE) {
+        return Ok(None);
+    }
+    Answers::parse_toml(&path, &document, TABLE).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{changes, fs::Scratch};
+
+    const TOML: &str = "\
+name = \"my-crate\"
+owner = \"me\"
+year = 2026
+keywords = [\"parsing\"]
+msrv = \"1.85\"
+license = \"MIT\"
+variants = [\"no_std\"]
+
+[conditions]
+unsafe = false
+";
+
+    fn parse_toml(contents: &str) -> Result<Answers> {
+        let path = Path::new("answers.toml");
+        Answers::parse_toml(path, &Document::parse(path, contents)?, "")
+    }
+
+    #[test]
+    fn toml_and_json_answers() {
+        let answers = parse_toml(TOML).unwrap();
+        assert_eq!(answers.values.get(Variable::Name), Some("my-crate"));
+        assert_eq!(answers.values.get(Variable::Year), Some("2026"));
+        assert_eq!(answers.keywords, ["parsing"]);
+        assert_eq!(
+            answers.msrv.map(|msrv| msrv.to_string()).as_deref(),
+            Some("1.85")
+        );
+        assert_eq!(answers.license, Some(parse_license("MIT").unwrap()));
+        assert_eq!(
+            answers.conditions,
+            BTreeMap::from([("unsafe".to_owned(), false)])
+        );
+
+        let path = Path::new("answers.json");
+        let json = json::Value::parse(
+            path,
+            r#"{"name": "my-crate", "owner": "me", "year": 2026, "keywords": ["parsing"],
+                "msrv": "1.85", "license": "MIT", "variants": ["no_std"],
+                "conditions": {"unsafe": false}}"#,
+        )
+        .unwrap();
+        assert_eq!(Answers::parse_json(path, &json).unwrap(), answers);
+    }
+
+    #[test]
+    fn invalid_answers() {
+        for (contents, message) in [
+            ("name = \"my crate\"\n", "answer `name`: invalid crate name"),
+            (
+                "keywords = \"parsing\"\n",
+                "answer `keywords`: has the wrong type",
+            ),
+            ("colour = \"red\"\n", "answer `colour`: unknown question"),
+            (
+                "[conditions]\nfast = true\n",
+                "answer `conditions`: unknown condition",
+            ),
+            ("msrv = true\n", "`msrv` is not a string"),
+        ] {
+            let error = parse_toml(contents).unwrap_err().to_string();
+            assert!(
+                error.starts_with(&format!("answers.toml: {message}")),
+                "{contents:?}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn merging() {
+        let mut answers = parse_toml(TOML).unwrap();
+        answers.merge(
+            parse_toml("owner = \"you\"\nkeywords = []\n[conditions]\nloom = true\n").unwrap(),
+        );
+        assert_eq!(answers.values.get(Variable::Owner), Some("you"));
+        assert_eq!(answers.values.get(Variable::Name), Some("my-crate"));
+        assert_eq!(answers.keywords, ["parsing"]);
+        assert_eq!(answers.conditions.len(), 2);
+    }
+
+    #[test]
+    fn recorded_answers_round_trip() {
+        let scratch = Scratch::new(&[]);
+        assert_eq!(recorded(scratch.path()).unwrap(), None);
+
+        let answers = parse_toml(TOML).unwrap();
+        let mut files = Files::new(scratch.path());
+        answers.record(&mut files).unwrap();
+        changes::apply(scratch.path(), &files.into_changes()).unwrap();
+        assert_eq!(recorded(scratch.path()).unwrap(), Some(answers));
+    }
+}
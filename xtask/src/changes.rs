@@ -0,0 +1,181 @@
+//! Planned edits to files in the crate being maintained.
+//!
+//! Commands that rewrite files first compute a list of [`FileChange`]s and
+//! only then [`apply`] them, so nothing is written if any step fails. When
+//! several steps edit the same files, they share a [`Files`].
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{Result, fs};
+
+/// A new version of one file, relative to the crate root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// The path of the file, relative to the crate root.
+    pub path: PathBuf,
+    /// The current contents of the file.
+    pub original: String,
+    /// The contents the file should have after the change.
+    pub updated: String,
+    /// Whether the file is deleted, rather than given the `updated` contents
+    /// (which are then empty).
+    pub deleted: bool,
+}
+
+impl FileChange {
+    /// Creates a change to `path`, or returns [`None`] if `updated` is
+    /// identical to `original`.
+    pub fn new(path: impl Into<PathBuf>, original: String, updated: String) -> Option<Self> {
+        (original != updated).then(|| Self {
+            path: path.into(),
+            original,
+            updated,
+            deleted: false,
+        })
+    }
+
+    /// Creates a change deleting `path`, whose current contents are
+    /// `original`.
+    pub fn deletion(path: impl Into<PathBuf>, original: String) -> Self {
+        Self {
+            path: path.into(),
+            original,
+            updated: String::new(),
+            deleted: true,
+        }
+    }
+}
+
+/// Writes (or deletes) every change on disk, relative to `root`.
+///
+/// # Errors
+///
+/// Returns an error if any file cannot be written or deleted. Files earlier in
+/// `changes` will already have been written when that happens.
+pub fn apply(root: &Path, changes: &[FileChange]) -> Result<()> {
+    for change in changes {
+        let path = root.join(&change.path);
+        if change.deleted {
+            fs::remove(&path)?;
+        } else {
+            fs::write(&path, &change.updated)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Files of the crate being edited in memory, so several steps can build on
+/// each other's edits before any of them are written.
+#[derive(Debug, Clone)]
+pub struct Files<'a> {
+    root: &'a Path,
+    /// The original contents (if the file existed) and current contents (if
+    /// it still exists) of every file read or written so far.
+    files: BTreeMap<PathBuf, (Option<String>, Option<String>)>,
+}
+
+impl<'a> Files<'a> {
+    /// Starts editing the files of the crate at `root`.
+    pub fn new(root: &'a Path) -> Self {
+        Self {
+            root,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// The current contents of `path`, or [`None`] if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn get(&mut self, path: impl AsRef<Path>) -> Result<Option<&str>> {
+        let path = path.as_ref();
+        if !self.files.contains_key(path) {
+            let full_path = self.root.join(path);
+            let contents = if full_path.is_file() {
+                Some(fs::read(&full_path)?)
+            } else {
+                None
+            };
+            self.files
+                .insert(path.to_owned(), (contents.clone(), contents));
+        }
+        Ok(self.files[path].1.as_deref())
+    }
+
+    /// Lists every file that currently exists, sorted, including those
+    /// created in memory. Version control metadata and build output are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory tree or a file cannot be read.
+    pub fn paths(&mut self) -> Result<Vec<PathBuf>> {
+        let mut paths = fs::walk(self.root)?;
+        paths.extend(self.files.keys().cloned());
+        paths.sort();
+        paths.dedup();
+        let mut existing = Vec::with_capacity(paths.len());
+        for path in paths {
+            if self.exists(&path)? {
+                existing.push(path);
+            }
+        }
+        Ok(existing)
+    }
+
+    /// Whether `path` currently exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn exists(&mut self, path: impl AsRef<Path>) -> Result<bool> {
+        Ok(self.get(path)?.is_some())
+    }
+
+    /// Sets the contents of `path`, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn set(&mut self, path: impl AsRef<Path>, contents: String) -> Result<()> {
+        let path = path.as_ref();
+        self.get(path)?;
+        if let Some((_, current)) = self.files.get_mut(path) {
+            *current = Some(contents);
+        }
+        Ok(())
+    }
+
+    /// Deletes `path`, if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        self.get(path)?;
+        if let Some((_, current)) = self.files.get_mut(path) {
+            *current = None;
+        }
+        Ok(())
+    }
+
+    /// The changes made, sorted by path.
+    pub fn into_changes(self) -> Vec<FileChange> {
+        self.files
+            .into_iter()
+            .filter_map(|(path, files)| match files {
+                (Some(original), None) => Some(FileChange::deletion(path, original)),
+                (original, Some(updated)) => {
+                    FileChange::new(path, original.unwrap_or_default(), updated)
+                }
+                (None, None) => None,
+            })
+            .collect()
+    }
+}
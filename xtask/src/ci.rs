@@ -0,0 +1,521 @@
+//! The CI stages, natively in Rust.
+//!
+//! `scripts/ci.py` runs its `CI_STAGES` one after another. The [`Registry`]
+//! describes the same stages as data instead: the cargo commands each stage
+//! runs, on which toolchains, and which other stages it depends on. That's
+//! enough for the [`executor`] to run independent stages in parallel, and to
+//! skip the stages depending on one that failed, and for [`workflow`] to
+//! generate the GitHub Actions workflow running the stages as its [`JOBS`].
+//! The workflow itself can be run locally with [`actions`], and the [`cache`]
+//! skips the stages whose inputs haven't changed since they last passed.
+//!
+//! Stages only share what their [`Resource`]s say they do. Every toolchain
+//! builds into a target directory of its own (`target/ci/TOOLCHAIN`), since
+//! Cargo locks a target directory while building and toolchains can't reuse
+//! each other's artifacts anyway, so tests on stable, beta and the MSRV can
+//! all build at once.
+
+pub mod actions;
+pub mod cache;
+pub mod executor;
+pub mod report;
+pub mod workflow;
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Error, Result, answers, conditional::CONDITIONS, fs, msrv::RustVersion, toml::Document,
+};
+
+/// The directory, relative to the crate root, holding each toolchain's
+/// target directory.
+pub const TARGET_DIR: &str = "target/ci";
+
+/// The bare-metal target the `build_nostd` stage builds for.
+pub const NOSTD_TARGET: &str = "thumbv6m-none-eabi";
+
+/// One cargo invocation of a [`Stage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The rustup toolchain to run cargo with, like `stable` or `1.85.0`.
+    pub toolchain: String,
+    /// The arguments to cargo, after `+TOOLCHAIN`.
+    pub args: Vec<String>,
+    /// The environment variables to set, like `RUSTFLAGS`.
+    pub env: Vec<(String, String)>,
+    /// The rustup components the step needs, like `clippy`.
+    pub components: Vec<String>,
+    /// The rustup targets the step needs, besides the host's.
+    pub targets: Vec<String>,
+}
+
+impl Step {
+    /// Creates a step running `cargo +TOOLCHAIN ARGS...`.
+    fn new(toolchain: &str, args: &[&str]) -> Self {
+        Self {
+            toolchain: toolchain.to_owned(),
+            args: args.iter().map(|&arg| arg.to_owned()).collect(),
+            env: Vec::new(),
+            components: Vec::new(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Sets the environment variable `key`.
+    fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Adds a rustup component the step needs.
+    fn component(mut self, component: &str) -> Self {
+        self.components.push(component.to_owned());
+        self
+    }
+
+    /// Adds a rustup target the step needs.
+    fn target(mut self, target: &str) -> Self {
+        self.targets.push(target.to_owned());
+        self
+    }
+
+    /// Whether the step builds anything, and so needs a target directory
+    /// (`cargo fmt` doesn't).
+    pub fn builds(&self) -> bool {
+        self.args.first().is_none_or(|command| command != "fmt")
+    }
+
+    /// The target directory the step builds into, relative to the crate root.
+    pub fn target_dir(&self) -> PathBuf {
+        Path::new(TARGET_DIR).join(&self.toolchain)
+    }
+
+    /// The step as a command line, like `cargo +stable test`.
+    pub fn command_line(&self) -> String {
+        let mut words = vec!["cargo".to_owned(), format!("+{}", self.toolchain)];
+        words.extend(self.args.iter().cloned());
+        words.join(" ")
+    }
+}
+
+/// Something stages share.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Resource {
+    /// A rustup toolchain, which any number of stages can use at once.
+    Toolchain(String),
+    /// A target directory (relative to the crate root), which Cargo locks
+    /// while building, so only one stage can use it at a time.
+    TargetDir(PathBuf),
+}
+
+impl Resource {
+    /// Whether only one stage can use the resource at a time.
+    pub fn is_exclusive(&self) -> bool {
+        matches!(self, Self::TargetDir(_))
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Toolchain(toolchain) => write!(f, "toolchain {toolchain}"),
+            Self::TargetDir(dir) => write!(f, "target dir {}", dir.display()),
+        }
+    }
+}
+
+/// A CI stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stage {
+    /// The stage's name, as in `scripts/ci.py`'s `CI_STAGES`.
+    pub name: &'static str,
+    /// What the stage does, as its header says.
+    pub title: String,
+    /// The cargo invocations making up the stage, run in order.
+    pub steps: Vec<Step>,
+    /// The stages that have to succeed before this one is worth running.
+    pub dependencies: Vec<&'static str>,
+    /// The [condition](crate::conditional) the crate needs for the stage to
+    /// exist, if any.
+    pub condition: Option<&'static str>,
+}
+
+impl Stage {
+    /// The resources the stage uses, sorted.
+    pub fn resources(&self) -> Vec<Resource> {
+        let mut resources: Vec<Resource> = self
+            .steps
+            .iter()
+            .flat_map(|step| {
+                let target_dir = step
+                    .builds()
+                    .then(|| Resource::TargetDir(step.target_dir()));
+                [
+                    Some(Resource::Toolchain(step.toolchain.clone())),
+                    target_dir,
+                ]
+            })
+            .flatten()
+            .collect();
+        resources.sort();
+        resources.dedup();
+        resources
+    }
+}
+
+/// Every CI stage of a crate, in the order `scripts/ci.py` runs them.
+///
+/// The registry knows the optional stages even when their condition doesn't
+/// hold for the crate, since the template's workflow has to keep them (in
+/// `# @if` blocks) for the crates instantiated from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registry {
+    stages: Vec<Stage>,
+    conditions: BTreeMap<String, bool>,
+}
+
+/// The runner of most jobs.
+const UBUNTU: &str = "ubuntu-latest";
+
+/// The runner of the jobs testing on Windows.
+const WINDOWS: &str = "windows-latest";
+
+/// A job of the CI workflow, running one stage on one runner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Job {
+    /// The job's key in the workflow's `jobs`, like `run_tests_windows`.
+    pub id: &'static str,
+    /// The job's display name, like `Tests (Windows)`.
+    pub name: &'static str,
+    /// The name of the stage the job runs.
+    pub stage: &'static str,
+    /// The GitHub Actions runner the job runs on, like `ubuntu-latest`.
+    pub runner: &'static str,
+}
+
+/// Every job of the CI workflow, in order. Most stages have a job of their
+/// own, but the tests on stable also run on Windows.
+///
+/// Branch protection rules refer to jobs by their names (as checks), so
+/// renaming one, or running a stage on more runners with a matrix, has to be
+/// done deliberately rather than as a side effect of regenerating the
+/// workflow.
+pub const JOBS: &[Job] = &[
+    Job {
+        id: "check_fmt",
+        name: "Cargo fmt",
+        stage: "check_fmt",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "check_docs",
+        name: "Cargo docs",
+        stage: "check_docs",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "lint",
+        name: "Lint (clippy)",
+        stage: "lint",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "build",
+        name: "Build",
+        stage: "build",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "build_nostd",
+        name: "Build (nostd)",
+        stage: "build_nostd",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "run_tests_stable",
+        name: "Tests (stable)",
+        stage: "run_tests_stable",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "run_tests_beta",
+        name: "Tests (beta)",
+        stage: "run_tests_beta",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "run_tests_msrv",
+        name: "Tests (MSRV)",
+        stage: "run_tests_msrv",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "run_tests_windows",
+        name: "Tests (Windows)",
+        stage: "run_tests_stable",
+        runner: WINDOWS,
+    },
+    Job {
+        id: "run_tests_leak_sanitizer",
+        name: "Tests (leak sanitizer)",
+        stage: "run_tests_leak_sanitizer",
+        runner: UBUNTU,
+    },
+    Job {
+        id: "run_tests_miri",
+        name: "Tests (MIRI)",
+        stage: "run_tests_miri",
+        runner: UBUNTU,
+    },
+];
+
+impl Registry {
+    /// The stages of a crate whose MSRV is `msrv`. `conditions` (see
+    /// [`conditional`](crate::conditional)) decides which optional stages
+    /// there are: `build_nostd` needs `no_std` and `run_tests_miri` needs
+    /// `unsafe`, while `loom` makes the leak sanitizer skip loom's tests.
+    pub fn new(msrv: RustVersion, conditions: &BTreeMap<String, bool>) -> Self {
+        let warnings = "-D warnings";
+        let msrv = msrv.to_string();
+
+        let mut stages = vec![
+            Stage {
+                name: "check_fmt",
+                title: "Checking code formatting".to_owned(),
+                steps: vec![
+                    Step::new("stable", &["fmt", "--check"])
+                        .env("RUSTFLAGS", warnings)
+                        .component("rustfmt"),
+                ],
+                dependencies: vec![],
+                condition: None,
+            },
+            Stage {
+                name: "check_docs",
+                title: "Building documentation (stable and nightly)".to_owned(),
+                steps: ["stable", "nightly"]
+                    .map(|toolchain| {
+                        Step::new(toolchain, &["doc", "--document-private-items", "--no-deps"])
+                            .env("RUSTDOCFLAGS", warnings)
+                    })
+                    .into(),
+                dependencies: vec![],
+                condition: None,
+            },
+            Stage {
+                name: "build",
+                title: "Running cargo build".to_owned(),
+                steps: vec![
+                    Step::new("stable", &["build", "--all-targets"]).env("RUSTFLAGS", warnings),
+                ],
+                dependencies: vec![],
+                condition: None,
+            },
+            Stage {
+                name: "build_nostd",
+                title: "Building on no_std target".to_owned(),
+                steps: vec![
+                    Step::new("stable", &["build", "--target", NOSTD_TARGET])
+                        .env("RUSTFLAGS", warnings)
+                        .target(NOSTD_TARGET),
+                ],
+                dependencies: vec!["build"],
+                condition: Some("no_std"),
+            },
+            Stage {
+                name: "lint",
+                title: "Linting with cargo clippy".to_owned(),
+                steps: vec![
+                    Step::new(
+                        "stable",
+                        &[
+                            "clippy",
+                            "--no-deps",
+                            "--all-targets",
+                            "--",
+                            "-D",
+                            "warnings",
+                        ],
+                    )
+                    .component("clippy"),
+                ],
+                dependencies: vec![],
+                condition: None,
+            },
+        ];
+        let loom = conditions.get("loom").copied().unwrap_or_default();
+        stages.extend(test_stages(&msrv, loom));
+
+        Self {
+            stages,
+            conditions: conditions.clone(),
+        }
+    }
+
+    /// The stages of the crate at `root`, with its MSRV and [`conditions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Cargo.toml cannot be read or has no valid
+    /// `rust-version`, or the recorded answers cannot be read.
+    pub fn for_crate(root: &Path) -> Result<Self> {
+        let path = root.join("Cargo.toml");
+        let msrv = Document::read(&path)?
+            .get_str("package", "rust-version")
+            .ok_or_else(|| Error::malformed(&path, "`package.rust-version` is missing"))?
+            .parse()?;
+        Ok(Self::new(msrv, &conditions(root)?))
+    }
+
+    /// Whether the crate has `stage`, i.e. its condition (if any) holds.
+    pub fn has(&self, stage: &Stage) -> bool {
+        stage
+            .condition
+            .is_none_or(|condition| self.conditions.get(condition).copied().unwrap_or_default())
+    }
+
+    /// Every stage the crate has, in order.
+    pub fn stages(&self) -> Vec<&Stage> {
+        self.stages.iter().filter(|stage| self.has(stage)).collect()
+    }
+
+    /// Every stage, including those whose condition doesn't hold, in order.
+    pub fn all_stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Looks up the stage called `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the crate has no such stage.
+    pub fn get(&self, name: &str) -> Result<&Stage> {
+        match self.stages.iter().find(|stage| stage.name == name) {
+            Some(stage) if self.has(stage) => Ok(stage),
+            Some(stage) => Err(Error::InvalidInput(format!(
+                "CI stage `{name}` needs the `{}` condition, which doesn't hold for this crate",
+                stage.condition.unwrap_or_default()
+            ))),
+            None => {
+                let known: Vec<&str> = self.stages().iter().map(|stage| stage.name).collect();
+                Err(Error::InvalidInput(format!(
+                    "unknown CI stage `{name}` (expected one of {})",
+                    known.join(", ")
+                )))
+            }
+        }
+    }
+
+    /// The stages called `names`, along with every stage they (transitively)
+    /// depend on unless `only`, in registry order. No names means every
+    /// stage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a name is unknown.
+    pub fn select(&self, names: &[String], only: bool) -> Result<Vec<&Stage>> {
+        if names.is_empty() {
+            return Ok(self.stages());
+        }
+
+        let mut wanted: Vec<&str> = Vec::new();
+        let mut pending: Vec<&str> = names.iter().map(String::as_str).collect();
+        while let Some(name) = pending.pop() {
+            let stage = self.get(name)?;
+            if wanted.contains(&stage.name) {
+                continue;
+            }
+            wanted.push(stage.name);
+            if !only {
+                pending.extend(stage.dependencies.iter().copied());
+            }
+        }
+        Ok(self
+            .stages()
+            .into_iter()
+            .filter(|stage| wanted.contains(&stage.name))
+            .collect())
+    }
+}
+
+/// The stages running the tests, for a crate whose MSRV is `msrv`, and which
+/// uses loom if `loom`.
+fn test_stages(msrv: &str, loom: bool) -> Vec<Stage> {
+    let warnings = "-D warnings";
+    let mut stages = Vec::new();
+    for (name, toolchain, title) in [
+        ("run_tests_stable", "stable", "stable compiler".to_owned()),
+        ("run_tests_beta", "beta", "beta compiler".to_owned()),
+        ("run_tests_msrv", msrv, format!("MSRV compiler ({msrv})")),
+    ] {
+        stages.push(Stage {
+            name,
+            title: format!("Running tests ({title})"),
+            steps: vec![Step::new(toolchain, &["test"]).env("RUSTFLAGS", warnings)],
+            dependencies: vec!["build"],
+            condition: None,
+        });
+    }
+    // loom seems to make the leak sanitizer unhappy, and that combination
+    // of tests isn't important
+    let sanitizer_args: &[&str] = if loom {
+        &["test", "--", "--skip", "loom"]
+    } else {
+        &["test"]
+    };
+    stages.push(Stage {
+        name: "run_tests_leak_sanitizer",
+        title: "Running tests with leak sanitizer".to_owned(),
+        steps: vec![
+            Step::new("nightly", sanitizer_args).env("RUSTFLAGS", "-D warnings -Z sanitizer=leak"),
+        ],
+        dependencies: vec!["run_tests_stable"],
+        condition: None,
+    });
+    stages.push(Stage {
+        name: "run_tests_miri",
+        title: "Running tests with MIRI".to_owned(),
+        steps: vec![
+            Step::new("nightly", &["miri", "test"])
+                .env("RUSTFLAGS", "-D warnings -C opt-level=0")
+                .env("MIRIFLAGS", "-Zmiri-strict-provenance")
+                .component("miri"),
+        ],
+        dependencies: vec!["run_tests_stable"],
+        condition: Some("unsafe"),
+    });
+
+    stages
+}
+
+/// Which conditions hold for the crate at `root`: the template's value for
+/// each, unless src/lib.rs is `#![no_std]` (for `no_std`) or the crate's
+/// recorded answers say otherwise.
+///
+/// # Errors
+///
+/// Returns an error if src/lib.rs or the recorded answers cannot be read.
+pub fn conditions(root: &Path) -> Result<BTreeMap<String, bool>> {
+    let mut conditions: BTreeMap<String, bool> = CONDITIONS
+        .iter()
+        .map(|condition| (condition.name.to_owned(), condition.template))
+        .collect();
+
+    let lib = root.join("src/lib.rs");
+    if lib.is_file()
+        && fs::read(&lib)?
+            .lines()
+            .any(|line| line.trim() == "#![no_std]")
+    {
+        conditions.insert("no_std".to_owned(), true);
+    }
+    if let Some(recorded) = answers::recorded(root)? {
+        conditions.extend(recorded.conditions);
+    }
+
+    Ok(conditions)
+}
@@ -0,0 +1,572 @@
+//! Running the jobs of the GitHub Actions workflow locally.
+//!
+//! Rather than trusting that `scripts/ci.py` does what the workflow does,
+//! [`plan`] interprets the workflow itself, within the subset the template
+//! uses:
+//!
+//! - `actions/checkout` uses the working tree as it is.
+//! - `dtolnay/rust-toolchain@X` selects the local rustup toolchain `X` for the
+//!   steps after it (like the action's `rustup default`), after checking that
+//!   the toolchain and the components and targets it asks for are installed.
+//!   Nothing is ever installed, so running needs no network.
+//! - `run:` steps run in the shell GitHub uses on the runner's OS, with the
+//!   workflow's, the job's and the step's environment variables.
+//!
+//! Matrix jobs are expanded into one job per combination of values. A job
+//! that can't run here (on another OS, using another action, or with an
+//! expression other than `${{ matrix.KEY }}`) is skipped, with the reason.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use super::executor::Status;
+use crate::{
+    Error, Result, process,
+    workflow::{self, Workflow},
+};
+
+/// One thing a [`Job`] does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Checks the crate out, which locally means using the working tree.
+    Checkout,
+    /// Selects a rustup toolchain for the steps after it.
+    Toolchain(String),
+    /// Runs a shell command.
+    Run {
+        /// The command.
+        command: String,
+        /// The environment variables set for it.
+        env: Vec<(String, String)>,
+    },
+}
+
+/// Whether a [`Job`] can run locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Plan {
+    /// The job can run, doing these actions in order.
+    Runnable(Vec<Action>),
+    /// The job can't run here, for the given reason.
+    Skipped(String),
+}
+
+/// A job of the workflow, with one combination of its matrix's values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// The job's key in the workflow's `jobs`.
+    pub id: String,
+    /// The job's display name, if it has one.
+    pub name: Option<String>,
+    /// The matrix values of this run of the job, in order.
+    pub matrix: Vec<(String, String)>,
+    /// The (1-based) line the job starts on.
+    pub line: usize,
+    /// What running the job locally takes.
+    pub plan: Plan,
+}
+
+impl Job {
+    /// The job's key, followed by its matrix values (if any), like
+    /// `run_tests_stable (windows-latest)`.
+    pub fn label(&self) -> String {
+        if self.matrix.is_empty() {
+            return self.id.clone();
+        }
+        let values: Vec<&str> = self
+            .matrix
+            .iter()
+            .map(|(_, value)| value.as_str())
+            .collect();
+        format!("{} ({})", self.id, values.join(", "))
+    }
+}
+
+/// A locally installed rustup toolchain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Installed {
+    /// The toolchain's full name, like `stable-x86_64-unknown-linux-gnu`.
+    name: String,
+    /// The installed components, like `clippy-x86_64-unknown-linux-gnu` (or
+    /// `rust-src`, for those not specific to a host).
+    components: Vec<String>,
+    /// The installed targets.
+    targets: Vec<String>,
+    /// The toolchain's host triple.
+    host: String,
+}
+
+impl Installed {
+    /// Whether `component` is installed.
+    fn has_component(&self, component: &str) -> bool {
+        self.components.iter().any(|installed| {
+            installed == component || *installed == format!("{component}-{}", self.host)
+        })
+    }
+}
+
+/// The rustup toolchains asked for so far, looked up once each.
+#[derive(Debug, Default)]
+struct Rustup {
+    /// Every installed toolchain's full name, once listed.
+    toolchains: Option<Vec<String>>,
+    /// The toolchains looked up, by the name they were asked for by.
+    installed: BTreeMap<String, Option<Installed>>,
+}
+
+impl Rustup {
+    /// Looks up the toolchain `toolchain` (like `stable` or `1.85.0`), or
+    /// [`None`] if it isn't installed.
+    fn get(&mut self, toolchain: &str) -> Result<Option<&Installed>> {
+        if !self.installed.contains_key(toolchain) {
+            let installed = self.lookup(toolchain)?;
+            self.installed.insert(toolchain.to_owned(), installed);
+        }
+        Ok(self.installed[toolchain].as_ref())
+    }
+
+    fn lookup(&mut self, toolchain: &str) -> Result<Option<Installed>> {
+        if self.toolchains.is_none() {
+            let list = rustup(&["toolchain", "list"])?;
+            self.toolchains = Some(
+                list.lines()
+                    .filter_map(|line| line.split_whitespace().next())
+                    .map(str::to_owned)
+                    .collect(),
+            );
+        }
+        let Some(name) = self
+            .toolchains
+            .iter()
+            .flatten()
+            .find(|name| *name == toolchain || name.starts_with(&format!("{toolchain}-")))
+        else {
+            return Ok(None);
+        };
+
+        // `rustc -vV` has a `host: x86_64-unknown-linux-gnu` line
+        let host = process::stdout(Command::new("rustc").arg(format!("+{name}")).arg("-vV"))?
+            .unwrap_or_default()
+            .lines()
+            .find_map(|line| line.strip_prefix("host: "))
+            .unwrap_or_default()
+            .to_owned();
+        let list = |what: &str| -> Result<Vec<String>> {
+            Ok(rustup(&[what, "list", "--installed", "--toolchain", name])?
+                .lines()
+                .map(str::to_owned)
+                .collect())
+        };
+        Ok(Some(Installed {
+            name: name.clone(),
+            components: list("component")?,
+            targets: list("target")?,
+            host,
+        }))
+    }
+}
+
+/// Runs `rustup ARGS...`, returning its stdout.
+fn rustup(args: &[&str]) -> Result<String> {
+    process::stdout(Command::new("rustup").args(args))?
+        .ok_or_else(|| Error::InvalidInput(format!("`rustup {}` failed", args.join(" "))))
+}
+
+/// Expands every job of `workflow` (one per combination of its matrix's
+/// values) and works out whether and how each can run locally.
+///
+/// # Errors
+///
+/// Returns an error if `rustup` cannot be run.
+pub fn plan(workflow: &Workflow) -> Result<Vec<Job>> {
+    let mut rustup = Rustup::default();
+    let mut jobs = Vec::new();
+    for job in &workflow.jobs {
+        let mut combinations: Vec<Vec<(String, String)>> = vec![Vec::new()];
+        for (key, values) in &job.matrix {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((key.clone(), value.clone()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+
+        for matrix in combinations {
+            let plan = plan_job(workflow, job, &matrix, &mut rustup)?;
+            jobs.push(Job {
+                id: job.id.clone(),
+                name: job
+                    .name
+                    .as_deref()
+                    .map(|name| expand(name, &matrix).unwrap_or_else(|_| name.to_owned())),
+                matrix,
+                line: job.line,
+                plan,
+            });
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// The plan for one run of `job`, with the `matrix` values.
+fn plan_job(
+    workflow: &Workflow,
+    job: &workflow::Job,
+    matrix: &[(String, String)],
+    rustup: &mut Rustup,
+) -> Result<Plan> {
+    let runner = match job.runs_on.as_deref().map(|runner| expand(runner, matrix)) {
+        None => return Ok(Plan::Skipped("has no `runs-on`".to_owned())),
+        Some(Err(reason)) => return Ok(Plan::Skipped(reason)),
+        Some(Ok(runner)) => runner,
+    };
+    if let Some(reason) = runner_problem(&runner) {
+        return Ok(Plan::Skipped(reason));
+    }
+
+    let mut actions = Vec::new();
+    for step in &job.steps {
+        if let Some(command) = &step.run {
+            let mut env = Vec::new();
+            for (key, value) in workflow.env.iter().chain(&job.env).chain(&step.env) {
+                match expand(value, matrix) {
+                    Ok(value) => env.push((key.clone(), value)),
+                    Err(reason) => return Ok(Plan::Skipped(reason)),
+                }
+            }
+            match expand(command, matrix) {
+                Ok(command) => actions.push(Action::Run { command, env }),
+                Err(reason) => return Ok(Plan::Skipped(reason)),
+            }
+            continue;
+        }
+
+        let Some((action, version)) = step.action() else {
+            return Ok(Plan::Skipped(format!(
+                "has a step (line {}) that neither uses an action nor runs a command",
+                step.line
+            )));
+        };
+        match action {
+            "actions/checkout" => actions.push(Action::Checkout),
+            "dtolnay/rust-toolchain" => {
+                let input = |key: &str| -> std::result::Result<Vec<String>, String> {
+                    Ok(expand(step.input(key).unwrap_or_default(), matrix)?
+                        .split([',', ' '])
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_owned)
+                        .collect())
+                };
+                // The action's version is the toolchain, unless it's a
+                // branch of the action taking the toolchain as an input
+                let toolchain = match input("toolchain") {
+                    Ok(toolchain) => toolchain.first().cloned(),
+                    Err(reason) => return Ok(Plan::Skipped(reason)),
+                }
+                .unwrap_or_else(|| version.to_owned());
+                let (components, targets) = match (input("components"), input("targets")) {
+                    (Ok(components), Ok(targets)) => (components, targets),
+                    (Err(reason), _) | (_, Err(reason)) => return Ok(Plan::Skipped(reason)),
+                };
+
+                let Some(installed) = rustup.get(&toolchain)? else {
+                    return Ok(Plan::Skipped(format!(
+                        "needs toolchain `{toolchain}`, which isn't installed (`rustup toolchain \
+                         install {toolchain}`)"
+                    )));
+                };
+                if let Some(component) = components
+                    .iter()
+                    .find(|component| !installed.has_component(component))
+                {
+                    return Ok(Plan::Skipped(format!(
+                        "needs component `{component}` of toolchain `{toolchain}`, which isn't \
+                         installed (`rustup component add {component} --toolchain {toolchain}`)"
+                    )));
+                }
+                if let Some(target) = targets
+                    .iter()
+                    .find(|target| !installed.targets.contains(target))
+                {
+                    return Ok(Plan::Skipped(format!(
+                        "needs target `{target}` of toolchain `{toolchain}`, which isn't \
+                         installed (`rustup target add {target} --toolchain {toolchain}`)"
+                    )));
+                }
+                actions.push(Action::Toolchain(installed.name.clone()));
+            }
+            _ => {
+                return Ok(Plan::Skipped(format!(
+                    "uses `{}`, which can't run locally",
+                    step.uses.as_deref().unwrap_or(action)
+                )));
+            }
+        }
+    }
+
+    Ok(Plan::Runnable(actions))
+}
+
+/// Why a job running on the GitHub-hosted `runner` can't run on this
+/// machine, if it can't.
+fn runner_problem(runner: &str) -> Option<String> {
+    let os = match runner.split('-').next() {
+        Some("ubuntu") => "linux",
+        Some("windows") => "windows",
+        Some("macos") => "macos",
+        _ => {
+            return Some(format!(
+                "runs on `{runner}`, which isn't a GitHub-hosted runner"
+            ));
+        }
+    };
+    (os != env::consts::OS).then(|| {
+        format!(
+            "runs on `{runner}`, which needs a {os} machine rather than {}",
+            env::consts::OS
+        )
+    })
+}
+
+/// Replaces the `${{ matrix.KEY }}` expressions in `text` with the `matrix`
+/// values, or says which expression can't be evaluated.
+fn expand(text: &str, matrix: &[(String, String)]) -> std::result::Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let expression = rest[start + 3..start + end].trim();
+        let value = expression
+            .strip_prefix("matrix.")
+            .and_then(|key| matrix.iter().find(|(name, _)| name == key))
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                format!("uses `${{{{ {expression} }}}}`, which can't be evaluated locally")
+            })?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Runs the `actions` of a job in the crate at `root`.
+///
+/// The output goes straight to the terminal; `on_command` is called with each
+/// command before it runs. Returns how the job ended and how long it took.
+///
+/// # Errors
+///
+/// Returns an error if the shell cannot be run.
+pub fn run(
+    root: &Path,
+    actions: &[Action],
+    mut on_command: impl FnMut(&str),
+) -> Result<(Status, Duration)> {
+    let start = Instant::now();
+    let mut toolchain = None;
+    for (index, action) in actions.iter().enumerate() {
+        let (command, env) = match action {
+            Action::Checkout => continue,
+            Action::Toolchain(name) => {
+                toolchain = Some(name);
+                continue;
+            }
+            Action::Run { command, env } => (command, env),
+        };
+
+        on_command(command);
+        // The default shells of GitHub's runners
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("pwsh");
+            shell.args(["-NoProfile", "-Command", command]);
+            shell
+        } else {
+            let mut shell = Command::new("bash");
+            shell.args(["--noprofile", "--norc", "-eo", "pipefail", "-c", command]);
+            shell
+        };
+        shell
+            .current_dir(root)
+            .env("CI", "true")
+            .envs(env.iter().map(|(key, value)| (key, value)));
+        if let Some(toolchain) = toolchain {
+            shell.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+
+        let status = process::status(&mut shell)?;
+        if !status.success() {
+            let failed = Status::Failed {
+                step: index,
+                code: status.code(),
+            };
+            return Ok((failed, start.elapsed()));
+        }
+    }
+
+    Ok((Status::Passed, start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Scratch;
+
+    /// A GitHub-hosted runner of this machine's OS, and one of another.
+    fn runners() -> (&'static str, &'static str) {
+        match env::consts::OS {
+            "windows" => ("windows-latest", "ubuntu-latest"),
+            "macos" => ("macos-latest", "ubuntu-latest"),
+            _ => ("ubuntu-latest", "windows-latest"),
+        }
+    }
+
+    #[test]
+    fn expands_matrix_values() {
+        let matrix = [("os".to_owned(), "ubuntu-latest".to_owned())];
+        assert_eq!(
+            expand("on ${{ matrix.os }}, ${{matrix.os}}", &matrix).unwrap(),
+            "on ubuntu-latest, ubuntu-latest"
+        );
+        assert_eq!(
+            expand("${{ secrets.TOKEN }}", &matrix).unwrap_err(),
+            "uses `${{ secrets.TOKEN }}`, which can't be evaluated locally"
+        );
+        assert_eq!(
+            expand("${{ matrix.toolchain }}", &matrix).unwrap_err(),
+            "uses `${{ matrix.toolchain }}`, which can't be evaluated locally"
+        );
+    }
+
+    #[test]
+    fn runners_of_other_machines_are_skipped() {
+        let (local, other) = runners();
+        assert_eq!(runner_problem(local), None);
+        assert!(runner_problem(other).unwrap().contains("rather than"));
+        assert_eq!(
+            runner_problem("self-hosted").unwrap(),
+            "runs on `self-hosted`, which isn't a GitHub-hosted runner"
+        );
+    }
+
+    #[test]
+    fn plans_jobs() {
+        let (local, other) = runners();
+        let contents = format!(
+            "name: CI\n\
+             env:\n  CARGO_TERM_COLOR: always\n\
+             jobs:\n\
+             \x20 test:\n\
+             \x20   name: Test on ${{{{ matrix.os }}}}\n\
+             \x20   runs-on: ${{{{ matrix.os }}}}\n\
+             \x20   strategy:\n\
+             \x20     matrix:\n\
+             \x20       os: [{local}, {other}]\n\
+             \x20   env:\n      RUSTFLAGS: -Dwarnings\n\
+             \x20   steps:\n\
+             \x20     - uses: actions/checkout@v4\n\
+             \x20     - run: cargo test --${{{{ matrix.os }}}}\n\
+             \x20       env:\n          STEP: one\n\
+             \x20 coverage:\n\
+             \x20   runs-on: {local}\n\
+             \x20   steps:\n\
+             \x20     - uses: taiki-e/install-action@cargo-llvm-cov\n\
+             \x20 publish:\n\
+             \x20   runs-on: {local}\n\
+             \x20   steps:\n\
+             \x20     - run: cargo publish --token ${{{{ secrets.TOKEN }}}}\n"
+        );
+        let workflow = Workflow::parse(Path::new(workflow::PATH), &contents).unwrap();
+        let jobs = plan(&workflow).unwrap();
+
+        let labels: Vec<String> = jobs.iter().map(Job::label).collect();
+        assert_eq!(
+            labels,
+            [
+                format!("test ({local})"),
+                format!("test ({other})"),
+                "coverage".to_owned(),
+                "publish".to_owned(),
+            ]
+        );
+        assert_eq!(jobs[0].name.as_deref(), Some(&*format!("Test on {local}")));
+        assert_eq!(
+            jobs[0].plan,
+            Plan::Runnable(vec![
+                Action::Checkout,
+                Action::Run {
+                    command: format!("cargo test --{local}"),
+                    env: vec![
+                        ("CARGO_TERM_COLOR".to_owned(), "always".to_owned()),
+                        ("RUSTFLAGS".to_owned(), "-Dwarnings".to_owned()),
+                        ("STEP".to_owned(), "one".to_owned()),
+                    ],
+                },
+            ])
+        );
+        assert!(matches!(&jobs[1].plan, Plan::Skipped(reason) if reason.contains(other)));
+        assert_eq!(
+            jobs[2].plan,
+            Plan::Skipped(
+                "uses `taiki-e/install-action@cargo-llvm-cov`, which can't run locally".to_owned()
+            )
+        );
+        assert_eq!(
+            jobs[3].plan,
+            Plan::Skipped(
+                "uses `${{ secrets.TOKEN }}`, which can't be evaluated locally".to_owned()
+            )
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn runs_actions_until_one_fails() {
+        let scratch = Scratch::new(&[]);
+        let run_step = |command: &str| Action::Run {
+            command: command.to_owned(),
+            env: vec![("GREETING".to_owned(), "hello".to_owned())],
+        };
+        let actions = [
+            Action::Checkout,
+            run_step("echo \"$GREETING $CI\" > out.txt"),
+            run_step("exit 3"),
+            run_step("touch never.txt"),
+        ];
+
+        let mut commands = Vec::new();
+        let (status, _) = run(scratch.path(), &actions, |command| {
+            commands.push(command.to_owned());
+        })
+        .unwrap();
+        assert_eq!(
+            status,
+            Status::Failed {
+                step: 2,
+                code: Some(3)
+            }
+        );
+        assert_eq!(commands, ["echo \"$GREETING $CI\" > out.txt", "exit 3"]);
+        assert_eq!(
+            std::fs::read_to_string(scratch.path().join("out.txt")).unwrap(),
+            "hello true\n"
+        );
+        assert!(!scratch.path().join("never.txt").exists());
+
+        let (status, _) = run(scratch.path(), &actions[..2], |_| {}).unwrap();
+        assert_eq!(status, Status::Passed);
+    }
+}
@@ -0,0 +1,557 @@
+//! Skipping CI stages whose inputs haven't changed since they last passed.
+//!
+//! A stage's [`Fingerprint`] maps each of its inputs to a hash (or, for the
+//! inputs small enough, the value itself):
+//!
+//! - the crate's files that can affect a build: every `.rs` file, the files
+//!   they `include_str!` or `include_bytes!` (like README.md, for the crate
+//!   docs), every `Cargo.toml`, `Cargo.lock`, and the rustfmt, clippy, rustup
+//!   and Cargo configuration files. Stages that only format (`cargo fmt`)
+//!   only depend on the Rust files, the manifests and rustfmt's
+//!   configuration. A missing `Cargo.lock` is generated first, as the
+//!   stages' first build would, so that it isn't new the next time;
+//! - the exact version of each toolchain the stage uses;
+//! - `RUSTFLAGS`, `RUSTDOCFLAGS` and `MIRIFLAGS`;
+//! - the stage's own steps.
+//!
+//! The [`Cache`] keeps the fingerprint of each stage's last successful run,
+//! at [`PATH`], and a stage whose fingerprint matches needn't run again.
+//! Comparing the fingerprints also says what changed when one doesn't.
+//!
+//! Files a build script reads without `include_str!` aren't noticed, so
+//! `cargo xtask ci --no-cache` is the way to force a rerun.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    fmt::Write as _,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use super::{
+    Stage,
+    executor::{Outcome, Status},
+};
+use crate::{Result, fs, json, process};
+
+/// Where the cache lives, relative to the crate root.
+pub const PATH: &str = "target/ci/cache.json";
+
+/// Configuration files that can affect any stage.
+const CONFIG_FILES: &[&str] = &[
+    "rustfmt.toml",
+    ".rustfmt.toml",
+    "clippy.toml",
+    ".clippy.toml",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+    ".cargo/config",
+    ".cargo/config.toml",
+];
+
+/// The configuration files among [`CONFIG_FILES`] that affect `cargo fmt`.
+const FORMAT_CONFIG_FILES: &[&str] = &["rustfmt.toml", ".rustfmt.toml"];
+
+/// The environment variables that can change a stage's result.
+const ENV_VARS: &[&str] = &["RUSTFLAGS", "RUSTDOCFLAGS", "MIRIFLAGS"];
+
+/// The most changed files named when explaining why a stage reruns.
+const LISTED_FILES: usize = 3;
+
+/// The inputs of a stage, each mapped to its hash or value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The inputs, keyed `file:PATH`, `toolchain:NAME`, `env:NAME` or
+    /// `stage`.
+    inputs: BTreeMap<String, String>,
+}
+
+impl Fingerprint {
+    /// Describes how the inputs changed since `old` (an empty list if they
+    /// didn't).
+    pub fn changes_since(&self, old: &Self) -> Vec<String> {
+        let keys: BTreeSet<&String> = self.inputs.keys().chain(old.inputs.keys()).collect();
+        let mut files = Vec::new();
+        let mut changes = Vec::new();
+        for key in keys {
+            let (new, old) = (self.inputs.get(key), old.inputs.get(key));
+            if new == old {
+                continue;
+            }
+            let (kind, name) = key.split_once(':').unwrap_or((key, ""));
+            let value = |value: Option<&String>| value.map_or("", String::as_str).to_owned();
+            match (kind, old, new) {
+                ("file", None, _) => files.push(format!("{name} (new)")),
+                ("file", _, None) => files.push(format!("{name} (removed)")),
+                ("file", ..) => files.push(name.to_owned()),
+                ("toolchain", ..) => changes.push(format!(
+                    "toolchain `{name}` is now `{}` (was `{}`)",
+                    value(new),
+                    value(old)
+                )),
+                ("env", ..) => {
+                    let shown =
+                        |value: Option<&String>| match value.filter(|value| !value.is_empty()) {
+                            Some(value) => format!("`{value}`"),
+                            None => "unset".to_owned(),
+                        };
+                    changes.push(format!(
+                        "`{name}` is now {} (was {})",
+                        shown(new),
+                        shown(old)
+                    ));
+                }
+                _ => changes.push("the stage's steps changed".to_owned()),
+            }
+        }
+
+        if !files.is_empty() {
+            let mut list = files[..files.len().min(LISTED_FILES)].join(", ");
+            if files.len() > LISTED_FILES {
+                let _ = write!(list, " and {} more", files.len() - LISTED_FILES);
+            }
+            changes.insert(0, format!("{} file(s) changed: {list}", files.len()));
+        }
+        changes
+    }
+
+    fn to_json(&self) -> json::Value {
+        json::Value::Object(
+            self.inputs
+                .iter()
+                .map(|(key, value)| (key.clone(), value.as_str().into()))
+                .collect(),
+        )
+    }
+
+    fn from_json(value: &json::Value) -> Option<Self> {
+        let json::Value::Object(entries) = value else {
+            return None;
+        };
+        let inputs = entries
+            .iter()
+            .map(|(key, value)| match value {
+                json::Value::String(value) => Some((key.clone(), value.clone())),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { inputs })
+    }
+}
+
+/// The inputs of every stage of a crate, gathered once and shared between
+/// the stages' fingerprints.
+#[derive(Debug, Clone)]
+pub struct Inputs {
+    /// The hash of each input file, by its `/`-separated path relative to the
+    /// crate root.
+    files: BTreeMap<String, String>,
+    /// The version of each toolchain looked up so far, or [`None`] if it
+    /// couldn't be determined.
+    toolchains: BTreeMap<String, Option<String>>,
+}
+
+impl Inputs {
+    /// Hashes the input files of the crate at `root`, after letting cargo
+    /// write its `Cargo.lock` if it has none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the crate's files cannot be listed or read, or
+    /// cargo cannot be run.
+    pub fn collect(root: &Path) -> Result<Self> {
+        resolve_lockfile(root)?;
+
+        let mut paths = BTreeSet::new();
+        for path in fs::walk(root)? {
+            let name = slash_path(&path);
+            if path.extension().is_some_and(|extension| extension == "rs") {
+                paths.extend(included(root, &path)?);
+                paths.insert(name);
+            } else if path.file_name().is_some_and(|file| file == "Cargo.toml")
+                || name == "Cargo.lock"
+                || CONFIG_FILES.contains(&name.as_str())
+            {
+                paths.insert(name);
+            }
+        }
+
+        let mut files = BTreeMap::new();
+        for path in paths {
+            let hash = hash(&fs::read_bytes(&root.join(&path))?);
+            files.insert(path, hash);
+        }
+        Ok(Self {
+            files,
+            toolchains: BTreeMap::new(),
+        })
+    }
+
+    /// The fingerprint of `stage`, or [`None`] if the version of one of its
+    /// toolchains can't be determined (so it always runs).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `rustc` cannot be run.
+    pub fn fingerprint(&mut self, stage: &Stage) -> Result<Option<Fingerprint>> {
+        let mut inputs = BTreeMap::new();
+        let builds = stage.steps.iter().any(super::Step::builds);
+        for (path, hash) in &self.files {
+            let formatted = Path::new(path)
+                .extension()
+                .is_some_and(|extension| extension == "rs")
+                || path.ends_with("Cargo.toml")
+                || FORMAT_CONFIG_FILES.contains(&path.as_str());
+            if builds || formatted {
+                inputs.insert(format!("file:{path}"), hash.clone());
+            }
+        }
+
+        for step in &stage.steps {
+            if !self.toolchains.contains_key(&step.toolchain) {
+                // Like `rustc 1.85.0 (4d91de4e4 2025-02-17)`, which even
+                // tells nightlies apart
+                let version = process::stdout(
+                    Command::new("rustc")
+                        .arg(format!("+{}", step.toolchain))
+                        .arg("--version"),
+                )?
+                .map(|version| version.trim().to_owned());
+                self.toolchains.insert(step.toolchain.clone(), version);
+            }
+            let Some(version) = &self.toolchains[&step.toolchain] else {
+                return Ok(None);
+            };
+            inputs.insert(format!("toolchain:{}", step.toolchain), version.clone());
+        }
+
+        for var in ENV_VARS {
+            inputs.insert(format!("env:{var}"), env::var(var).unwrap_or_default());
+        }
+        inputs.insert(
+            "stage".to_owned(),
+            hash(format!("{:?}", stage.steps).as_bytes()),
+        );
+
+        Ok(Some(Fingerprint { inputs }))
+    }
+}
+
+/// Has cargo resolve the dependencies of the crate at `root` if it has no
+/// `Cargo.lock` yet. Otherwise the first stage to build would write it, and
+/// it would count as a new input the next time. `cargo metadata` keeps an
+/// existing lockfile as it is, unlike `cargo generate-lockfile`; if it fails,
+/// the stages will report why.
+fn resolve_lockfile(root: &Path) -> Result<()> {
+    if root.join("Cargo.toml").is_file() && !root.join("Cargo.lock").exists() {
+        process::output(
+            Command::new("cargo")
+                .args(["metadata", "--format-version", "1", "--manifest-path"])
+                .arg(root.join("Cargo.toml")),
+        )?;
+    }
+    Ok(())
+}
+
+/// The files that the Rust file at `path` (relative to `root`) includes with
+/// `include_str!` or `include_bytes!` and a literal path, as `/`-separated
+/// paths relative to `root`.
+fn included(root: &Path, path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_bytes(&root.join(path))?;
+    let contents = String::from_utf8_lossy(&contents);
+    let contents = contents.as_ref();
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut files = Vec::new();
+    for rest in ["include_str!", "include_bytes!"]
+        .into_iter()
+        .flat_map(|name| {
+            contents
+                .match_indices(name)
+                .map(move |(index, _)| &contents[index + name.len()..])
+        })
+    {
+        let Some(rest) = rest.trim_start().strip_prefix('(') else {
+            continue;
+        };
+        let Some(literal) = rest.trim_start().strip_prefix('"') else {
+            continue;
+        };
+        let Some((literal, _)) = literal.split_once('"') else {
+            continue;
+        };
+
+        // Resolve `..` without touching the file system, ignoring paths that
+        // leave the crate
+        let mut resolved = PathBuf::new();
+        let mut outside = false;
+        for component in directory.join(literal).components() {
+            match component {
+                Component::ParentDir => outside |= !resolved.pop(),
+                Component::Normal(part) => resolved.push(part),
+                _ => outside = true,
+            }
+        }
+        if !outside && root.join(&resolved).is_file() {
+            files.push(slash_path(&resolved));
+        }
+    }
+    Ok(files)
+}
+
+/// `path` with `/` between its components, whatever the platform.
+fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, in hex. It only has to tell versions of
+/// the same file apart, so it needn't be cryptographic.
+fn hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// The fingerprints of the last successful run of each stage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cache {
+    stages: BTreeMap<String, Fingerprint>,
+}
+
+impl Cache {
+    /// Loads the cache of the crate at `root`. A missing or unreadable cache
+    /// is an empty one, since it only ever saves time.
+    pub fn load(root: &Path) -> Self {
+        let path = root.join(PATH);
+        let Some(value) = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| json::Value::parse(&path, &contents).ok())
+        else {
+            return Self::default();
+        };
+        let Some(json::Value::Object(stages)) = value.get("stages") else {
+            return Self::default();
+        };
+
+        Self {
+            stages: stages
+                .iter()
+                .filter_map(|(stage, fingerprint)| {
+                    Some((stage.clone(), Fingerprint::from_json(fingerprint)?))
+                })
+                .collect(),
+        }
+    }
+
+    /// Why `stage` has to run given its current `fingerprint`, or [`None`]
+    /// if it passed before with the same one.
+    pub fn reason_to_run(&self, stage: &str, fingerprint: Option<&Fingerprint>) -> Option<String> {
+        let Some(fingerprint) = fingerprint else {
+            return Some("a toolchain's version couldn't be determined".to_owned());
+        };
+        let Some(cached) = self.stages.get(stage) else {
+            return Some("it has no successful run recorded".to_owned());
+        };
+        let changes = fingerprint.changes_since(cached);
+        (!changes.is_empty()).then(|| changes.join("; "))
+    }
+
+    /// Records how a stage with the given `fingerprint` went: a pass is
+    /// remembered, a failure forgets the stage's last pass, and a skip
+    /// changes nothing.
+    pub fn record(&mut self, outcome: &Outcome, fingerprint: Option<&Fingerprint>) {
+        match (&outcome.status, fingerprint) {
+            (Status::Passed, Some(fingerprint)) => {
+                self.stages
+                    .insert(outcome.stage.to_owned(), fingerprint.clone());
+            }
+            (Status::Passed | Status::Failed { .. }, _) => {
+                self.stages.remove(outcome.stage);
+            }
+            (Status::Skipped(_) | Status::Cached, _) => {}
+        }
+    }
+
+    /// Saves the cache of the crate at `root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache cannot be written.
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let stages = self
+            .stages
+            .iter()
+            .map(|(stage, fingerprint)| (stage.clone(), fingerprint.to_json()))
+            .collect();
+        let value = json::Value::object([("stages", json::Value::Object(stages))]);
+        fs::write(&root.join(PATH), &(value.to_pretty_string() + "\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn fingerprint(inputs: &[(&str, &str)]) -> Fingerprint {
+        Fingerprint {
+            inputs: inputs
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+        }
+    }
+
+    fn outcome(status: Status) -> Outcome {
+        Outcome {
+            stage: "lint",
+            status,
+            duration: Duration::ZERO,
+            output: String::new(),
+        }
+    }
+
+    #[test]
+    fn changes() {
+        let old = fingerprint(&[
+            ("file:a.rs", "1"),
+            ("file:b.rs", "2"),
+            ("toolchain:stable", "rustc 1.84.0"),
+            ("env:RUSTFLAGS", ""),
+            ("stage", "1"),
+        ]);
+        assert!(old.changes_since(&old).is_empty());
+
+        let new = fingerprint(&[
+            ("file:a.rs", "3"),
+            ("file:c.rs", "4"),
+            ("toolchain:stable", "rustc 1.85.0"),
+            ("env:RUSTFLAGS", "-Dwarnings"),
+            ("stage", "2"),
+        ]);
+        assert_eq!(
+            new.changes_since(&old),
+            [
+                "3 file(s) changed: a.rs, b.rs (removed), c.rs (new)",
+                "`RUSTFLAGS` is now `-Dwarnings` (was unset)",
+                "the stage's steps changed",
+                "toolchain `stable` is now `rustc 1.85.0` (was `rustc 1.84.0`)",
+            ]
+        );
+
+        let many = fingerprint(&[
+            ("file:a.rs", "1"),
+            ("file:b.rs", "1"),
+            ("file:c.rs", "1"),
+            ("file:d.rs", "1"),
+        ]);
+        assert_eq!(
+            many.changes_since(&Fingerprint::default()),
+            ["4 file(s) changed: a.rs (new), b.rs (new), c.rs (new) and 1 more"]
+        );
+    }
+
+    #[test]
+    fn reasons_and_records() {
+        let passed = fingerprint(&[("file:a.rs", "1")]);
+        let mut cache = Cache::default();
+        assert_eq!(
+            cache.reason_to_run("lint", Some(&passed)).as_deref(),
+            Some("it has no successful run recorded")
+        );
+
+        cache.record(&outcome(Status::Passed), Some(&passed));
+        assert_eq!(cache.reason_to_run("lint", Some(&passed)), None);
+        assert_eq!(
+            cache.reason_to_run("lint", None).as_deref(),
+            Some("a toolchain's version couldn't be determined")
+        );
+        assert_eq!(
+            cache
+                .reason_to_run("lint", Some(&fingerprint(&[("file:a.rs", "2")])))
+                .as_deref(),
+            Some("1 file(s) changed: a.rs")
+        );
+
+        cache.record(&outcome(Status::Skipped("no miri".to_owned())), None);
+        cache.record(&outcome(Status::Cached), None);
+        assert_eq!(cache.reason_to_run("lint", Some(&passed)), None);
+
+        let failed = Status::Failed {
+            step: 0,
+            code: Some(1),
+        };
+        cache.record(&outcome(failed), Some(&passed));
+        assert!(cache.reason_to_run("lint", Some(&passed)).is_some());
+
+        // A pass without a fingerprint can't be compared later
+        cache.record(&outcome(Status::Passed), Some(&passed));
+        cache.record(&outcome(Status::Passed), None);
+        assert!(cache.reason_to_run("lint", Some(&passed)).is_some());
+    }
+
+    #[test]
+    fn saved_and_loaded() {
+        let scratch = fs::Scratch::new(&[]);
+        assert_eq!(Cache::load(scratch.path()), Cache::default());
+
+        let mut cache = Cache::default();
+        let passed = fingerprint(&[("file:a.rs", "1"), ("env:RUSTFLAGS", "")]);
+        cache.record(&outcome(Status::Passed), Some(&passed));
+        cache.save(scratch.path()).unwrap();
+        assert_eq!(Cache::load(scratch.path()), cache);
+
+        fs::write(&scratch.path().join(PATH), "{ not json").unwrap();
+        assert_eq!(Cache::load(scratch.path()), Cache::default());
+    }
+
+    #[test]
+    fn inputs() {
+        let scratch = fs::Scratch::new(&[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            ),
+            (
+                "src/lib.rs",
+                "#![doc = include_str!(\"../README.md\")]\nconst _: &str = include_str!( \"../../outside\");\n",
+            ),
+            ("README.md", "# demo\n"),
+            ("notes.txt", "not an input\n"),
+            ("rustfmt.toml", ""),
+        ]);
+        let root = scratch.path();
+        assert_eq!(
+            included(root, Path::new("src/lib.rs")).unwrap(),
+            ["README.md"]
+        );
+
+        // The lockfile cargo writes on the first run is already there, so it
+        // isn't a new file on the next
+        let first = Inputs::collect(root).unwrap();
+        assert!(root.join("Cargo.lock").is_file());
+        assert_eq!(
+            first.files.keys().collect::<Vec<_>>(),
+            [
+                "Cargo.lock",
+                "Cargo.toml",
+                "README.md",
+                "rustfmt.toml",
+                "src/lib.rs"
+            ]
+        );
+        assert_eq!(Inputs::collect(root).unwrap().files, first.files);
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(hash(b""), "cbf29ce484222325");
+        assert_eq!(hash(b"a"), "af63dc4c8601ec8c");
+        assert_ne!(hash(b"ab"), hash(b"ba"));
+    }
+}
@@ -0,0 +1,368 @@
+//! Running CI stages, independent ones in parallel.
+//!
+//! [`run`] starts every stage whose dependencies have succeeded as soon as a
+//! job slot is free and no running stage holds one of its exclusive
+//! [`Resource`](super::Resource)s. A stage's output (stdout and stderr together) is buffered
+//! and handed over whole once the stage finishes, so the output of stages
+//! running at the same time never interleaves.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    path::Path,
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::Stage;
+use crate::process;
+
+/// How to run the stages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// The most stages to run at once (at least 1).
+    pub jobs: usize,
+    /// Whether to stop starting stages once one has failed.
+    pub fail_fast: bool,
+}
+
+impl Options {
+    /// The default number of jobs: the available parallelism, capped at 4
+    /// since every stage is itself a parallel build.
+    pub fn default_jobs() -> usize {
+        thread::available_parallelism().map_or(1, |jobs| jobs.get().min(4))
+    }
+}
+
+/// How a stage ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Every step succeeded.
+    Passed,
+    /// A step failed.
+    Failed {
+        /// The index of the step that failed.
+        step: usize,
+        /// The step's exit code, if it ran and exited normally.
+        code: Option<i32>,
+    },
+    /// The stage didn't run, for the given reason.
+    Skipped(String),
+    /// The stage didn't run, since it passed before with the same inputs (see
+    /// [`cache`](super::cache)).
+    Cached,
+}
+
+impl Status {
+    /// Whether the stage passed, now or in a cached run.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Passed | Self::Cached)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed => f.write_str("passed"),
+            Self::Failed {
+                code: Some(code), ..
+            } => write!(f, "failed (exit code {code})"),
+            Self::Failed { code: None, .. } => f.write_str("failed"),
+            Self::Skipped(reason) => write!(f, "skipped ({reason})"),
+            Self::Cached => f.write_str("cached"),
+        }
+    }
+}
+
+/// The outcome of one stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// The stage's name.
+    pub stage: &'static str,
+    /// How the stage ended.
+    pub status: Status,
+    /// How long the stage took to run.
+    pub duration: Duration,
+    /// The commands the stage ran and everything they printed.
+    pub output: String,
+}
+
+/// Something that happened while running the stages.
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    /// A stage started.
+    Started(&'a Stage),
+    /// A stage finished or was skipped.
+    Finished(&'a Outcome),
+}
+
+/// Runs `stages` of the crate at `root`, calling `on_event` as they start and
+/// finish, and returns their outcomes in the order of `stages`.
+///
+/// A stage only runs once the stages it depends on have passed, and is
+/// skipped if one of them didn't. Dependencies that aren't among `stages` are
+/// assumed to have passed.
+pub fn run(
+    root: &Path,
+    stages: &[&Stage],
+    options: Options,
+    mut on_event: impl FnMut(Event<'_>),
+) -> Vec<Outcome> {
+    let mut outcomes: BTreeMap<&str, Outcome> = BTreeMap::new();
+    let mut pending: Vec<&Stage> = stages.to_vec();
+    let mut running: Vec<&Stage> = Vec::new();
+    let selected = |name: &str| stages.iter().any(|stage| stage.name == name);
+
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        loop {
+            let failed = outcomes
+                .values()
+                .any(|outcome| matches!(outcome.status, Status::Failed { .. }));
+
+            let mut skipped = false;
+            let mut index = 0;
+            while index < pending.len() {
+                let stage = pending[index];
+                // A dependency that didn't pass skips the stage, while one
+                // that hasn't finished yet holds it back
+                let mut waiting = false;
+                let mut skip = None;
+                for &dependency in &stage.dependencies {
+                    match outcomes.get(dependency) {
+                        Some(outcome) if !outcome.status.is_success() => {
+                            skip = Some(format!("`{dependency}` didn't pass"));
+                            break;
+                        }
+                        None if selected(dependency) => waiting = true,
+                        _ => {}
+                    }
+                }
+                if skip.is_none() && failed && options.fail_fast {
+                    skip = Some("an earlier stage failed".to_owned());
+                }
+                if let Some(reason) = skip {
+                    pending.remove(index);
+                    let outcome = Outcome {
+                        stage: stage.name,
+                        status: Status::Skipped(reason),
+                        duration: Duration::ZERO,
+                        output: String::new(),
+                    };
+                    on_event(Event::Finished(&outcome));
+                    outcomes.insert(stage.name, outcome);
+                    skipped = true;
+                    continue;
+                }
+
+                let resources = stage.resources();
+                let conflicts = running.iter().any(|other| {
+                    other
+                        .resources()
+                        .iter()
+                        .any(|resource| resource.is_exclusive() && resources.contains(resource))
+                });
+                if waiting || conflicts || running.len() >= options.jobs.max(1) {
+                    index += 1;
+                    continue;
+                }
+
+                pending.remove(index);
+                running.push(stage);
+                on_event(Event::Started(stage));
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let _ = sender.send(run_stage(root, stage));
+                });
+            }
+
+            if running.is_empty() {
+                // Skipping a stage may have decided the fate of one before it
+                if skipped && !pending.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            let Ok(outcome) = receiver.recv() else {
+                break;
+            };
+            running.retain(|stage| stage.name != outcome.stage);
+            on_event(Event::Finished(&outcome));
+            outcomes.insert(outcome.stage, outcome);
+        }
+    });
+
+    stages
+        .iter()
+        .filter_map(|stage| outcomes.remove(stage.name))
+        .collect()
+}
+
+/// Runs the steps of `stage` one after another, stopping at the first that
+/// fails.
+fn run_stage(root: &Path, stage: &Stage) -> Outcome {
+    let start = Instant::now();
+    let mut output = String::new();
+    let mut status = Status::Passed;
+
+    for (index, step) in stage.steps.iter().enumerate() {
+        let _ = writeln!(output, "$ {}", step.command_line());
+        let mut command = Command::new("cargo");
+        command
+            .arg(format!("+{}", step.toolchain))
+            .args(&step.args)
+            .envs(step.env.iter().map(|(key, value)| (key, value)))
+            .current_dir(root);
+        if step.builds() {
+            command.env("CARGO_TARGET_DIR", root.join(step.target_dir()));
+        }
+
+        match process::combined_output(&mut command) {
+            Ok((exit, printed)) => {
+                output.push_str(&printed);
+                if !exit.success() {
+                    status = Status::Failed {
+                        step: index,
+                        code: exit.code(),
+                    };
+                }
+            }
+            Err(error) => {
+                let _ = writeln!(output, "error: {error}");
+                status = Status::Failed {
+                    step: index,
+                    code: None,
+                };
+            }
+        }
+        if status != Status::Passed {
+            break;
+        }
+    }
+
+    Outcome {
+        stage: stage.name,
+        status,
+        duration: start.elapsed(),
+        output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ci::Step;
+
+    /// A stage that passes right away, or fails at once if `fails`, since
+    /// there is no toolchain of that name.
+    fn stage(name: &'static str, dependencies: &[&'static str], fails: bool) -> Stage {
+        Stage {
+            name,
+            title: name.to_owned(),
+            steps: if fails {
+                vec![Step::new("no such toolchain", &["check"])]
+            } else {
+                Vec::new()
+            },
+            dependencies: dependencies.to_vec(),
+            condition: None,
+        }
+    }
+
+    /// Runs `stages` one at a time, returning the stages in the order they
+    /// started and how each ended (without the exit code of failed steps).
+    fn run_one_at_a_time(
+        stages: &[Stage],
+        fail_fast: bool,
+    ) -> (Vec<&'static str>, Vec<(&'static str, Status)>) {
+        let stages: Vec<&Stage> = stages.iter().collect();
+        let mut started = Vec::new();
+        let options = Options { jobs: 1, fail_fast };
+        let outcomes = run(Path::new("."), &stages, options, |event| {
+            if let Event::Started(stage) = event {
+                started.push(stage.name);
+            }
+        });
+        let statuses = outcomes
+            .into_iter()
+            .map(|outcome| match outcome.status {
+                Status::Failed { step, .. } => (outcome.stage, Status::Failed { step, code: None }),
+                status => (outcome.stage, status),
+            })
+            .collect();
+        (started, statuses)
+    }
+
+    #[test]
+    fn dependencies() {
+        let stages = [
+            stage("test", &["build"], false),
+            stage("build", &["fmt"], false),
+            stage("fmt", &[], true),
+            stage("docs", &["lint"], false),
+        ];
+        let (started, statuses) = run_one_at_a_time(&stages, false);
+        assert_eq!(started, ["fmt", "docs"]);
+        assert_eq!(
+            statuses,
+            [
+                ("test", Status::Skipped("`build` didn't pass".to_owned())),
+                ("build", Status::Skipped("`fmt` didn't pass".to_owned())),
+                (
+                    "fmt",
+                    Status::Failed {
+                        step: 0,
+                        code: None
+                    }
+                ),
+                // `lint` wasn't selected, so it's assumed to have passed
+                ("docs", Status::Passed),
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_fast() {
+        let stages = [
+            stage("fmt", &[], true),
+            stage("lint", &[], false),
+            stage("test", &[], false),
+        ];
+        let skipped = Status::Skipped("an earlier stage failed".to_owned());
+        assert_eq!(
+            run_one_at_a_time(&stages, true).1,
+            [
+                (
+                    "fmt",
+                    Status::Failed {
+                        step: 0,
+                        code: None
+                    }
+                ),
+                ("lint", skipped.clone()),
+                ("test", skipped),
+            ]
+        );
+        assert!(
+            run_one_at_a_time(&stages, false).1[1..]
+                .iter()
+                .all(|(_, status)| *status == Status::Passed)
+        );
+    }
+
+    #[test]
+    fn failed_steps_show_their_command() {
+        let failing = stage("fmt", &[], true);
+        let outcome = run_stage(Path::new("."), &failing);
+        assert!(!outcome.status.is_success());
+        assert!(
+            outcome
+                .output
+                .starts_with("$ cargo +no such toolchain check\n"),
+            "{}",
+            outcome.output
+        );
+    }
+}
@@ -0,0 +1,567 @@
+//! Reports of a CI run, for people and for other tools.
+//!
+//! A [`Report`] pairs each stage that was selected with its
+//! [`Outcome`](super::executor::Outcome) and renders them as a summary table,
+//! as JSON, or as `JUnit` XML (one test case per stage, which CI services know
+//! how to display). Each stage's captured output is searched for libtest's
+//! `test result:` lines, so the reports say how many tests ran too.
+
+use std::{fmt::Write as _, time::Duration};
+
+use super::{
+    Stage,
+    executor::{Outcome, Status},
+};
+use crate::json;
+
+/// The test counts libtest printed while a stage ran, summed over every test
+/// binary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestSummary {
+    /// The number of tests that passed.
+    pub passed: usize,
+    /// The number of tests that failed.
+    pub failed: usize,
+    /// The number of tests that were ignored.
+    pub ignored: usize,
+    /// The number of benchmarks that were measured.
+    pub measured: usize,
+    /// The number of tests that were filtered out.
+    pub filtered_out: usize,
+}
+
+impl TestSummary {
+    /// Sums the `test result: ok. 3 passed; 0 failed; ...` lines of
+    /// `output`, or returns [`None`] if there are none.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut summary = None;
+        for line in strip_ansi(output).lines() {
+            let Some((_, counts)) = line.split_once("test result: ") else {
+                continue;
+            };
+            let summary = summary.get_or_insert_with(Self::default);
+            // `ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s`
+            let counts = counts.split_once(". ").map_or(counts, |(_, counts)| counts);
+            for count in counts.split("; ") {
+                let Some((number, what)) = count.split_once(' ') else {
+                    continue;
+                };
+                let Ok(number) = number.parse::<usize>() else {
+                    continue;
+                };
+                let total = match what {
+                    "passed" => &mut summary.passed,
+                    "failed" => &mut summary.failed,
+                    "ignored" => &mut summary.ignored,
+                    "measured" => &mut summary.measured,
+                    "filtered out" => &mut summary.filtered_out,
+                    _ => continue,
+                };
+                *total += number;
+            }
+        }
+        summary
+    }
+
+    /// Converts the summary to JSON.
+    pub fn to_json(self) -> json::Value {
+        json::Value::object([
+            ("passed", self.passed.into()),
+            ("failed", self.failed.into()),
+            ("ignored", self.ignored.into()),
+            ("measured", self.measured.into()),
+            ("filtered_out", self.filtered_out.into()),
+        ])
+    }
+}
+
+/// The stages of a CI run with their outcomes, in order.
+#[derive(Debug, Clone)]
+pub struct Report<'a> {
+    /// Each stage with its outcome.
+    pub stages: Vec<(&'a Stage, &'a Outcome)>,
+    /// How long the whole run took.
+    pub duration: Duration,
+}
+
+impl<'a> Report<'a> {
+    /// Pairs `stages` with their `outcomes` (stages without one are left
+    /// out).
+    pub fn new(stages: &[&'a Stage], outcomes: &'a [Outcome], duration: Duration) -> Self {
+        let stages = stages
+            .iter()
+            .filter_map(|&stage| {
+                let outcome = outcomes
+                    .iter()
+                    .find(|outcome| outcome.stage == stage.name)?;
+                Some((stage, outcome))
+            })
+            .collect();
+        Self { stages, duration }
+    }
+
+    /// Whether every stage passed.
+    pub fn passed(&self) -> bool {
+        self.stages
+            .iter()
+            .all(|(_, outcome)| outcome.status.is_success())
+    }
+
+    /// The number of stages whose status `matches`.
+    fn count(&self, matches: impl Fn(&Status) -> bool) -> usize {
+        self.stages
+            .iter()
+            .filter(|(_, outcome)| matches(&outcome.status))
+            .count()
+    }
+
+    /// The number of stages that failed.
+    fn failed(&self) -> usize {
+        self.count(|status| matches!(status, Status::Failed { .. }))
+    }
+
+    /// The number of stages that were skipped.
+    fn skipped(&self) -> usize {
+        self.count(|status| matches!(status, Status::Skipped(_)))
+    }
+
+    /// A table with a row per stage: its status, how long it took and its
+    /// test counts.
+    pub fn summary(&self) -> String {
+        let rows: Vec<[String; 4]> = self
+            .stages
+            .iter()
+            .map(|(stage, outcome)| {
+                let tests = TestSummary::parse(&outcome.output).map_or_else(
+                    || "-".to_owned(),
+                    |tests| {
+                        format!(
+                            "{} passed, {} failed, {} ignored",
+                            tests.passed, tests.failed, tests.ignored
+                        )
+                    },
+                );
+                [
+                    stage.name.to_owned(),
+                    outcome.status.to_string(),
+                    format!("{:.1}s", outcome.duration.as_secs_f64()),
+                    tests,
+                ]
+            })
+            .collect();
+
+        let header = ["Stage", "Status", "Time", "Tests"].map(str::to_owned);
+        let mut widths = header.clone().map(|cell| cell.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut table = String::new();
+        for row in std::iter::once(&header).chain(&rows) {
+            let [stage, status, time, tests] = row;
+            let _ = writeln!(
+                table,
+                "{stage:stage_width$}  {status:status_width$}  {time:>time_width$}  {tests}",
+                stage_width = widths[0],
+                status_width = widths[1],
+                time_width = widths[2],
+            );
+        }
+        let _ = writeln!(
+            table,
+            "{} passed, {} cached, {} failed, {} skipped in {:.1}s",
+            self.count(|status| *status == Status::Passed),
+            self.count(|status| *status == Status::Cached),
+            self.failed(),
+            self.skipped(),
+            self.duration.as_secs_f64()
+        );
+        table
+    }
+
+    /// Converts the report to JSON.
+    pub fn to_json(&self) -> json::Value {
+        let stages = self
+            .stages
+            .iter()
+            .map(|(stage, outcome)| {
+                let (status, reason, failed) = match &outcome.status {
+                    Status::Passed => ("passed", None, None),
+                    Status::Failed { step, code } => ("failed", None, Some((*step, *code))),
+                    Status::Skipped(reason) => ("skipped", Some(reason.clone()), None),
+                    Status::Cached => ("cached", None, None),
+                };
+                let steps = stage
+                    .steps
+                    .iter()
+                    .map(|step| {
+                        let env = step
+                            .env
+                            .iter()
+                            .map(|(key, value)| (key.clone(), value.as_str().into()))
+                            .collect();
+                        json::Value::object([
+                            ("command", step.command_line().into()),
+                            ("toolchain", step.toolchain.as_str().into()),
+                            ("env", json::Value::Object(env)),
+                        ])
+                    })
+                    .collect();
+                let failed_step = failed.map(|(step, code)| {
+                    json::Value::object([
+                        ("index", step.into()),
+                        ("command", stage.steps[step].command_line().into()),
+                        ("toolchain", stage.steps[step].toolchain.as_str().into()),
+                        (
+                            "exit_code",
+                            code.map_or(json::Value::Null, |code| {
+                                json::Value::Number(code.to_string())
+                            }),
+                        ),
+                    ])
+                });
+
+                json::Value::object([
+                    ("name", stage.name.into()),
+                    ("title", stage.title.as_str().into()),
+                    ("status", status.into()),
+                    ("reason", reason.into()),
+                    ("duration_secs", seconds(outcome.duration)),
+                    ("steps", json::Value::Array(steps)),
+                    ("failed_step", failed_step.unwrap_or(json::Value::Null)),
+                    (
+                        "tests",
+                        TestSummary::parse(&outcome.output)
+                            .map_or(json::Value::Null, TestSummary::to_json),
+                    ),
+                    ("output", strip_ansi(&outcome.output).into()),
+                ])
+            })
+            .collect();
+
+        json::Value::object([
+            ("passed", self.passed().into()),
+            ("duration_secs", seconds(self.duration)),
+            ("stages", json::Value::Array(stages)),
+        ])
+    }
+
+    /// Converts the report to `JUnit` XML, with a test case per stage.
+    pub fn to_junit(&self) -> String {
+        let counts = format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\"",
+            self.stages.len(),
+            self.failed(),
+            self.skipped() + self.count(|status| *status == Status::Cached),
+            self.duration.as_secs_f64()
+        );
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(xml, "<testsuites name=\"CI\" {counts}>");
+        let _ = writeln!(xml, "  <testsuite name=\"ci\" {counts}>");
+        for (stage, outcome) in &self.stages {
+            let _ = writeln!(
+                xml,
+                "    <testcase classname=\"ci\" name=\"{}\" time=\"{:.3}\">",
+                stage.name,
+                outcome.duration.as_secs_f64()
+            );
+            if let Some(tests) = TestSummary::parse(&outcome.output) {
+                xml.push_str("      <properties>\n");
+                for (name, count) in [
+                    ("passed", tests.passed),
+                    ("failed", tests.failed),
+                    ("ignored", tests.ignored),
+                    ("measured", tests.measured),
+                    ("filtered_out", tests.filtered_out),
+                ] {
+                    let _ = writeln!(
+                        xml,
+                        "        <property name=\"tests.{name}\" value=\"{count}\"/>"
+                    );
+                }
+                xml.push_str("      </properties>\n");
+            }
+            match &outcome.status {
+                Status::Passed => {}
+                Status::Failed { step, .. } => {
+                    let _ = writeln!(
+                        xml,
+                        "      <failure message=\"{}\" type=\"{}\"/>",
+                        escape(&outcome.status.to_string()),
+                        escape(&stage.steps[*step].command_line())
+                    );
+                }
+                Status::Skipped(reason) => {
+                    let _ = writeln!(xml, "      <skipped message=\"{}\"/>", escape(reason));
+                }
+                Status::Cached => {
+                    xml.push_str(
+                        "      <skipped message=\"cached: passed before with the same inputs\"/>\n",
+                    );
+                }
+            }
+            if !outcome.output.is_empty() {
+                let _ = writeln!(
+                    xml,
+                    "      <system-out><![CDATA[{}]]></system-out>",
+                    xml_text(&outcome.output).replace("]]>", "]]]]><![CDATA[>")
+                );
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+/// `duration` in seconds, as a JSON number with millisecond precision.
+fn seconds(duration: Duration) -> json::Value {
+    json::Value::Number(format!("{:.3}", duration.as_secs_f64()))
+}
+
+/// Removes the ANSI escape sequences (colors, mostly) from `text`.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+        // `ESC [ parameters letter`; any other escape is dropped on its own
+        if chars.clone().next() == Some('[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+/// `text` without ANSI escapes and the control characters XML can't hold.
+fn xml_text(text: &str) -> String {
+    strip_ansi(text)
+        .chars()
+        .filter(|&c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+/// Escapes `text` for an XML attribute.
+fn escape(text: &str) -> String {
+    xml_text(text)
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::ci::Step;
+
+    const TEST_OUTPUT: &str = "\
+running 3 tests
+test result: \u{1b}[32mok\u{1b}[0m. 3 passed; 0 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.01s
+running 2 tests
+test result: FAILED. 1 passed; 1 failed; 0 ignored; 0 measured; 2 filtered out; finished in 0.00s
+";
+
+    fn stage(name: &'static str, steps: Vec<Step>) -> Stage {
+        Stage {
+            name,
+            title: format!("Running {name}"),
+            steps,
+            dependencies: Vec::new(),
+            condition: None,
+        }
+    }
+
+    fn outcome(stage: &'static str, status: Status, output: &str) -> Outcome {
+        Outcome {
+            stage,
+            status,
+            duration: Duration::from_millis(1500),
+            output: output.to_owned(),
+        }
+    }
+
+    /// A run where `check` passed, `test` failed in its second step, `miri`
+    /// was skipped and `docs` was cached (and `unused` has no outcome).
+    fn fixture() -> (Vec<Stage>, Vec<Outcome>) {
+        let stages = vec![
+            stage("check", vec![Step::new("stable", &["check"])]),
+            stage(
+                "test",
+                vec![
+                    Step::new("stable", &["build"]),
+                    Step::new("stable", &["test"]).env("RUST_BACKTRACE", "1"),
+                ],
+            ),
+            stage("miri", vec![Step::new("nightly", &["miri", "test"])]),
+            stage("docs", vec![Step::new("stable", &["doc"])]),
+            stage("unused", Vec::new()),
+        ];
+        let outcomes = vec![
+            outcome("check", Status::Passed, ""),
+            outcome(
+                "test",
+                Status::Failed {
+                    step: 1,
+                    code: Some(101),
+                },
+                TEST_OUTPUT,
+            ),
+            outcome(
+                "miri",
+                Status::Skipped("needs <nightly> & \"miri\"".to_owned()),
+                "",
+            ),
+            outcome("docs", Status::Cached, ""),
+        ];
+        (stages, outcomes)
+    }
+
+    #[test]
+    fn sums_test_results() {
+        assert_eq!(
+            TestSummary::parse(TEST_OUTPUT),
+            Some(TestSummary {
+                passed: 4,
+                failed: 1,
+                ignored: 1,
+                measured: 0,
+                filtered_out: 2,
+            })
+        );
+        assert_eq!(TestSummary::parse("Compiling demo v0.1.0\n"), None);
+    }
+
+    #[test]
+    fn strips_escapes() {
+        assert_eq!(strip_ansi("\u{1b}[1;31merror\u{1b}[0m: x"), "error: x");
+        assert_eq!(xml_text("a\u{7}b\tc\n"), "ab\tc\n");
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn summarizes() {
+        let (stages, outcomes) = fixture();
+        let stages: Vec<&Stage> = stages.iter().collect();
+        let report = Report::new(&stages, &outcomes, Duration::from_secs(6));
+        assert!(!report.passed());
+        assert_eq!(
+            report.summary(),
+            "\
+Stage  Status                              Time  Tests
+check  passed                              1.5s  -
+test   failed (exit code 101)              1.5s  4 passed, 1 failed, 1 ignored
+miri   skipped (needs <nightly> & \"miri\")  1.5s  -
+docs   cached                              1.5s  -
+1 passed, 1 cached, 1 failed, 1 skipped in 6.0s
+"
+        );
+    }
+
+    #[test]
+    fn json_report_shape() {
+        let (stages, outcomes) = fixture();
+        let stages: Vec<&Stage> = stages.iter().collect();
+        let report = Report::new(&stages, &outcomes, Duration::from_secs(6));
+        let json = json::Value::parse(
+            Path::new("report.json"),
+            &report.to_json().to_pretty_string(),
+        )
+        .unwrap();
+
+        assert_eq!(json.get("passed"), Some(&json::Value::Bool(false)));
+        assert_eq!(
+            json.get("duration_secs"),
+            Some(&json::Value::Number("6.000".to_owned()))
+        );
+        let Some(json::Value::Array(stages)) = json.get("stages") else {
+            panic!("no stages in {json}");
+        };
+        let field = |stage: usize, key: &str| stages[stage].get(key).unwrap().to_string();
+        let statuses: Vec<String> = (0..stages.len())
+            .map(|stage| field(stage, "status"))
+            .collect();
+        assert_eq!(
+            statuses,
+            ["\"passed\"", "\"failed\"", "\"skipped\"", "\"cached\""]
+        );
+
+        assert_eq!(field(0, "failed_step"), "null");
+        assert_eq!(field(0, "tests"), "null");
+        assert_eq!(
+            field(1, "steps"),
+            "[{\"command\":\"cargo +stable build\",\"toolchain\":\"stable\",\"env\":{}},\
+             {\"command\":\"cargo +stable test\",\"toolchain\":\"stable\",\
+             \"env\":{\"RUST_BACKTRACE\":\"1\"}}]"
+        );
+        assert_eq!(
+            field(1, "failed_step"),
+            "{\"index\":1,\"command\":\"cargo +stable test\",\"toolchain\":\"stable\",\
+             \"exit_code\":101}"
+        );
+        assert_eq!(
+            field(1, "tests"),
+            "{\"passed\":4,\"failed\":1,\"ignored\":1,\"measured\":0,\"filtered_out\":2}"
+        );
+        assert!(!field(1, "output").contains('\u{1b}'));
+        assert_eq!(field(2, "reason"), "\"needs <nightly> & \\\"miri\\\"\"");
+    }
+
+    #[test]
+    fn junit_report_shape() {
+        let (stages, outcomes) = fixture();
+        let stages: Vec<&Stage> = stages.iter().collect();
+        let report = Report::new(&stages[..3], &outcomes, Duration::from_secs(6));
+        let counts = "tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"6.000\"";
+        assert_eq!(
+            report.to_junit(),
+            format!(
+                "\
+<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuites name=\"CI\" {counts}>
+  <testsuite name=\"ci\" {counts}>
+    <testcase classname=\"ci\" name=\"check\" time=\"1.500\">
+    </testcase>
+    <testcase classname=\"ci\" name=\"test\" time=\"1.500\">
+      <properties>
+        <property name=\"tests.passed\" value=\"4\"/>
+        <property name=\"tests.failed\" value=\"1\"/>
+        <property name=\"tests.ignored\" value=\"1\"/>
+        <property name=\"tests.measured\" value=\"0\"/>
+        <property name=\"tests.filtered_out\" value=\"2\"/>
+      </properties>
+      <failure message=\"failed (exit code 101)\" type=\"cargo +stable test\"/>
+      <system-out><![CDATA[{}]]></system-out>
+    </testcase>
+    <testcase classname=\"ci\" name=\"miri\" time=\"1.500\">
+      <skipped message=\"needs &lt;nightly&gt; &amp; &quot;miri&quot;\"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+",
+                strip_ansi(TEST_OUTPUT)
+            )
+        );
+
+        let output = outcome("check", Status::Passed, "a ]]> b");
+        let xml =
+            Report::new(&stages[..1], std::slice::from_ref(&output), Duration::ZERO).to_junit();
+        assert!(xml.contains("<system-out><![CDATA[a ]]]]><![CDATA[> b]]></system-out>"));
+    }
+}
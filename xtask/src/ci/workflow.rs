@@ -0,0 +1,410 @@
+//! Generating the GitHub Actions workflow from the [`Registry`].
+//!
+//! Each of the [`JOBS`] checks the crate out, installs the toolchains (with
+//! the components and targets) its stage's steps use, and runs the stage with
+//! `scripts/ci.py`.
+//!
+//! In the template, the jobs of stages with a [condition](Stage::condition)
+//! are wrapped in `# @if` blocks (commented out if the condition doesn't hold
+//! for the template), so instantiation keeps or drops them like any other
+//! conditional block. Other crates only get the jobs of the stages they have.
+
+use std::{collections::BTreeSet, fmt::Write as _, path::Path};
+
+use super::{JOBS, Job, Registry, Stage};
+use crate::{
+    Result,
+    changes::FileChange,
+    ci_script, fs,
+    release::Diagnostic,
+    template,
+    workflow::{self, Workflow},
+};
+
+/// Everything before the jobs.
+const PREAMBLE: &str = "\
+# Generated from the CI stage registry (xtask/src/ci.rs) by
+# `cargo xtask ci workflow`; change the registry and regenerate instead of
+# editing this file.
+name: CI
+
+# Run CI on any PR (into any branch), or pushes to main
+on:
+  pull_request:
+  push:
+    branches:
+      - main
+
+env:
+  CARGO_TERM_COLOR: always # Pretty colors
+
+jobs:
+";
+
+/// The workflow for the stages of `registry`, with the optional stages in
+/// `# @if` blocks if `template` (see the [module docs](self)).
+pub fn generate(registry: &Registry, template: bool) -> String {
+    let mut jobs = Vec::new();
+    for job in JOBS {
+        let Some(stage) = registry
+            .all_stages()
+            .iter()
+            .find(|stage| stage.name == job.stage)
+        else {
+            continue;
+        };
+        let has = registry.has(stage);
+        let text = job_text(job, stage);
+        match stage.condition {
+            Some(condition) if template => {
+                let mut block = format!("  # @if {condition}\n");
+                for line in text.lines() {
+                    if has {
+                        let _ = writeln!(block, "{line}");
+                    } else {
+                        let _ = writeln!(block, "  # {}", line.strip_prefix("  ").unwrap_or(line));
+                    }
+                }
+                block.push_str("  # @endif\n");
+                jobs.push(block);
+            }
+            _ if has => jobs.push(text),
+            _ => {}
+        }
+    }
+
+    format!("{PREAMBLE}{}", jobs.join("\n"))
+}
+
+/// The YAML of `job`, which runs `stage`.
+fn job_text(job: &Job, stage: &Stage) -> String {
+    let mut text = format!(
+        "  {}:\n    name: {}\n    runs-on: {}\n    steps:\n      - uses: actions/checkout@v5\n",
+        job.id, job.name, job.runner
+    );
+
+    // Each toolchain once, with everything any step needs from it
+    let mut toolchains: Vec<(&str, Vec<&str>, Vec<&str>)> = Vec::new();
+    for step in &stage.steps {
+        let index = toolchains
+            .iter()
+            .position(|(toolchain, ..)| *toolchain == step.toolchain)
+            .unwrap_or_else(|| {
+                toolchains.push((&step.toolchain, Vec::new(), Vec::new()));
+                toolchains.len() - 1
+            });
+        let (_, components, targets) = &mut toolchains[index];
+        components.extend(step.components.iter().map(String::as_str));
+        targets.extend(step.targets.iter().map(String::as_str));
+    }
+    for (toolchain, mut components, mut targets) in toolchains {
+        let _ = writeln!(text, "      - uses: dtolnay/rust-toolchain@{toolchain}");
+        components.dedup();
+        targets.dedup();
+        if !components.is_empty() || !targets.is_empty() {
+            text.push_str("        with:\n");
+        }
+        if !components.is_empty() {
+            let _ = writeln!(text, "          components: {}", components.join(", "));
+        }
+        if !targets.is_empty() {
+            let _ = writeln!(text, "          targets: {}", targets.join(", "));
+        }
+    }
+
+    let _ = writeln!(
+        text,
+        "      - run: python3 {} {}",
+        ci_script::PATH,
+        job.stage
+    );
+    text
+}
+
+/// The workflow the crate at `root` should have: with `# @if` blocks if it's
+/// the template (has a `template.toml`).
+///
+/// # Errors
+///
+/// Returns an error if the registry cannot be built (see
+/// [`Registry::for_crate`]).
+pub fn expected(root: &Path) -> Result<String> {
+    let registry = Registry::for_crate(root)?;
+    Ok(generate(&registry, root.join(template::PATH).is_file()))
+}
+
+/// The change regenerating the workflow of the crate at `root`, or [`None`]
+/// if it's up to date.
+///
+/// # Errors
+///
+/// Returns an error if the workflow exists but cannot be read, or the
+/// registry cannot be built.
+pub fn plan(root: &Path) -> Result<Option<FileChange>> {
+    let path = root.join(workflow::PATH);
+    let original = if path.is_file() {
+        fs::read(&path)?
+    } else {
+        String::new()
+    };
+    Ok(FileChange::new(workflow::PATH, original, expected(root)?))
+}
+
+/// Checks that the workflow of the crate at `root` is the generated one, and
+/// that its jobs, the `CI_STAGES` of `scripts/ci.py` and the registry all
+/// agree on the stages.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read, or the registry cannot be
+/// built.
+pub fn check(root: &Path) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let registry = Registry::for_crate(root)?;
+
+    // The workflow as a whole
+    let path = root.join(workflow::PATH);
+    let committed = if path.is_file() {
+        Some(fs::read(&path)?)
+    } else {
+        None
+    };
+    let expected = generate(&registry, root.join(template::PATH).is_file());
+    match &committed {
+        None => diagnostics.push(Diagnostic::new(
+            workflow::PATH,
+            None,
+            "is missing; run `cargo xtask ci workflow` to generate it",
+        )),
+        Some(committed) if *committed != expected => {
+            let line = committed
+                .lines()
+                .zip(expected.lines())
+                .position(|(committed, expected)| committed != expected)
+                .unwrap_or_else(|| committed.lines().count().min(expected.lines().count()));
+            diagnostics.push(Diagnostic::new(
+                workflow::PATH,
+                Some(line + 1),
+                "doesn't match the CI stage registry; run `cargo xtask ci workflow` to \
+                 regenerate it",
+            ));
+        }
+        Some(_) => {}
+    }
+
+    // The script's stages against the registry's
+    let script = root.join(ci_script::PATH);
+    if !script.is_file() {
+        diagnostics.push(Diagnostic::new(ci_script::PATH, None, "is missing"));
+        return Ok(diagnostics);
+    }
+    let Some(entries) = ci_script::stages(&fs::read(&script)?) else {
+        diagnostics.push(Diagnostic::new(
+            ci_script::PATH,
+            None,
+            "doesn't define `CI_STAGES`",
+        ));
+        return Ok(diagnostics);
+    };
+    let enabled: Vec<&ci_script::Stage> = entries.iter().filter(|entry| entry.enabled).collect();
+    for entry in &enabled {
+        let message = match registry
+            .all_stages()
+            .iter()
+            .find(|stage| stage.name == entry.name)
+        {
+            Some(stage) if registry.has(stage) => continue,
+            Some(stage) => format!(
+                "stage `{}` needs the `{}` condition, which doesn't hold for this crate",
+                entry.name,
+                stage.condition.unwrap_or_default()
+            ),
+            None => format!("stage `{}` isn't in the CI stage registry", entry.name),
+        };
+        diagnostics.push(Diagnostic::new(ci_script::PATH, Some(entry.line), message));
+    }
+    for stage in registry.stages() {
+        if enabled.iter().any(|entry| entry.name == stage.name) {
+            continue;
+        }
+        let diagnostic = match entries.iter().find(|entry| entry.name == stage.name) {
+            Some(entry) => Diagnostic::new(
+                ci_script::PATH,
+                Some(entry.line),
+                format!("stage `{}` is commented out", stage.name),
+            ),
+            None => Diagnostic::new(
+                ci_script::PATH,
+                None,
+                format!("`CI_STAGES` lacks stage `{}`", stage.name),
+            ),
+        };
+        diagnostics.push(diagnostic);
+    }
+
+    // The workflow's jobs against the script's stages
+    if let Some(committed) = committed {
+        diagnostics.extend(check_jobs(&path, &committed, &enabled));
+    }
+
+    Ok(diagnostics)
+}
+
+/// Checks that every job of the workflow `contents` (read from `path`) runs
+/// one of the `enabled` stages of `scripts/ci.py`, and every stage has a job.
+fn check_jobs(path: &Path, contents: &str, enabled: &[&ci_script::Stage]) -> Vec<Diagnostic> {
+    let workflow = match Workflow::parse(path, contents) {
+        Ok(workflow) => workflow,
+        Err(error) => return vec![Diagnostic::new(workflow::PATH, None, error.to_string())],
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut run = BTreeSet::new();
+    for job in &workflow.jobs {
+        for stage in job
+            .steps
+            .iter()
+            .filter_map(|step| script_stage(step.run.as_deref()?))
+        {
+            run.insert(stage);
+            if !enabled.iter().any(|entry| entry.name == stage) {
+                diagnostics.push(Diagnostic::new(
+                    workflow::PATH,
+                    Some(job.line),
+                    format!(
+                        "job `{}` runs stage `{stage}`, which isn't in `CI_STAGES` of {}",
+                        job.id,
+                        ci_script::PATH
+                    ),
+                ));
+            }
+        }
+    }
+    for entry in enabled {
+        if !run.contains(entry.name.as_str()) {
+            diagnostics.push(Diagnostic::new(
+                ci_script::PATH,
+                Some(entry.line),
+                format!(
+                    "stage `{}` has no job in {} running it",
+                    entry.name,
+                    workflow::PATH
+                ),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+/// The stage a `run:` command runs with `scripts/ci.py`, if it does.
+fn script_stage(command: &str) -> Option<&str> {
+    let (_, arguments) = command.split_once(ci_script::PATH)?;
+    arguments.split_whitespace().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::conditional::{self, CONDITIONS};
+
+    fn registry(conditions: &[(&str, bool)]) -> Registry {
+        let conditions = conditions
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value))
+            .collect();
+        Registry::new(
+            "1.85.0".parse().unwrap(),
+            &conditional::complete(&conditions),
+        )
+    }
+
+    #[test]
+    fn every_stage_has_jobs() {
+        let registry = registry(&[]);
+        for stage in registry.all_stages() {
+            assert!(
+                JOBS.iter().any(|job| job.stage == stage.name),
+                "{}",
+                stage.name
+            );
+        }
+        for job in JOBS {
+            assert!(
+                registry
+                    .all_stages()
+                    .iter()
+                    .any(|stage| stage.name == job.stage),
+                "{}",
+                job.id
+            );
+            assert_eq!(JOBS.iter().filter(|other| other.id == job.id).count(), 1);
+        }
+    }
+
+    #[test]
+    fn template_workflow_is_generated() {
+        let template: BTreeMap<String, bool> = CONDITIONS
+            .iter()
+            .map(|condition| (condition.name.to_owned(), condition.template))
+            .collect();
+        let manifest = crate::toml::Document::parse(
+            Path::new("Cargo.toml"),
+            include_str!("../../../Cargo.toml"),
+        )
+        .unwrap();
+        let msrv = manifest.get_str("package", "rust-version").unwrap();
+        let registry = Registry::new(msrv.parse().unwrap(), &template);
+        assert_eq!(
+            generate(&registry, true),
+            include_str!("../../../.github/workflows/ci.yaml")
+        );
+    }
+
+    #[test]
+    fn crate_workflows_run_every_stage() {
+        let registry = registry(&[("no_std", true), ("unsafe", false)]);
+        let workflow = generate(&registry, false);
+        assert!(!workflow.contains("@if"));
+
+        let enabled: Vec<ci_script::Stage> = registry
+            .stages()
+            .iter()
+            .enumerate()
+            .map(|(index, stage)| ci_script::Stage {
+                line: index + 1,
+                name: stage.name.to_owned(),
+                enabled: true,
+            })
+            .collect();
+        let enabled: Vec<&ci_script::Stage> = enabled.iter().collect();
+        assert_eq!(
+            check_jobs(Path::new(workflow::PATH), &workflow, &enabled),
+            []
+        );
+
+        let parsed = Workflow::parse(Path::new(workflow::PATH), &workflow).unwrap();
+        let ids: Vec<&str> = parsed.jobs.iter().map(|job| job.id.as_str()).collect();
+        assert!(ids.contains(&"build_nostd"));
+        assert!(ids.contains(&"run_tests_windows"));
+        assert!(!ids.contains(&"run_tests_miri"));
+    }
+
+    #[test]
+    fn jobs_running_unknown_stages_are_reported() {
+        let workflow = format!(
+            "{PREAMBLE}  extra:\n    runs-on: ubuntu-latest\n    steps:\n      - run: python3 \
+             {} unknown\n",
+            ci_script::PATH
+        );
+        let diagnostics = check_jobs(Path::new(workflow::PATH), &workflow, &[]);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(
+            diagnostics[0]
+                .message
+                .contains("job `extra` runs stage `unknown`")
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! The Python CI script at `scripts/ci.py`.
+
+/// The path of the CI script, relative to the crate root.
+pub const PATH: &str = "scripts/ci.py";
+
+/// A `NAME = "value"` assignment of a string constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    /// The (1-based) line of the assignment.
+    pub line: usize,
+    /// The assigned string, without quotes.
+    pub value: String,
+}
+
+/// Finds the top-level assignment of the string constant `name`, like
+/// `MSRV = "1.85.0"`.
+pub fn string_constant(contents: &str, name: &str) -> Option<Assignment> {
+    contents.lines().enumerate().find_map(|(index, line)| {
+        let (left, right) = line.split_once('=')?;
+        if left.trim_end() != name || line.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let right = right.split('#').next()?.trim();
+        let value = right
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .or_else(|| right.strip_prefix('\'')?.strip_suffix('\''))?;
+        Some(Assignment {
+            line: index + 1,
+            value: value.to_owned(),
+        })
+    })
+}
+
+/// One entry of the script's `CI_STAGES` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stage {
+    /// The (1-based) line of the entry.
+    pub line: usize,
+    /// The stage's name, as passed on the command line.
+    pub name: String,
+    /// Whether the entry is active, rather than commented out (like the
+    /// template's `# ("build_nostd", build_nostd),`).
+    pub enabled: bool,
+}
+
+/// Finds the entries of the `CI_STAGES` list, in order, or [`None`] if the
+/// script doesn't define it.
+pub fn stages(contents: &str) -> Option<Vec<Stage>> {
+    let mut lines = contents.lines().enumerate();
+    lines.find(|(_, line)| line.starts_with("CI_STAGES") && line.trim_end().ends_with('['))?;
+
+    let mut stages = Vec::new();
+    for (index, line) in lines {
+        let trimmed = line.trim();
+        if trimmed.starts_with(']') {
+            return Some(stages);
+        }
+        let (enabled, entry) = match trimmed.strip_prefix('#') {
+            Some(entry) => (false, entry.trim_start()),
+            None => (true, trimmed),
+        };
+        let Some(entry) = entry.strip_prefix('(') else {
+            continue;
+        };
+        let name = entry.split(',').next().unwrap_or_default().trim();
+        let name = name
+            .strip_prefix('"')
+            .and_then(|name| name.strip_suffix('"'))
+            .or_else(|| name.strip_prefix('\'')?.strip_suffix('\''));
+        if let Some(name) = name {
+            stages.push(Stage {
+                line: index + 1,
+                name: name.to_owned(),
+                enabled,
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_constants() {
+        let script = "\
+def f():
+    MSRV = \"1.0\"
+
+MSRV = '1.85.0'  # the minimum
+OTHER = 3
+";
+        assert_eq!(
+            string_constant(script, "MSRV"),
+            Some(Assignment {
+                line: 4,
+                value: "1.85.0".to_owned(),
+            })
+        );
+        assert_eq!(string_constant(script, "OTHER"), None);
+        assert_eq!(string_constant(script, "MISSING"), None);
+    }
+
+    #[test]
+    fn stages_of_the_template() {
+        let template = stages(include_str!("../../scripts/ci.py")).unwrap();
+        let names: Vec<(&str, bool)> = template
+            .iter()
+            .map(|stage| (stage.name.as_str(), stage.enabled))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("check_fmt", true),
+                ("check_docs", true),
+                ("build", true),
+                ("build_nostd", false),
+                ("lint", true),
+                ("run_tests_stable", true),
+                ("run_tests_beta", true),
+                ("run_tests_msrv", true),
+                ("run_tests_leak_sanitizer", true),
+                ("run_tests_miri", false),
+            ]
+        );
+
+        assert_eq!(stages("CI_STAGES = [\n"), None);
+        assert_eq!(stages("STAGES = []\n"), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! The command-line interface of the `xtask` binary.
+
+mod args;
+mod ci;
+mod doc_links;
+mod drift;
+mod fleet;
+mod instantiate;
+mod lints;
+mod markers;
+mod metadata;
+mod msrv;
+mod release;
+mod template;
+mod wizard;
+
+use std::process::ExitCode;
+
+use self::args::Args;
+use crate::{Error, Result};
+
+/// The help text printed by `xtask help`.
+const USAGE: &str = "\
+Maintenance tooling for crates derived from this template.
+
+Usage: cargo xtask <COMMAND> [OPTIONS]
+
+Every command accepts `--root DIR` to operate on a crate other than the one in
+the current directory.
+
+Commands:
+    instantiate [--answers FILE] [--interactive] [--VARIABLE VALUE]... [--keyword KEYWORD]...
+                [--category SLUG]... [--msrv VERSION] [--license LICENSE] [--variant VARIANT]...
+                [--with CONDITION]... [--without CONDITION]... [--skip-checks]
+        Apply variants, resolve conditional blocks and fill in the template's placeholders, as
+        template.toml describes, then run its checks (variables: --name, --owner, --author,
+        --version, --description, --year). Answers come from a TOML or JSON answers file, then
+        flags, then the wizard, and are recorded in .rust-template.toml for upgrades
+    variants
+        List the template variants defined in template.toml
+    conditions
+        List the conditions (no_std, unsafe, loom) and the conditional blocks depending on them
+    placeholders
+        List the placeholders using each variable
+    lints [audit] [--all]
+        List lints added since the lint tables were last updated, and problems with their entries
+    lints stamp [--dry-run]
+        Record the current Rust version in the lint tables' notes
+    lints profile [status]
+        Show which lint profile the crate uses, and its local overrides
+    lints profile list
+        List the available lint profiles
+    lints profile show NAME
+        Print a lint profile's [lints] tables, as they'd appear in Cargo.toml
+    lints profile switch NAME [--dry-run]
+        Regenerate the [lints] tables from a profile, keeping local overrides
+    markers [--format text|json] [--kind TODO|ON_RELEASE|NOTE]...
+        List the TODO, ON_RELEASE and NOTE marker comments left in the crate
+    metadata [check]
+        Check that crates.io would accept Cargo.toml's keywords and categories
+    metadata categories [SEARCH]
+        List the crates.io category slugs in the embedded snapshot (containing SEARCH)
+    metadata update-categories FILE [--dry-run]
+        Refresh the snapshot from a download of https://crates.io/api/v1/category_slugs
+    msrv [check]
+        Check that the MSRV in ci.yaml and scripts/ci.py matches Cargo.toml's
+    msrv find [--write]
+        Find the oldest installed rustup toolchain that compiles the crate
+    msrv set VERSION [--dry-run]
+        Set the MSRV everywhere it is written
+    msrv sync [--dry-run]
+        Copy Cargo.toml's MSRV everywhere else it is written
+    release check
+        Fail if anything (markers, placeholder metadata, ...) should block a release
+    release <major|minor|patch|pre> [--label LABEL] [--notes TEXT] [--dry-run]
+        Bump the version and carry out the ON_RELEASE steps, showing a diff
+    template [status] [--template DIR]
+        Show the template commit the crate was created from, and what has changed since
+    template stamp --template DIR [--commit REV] [--dry-run]
+        Record a commit of a template clone as the one the crate was created from
+    template upgrade --template DIR [--to REV] [--allow-dirty] [--dry-run]
+        Three-way merge the template's changes since then into the crate
+    template check
+        Check that template.toml agrees with the template's files
+    ci [run] [STAGE]... [--jobs N] [--only] [--fail-fast] [--json FILE] [--junit FILE]
+           [--no-cache]
+        Run the CI stages (default: all), along with the stages they depend on unless --only,
+        running independent stages in parallel; ends with a summary table, and writes JSON
+        and JUnit XML reports if asked. Stages whose inputs (sources, manifests, toolchain
+        versions, RUSTFLAGS and the like) haven't changed since they last passed are skipped
+        unless --no-cache, and the others say what changed
+    ci list
+        List the CI stages, with their commands, dependencies and shared resources
+    ci workflow [--dry-run]
+        Regenerate .github/workflows/ci.yaml from the CI stages, showing a diff
+    ci workflow check
+        Check that ci.yaml is the generated one, and that its jobs and scripts/ci.py's
+        CI_STAGES match the CI stages
+    ci workflow run [JOB]... [--dry-run]
+        Run ci.yaml's jobs (default: all) locally, with the installed toolchains; jobs that
+        can't run here (like Windows ones on Linux) are skipped, saying why
+    doc-links [check]
+        Check that README.md's docs.rs links match the `//!` links in src/lib.rs
+    doc-links sync --from readme|lib|items [--dry-run]
+        Regenerate one side's links from the other, or both from the crate's public items,
+        showing a diff
+    drift --template DIR [--rev REV] [--format text|json]
+        Report how the crate differs from the template as rendered with its recorded answers and
+        variants, by category
+    fleet [DIR] [--lint NAME]... [--format text|json]
+        Summarize every template-derived crate in DIR (default: the crate's parent directory)
+    help
+        Print this message
+";
+
+/// Runs the command described by `args` (not including the binary name),
+/// returning the process exit code.
+///
+/// Errors are reported on stderr and result in [`ExitCode::FAILURE`].
+pub fn run(args: impl IntoIterator<Item = String>) -> ExitCode {
+    match dispatch(Args::new(args)) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn dispatch(mut args: Args) -> Result<ExitCode> {
+    let command = args.subcommand();
+    match command.as_deref() {
+        Some("instantiate") => instantiate::run(args),
+        Some("variants") => instantiate::variants(args),
+        Some("conditions") => instantiate::conditions(args),
+        Some("placeholders") => instantiate::placeholders(args),
+        Some("ci") => ci::run(args),
+        Some("doc-links") => doc_links::run(args),
+        Some("drift") => drift::run(args),
+        Some("fleet") => fleet::run(args),
+        Some("lints") => lints::run(args),
+        Some("markers") => markers::run(args),
+        Some("metadata") => metadata::run(args),
+        Some("msrv") => msrv::run(args),
+        Some("release") => release::run(args),
+        Some("template") => template::run(args),
+        Some("help") => {
+            print!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        None if args.flag("help") => {
+            print!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(command) => Err(Error::InvalidInput(format!(
+            "unknown command `{command}` (see `cargo xtask help`)"
+        ))),
+        None => Err(Error::InvalidInput(format!("no command given\n\n{USAGE}"))),
+    }
+}
+
+/// Prints `rows` as a table with left-aligned columns, under `headers`.
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let line: Vec<String> = cells
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&mut headers.iter().copied());
+    for row in rows {
+        print_row(&mut row.iter().map(String::as_str));
+    }
+}
+
+/// How a command should print its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable text.
+    Text,
+    /// Pretty-printed JSON.
+    Json,
+}
+
+impl OutputFormat {
+    /// Removes `--format text|json`, defaulting to text.
+    fn from_args(args: &mut Args) -> Result<Self> {
+        match args.option("format")?.as_deref() {
+            None | Some("text") => Ok(Self::Text),
+            Some("json") => Ok(Self::Json),
+            Some(other) => Err(Error::InvalidInput(format!(
+                "unknown format `{other}` (expected `text` or `json`)"
+            ))),
+        }
+    }
+}
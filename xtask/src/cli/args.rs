@@ -0,0 +1,173 @@
+//! A minimal command-line argument parser.
+//!
+//! Command handlers pull the options and flags they understand out of
+//! [`Args`] first, then its positional arguments, and finally call
+//! [`Args::finish`] to reject anything left over.
+
+use std::{collections::VecDeque, path::PathBuf};
+
+use crate::{Error, Result};
+
+/// The not-yet-consumed arguments of one invocation.
+#[derive(Debug)]
+pub(crate) struct Args {
+    remaining: VecDeque<String>,
+}
+
+impl Args {
+    /// Wraps the arguments following the binary name.
+    pub(crate) fn new(args: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            remaining: args.into_iter().collect(),
+        }
+    }
+
+    /// Removes and returns the leading subcommand, if the first argument is
+    /// not an option.
+    pub(crate) fn subcommand(&mut self) -> Option<String> {
+        let is_command = self
+            .remaining
+            .front()
+            .is_some_and(|arg| !arg.starts_with('-'));
+        if is_command {
+            self.remaining.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Removes `--<name>` if present, returning whether it was.
+    pub(crate) fn flag(&mut self, name: &str) -> bool {
+        let flag = format!("--{name}");
+        let before = self.remaining.len();
+        self.remaining.retain(|arg| *arg != flag);
+        self.remaining.len() != before
+    }
+
+    /// Removes every `--<name> VALUE` (or `--<name>=VALUE`), returning the
+    /// values in order.
+    pub(crate) fn options(&mut self, name: &str) -> Result<Vec<String>> {
+        let flag = format!("--{name}");
+        let prefix = format!("--{name}=");
+        let mut values = Vec::new();
+        let mut index = 0;
+
+        while index < self.remaining.len() {
+            let arg = &self.remaining[index];
+            if let Some(value) = arg.strip_prefix(&prefix) {
+                values.push(value.to_owned());
+                self.remaining.remove(index);
+            } else if *arg == flag {
+                self.remaining.remove(index);
+                let value = self
+                    .remaining
+                    .remove(index)
+                    .ok_or_else(|| Error::InvalidInput(format!("`{flag}` requires a value")))?;
+                values.push(value);
+            } else {
+                index += 1;
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Removes `--<name> VALUE` (or `--<name>=VALUE`), returning the value.
+    pub(crate) fn option(&mut self, name: &str) -> Result<Option<String>> {
+        let mut values = self.options(name)?;
+        if values.len() > 1 {
+            return Err(Error::InvalidInput(format!(
+                "`--{name}` given more than once"
+            )));
+        }
+
+        Ok(values.pop())
+    }
+
+    /// Like [`Args::option`], but the option must be present.
+    pub(crate) fn required(&mut self, name: &str) -> Result<String> {
+        self.option(name)?
+            .ok_or_else(|| Error::InvalidInput(format!("missing required option `--{name}`")))
+    }
+
+    /// Removes `--root DIR`, defaulting to the current directory.
+    pub(crate) fn root(&mut self) -> Result<PathBuf> {
+        Ok(self
+            .option("root")?
+            .map_or_else(|| PathBuf::from("."), PathBuf::from))
+    }
+
+    /// Removes and returns the next positional argument.
+    pub(crate) fn positional(&mut self) -> Option<String> {
+        let index = self
+            .remaining
+            .iter()
+            .position(|arg| !arg.starts_with("--"))?;
+        self.remaining.remove(index)
+    }
+
+    /// Fails if any argument has not been consumed.
+    pub(crate) fn finish(self) -> Result<()> {
+        match self.remaining.front() {
+            Some(arg) => Err(Error::InvalidInput(format!("unexpected argument `{arg}`"))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::new(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn options_flags_and_positionals() {
+        let mut args = args(&[
+            "ci",
+            "--with",
+            "unsafe",
+            "lint",
+            "--dry-run",
+            "--with=loom",
+            "--root",
+            "dir",
+            "test",
+        ]);
+        assert_eq!(args.subcommand().as_deref(), Some("ci"));
+        assert_eq!(args.subcommand().as_deref(), None);
+        assert!(args.flag("dry-run"));
+        assert!(!args.flag("dry-run"));
+        assert_eq!(args.options("with").unwrap(), ["unsafe", "loom"]);
+        assert_eq!(args.root().unwrap(), PathBuf::from("dir"));
+        assert_eq!(args.option("json").unwrap(), None);
+        assert_eq!(args.positional().as_deref(), Some("lint"));
+        assert_eq!(args.positional().as_deref(), Some("test"));
+        assert_eq!(args.positional(), None);
+        args.finish().unwrap();
+    }
+
+    #[test]
+    fn rejects_misuse() {
+        let message = |result: Result<()>| result.unwrap_err().to_string();
+        assert_eq!(
+            message(args(&["--name"]).option("name").map(drop)),
+            "`--name` requires a value"
+        );
+        assert_eq!(
+            message(args(&["--name", "a", "--name=b"]).option("name").map(drop)),
+            "`--name` given more than once"
+        );
+        assert_eq!(
+            message(args(&[]).required("name").map(drop)),
+            "missing required option `--name`"
+        );
+        assert_eq!(
+            message(args(&["--verbose"]).finish()),
+            "unexpected argument `--verbose`"
+        );
+        assert_eq!(args(&[]).root().unwrap(), PathBuf::from("."));
+    }
+}
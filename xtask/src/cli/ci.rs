@@ -0,0 +1,320 @@
+//! `xtask ci`
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use super::args::Args;
+use crate::{
+    Error, Result, changes,
+    ci::{
+        self, Registry,
+        actions::{self, Action, Plan},
+        cache::{Cache, Inputs},
+        executor::{self, Event, Options, Outcome, Status},
+        report::Report,
+    },
+    diff, fs,
+    workflow::Workflow,
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        Some("list") => list(args),
+        Some("workflow") => workflow(args),
+        None | Some("run") => run_stages(args, Vec::new()),
+        // Anything else is the first stage to run
+        Some(stage) => run_stages(args, vec![stage.to_owned()]),
+    }
+}
+
+fn list(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    for stage in Registry::for_crate(&root)?.stages() {
+        println!("{}", stage.name);
+        println!("    {}", stage.title);
+        for step in &stage.steps {
+            println!("    $ {}", step.command_line());
+        }
+        if !stage.dependencies.is_empty() {
+            println!("    Depends on: {}", stage.dependencies.join(", "));
+        }
+        let resources: Vec<String> = stage.resources().iter().map(ToString::to_string).collect();
+        println!("    Uses: {}", resources.join(", "));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn workflow(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None => generate_workflow(args),
+        Some("check") => check_workflow(args),
+        Some("run") => run_workflow(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown ci workflow command `{other}` (expected `check` or `run`)"
+        ))),
+    }
+}
+
+fn generate_workflow(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let Some(change) = ci::workflow::plan(&root)? else {
+        println!("The workflow is already up to date");
+        return Ok(ExitCode::SUCCESS);
+    };
+    print!("{}", diff::unified(&change));
+    if !dry_run {
+        changes::apply(&root, &[change])?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check_workflow(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let diagnostics = ci::workflow::check(&root)?;
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    if diagnostics.is_empty() {
+        println!("The workflow, scripts/ci.py and the CI stages agree");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {} CI workflow problem(s) listed above",
+            diagnostics.len()
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn run_workflow(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let dry_run = args.flag("dry-run");
+    let mut ids = Vec::new();
+    while let Some(id) = args.positional() {
+        ids.push(id);
+    }
+    args.finish()?;
+
+    let workflow = Workflow::read(&root)?;
+    if let Some(id) = ids
+        .iter()
+        .find(|id| !workflow.jobs.iter().any(|job| job.id == **id))
+    {
+        let known: Vec<&str> = workflow.jobs.iter().map(|job| job.id.as_str()).collect();
+        return Err(Error::InvalidInput(format!(
+            "unknown job `{id}` (expected one of {})",
+            known.join(", ")
+        )));
+    }
+    let jobs: Vec<actions::Job> = actions::plan(&workflow)?
+        .into_iter()
+        .filter(|job| ids.is_empty() || ids.contains(&job.id))
+        .collect();
+
+    let mut results = Vec::new();
+    for job in &jobs {
+        let label = job.label();
+        let actions = match &job.plan {
+            Plan::Skipped(reason) => {
+                println!("==> {label} skipped: {reason}");
+                results.push((label, Status::Skipped(reason.clone())));
+                continue;
+            }
+            Plan::Runnable(actions) if dry_run => {
+                println!("==> {label} would run:");
+                for action in actions {
+                    match action {
+                        Action::Checkout => println!("    (check out: the working tree)"),
+                        Action::Toolchain(toolchain) => println!("    (toolchain: {toolchain})"),
+                        Action::Run { command, .. } => println!("    $ {command}"),
+                    }
+                }
+                continue;
+            }
+            Plan::Runnable(actions) => actions,
+        };
+
+        println!(
+            "==> Started {label}: {}",
+            job.name.as_deref().unwrap_or(&job.id)
+        );
+        let (status, duration) = actions::run(&root, actions, |command| println!("$ {command}"))?;
+        println!("==> {label} {status} after {:.1}s", duration.as_secs_f64());
+        results.push((label, status));
+    }
+    if dry_run {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let ran = results
+        .iter()
+        .filter(|(_, status)| !matches!(status, Status::Skipped(_)))
+        .count();
+    println!(
+        "\nRan {ran} job(s) locally, skipped {}:",
+        results.len() - ran
+    );
+    let width = results
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    for (label, status) in &results {
+        println!("    {label:width$}  {status}");
+    }
+
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|(_, status)| matches!(status, Status::Failed { .. }))
+        .map(|(label, _)| label.as_str())
+        .collect();
+    if failed.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {} job(s) failed: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn run_stages(mut args: Args, mut names: Vec<String>) -> Result<ExitCode> {
+    let root = args.root()?;
+    let jobs = match args.option("jobs")? {
+        Some(jobs) => jobs
+            .parse::<usize>()
+            .ok()
+            .filter(|&jobs| jobs > 0)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("invalid job count `{jobs}` (expected at least 1)"))
+            })?,
+        None => Options::default_jobs(),
+    };
+    let only = args.flag("only");
+    let fail_fast = args.flag("fail-fast");
+    let json_path = args.option("json")?;
+    let junit_path = args.option("junit")?;
+    let no_cache = args.flag("no-cache");
+    while let Some(name) = args.positional() {
+        names.push(name);
+    }
+    args.finish()?;
+
+    let registry = Registry::for_crate(&root)?;
+    let stages = registry.select(&names, only)?;
+    let start = Instant::now();
+
+    // Fingerprint before running, so edits made meanwhile count as changes
+    let mut cache = Cache::load(&root);
+    let mut inputs = Inputs::collect(&root)?;
+    let mut fingerprints = BTreeMap::new();
+    let mut outcomes = Vec::new();
+    let mut pending = Vec::new();
+    for &stage in &stages {
+        let fingerprint = inputs.fingerprint(stage)?;
+        let reason = cache.reason_to_run(stage.name, fingerprint.as_ref());
+        fingerprints.insert(stage.name, fingerprint);
+        match reason {
+            _ if no_cache => pending.push(stage),
+            Some(reason) => {
+                println!("==> {} has to run: {reason}", stage.name);
+                pending.push(stage);
+            }
+            None => {
+                println!(
+                    "==> {} cached: it passed before with the same inputs",
+                    stage.name
+                );
+                outcomes.push(Outcome {
+                    stage: stage.name,
+                    status: Status::Cached,
+                    duration: Duration::ZERO,
+                    output: String::new(),
+                });
+            }
+        }
+    }
+    println!("Running {} stage(s), up to {jobs} at a time", pending.len());
+
+    outcomes.extend(executor::run(
+        &root,
+        &pending,
+        Options { jobs, fail_fast },
+        |event| match event {
+            Event::Started(stage) => println!("==> Started {}: {}", stage.name, stage.title),
+            Event::Finished(outcome) if matches!(outcome.status, Status::Skipped(_)) => {
+                println!("==> {} {}", outcome.stage, outcome.status);
+            }
+            Event::Finished(outcome) => {
+                println!(
+                    "==> {} {} after {:.1}s",
+                    outcome.stage,
+                    outcome.status,
+                    outcome.duration.as_secs_f64()
+                );
+                print!("{}", outcome.output);
+            }
+        },
+    ));
+    for outcome in &outcomes {
+        cache.record(outcome, fingerprints[outcome.stage].as_ref());
+    }
+    cache.save(&root)?;
+
+    let report = Report::new(&stages, &outcomes, start.elapsed());
+    println!("\n{}", report.summary());
+    write_reports(&report, json_path.as_deref(), junit_path.as_deref())?;
+
+    let unsuccessful: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| !outcome.status.is_success())
+        .map(|outcome| outcome.stage)
+        .collect();
+    if unsuccessful.is_empty() {
+        println!("==> All checks passed! 🎉");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {} stage(s) failed or were skipped: {}",
+            unsuccessful.len(),
+            unsuccessful.join(", ")
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn write_reports(
+    report: &Report<'_>,
+    json_path: Option<&str>,
+    junit_path: Option<&str>,
+) -> Result<()> {
+    if let Some(path) = json_path {
+        fs::write(
+            Path::new(path),
+            &(report.to_json().to_pretty_string() + "\n"),
+        )?;
+        println!("Wrote the JSON report to {path}");
+    }
+    if let Some(path) = junit_path {
+        fs::write(Path::new(path), &report.to_junit())?;
+        println!("Wrote the JUnit report to {path}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! `xtask doc-links`
+
+use std::process::ExitCode;
+
+use super::args::Args;
+use crate::{
+    Error, Result, changes, diff,
+    doc_links::{self, Source},
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None | Some("check") => check(args),
+        Some("sync") => sync(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown doc-links command `{other}` (expected `check` or `sync`)"
+        ))),
+    }
+}
+
+fn check(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let problems = doc_links::check(&root)?;
+    for problem in &problems {
+        println!("{problem}");
+    }
+
+    if problems.is_empty() {
+        println!("README.md and src/lib.rs links are in sync");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {} doc link problem(s) (fix with `cargo xtask doc-links sync --from readme|lib|items`)",
+            problems.len()
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn sync(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let source = match args.required("from")?.as_str() {
+        "readme" => Source::Readme,
+        "lib" => Source::Lib,
+        "items" => Source::Items,
+        other => {
+            return Err(Error::InvalidInput(format!(
+                "unknown link source `{other}` (expected `readme`, `lib` or `items`)"
+            )));
+        }
+    };
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let changes = doc_links::sync(&root, source)?;
+    for change in &changes {
+        print!("{}", diff::unified(change));
+    }
+    if changes.is_empty() {
+        println!("Already in sync");
+    } else if !dry_run {
+        changes::apply(&root, &changes)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
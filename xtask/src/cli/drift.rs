@@ -0,0 +1,60 @@
+//! `xtask drift`
+
+use std::{path::PathBuf, process::ExitCode};
+
+use super::{OutputFormat, args::Args};
+use crate::{
+    Error, Result,
+    drift::{self, Category},
+    git, json, upgrade,
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let format = OutputFormat::from_args(&mut args)?;
+    let template = PathBuf::from(args.required("template")?);
+    let rev = args.option("rev")?;
+    args.finish()?;
+
+    // Comparing against the commit the crate was created from shows only
+    // local changes, rather than mixing in the template's own since then
+    let rev = match rev {
+        Some(rev) => rev,
+        None => upgrade::baseline(&root)?.unwrap_or_else(|| "HEAD".to_owned()),
+    };
+    if git::resolve(&template, &rev)?.is_none() {
+        return Err(Error::InvalidInput(format!(
+            "`{rev}` is not a commit in the template repository at {}",
+            template.display()
+        )));
+    }
+    let differences = drift::report(&root, &template, &rev)?;
+
+    match format {
+        OutputFormat::Text => {
+            println!("Compared against template commit {}", upgrade::short(&rev));
+            for category in Category::ALL {
+                let in_category: Vec<_> = differences
+                    .iter()
+                    .filter(|difference| difference.category == category)
+                    .collect();
+                if in_category.is_empty() {
+                    continue;
+                }
+                println!("\n{category} ({}):", in_category.len());
+                for difference in in_category {
+                    println!("  {difference}");
+                }
+            }
+            if differences.is_empty() {
+                println!("\nNo drift from the template");
+            }
+        }
+        OutputFormat::Json => {
+            let differences = differences.iter().map(drift::Difference::to_json).collect();
+            println!("{}", json::Value::Array(differences).to_pretty_string());
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,92 @@
+//! `xtask fleet`
+
+use std::{path::PathBuf, process::ExitCode};
+
+use super::{OutputFormat, args::Args, print_table};
+use crate::{Result, fleet, json, upgrade};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let format = OutputFormat::from_args(&mut args)?;
+    let lints = args.options("lint")?;
+    // Derived crates normally live next to each other, so by default the
+    // crate's siblings are audited
+    let dir = match args.positional() {
+        Some(dir) => PathBuf::from(dir),
+        None => root
+            .canonicalize()
+            .ok()
+            .and_then(|root| root.parent().map(PathBuf::from))
+            .unwrap_or_else(|| root.join("..")),
+    };
+    args.finish()?;
+
+    let crates = fleet::audit(&dir, &lints)?;
+
+    match format {
+        OutputFormat::Text => {
+            let mut headers = vec![
+                "CRATE",
+                "MSRV",
+                "EDITION",
+                "LINT PROFILE",
+                "OVERRIDES",
+                "TODO",
+                "ON_RELEASE",
+                "TEMPLATE",
+            ];
+            headers.extend(lints.iter().map(String::as_str));
+
+            let missing = || "-".to_owned();
+            let rows: Vec<Vec<String>> = crates
+                .iter()
+                .map(|summary| {
+                    let directory = summary
+                        .path
+                        .file_name()
+                        .map_or_else(missing, |name| name.to_string_lossy().into_owned());
+                    let mut row = vec![
+                        directory,
+                        summary.msrv.clone().unwrap_or_else(missing),
+                        summary.edition.clone().unwrap_or_else(missing),
+                        summary.profile.clone().unwrap_or_else(missing),
+                        summary
+                            .overrides
+                            .map_or_else(missing, |overrides| overrides.to_string()),
+                        summary.todo.to_string(),
+                        summary.on_release.to_string(),
+                        summary
+                            .template
+                            .as_deref()
+                            .map_or_else(missing, |commit| upgrade::short(commit).to_owned()),
+                    ];
+                    row.extend(
+                        summary
+                            .lints
+                            .iter()
+                            .map(|(_, level)| level.clone().unwrap_or_else(missing)),
+                    );
+                    row
+                })
+                .collect();
+            print_table(&headers, &rows);
+
+            for summary in &crates {
+                for error in &summary.errors {
+                    eprintln!("warning: {}: {error}", summary.path.display());
+                }
+            }
+            println!(
+                "\n{} template-derived crate(s) in {}",
+                crates.len(),
+                dir.display()
+            );
+        }
+        OutputFormat::Json => {
+            let crates = crates.iter().map(fleet::Crate::to_json).collect();
+            println!("{}", json::Value::Array(crates).to_pretty_string());
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
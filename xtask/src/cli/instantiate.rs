@@ -0,0 +1,204 @@
+//! `xtask instantiate`
+
+use std::{io, path::Path, process::ExitCode};
+
+use super::{args::Args, wizard};
+use crate::{
+    Result,
+    answers::{self, Answers},
+    changes,
+    conditional::{self, CONDITIONS},
+    fs, instantiate,
+    markers::Language,
+    placeholder::{self, Variable},
+    template::Template,
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let template = Template::read(&root)?;
+    let mut answers = match args.option("answers")? {
+        Some(path) => Answers::read(Path::new(&path))?,
+        None => Answers::default(),
+    };
+    answers.merge(flag_answers(&mut args, &template)?);
+    let interactive = args.flag("interactive");
+    let skip_checks = args.flag("skip-checks");
+    args.finish()?;
+
+    if interactive {
+        answers = wizard::ask(
+            &root,
+            &template,
+            answers,
+            &mut io::stdin().lock(),
+            &mut io::stdout(),
+        )?;
+    }
+    answers.values = template.values(&root, answers.values)?;
+
+    let changes = instantiate::plan(&root, &template, &answers)?;
+    changes::apply(&root, &changes)?;
+
+    if changes.is_empty() {
+        println!("Nothing to do, the template is already instantiated");
+    }
+    for change in &changes {
+        let action = if change.deleted {
+            "Deleted"
+        } else if change.original.is_empty() {
+            "Created"
+        } else {
+            "Updated"
+        };
+        println!("{action} {}", change.path.display());
+    }
+    if skip_checks {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let rendered = template.rendered(&root)?;
+    let mut failed = 0;
+    for check in &template.checks {
+        let problems = check.run(&root, &rendered)?;
+        if problems.is_empty() {
+            println!("Passed check `{}`: {}", check.name, check.description);
+            continue;
+        }
+        failed += 1;
+        eprintln!("Failed check `{}`: {}", check.name, check.description);
+        for problem in problems {
+            eprintln!("    {problem}");
+        }
+    }
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("error: {failed} check(s) failed after instantiating");
+        ExitCode::FAILURE
+    })
+}
+
+/// The answers given with command-line flags.
+fn flag_answers(args: &mut Args, template: &Template) -> Result<Answers> {
+    let mut answers = Answers::default();
+    for declaration in &template.variables {
+        if let Some(value) = args.option(declaration.variable.name())? {
+            answers.values.set(declaration.variable, value)?;
+        }
+    }
+    answers.keywords = args.options("keyword")?;
+    answers.categories = args.options("category")?;
+    answers.msrv = args.option("msrv")?.map(|msrv| msrv.parse()).transpose()?;
+    answers.license = args
+        .option("license")?
+        .map(|license| answers::parse_license(&license))
+        .transpose()?;
+    answers.variants = args.options("variant")?;
+    for (flag, value) in [("with", true), ("without", false)] {
+        for name in args.options(flag)? {
+            answers.conditions.insert(name, value);
+        }
+    }
+    Ok(answers)
+}
+
+/// `xtask variants`
+pub(super) fn variants(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    for variant in Template::read(&root)?.variants {
+        println!("{}", variant.name);
+        println!("    {}", variant.description);
+        if !variant.conflicts.is_empty() {
+            println!("    Conflicts with: {}", variant.conflicts.join(", "));
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `xtask conditions`
+pub(super) fn conditions(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let mut blocks = Vec::new();
+    for path in fs::walk(&root)? {
+        if Language::from_path(&path).is_some() {
+            let contents = fs::read(&root.join(&path))?;
+            blocks.extend(
+                conditional::blocks(&path, &contents)?
+                    .into_iter()
+                    .map(|block| (path.clone(), block)),
+            );
+        }
+    }
+
+    for condition in CONDITIONS {
+        let holds = if condition.template {
+            "holds"
+        } else {
+            "doesn't hold"
+        };
+        println!("{} ({holds} in the template)", condition.name);
+        println!("    {}", condition.description);
+        for (path, block) in blocks
+            .iter()
+            .filter(|(_, block)| block.condition == condition.name)
+        {
+            let negated = if block.negated { " (negated)" } else { "" };
+            println!("    {}:{}{negated}", path.display(), block.line);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `xtask placeholders`
+pub(super) fn placeholders(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let mut directives = Vec::new();
+    for path in fs::walk(&root)? {
+        let plain_text = placeholder::PLAIN_TEXT
+            .iter()
+            .any(|(file, ..)| Path::new(file) == path);
+        if Language::from_path(&path).is_some() || plain_text {
+            let contents = fs::read(&root.join(&path))?;
+            directives.extend(
+                placeholder::directives(&path, &contents)?
+                    .into_iter()
+                    .map(|directive| (path.clone(), directive)),
+            );
+        }
+    }
+    if directives.is_empty() {
+        println!("No placeholders left to fill in");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for variable in Variable::ALL {
+        let uses: Vec<_> = directives
+            .iter()
+            .filter(|(_, directive)| directive.variables.contains(&variable))
+            .collect();
+        if uses.is_empty() {
+            continue;
+        }
+        println!("{variable}");
+        for (path, directive) in uses {
+            println!(
+                "    {}:{}: {}",
+                path.display(),
+                directive.line,
+                directive.text.trim()
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,210 @@
+//! `xtask lints`
+
+use std::process::ExitCode;
+
+use super::args::Args;
+use crate::{
+    Error, Result, changes, diff,
+    lints::{
+        self,
+        profile::{self, Profile},
+    },
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None | Some("audit") => audit(args),
+        Some("stamp") => stamp(args),
+        Some("profile") => profile(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown lints command `{other}` (expected `audit`, `stamp` or `profile`)"
+        ))),
+    }
+}
+
+fn audit(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let all = args.flag("all");
+    args.finish()?;
+
+    let mut problems = 0;
+    for audit in lints::audit(&root)? {
+        let table = audit.tool.table();
+        match audit.recorded {
+            Some(recorded) => println!(
+                "[{table}] last updated during Rust {recorded}, auditing against {}",
+                audit.current
+            ),
+            None => println!(
+                "[{table}] has no recorded version, auditing against {}",
+                audit.current
+            ),
+        }
+
+        if let Some(new_lints) = &audit.new_lints {
+            let unconfigured: Vec<_> = new_lints
+                .iter()
+                .filter(|lint| all || !lint.configured)
+                .collect();
+            println!(
+                "  {} {} lint(s) added since, {} not configured by the table{}",
+                new_lints.len(),
+                audit.tool,
+                new_lints.iter().filter(|lint| !lint.configured).count(),
+                if unconfigured.is_empty() { "" } else { ":" }
+            );
+            for lint in unconfigured {
+                println!("    {} ({})", lint.name, lint.level);
+            }
+        } else {
+            match audit.recorded {
+                Some(recorded) => println!(
+                    "  Rust {recorded} isn't installed, so new lints can't be told apart \
+                         (install it with `rustup toolchain install {recorded}`)"
+                ),
+                None => println!("  every lint is new to this table"),
+            }
+            println!(
+                "  {} allow-by-default lint(s) not enabled by the table{}",
+                audit.unconfigured.len(),
+                if all {
+                    ":"
+                } else {
+                    " (list them with `--all`)"
+                }
+            );
+            if all {
+                for lint in &audit.unconfigured {
+                    println!("    {}", lint.name);
+                }
+            }
+        }
+
+        for problem in &audit.problems {
+            println!("  {problem}");
+        }
+        problems += audit.problems.len();
+        println!();
+    }
+
+    if problems == 0 {
+        println!("Once reviewed, record the current version with `cargo xtask lints stamp`");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("error: {problems} lint table problem(s)");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn stamp(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let (version, change) = lints::stamp(&root)?;
+    match change {
+        Some(change) => {
+            print!("{}", diff::unified(&change));
+            if !dry_run {
+                changes::apply(&root, &[change])?;
+            }
+        }
+        None => println!("The lint tables were already last updated during Rust {version}"),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn profile(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None | Some("status") => profile_status(args),
+        Some("list") => {
+            // Accepted like everywhere else, though the profiles are built in
+            args.root()?;
+            args.finish()?;
+            for profile in Profile::all()? {
+                println!(
+                    "{} (version {}): {}",
+                    profile.name, profile.version, profile.description
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Some("show") => {
+            args.root()?;
+            let name = args
+                .positional()
+                .ok_or_else(|| Error::InvalidInput("missing profile to show".to_owned()))?;
+            args.finish()?;
+            print!("{}", Profile::find(&name)?.render());
+            Ok(ExitCode::SUCCESS)
+        }
+        Some("switch") => profile_switch(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown lints profile command `{other}` (expected `status`, `list`, `show` or `switch`)"
+        ))),
+    }
+}
+
+fn profile_status(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let status = profile::status(&root)?;
+    let profile = &status.profile;
+    match status.recorded_version {
+        Some(version) if version < profile.version => println!(
+            "Lint profile: {} (version {version}, but version {} is available; \
+             update with `cargo xtask lints profile switch {}`)",
+            profile.name, profile.version, profile.name
+        ),
+        Some(version) => println!("Lint profile: {} (version {version})", profile.name),
+        None => println!(
+            "Lint profile: not recorded, comparing against `{}` (version {})",
+            profile.name, profile.version
+        ),
+    }
+
+    if status.overrides.is_empty() {
+        println!("No local overrides");
+    } else {
+        println!("{} local override(s):", status.overrides.len());
+        for local in &status.overrides {
+            println!("  {local}");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn profile_switch(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let name = args
+        .positional()
+        .ok_or_else(|| Error::InvalidInput("missing profile to switch to".to_owned()))?;
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let (previous, change) = profile::switch(&root, &name)?;
+    match change {
+        Some(change) => {
+            print!("{}", diff::unified(&change));
+            if !dry_run {
+                changes::apply(&root, &[change])?;
+            }
+        }
+        None => println!("The lint tables already match the `{name}` profile"),
+    }
+    if !previous.overrides.is_empty() {
+        println!(
+            "\nKept {} local override(s) of `{}`:",
+            previous.overrides.len(),
+            previous.profile.name
+        );
+        for local in &previous.overrides {
+            println!("  {local}");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
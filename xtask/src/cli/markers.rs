@@ -0,0 +1,53 @@
+//! `xtask markers`
+
+use std::process::ExitCode;
+
+use super::{OutputFormat, args::Args};
+use crate::{
+    Error, Result, json,
+    markers::{self, MarkerKind},
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let format = OutputFormat::from_args(&mut args)?;
+    let kinds = args
+        .options("kind")?
+        .iter()
+        .map(|kind| {
+            MarkerKind::from_keyword(kind).ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "unknown marker kind `{kind}` (expected TODO, ON_RELEASE or NOTE)"
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    args.finish()?;
+
+    let mut markers = markers::scan(&root)?;
+    if !kinds.is_empty() {
+        markers.retain(|marker| kinds.contains(&marker.kind));
+    }
+
+    match format {
+        OutputFormat::Text => {
+            for marker in &markers {
+                println!("{marker}");
+            }
+            let counts = MarkerKind::ALL
+                .iter()
+                .map(|&kind| {
+                    let count = markers.iter().filter(|marker| marker.kind == kind).count();
+                    format!("{count} {kind}")
+                })
+                .collect::<Vec<_>>();
+            println!("\n{} markers ({})", markers.len(), counts.join(", "));
+        }
+        OutputFormat::Json => {
+            let markers = markers.iter().map(markers::Marker::to_json).collect();
+            println!("{}", json::Value::Array(markers).to_pretty_string());
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
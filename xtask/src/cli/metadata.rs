@@ -0,0 +1,101 @@
+//! `xtask metadata`
+
+use std::{path::Path, process::ExitCode};
+
+use super::args::Args;
+use crate::{
+    Error, Result,
+    changes::{self, FileChange},
+    diff, fs, json,
+    metadata::{self, Categories},
+    toml::Document,
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None | Some("check") => check(args),
+        Some("categories") => categories(args),
+        Some("update-categories") => update_categories(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown metadata command `{other}` (expected `check`, `categories` or \
+             `update-categories`)"
+        ))),
+    }
+}
+
+fn check(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let diagnostics = metadata::check(&Document::read(&root.join("Cargo.toml"))?)?;
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    if diagnostics.is_empty() {
+        println!("crates.io would accept the keywords and categories");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {} keyword or category problem(s) listed above",
+            diagnostics.len()
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn categories(mut args: Args) -> Result<ExitCode> {
+    // Accepted like everywhere else, though the snapshot is built in
+    args.root()?;
+    let search = args.positional();
+    args.finish()?;
+
+    let snapshot = Categories::snapshot()?;
+    println!("crates.io category slugs, as of {}:", snapshot.updated);
+    for slug in &snapshot.slugs {
+        if search
+            .as_ref()
+            .is_none_or(|search| slug.contains(search.as_str()))
+        {
+            println!("    {slug}");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn update_categories(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let dry_run = args.flag("dry-run");
+    let file = args.positional().ok_or_else(|| {
+        Error::InvalidInput(
+            "missing the downloaded https://crates.io/api/v1/category_slugs file".to_owned(),
+        )
+    })?;
+    args.finish()?;
+
+    let file = Path::new(&file);
+    let snapshot = Categories::from_api(file, &json::Value::parse(file, &fs::read(file)?)?)?;
+    let path = root.join(metadata::SNAPSHOT_PATH);
+    let original = if path.is_file() {
+        fs::read(&path)?
+    } else {
+        String::new()
+    };
+    let Some(change) = FileChange::new(metadata::SNAPSHOT_PATH, original, snapshot.to_toml())
+    else {
+        println!("The snapshot is already up to date");
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    print!("{}", diff::unified(&change));
+    if !dry_run {
+        changes::apply(&root, &[change])?;
+        println!(
+            "\nUpdated the snapshot to {} slug(s); rebuild the tooling to use it",
+            snapshot.slugs.len()
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
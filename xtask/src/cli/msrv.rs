@@ -0,0 +1,143 @@
+//! `xtask msrv`
+
+use std::process::ExitCode;
+
+use super::args::Args;
+use crate::{Error, Result, changes, diff, msrv};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None | Some("check") => check(args),
+        Some("set") => {
+            let version = args
+                .positional()
+                .ok_or_else(|| Error::InvalidInput("missing version to set".to_owned()))?;
+            set(args, Some(&version))
+        }
+        Some("sync") => set(args, None),
+        Some("find") => find(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown msrv command `{other}` (expected `check`, `find`, `set` or `sync`)"
+        ))),
+    }
+}
+
+fn check(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let report = msrv::check(&root)?;
+    println!("MSRV {} (Cargo.toml:{})", report.msrv, report.line);
+
+    let mut problems = 0;
+    for location in &report.locations {
+        if location.version.same_release(report.msrv) {
+            println!(
+                "  {}:{}: {} (ok)",
+                location.file, location.line, location.version
+            );
+        } else {
+            problems += 1;
+            println!(
+                "  {}:{}: {} (drifted, expected {})",
+                location.file, location.line, location.version, report.msrv
+            );
+        }
+    }
+    if let Some(problem) = report.edition_problem() {
+        problems += 1;
+        println!("  Cargo.toml: {problem}");
+    }
+
+    if problems == 0 {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("\nerror: {problems} MSRV problem(s) (fix drift with `cargo xtask msrv sync`)");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn find(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let write = args.flag("write");
+    args.finish()?;
+
+    let current = msrv::check(&root)?.msrv;
+    let toolchains = msrv::toolchains()?;
+    println!(
+        "Bisecting over {} installed stable toolchain(s): {}",
+        toolchains.len(),
+        toolchains
+            .iter()
+            .map(|toolchain| toolchain.version.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let discovery = msrv::discover(&root, &toolchains, |toolchain| {
+        let compiles = msrv::compiles(&root, toolchain)?;
+        let outcome = if compiles { "ok" } else { "fails" };
+        println!("  {} ({}): {outcome}", toolchain.version, toolchain.name);
+        Ok(compiles)
+    })?;
+
+    let Some(oldest) = discovery.oldest else {
+        eprintln!("error: no installed toolchain compiles the crate");
+        return Ok(ExitCode::FAILURE);
+    };
+    let is_oldest_installed = toolchains.first() == Some(&oldest);
+    println!(
+        "\nOldest installed toolchain that compiles the crate: {}{}",
+        oldest.version,
+        if is_oldest_installed {
+            " (older releases may work too, but aren't installed)"
+        } else {
+            ""
+        }
+    );
+
+    if oldest.version.same_release(current) {
+        println!("The MSRV is already {current}");
+    } else if is_oldest_installed && current < oldest.version {
+        println!(
+            "The MSRV is {current}, which can't be checked without an older toolchain \
+             (install one with `rustup toolchain install {current}`)"
+        );
+    } else if write {
+        let changes = msrv::set(&root, oldest.version)?;
+        for change in &changes {
+            print!("{}", diff::unified(change));
+        }
+        changes::apply(&root, &changes)?;
+    } else {
+        println!(
+            "The MSRV is {current}; update it with `cargo xtask msrv set {}` (or pass `--write`)",
+            oldest.version
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Sets the MSRV everywhere, to `version` or (if [`None`]) to Cargo.toml's.
+fn set(mut args: Args, version: Option<&str>) -> Result<ExitCode> {
+    let root = args.root()?;
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let version = match version {
+        Some(version) => version.parse()?,
+        None => msrv::check(&root)?.msrv,
+    };
+    let changes = msrv::set(&root, version)?;
+    for change in &changes {
+        print!("{}", diff::unified(change));
+    }
+    if changes.is_empty() {
+        println!("MSRV is already {version} everywhere");
+    } else if !dry_run {
+        changes::apply(&root, &changes)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,72 @@
+//! `xtask release`
+
+use std::process::ExitCode;
+
+use super::args::Args;
+use crate::{Error, Result, changes, diff, release, version::Bump};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    let bump = match args.subcommand().as_deref() {
+        Some("check") => return check(args),
+        Some("major") => Bump::Major,
+        Some("minor") => Bump::Minor,
+        Some("patch") => Bump::Patch,
+        Some("pre") => Bump::Pre(args.option("label")?.unwrap_or_else(|| "alpha".to_owned())),
+        Some(other) => {
+            return Err(Error::InvalidInput(format!(
+                "unknown release command `{other}` (expected `check`, `major`, `minor`, `patch` or `pre`)"
+            )));
+        }
+        None => {
+            return Err(Error::InvalidInput(
+                "missing release command (expected `check`, `major`, `minor`, `patch` or `pre`)"
+                    .to_owned(),
+            ));
+        }
+    };
+    let root = args.root()?;
+    let notes = args.option("notes")?;
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let release = release::prepare(&root, &bump, notes.as_deref())?;
+    for change in &release.changes {
+        print!("{}", diff::unified(change));
+    }
+
+    if dry_run {
+        println!(
+            "\nDry run: would release {} (previously {})",
+            release.version, release.previous
+        );
+    } else {
+        changes::apply(&root, &release.changes)?;
+        println!(
+            "\nPrepared release {} (previously {}). Run `cargo xtask release check` before publishing.",
+            release.version, release.previous
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let diagnostics = release::check(&root)?;
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    if diagnostics.is_empty() {
+        println!("Ready to release");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: release blocked by {} problem(s) listed above",
+            diagnostics.len()
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
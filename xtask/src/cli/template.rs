@@ -0,0 +1,180 @@
+//! `xtask template`
+
+use std::{path::PathBuf, process::ExitCode};
+
+use super::args::Args;
+use crate::{
+    Error, Result, changes, diff, git,
+    template::Template,
+    upgrade::{self, Outcome},
+};
+
+pub(super) fn run(mut args: Args) -> Result<ExitCode> {
+    match args.subcommand().as_deref() {
+        None | Some("status") => status(args),
+        Some("stamp") => stamp(args),
+        Some("upgrade") => upgrade(args),
+        Some("check") => check(args),
+        Some(other) => Err(Error::InvalidInput(format!(
+            "unknown template command `{other}` (expected `status`, `stamp`, `upgrade` or `check`)"
+        ))),
+    }
+}
+
+fn status(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let template = args.option("template")?.map(PathBuf::from);
+    args.finish()?;
+
+    let Some(baseline) = upgrade::baseline(&root)? else {
+        println!(
+            "No template baseline recorded in {} (record one with `cargo xtask template stamp`)",
+            upgrade::STAMP_PATH
+        );
+        return Ok(ExitCode::SUCCESS);
+    };
+    println!("Template baseline: {baseline}");
+
+    if let Some(template) = template {
+        let Some(head) = git::resolve(&template, "HEAD")? else {
+            return Err(Error::InvalidInput(format!(
+                "{} is not a git repository",
+                template.display()
+            )));
+        };
+        match git::count_commits(&template, &baseline, &head)? {
+            Some(0) => println!("Up to date with the template"),
+            Some(behind) => {
+                let files = git::changed_files(&template, &baseline, &head)?;
+                println!(
+                    "{behind} template commit(s) behind {}, changing {} file(s) \
+                     (merge them with `cargo xtask template upgrade`)",
+                    upgrade::short(&head),
+                    files.len()
+                );
+                for file in files {
+                    println!("  {}", file.display());
+                }
+            }
+            None => println!("The baseline commit is not in the template repository"),
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn stamp(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let template = PathBuf::from(args.required("template")?);
+    let commit = args.option("commit")?.unwrap_or_else(|| "HEAD".to_owned());
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    let resolved = git::resolve(&template, &commit)?.ok_or_else(|| {
+        Error::InvalidInput(format!(
+            "`{commit}` is not a commit in the template repository at {}",
+            template.display()
+        ))
+    })?;
+    match upgrade::stamp(&root, &resolved)? {
+        Some(change) => {
+            print!("{}", diff::unified(&change));
+            if !dry_run {
+                changes::apply(&root, &[change])?;
+            }
+        }
+        None => println!("The template baseline is already {resolved}"),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn upgrade(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    let template = PathBuf::from(args.required("template")?);
+    let to = args.option("to")?.unwrap_or_else(|| "HEAD".to_owned());
+    let allow_dirty = args.flag("allow-dirty");
+    let dry_run = args.flag("dry-run");
+    args.finish()?;
+
+    // Merging rewrites files in place, so uncommitted work would be hard to
+    // tell apart from the upgrade (or to get back)
+    if !dry_run && !allow_dirty && git::is_dirty(&root)? == Some(true) {
+        return Err(Error::InvalidInput(
+            "the crate has uncommitted changes; commit or stash them first (or pass `--allow-dirty`)"
+                .to_owned(),
+        ));
+    }
+
+    let upgrade = upgrade::plan(&root, &template, &to)?;
+    if upgrade.from == upgrade.to {
+        println!("Already at template commit {}", upgrade.to);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if dry_run {
+        for change in &upgrade.changes {
+            print!("{}", diff::unified(change));
+        }
+    } else {
+        changes::apply(&root, &upgrade.changes)?;
+    }
+
+    println!(
+        "Template {} -> {}:",
+        upgrade::short(&upgrade.from),
+        upgrade::short(&upgrade.to)
+    );
+    for (path, outcome) in &upgrade.files {
+        println!("  {}: {outcome}", path.display());
+    }
+    let count = |wanted: fn(&Outcome) -> bool| {
+        upgrade
+            .files
+            .iter()
+            .filter(|(_, outcome)| wanted(outcome))
+            .count()
+    };
+    println!(
+        "\n{} file(s): {} updated, {} merged, {} conflicted, {} added, {} removed, {} skipped",
+        upgrade.files.len(),
+        count(|outcome| *outcome == Outcome::Updated),
+        count(|outcome| *outcome == Outcome::Merged),
+        count(|outcome| matches!(outcome, Outcome::Conflicted(_))),
+        count(|outcome| *outcome == Outcome::Added),
+        count(|outcome| *outcome == Outcome::Removed),
+        count(|outcome| matches!(outcome, Outcome::Skipped(_))),
+    );
+
+    let conflicts = upgrade.conflicts();
+    if conflicts == 0 {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {conflicts} conflict(s) {}; resolve the `<<<<<<<` markers",
+            if dry_run { "would be left" } else { "left" }
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn check(mut args: Args) -> Result<ExitCode> {
+    let root = args.root()?;
+    args.finish()?;
+
+    let diagnostics = Template::read(&root)?.validate(&root)?;
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    if diagnostics.is_empty() {
+        println!("The template manifest agrees with the template's files");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!(
+            "\nerror: {} problem(s) with the template manifest listed above",
+            diagnostics.len()
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
@@ -0,0 +1,221 @@
+//! The interactive questions of `xtask instantiate --interactive`.
+
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    Error, Result,
+    answers::{self, Answers},
+    conditional::{self, Condition},
+    instantiate::License,
+    metadata, msrv,
+    template::Template,
+    variant,
+};
+
+/// Asks every question on `output`, reading the answers from `input`. The
+/// answers already given (or the template's defaults) are offered as the
+/// defaults, and an invalid answer asks the question again.
+pub(super) fn ask(
+    root: &Path,
+    template: &Template,
+    mut answers: Answers,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Answers> {
+    let mut wizard = Wizard { input, output };
+
+    for declaration in &template.variables {
+        let default = match answers.values.get(declaration.variable) {
+            Some(value) => Some(value.to_owned()),
+            None => declaration.default_value(root)?,
+        };
+        let value = wizard.ask(&declaration.prompt, default.as_deref(), |text| {
+            if text.is_empty() && !declaration.required {
+                return Ok(None);
+            }
+            declaration.variable.validate(text)?;
+            Ok(Some(text.to_owned()))
+        })?;
+        if let Some(value) = value {
+            answers.values.set(declaration.variable, value)?;
+        }
+    }
+
+    answers.keywords = wizard.ask(
+        "Keywords (comma-separated, up to 5)",
+        Some(&answers.keywords.join(", ")),
+        |text| {
+            let keywords = list(text);
+            metadata::validate(&keywords, &[])?;
+            Ok(keywords)
+        },
+    )?;
+    answers.categories = wizard.ask(
+        "Categories (comma-separated crates.io category slugs, up to 5)",
+        Some(&answers.categories.join(", ")),
+        |text| {
+            let categories = list(text);
+            metadata::validate(&[], &categories)?;
+            Ok(categories)
+        },
+    )?;
+
+    let msrv = match answers.msrv {
+        Some(msrv) => msrv,
+        None => msrv::check(root)?.msrv,
+    };
+    answers.msrv = Some(wizard.ask(
+        "Minimum supported Rust version",
+        Some(&msrv.to_string()),
+        str::parse,
+    )?);
+
+    let licenses: Vec<&str> = License::ALL
+        .iter()
+        .map(|license| license.expression())
+        .collect();
+    let license = answers.license.unwrap_or(License::MitOrApache);
+    answers.license = Some(wizard.ask(
+        &format!("License ({})", licenses.join(", ")),
+        Some(license.expression()),
+        answers::parse_license,
+    )?);
+
+    wizard.say("Variants:");
+    for variant in &template.variants {
+        wizard.say(&format!("    {}: {}", variant.name, variant.description));
+    }
+    answers.variants = wizard.ask(
+        "Variants (comma-separated, empty for none)",
+        Some(&answers.variants.join(", ")),
+        |text| {
+            let names = list(text);
+            variant::select(&template.variants, &names)?;
+            Ok(names)
+        },
+    )?;
+
+    // The variants were checked above, so this can't fail
+    let chosen = variant::select(&template.variants, &answers.variants)?;
+    let mut implied = variant::conditions(&template.variants, &chosen);
+    implied.extend(answers.conditions.clone());
+    for (name, value) in conditional::complete(&implied) {
+        let condition = Condition::find(&name)?;
+        let holds = wizard.ask(
+            &format!("{}? (yes/no)", condition.description),
+            Some(if value { "yes" } else { "no" }),
+            |text| match text.to_ascii_lowercase().as_str() {
+                "y" | "yes" => Ok(true),
+                "n" | "no" => Ok(false),
+                _ => Err(Error::InvalidInput("expected yes or no".to_owned())),
+            },
+        )?;
+        answers.conditions.insert(name, holds);
+    }
+
+    Ok(answers)
+}
+
+/// Splits a comma-separated answer into its items.
+fn list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// A terminal to ask questions on.
+struct Wizard<'a, I, O> {
+    input: &'a mut I,
+    output: &'a mut O,
+}
+
+impl<I: BufRead, O: Write> Wizard<'_, I, O> {
+    fn say(&mut self, text: &str) {
+        let _ = writeln!(self.output, "{text}");
+    }
+
+    /// Asks `question` until `parse` accepts the answer. An empty answer
+    /// stands for `default`, if there is one.
+    fn ask<T>(
+        &mut self,
+        question: &str,
+        default: Option<&str>,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<T> {
+        let default = default.filter(|default| !default.is_empty());
+        loop {
+            let _ = match default {
+                Some(default) => write!(self.output, "{question} [{default}]: "),
+                None => write!(self.output, "{question}: "),
+            };
+            let _ = self.output.flush();
+
+            let mut line = String::new();
+            let read = self
+                .input
+                .read_line(&mut line)
+                .map_err(|error| Error::InvalidInput(format!("cannot read an answer: {error}")))?;
+            if read == 0 {
+                return Err(Error::InvalidInput(
+                    "the input ended before every question was answered".to_owned(),
+                ));
+            }
+            let answer = match (line.trim(), default) {
+                ("", Some(default)) => default,
+                (answer, _) => answer,
+            };
+            match parse(answer) {
+                Ok(value) => return Ok(value),
+                Err(error) => self.say(&format!("error: {error}")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::fs::Scratch;
+
+    #[test]
+    fn asks_about_conditions() {
+        let scratch = Scratch::new(&[]);
+        let template = Template {
+            variables: Vec::new(),
+            variants: Vec::new(),
+            files: Vec::new(),
+            checks: Vec::new(),
+        };
+        let answers = Answers {
+            msrv: Some("1.85".parse().unwrap()),
+            ..Answers::default()
+        };
+        // Keywords, categories, MSRV, license and variants, then `loom`,
+        // `no_std` (answered badly first) and `unsafe`
+        let mut input = "\n\n\n\n\nno\nmaybe\nyes\n\n".as_bytes();
+        let mut output = Vec::new();
+        let answers = ask(scratch.path(), &template, answers, &mut input, &mut output).unwrap();
+
+        assert_eq!(
+            answers.conditions,
+            BTreeMap::from([
+                ("loom".to_owned(), false),
+                ("no_std".to_owned(), true),
+                ("unsafe".to_owned(), false),
+            ])
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("error: expected yes or no"));
+        assert!(output.contains(&format!(
+            "{}? (yes/no) [no]: ",
+            Condition::find("unsafe").unwrap().description
+        )));
+    }
+}
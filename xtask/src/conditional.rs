@@ -0,0 +1,418 @@
+//! Conditional blocks: parts of the template that only some crates need.
+//!
+//! A block is delimited by directive comments, each on a line of its own, in
+//! the comment syntax of the file's [`Language`]:
+//!
+//! ```text
+//! # @if no_std
+//! # def build_nostd() -> None:
+//! #     ...
+//! # @else
+//! ...
+//! # @endif
+//! ```
+//!
+//! The condition may be negated (`@if !unsafe`), and the `@else` branch is
+//! optional. Blocks can't be nested.
+//!
+//! The template itself has to stay a working crate, so each [`Condition`] has
+//! a value in the template, and the branch that is dead there is commented
+//! out line by line (at the indentation of its directives). [`resolve`] keeps
+//! the branch an answer selects, uncommenting it if needed, and strips the
+//! rest of the block. A condition that wasn't answered keeps its value in the
+//! template, so no block is left behind.
+
+use std::{collections::BTreeMap, fmt::Write as _, path::Path};
+
+use crate::{Error, Result, markers::Language};
+
+/// A named condition that template blocks can depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    /// The condition's name, as used in `@if` directives.
+    pub name: &'static str,
+    /// What it means for the condition to hold.
+    pub description: &'static str,
+    /// Whether the condition holds for the template itself, which decides
+    /// which branch of each block is commented out.
+    pub template: bool,
+}
+
+/// Every condition, in the order they're listed.
+pub const CONDITIONS: &[Condition] = &[
+    Condition {
+        name: "no_std",
+        description: "The crate is no_std, and is also built for a bare-metal target in CI",
+        template: false,
+    },
+    // The template forbids `unsafe_code`, so a crate only holds this once it
+    // has relaxed that lint
+    Condition {
+        name: "unsafe",
+        description: "The crate contains unsafe code, so its tests also run under Miri",
+        template: false,
+    },
+    Condition {
+        name: "loom",
+        description: "The crate has loom tests, which the leak sanitizer run skips",
+        template: true,
+    },
+];
+
+impl Condition {
+    /// Looks up the condition called `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such condition.
+    pub fn find(name: &str) -> Result<Self> {
+        CONDITIONS
+            .iter()
+            .find(|condition| condition.name == name)
+            .copied()
+            .ok_or_else(|| {
+                let known: Vec<&str> = CONDITIONS.iter().map(|condition| condition.name).collect();
+                Error::InvalidInput(format!(
+                    "unknown condition `{name}` (expected one of {})",
+                    known.join(", ")
+                ))
+            })
+    }
+}
+
+/// One conditional block in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// The name of the condition the block depends on.
+    pub condition: String,
+    /// Whether the condition is negated (`@if !NAME`).
+    pub negated: bool,
+    /// The (1-based) line of the `@if` directive.
+    pub line: usize,
+    /// The (1-based) line of the `@else` directive, if there is one.
+    pub else_line: Option<usize>,
+    /// The (1-based) line of the `@endif` directive.
+    pub end_line: usize,
+    /// The indentation of the directives.
+    indent: String,
+}
+
+impl Block {
+    /// Whether the `@if` branch is selected when the condition is `value`.
+    fn selects_if(&self, value: bool) -> bool {
+        value != self.negated
+    }
+}
+
+/// The directive on `line`, if it is one, along with its indentation.
+fn directive(language: Language, line: &str) -> Option<(&str, &str)> {
+    let (open, close) = language.line_comment();
+    let trimmed = line.trim_start();
+    let text = trimmed.strip_prefix(open)?.trim_end();
+    let text = text.strip_suffix(close)?.trim();
+    let is_directive = text == "@else" || text == "@endif" || text.starts_with("@if ");
+    is_directive.then(|| (&line[..line.len() - trimmed.len()], text))
+}
+
+/// Finds the conditional blocks in the contents of the file at `path`, and
+/// checks that the branch that is dead in the template is commented out.
+///
+/// # Errors
+///
+/// Returns an error if a directive is misplaced or names an unknown condition, or a dead branch contains a line
+/// that isn't commented out.
+pub fn blocks(path: &Path, contents: &str) -> Result<Vec<Block>> {
+    let Some(language) = Language::from_path(path) else {
+        return Ok(Vec::new());
+    };
+    let malformed =
+        |line: usize, message: &str| Error::malformed(path, format!("line {line}: {message}"));
+
+    let mut blocks = Vec::new();
+    let mut open: Option<Block> = None;
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let Some((indent, text)) = directive(language, line) else {
+            continue;
+        };
+        match (text, &mut open) {
+            ("@else", Some(block)) if block.else_line.is_none() && block.indent == indent => {
+                block.else_line = Some(number);
+            }
+            ("@endif", Some(block)) if block.indent == indent => {
+                block.end_line = number;
+                blocks.extend(open.take());
+            }
+            (_, Some(block)) => {
+                return Err(malformed(
+                    number,
+                    &format!(
+                        "unexpected `{text}` in the block starting on line {}",
+                        block.line
+                    ),
+                ));
+            }
+            ("@else" | "@endif", None) => {
+                return Err(malformed(number, &format!("`{text}` without `@if`")));
+            }
+            (_, None) => {
+                let expression = text.trim_start_matches("@if").trim();
+                let (negated, name) = match expression.strip_prefix('!') {
+                    Some(name) => (true, name.trim()),
+                    None => (false, expression),
+                };
+                Condition::find(name).map_err(|error| malformed(number, &error.to_string()))?;
+                open = Some(Block {
+                    condition: name.to_owned(),
+                    negated,
+                    line: number,
+                    else_line: None,
+                    end_line: number,
+                    indent: indent.to_owned(),
+                });
+            }
+        }
+    }
+    if let Some(block) = open {
+        return Err(malformed(block.line, "`@if` without `@endif`"));
+    }
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let (open, _) = language.line_comment();
+    for block in &blocks {
+        let template = Condition::find(&block.condition)?.template;
+        let mut dead = branch(block, !block.selects_if(template));
+        let leader = format!("{}{open}", block.indent);
+        if let Some(index) =
+            dead.find(|&index| !lines[index].is_empty() && !lines[index].starts_with(&leader))
+        {
+            return Err(malformed(
+                index + 1,
+                &format!(
+                    "the template doesn't meet the condition of this branch, so it should be \
+                     commented out with `{leader}`"
+                ),
+            ));
+        }
+    }
+
+    Ok(blocks)
+}
+
+/// The (0-based) lines of the `@if` branch (if `selects_if`) or the `@else`
+/// branch of `block`, without directives.
+fn branch(block: &Block, selects_if: bool) -> std::ops::Range<usize> {
+    match (selects_if, block.else_line) {
+        (true, Some(else_line)) => block.line..else_line - 1,
+        (true, None) => block.line..block.end_line - 1,
+        (false, Some(else_line)) => else_line..block.end_line - 1,
+        (false, None) => block.end_line - 1..block.end_line - 1,
+    }
+}
+
+/// The value of every condition: its answer in `answers` if it has one, and
+/// its value in the template otherwise.
+pub fn complete(answers: &BTreeMap<String, bool>) -> BTreeMap<String, bool> {
+    CONDITIONS
+        .iter()
+        .map(|condition| {
+            let value = answers
+                .get(condition.name)
+                .copied()
+                .unwrap_or(condition.template);
+            (condition.name.to_owned(), value)
+        })
+        .collect()
+}
+
+/// Resolves every block in the contents of the file at `path`, with the
+/// conditions' values in `answers` (see [`complete`]).
+///
+/// The selected branch is kept (and uncommented if it's dead in the
+/// template), and the directives and other branch are deleted.
+///
+/// # Errors
+///
+/// Returns an error if the file's blocks are malformed (see [`blocks`]).
+pub fn resolve(path: &Path, contents: &str, answers: &BTreeMap<String, bool>) -> Result<String> {
+    let blocks = blocks(path, contents)?;
+    let Some(language) = Language::from_path(path).filter(|_| !blocks.is_empty()) else {
+        return Ok(contents.to_owned());
+    };
+    let (open, close) = language.line_comment();
+
+    let answers = complete(answers);
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    for block in &blocks {
+        let value = answers[&block.condition];
+        let start = block.line - 1;
+        output.extend(lines[next..start].iter().map(|&line| line.to_owned()));
+        next = block.end_line;
+
+        let selects_if = block.selects_if(value);
+        let template_selects_if = block.selects_if(Condition::find(&block.condition)?.template);
+        let leader = format!("{}{open}", block.indent);
+        for &line in &lines[branch(block, selects_if)] {
+            if selects_if == template_selects_if {
+                output.push(line.to_owned());
+                continue;
+            }
+            let text = line.strip_prefix(&leader).unwrap_or(line).trim_end();
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            let text = text.strip_suffix(close).unwrap_or(text).trim_end();
+            output.push(if text.is_empty() {
+                String::new()
+            } else {
+                format!("{}{text}", block.indent)
+            });
+        }
+
+        // Don't leave a double gap where a deleted block was
+        if output.last().is_some_and(|line| line.trim().is_empty()) {
+            while lines.get(next).is_some_and(|line| line.trim().is_empty()) {
+                next += 1;
+            }
+        }
+    }
+    output.extend(lines[next..].iter().map(|&line| line.to_owned()));
+    if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+        while output.last().is_some_and(|line| line.trim().is_empty()) {
+            output.pop();
+        }
+    }
+
+    let mut updated = String::with_capacity(contents.len());
+    for line in output {
+        let _ = writeln!(updated, "{line}");
+    }
+    if !contents.ends_with('\n') {
+        updated.pop();
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "\
+CI_STAGES = [
+    # @if no_std
+    # \"build_nostd\",
+    # @else
+    \"build\",
+    # @endif
+    # @if unsafe
+    # \"run_tests_miri\",
+    # @endif
+]
+
+# @if !loom
+# def skip_loom() -> None:
+#     pass
+# @endif
+
+print(\"done\")
+";
+
+    fn resolve(answers: &[(&str, bool)]) -> String {
+        let answers = answers
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value))
+            .collect();
+        super::resolve(Path::new("ci.py"), SCRIPT, &answers).unwrap()
+    }
+
+    #[test]
+    fn blocks_are_found() {
+        let blocks = blocks(Path::new("ci.py"), SCRIPT).unwrap();
+        let found: Vec<_> = blocks
+            .iter()
+            .map(|block| {
+                (
+                    block.condition.as_str(),
+                    block.negated,
+                    block.line,
+                    block.else_line,
+                    block.end_line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("no_std", false, 2, Some(4), 6),
+                ("unsafe", false, 7, None, 9),
+                ("loom", true, 12, None, 15),
+            ]
+        );
+    }
+
+    #[test]
+    fn unanswered_conditions_keep_their_template_values() {
+        let resolved = resolve(&[]);
+        assert_eq!(
+            resolved,
+            "CI_STAGES = [\n    \"build\",\n]\n\nprint(\"done\")\n"
+        );
+        assert_eq!(resolve(&[("no_std", false), ("unsafe", false)]), resolved);
+    }
+
+    #[test]
+    fn dead_branches_are_uncommented() {
+        assert_eq!(
+            resolve(&[("no_std", true), ("unsafe", true), ("loom", false)]),
+            "CI_STAGES = [\n    \"build_nostd\",\n    \"run_tests_miri\",\n]\n\ndef skip_loom() -> None:\n    pass\n\n\
+             print(\"done\")\n"
+        );
+    }
+
+    #[test]
+    fn complete_fills_in_template_values() {
+        let answers = BTreeMap::from([("loom".to_owned(), false)]);
+        assert_eq!(
+            complete(&answers),
+            BTreeMap::from([
+                ("loom".to_owned(), false),
+                ("no_std".to_owned(), false),
+                ("unsafe".to_owned(), false),
+            ])
+        );
+    }
+
+    #[test]
+    fn other_languages() {
+        let markdown = "a\n<!-- @if no_std -->\n<!-- no_std only -->\n<!-- @endif -->\nb";
+        let answers = BTreeMap::from([("no_std".to_owned(), true)]);
+        assert_eq!(
+            super::resolve(Path::new("README.md"), markdown, &answers).unwrap(),
+            "a\nno_std only\nb"
+        );
+
+        let rust = "fn f() {\n    // @if loom\n    loom_model();\n    // @endif\n}\n";
+        assert_eq!(
+            super::resolve(Path::new("lib.rs"), rust, &BTreeMap::new()).unwrap(),
+            "fn f() {\n    loom_model();\n}\n"
+        );
+    }
+
+    #[test]
+    fn malformed_blocks() {
+        for contents in [
+            "# @if no_std\n",
+            "# @endif\n",
+            "# @else\n",
+            "# @if unknown\n# @endif\n",
+            "# @if no_std\n# @if unsafe\n# @endif\n# @endif\n",
+            "# @if no_std\nnot_commented = True\n# @endif\n",
+            "# @if loom\n# @else\nnot_commented = True\n# @endif\n",
+        ] {
+            assert!(
+                blocks(Path::new("ci.py"), contents).is_err(),
+                "{contents:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Validating crate names against the rules of crates.io and Cargo.
+//!
+//! The rules are embedded, so names are checked offline: a name must be at
+//! most [`MAX_LENGTH`] ASCII letters, digits, `-` and `_`, starting with a
+//! letter, and must not be one of the [`RESERVED`] names. crates.io compares
+//! names case-insensitively with `-` and `_` treated alike (see
+//! [`canonical`]), so `Proc-Macro` is as reserved as `proc_macro`.
+//!
+//! Whether a name is already taken on crates.io can't be checked offline, so
+//! it isn't.
+
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// The longest name crates.io accepts, in characters.
+pub const MAX_LENGTH: usize = 64;
+
+/// Why a name is reserved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// crates.io reserves it for the Rust project (mostly the standard
+    /// library's crates and the compiler's tools).
+    Rust,
+    /// It's a Rust keyword, which Cargo refuses as a package name.
+    Keyword,
+    /// It's one of the directories of Cargo's build output.
+    Artifact,
+    /// Windows can't create a file with this name, whatever the extension.
+    Windows,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rust => "reserved by crates.io for the Rust project",
+            Self::Keyword => "a Rust keyword",
+            Self::Artifact => "the name of a directory of Cargo's build output",
+            Self::Windows => "a reserved file name on Windows",
+        })
+    }
+}
+
+/// The reserved names, in their [`canonical`] form, and why each is
+/// reserved.
+pub const RESERVED: &[(&str, Reason)] = &[
+    // crates.io's list of names reserved for the Rust project
+    ("alloc", Reason::Rust),
+    ("arena", Reason::Rust),
+    ("ast", Reason::Rust),
+    ("builtins", Reason::Rust),
+    ("collections", Reason::Rust),
+    ("compiler_builtins", Reason::Rust),
+    ("compiler_rt", Reason::Rust),
+    ("compiletest", Reason::Rust),
+    ("core", Reason::Rust),
+    ("coretest", Reason::Rust),
+    ("debug", Reason::Rust),
+    ("driver", Reason::Rust),
+    ("flate", Reason::Rust),
+    ("fmt_macros", Reason::Rust),
+    ("grammar", Reason::Rust),
+    ("graphviz", Reason::Rust),
+    ("macros", Reason::Rust),
+    ("proc_macro", Reason::Rust),
+    ("rbml", Reason::Rust),
+    ("rust_installer", Reason::Rust),
+    ("rustbook", Reason::Rust),
+    ("rustc", Reason::Rust),
+    ("rustc_back", Reason::Rust),
+    ("rustc_borrowck", Reason::Rust),
+    ("rustc_driver", Reason::Rust),
+    ("rustc_llvm", Reason::Rust),
+    ("rustc_resolve", Reason::Rust),
+    ("rustc_trans", Reason::Rust),
+    ("rustc_typeck", Reason::Rust),
+    ("rustdoc", Reason::Rust),
+    ("rustllvm", Reason::Rust),
+    ("rustuv", Reason::Rust),
+    ("serialize", Reason::Rust),
+    ("std", Reason::Rust),
+    ("syntax", Reason::Rust),
+    ("test", Reason::Rust),
+    ("unicode", Reason::Rust),
+    // Strict and reserved keywords, as of the 2024 edition
+    ("abstract", Reason::Keyword),
+    ("as", Reason::Keyword),
+    ("async", Reason::Keyword),
+    ("await", Reason::Keyword),
+    ("become", Reason::Keyword),
+    ("box", Reason::Keyword),
+    ("break", Reason::Keyword),
+    ("const", Reason::Keyword),
+    ("continue", Reason::Keyword),
+    ("crate", Reason::Keyword),
+    ("do", Reason::Keyword),
+    ("dyn", Reason::Keyword),
+    ("else", Reason::Keyword),
+    ("enum", Reason::Keyword),
+    ("extern", Reason::Keyword),
+    ("false", Reason::Keyword),
+    ("final", Reason::Keyword),
+    ("fn", Reason::Keyword),
+    ("for", Reason::Keyword),
+    ("gen", Reason::Keyword),
+    ("if", Reason::Keyword),
+    ("impl", Reason::Keyword),
+    ("in", Reason::Keyword),
+    ("let", Reason::Keyword),
+    ("loop", Reason::Keyword),
+    ("macro", Reason::Keyword),
+    ("match", Reason::Keyword),
+    ("mod", Reason::Keyword),
+    ("move", Reason::Keyword),
+    ("mut", Reason::Keyword),
+    ("override", Reason::Keyword),
+    ("priv", Reason::Keyword),
+    ("pub", Reason::Keyword),
+    ("ref", Reason::Keyword),
+    ("return", Reason::Keyword),
+    ("self", Reason::Keyword),
+    ("static", Reason::Keyword),
+    ("struct", Reason::Keyword),
+    ("super", Reason::Keyword),
+    ("trait", Reason::Keyword),
+    ("true", Reason::Keyword),
+    ("try", Reason::Keyword),
+    ("type", Reason::Keyword),
+    ("typeof", Reason::Keyword),
+    ("unsafe", Reason::Keyword),
+    ("unsized", Reason::Keyword),
+    ("use", Reason::Keyword),
+    ("virtual", Reason::Keyword),
+    ("where", Reason::Keyword),
+    ("while", Reason::Keyword),
+    ("yield", Reason::Keyword),
+    // Directories of target/<profile>/
+    ("build", Reason::Artifact),
+    ("deps", Reason::Artifact),
+    ("examples", Reason::Artifact),
+    ("incremental", Reason::Artifact),
+    // Device names reserved by Windows
+    ("aux", Reason::Windows),
+    ("com1", Reason::Windows),
+    ("com2", Reason::Windows),
+    ("com3", Reason::Windows),
+    ("com4", Reason::Windows),
+    ("com5", Reason::Windows),
+    ("com6", Reason::Windows),
+    ("com7", Reason::Windows),
+    ("com8", Reason::Windows),
+    ("com9", Reason::Windows),
+    ("con", Reason::Windows),
+    ("lpt1", Reason::Windows),
+    ("lpt2", Reason::Windows),
+    ("lpt3", Reason::Windows),
+    ("lpt4", Reason::Windows),
+    ("lpt5", Reason::Windows),
+    ("lpt6", Reason::Windows),
+    ("lpt7", Reason::Windows),
+    ("lpt8", Reason::Windows),
+    ("lpt9", Reason::Windows),
+    ("nul", Reason::Windows),
+    ("prn", Reason::Windows),
+];
+
+/// Something wrong with a crate name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The name is empty.
+    Empty,
+    /// The name is longer than [`MAX_LENGTH`] characters.
+    TooLong(usize),
+    /// The name doesn't start with an ASCII letter.
+    BadStart(char),
+    /// The name contains characters other than ASCII letters, digits, `-` and
+    /// `_` (each listed once).
+    BadCharacters(Vec<char>),
+    /// The name is reserved. The reserved name is given in its canonical
+    /// form, which may differ from the name as written.
+    Reserved(&'static str, Reason),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("must not be empty"),
+            Self::TooLong(length) => write!(
+                f,
+                "is {length} characters long (crates.io allows at most {MAX_LENGTH})"
+            ),
+            Self::BadStart(c) => write!(f, "starts with `{c}` (it must start with a letter)"),
+            Self::BadCharacters(characters) => {
+                let characters: Vec<String> = characters.iter().map(|c| format!("`{c}`")).collect();
+                write!(
+                    f,
+                    "contains {} (only ASCII letters, digits, `-` and `_` are allowed)",
+                    characters.join(", ")
+                )
+            }
+            Self::Reserved(reserved, reason) => write!(
+                f,
+                "is `{reserved}` (ignoring case, and with `-` and `_` treated alike), which is \
+                 {reason}"
+            ),
+        }
+    }
+}
+
+/// The form crates.io compares names in: lowercase, with every `-` replaced
+/// by `_`. Two names with the same canonical form are the same crate.
+pub fn canonical(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// Checks `name` against every rule, returning everything wrong with it (an
+/// empty list if it's a valid crate name).
+pub fn check(name: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let Some(first) = name.chars().next() else {
+        problems.push(Problem::Empty);
+        return problems;
+    };
+
+    let length = name.chars().count();
+    if length > MAX_LENGTH {
+        problems.push(Problem::TooLong(length));
+    }
+    if !first.is_ascii_alphabetic() {
+        problems.push(Problem::BadStart(first));
+    }
+    let mut bad = Vec::new();
+    for c in name.chars() {
+        let allowed = c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if !allowed && !bad.contains(&c) {
+            bad.push(c);
+        }
+    }
+    if !bad.is_empty() {
+        problems.push(Problem::BadCharacters(bad));
+    }
+
+    let canonical = canonical(name);
+    if let Some(&(reserved, reason)) = RESERVED
+        .iter()
+        .find(|&&(reserved, _)| reserved == canonical)
+    {
+        problems.push(Problem::Reserved(reserved, reason));
+    }
+
+    problems
+}
+
+/// A validated crate name.
+///
+/// Cargo treats `-` and `_` in package names as equivalent, but they are not
+/// interchangeable everywhere: the package name and URLs use the name as
+/// given, while Rust paths (and the docs.rs path segment after the version)
+/// need the `snake_case` form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrateName(String);
+
+impl CrateName {
+    /// Validates `name` as a crate name (see [`check`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing everything wrong with `name`.
+    pub fn new(name: &str) -> Result<Self> {
+        let problems: Vec<String> = check(name).iter().map(Problem::to_string).collect();
+        if !problems.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "invalid crate name `{name}`: {}",
+                problems.join("; ")
+            )));
+        }
+
+        Ok(Self(name.to_owned()))
+    }
+
+    /// The name exactly as it was given.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The name with every `_` replaced by `-`, as used in URLs.
+    pub fn kebab_case(&self) -> String {
+        self.0.replace('_', "-")
+    }
+
+    /// The name with every `-` replaced by `_`, as used in Rust paths.
+    pub fn snake_case(&self) -> String {
+        self.0.replace('-', "_")
+    }
+}
+
+impl fmt::Display for CrateName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in [
+            "a",
+            "my-crate",
+            "my_crate2",
+            "Serde",
+            &"a".repeat(MAX_LENGTH),
+        ] {
+            assert_eq!(check(name), [], "{name}");
+        }
+        let name = CrateName::new("my_fancy-crate").unwrap();
+        assert_eq!(name.as_str(), "my_fancy-crate");
+        assert_eq!(name.kebab_case(), "my-fancy-crate");
+        assert_eq!(name.snake_case(), "my_fancy_crate");
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert_eq!(check(""), [Problem::Empty]);
+        assert_eq!(
+            check(&"a".repeat(MAX_LENGTH + 1)),
+            [Problem::TooLong(MAX_LENGTH + 1)]
+        );
+        assert_eq!(
+            check("1 crate.rs!"),
+            [
+                Problem::BadStart('1'),
+                Problem::BadCharacters(vec![' ', '.', '!'])
+            ]
+        );
+        assert_eq!(
+            check("Proc-Macro"),
+            [Problem::Reserved("proc_macro", Reason::Rust)]
+        );
+        assert_eq!(check("fn"), [Problem::Reserved("fn", Reason::Keyword)]);
+        assert_eq!(check("CON"), [Problem::Reserved("con", Reason::Windows)]);
+        assert_eq!(
+            CrateName::new("-x").unwrap_err().to_string(),
+            "invalid crate name `-x`: starts with `-` (it must start with a letter)"
+        );
+    }
+
+    #[test]
+    fn reserved_names_are_canonical() {
+        for &(name, _) in RESERVED {
+            assert_eq!(canonical(name), name);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Unified diffs, for showing planned [`FileChange`]s before they're applied.
+
+use std::fmt::Write as _;
+
+use crate::changes::FileChange;
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+/// One line of an edit script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+/// Formats `change` as a unified diff, like `git diff` would.
+pub fn unified(change: &FileChange) -> String {
+    let path = change.path.display();
+    let old: Vec<&str> = change.original.lines().collect();
+    let new: Vec<&str> = change.updated.lines().collect();
+    let edits = edits(&old, &new);
+
+    let mut output = if change.deleted {
+        format!("--- a/{path}\n+++ /dev/null\n")
+    } else {
+        format!("--- a/{path}\n+++ b/{path}\n")
+    };
+    let changed: Vec<usize> = (0..edits.len())
+        .filter(|&index| !matches!(edits[index], Edit::Keep(_)))
+        .collect();
+
+    let mut index = 0;
+    while index < changed.len() {
+        // Group changes whose context would overlap into one hunk
+        let start = changed[index].saturating_sub(CONTEXT);
+        let mut end = changed[index];
+        while index < changed.len() && changed[index] <= end + 2 * CONTEXT {
+            end = changed[index];
+            index += 1;
+        }
+        let end = (end + CONTEXT + 1).min(edits.len());
+
+        let old_start = 1 + edits[..start]
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Add(_)))
+            .count();
+        let new_start = 1 + edits[..start]
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Remove(_)))
+            .count();
+        let hunk = &edits[start..end];
+        let old_len = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Add(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Remove(_)))
+            .count();
+
+        let _ = writeln!(
+            output,
+            "@@ -{old_start},{old_len} +{new_start},{new_len} @@"
+        );
+        for edit in hunk {
+            let (prefix, line) = match edit {
+                Edit::Keep(line) => (' ', line),
+                Edit::Remove(line) => ('-', line),
+                Edit::Add(line) => ('+', line),
+            };
+            output.push(prefix);
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+/// Computes a minimal edit script turning `old` into `new`, using the longest
+/// common subsequence of their lines.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    // lengths[i][j] is the LCS length of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0_usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Keep(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            edits.push(Edit::Remove(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(new[j]));
+            j += 1;
+        }
+    }
+
+    edits
+}
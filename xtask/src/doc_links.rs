@@ -0,0 +1,774 @@
+//! Keeping the docs.rs links in README.md in sync with src/lib.rs.
+//!
+//! README.md ends with Markdown link reference definitions pointing at
+//! docs.rs, so that item links work when the README is rendered on GitHub or
+//! crates.io. src/lib.rs includes the README as the crate documentation, and
+//! overrides each of those definitions with a `//!` definition pointing at the
+//! item itself, so rustdoc renders a relative link instead. If the two lists
+//! drift apart, the crate docs silently fall back to hard-coded docs.rs links
+//! (possibly to a different version of the crate).
+//!
+//! [`check`] compares the two lists (and the crate's actual public items), and
+//! [`sync`] regenerates one list from the other, or both from the public
+//! items.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result, changes::FileChange, fs, release::VERSION_PLACEHOLDER, toml::Document};
+
+/// A link reference definition, like ``[`Item`]: target``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// The label, including its brackets' contents only (like `` `Item` ``).
+    pub label: String,
+    /// The link destination.
+    pub target: String,
+    /// The (1-based) line the definition is on.
+    pub line: usize,
+}
+
+/// The kind of a public item, as named in docs.rs URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    /// A module (`index.html`).
+    Module,
+    /// `struct.Name.html`
+    Struct,
+    /// `enum.Name.html`
+    Enum,
+    /// `union.Name.html`
+    Union,
+    /// `trait.Name.html`
+    Trait,
+    /// `fn.name.html`
+    Function,
+    /// `type.Name.html`
+    TypeAlias,
+    /// `constant.NAME.html`
+    Constant,
+    /// `static.NAME.html`
+    Static,
+    /// `macro.name.html`
+    Macro,
+}
+
+impl ItemKind {
+    /// The prefix of the item's page in docs.rs URLs.
+    fn url_prefix(self) -> &'static str {
+        match self {
+            Self::Module => "index",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Union => "union",
+            Self::Trait => "trait",
+            Self::Function => "fn",
+            Self::TypeAlias => "type",
+            Self::Constant => "constant",
+            Self::Static => "static",
+            Self::Macro => "macro",
+        }
+    }
+
+    /// Parses the keyword introducing an item declaration.
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "mod" => Some(Self::Module),
+            "struct" => Some(Self::Struct),
+            "enum" => Some(Self::Enum),
+            "union" => Some(Self::Union),
+            "trait" => Some(Self::Trait),
+            "fn" => Some(Self::Function),
+            "type" => Some(Self::TypeAlias),
+            "const" => Some(Self::Constant),
+            "static" => Some(Self::Static),
+            _ => None,
+        }
+    }
+}
+
+/// A public item of the crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// The path of the item relative to the crate root, like `module::Item`.
+    pub path: String,
+    /// The kind of item.
+    pub kind: ItemKind,
+}
+
+/// A way in which the two lists of links disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// README.md links to an item that src/lib.rs does not override.
+    MissingInLib {
+        /// The README.md definition.
+        readme: Link,
+    },
+    /// src/lib.rs overrides a link that README.md does not define.
+    MissingInReadme {
+        /// The src/lib.rs definition.
+        lib: Link,
+    },
+    /// The two definitions of a label point at different items.
+    Mismatch {
+        /// The README.md definition.
+        readme: Link,
+        /// The src/lib.rs definition.
+        lib: Link,
+    },
+    /// A src/lib.rs definition points at something that is not a public item
+    /// of the crate.
+    UnknownItem {
+        /// The src/lib.rs definition.
+        lib: Link,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingInLib { readme } => write!(
+                f,
+                "{README}:{}: [{}] has no matching `//!` definition in {LIB}",
+                readme.line, readme.label
+            ),
+            Self::MissingInReadme { lib } => write!(
+                f,
+                "{LIB}:{}: [{}] has no matching docs.rs link in {README}",
+                lib.line, lib.label
+            ),
+            Self::Mismatch { readme, lib } => write!(
+                f,
+                "{LIB}:{}: [{}] points at `{}`, but {README}:{} links to `{}`",
+                lib.line,
+                lib.label,
+                lib.target,
+                readme.line,
+                item_path_from_url(&readme.target).unwrap_or_default()
+            ),
+            Self::UnknownItem { lib } => write!(
+                f,
+                "{LIB}:{}: [{}] points at `{}`, which is not a public item of the crate",
+                lib.line, lib.label, lib.target
+            ),
+        }
+    }
+}
+
+/// The path of the README, relative to the crate root.
+const README: &str = "README.md";
+
+/// The path of the crate root module, relative to the crate root.
+const LIB: &str = "src/lib.rs";
+
+/// What [`sync`] regenerates the lists of links from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Regenerate the `//!` definitions in src/lib.rs from README.md.
+    Readme,
+    /// Regenerate the docs.rs links in README.md from src/lib.rs (using the
+    /// crate's public items to build each URL).
+    Lib,
+    /// Regenerate both lists from the crate's public items, linking every one
+    /// of them. An item src/lib.rs already links to keeps its label, and the
+    /// others are labelled with their path, like `` `module::Item` ``.
+    Items,
+}
+
+/// Compares the link definitions of the crate at `root`.
+///
+/// # Errors
+///
+/// Returns an error if README.md or the crate's source cannot be read.
+pub fn check(root: &Path) -> Result<Vec<Problem>> {
+    let readme = readme_links(&fs::read(&root.join(README))?);
+    let lib = lib_links(&fs::read(&root.join(LIB))?);
+    let items = public_items(root)?;
+
+    let mut problems = Vec::new();
+    for readme in &readme {
+        match lib.iter().find(|lib| lib.label == readme.label) {
+            None => problems.push(Problem::MissingInLib {
+                readme: readme.clone(),
+            }),
+            Some(lib) if item_path_from_url(&readme.target).as_deref() != Some(&lib.target) => {
+                problems.push(Problem::Mismatch {
+                    readme: readme.clone(),
+                    lib: lib.clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    for lib in &lib {
+        if !readme.iter().any(|readme| readme.label == lib.label) {
+            problems.push(Problem::MissingInReadme { lib: lib.clone() });
+        }
+        if !items.iter().any(|item| item.path == lib.target) {
+            problems.push(Problem::UnknownItem { lib: lib.clone() });
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Regenerates the links of one file from the other's, or of both from the
+/// crate's public items.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read, or (when regenerating README.md
+/// from src/lib.rs) a `//!` definition points at something that is not a
+/// public item.
+pub fn sync(root: &Path, source: Source) -> Result<Vec<FileChange>> {
+    let readme = fs::read(&root.join(README))?;
+    let lib = fs::read(&root.join(LIB))?;
+
+    let (updated_readme, updated_lib) = match source {
+        Source::Readme => {
+            let definitions = readme_links(&readme)
+                .iter()
+                .filter_map(|link| {
+                    let path = item_path_from_url(&link.target)?;
+                    Some(format!("//! [{}]: {path}\n", link.label))
+                })
+                .collect::<String>();
+            (None, Some(with_lib_definitions(&lib, &definitions)))
+        }
+        Source::Lib => {
+            let items = public_items(root)?;
+            let links = lib_links(&lib)
+                .into_iter()
+                .map(|link| {
+                    let item = items
+                        .iter()
+                        .find(|item| item.path == link.target)
+                        .ok_or_else(|| {
+                            Error::malformed(
+                                LIB,
+                                format!(
+                                    "line {}: `{}` is not a public item of the crate",
+                                    link.line, link.target
+                                ),
+                            )
+                        })?;
+                    Ok((link.label, item))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (Some(with_readme_definitions(root, &readme, &links)?), None)
+        }
+        Source::Items => {
+            let items = public_items(root)?;
+            let existing = lib_links(&lib);
+            // Items that are already linked keep their labels
+            let links: Vec<(String, &Item)> = items
+                .iter()
+                .map(|item| {
+                    let label = existing
+                        .iter()
+                        .find(|link| link.target == item.path)
+                        .map_or_else(|| format!("`{}`", item.path), |link| link.label.clone());
+                    (label, item)
+                })
+                .collect();
+            let mut definitions = String::new();
+            for (label, item) in &links {
+                let _ = writeln!(definitions, "//! [{label}]: {}", item.path);
+            }
+            (
+                Some(with_readme_definitions(root, &readme, &links)?),
+                Some(with_lib_definitions(&lib, &definitions)),
+            )
+        }
+    };
+
+    Ok([(README, readme, updated_readme), (LIB, lib, updated_lib)]
+        .into_iter()
+        .filter_map(|(path, original, updated)| FileChange::new(path, original, updated?))
+        .collect())
+}
+
+/// src/lib.rs (with `contents`) with its `//!` definitions replaced by
+/// `definitions`.
+fn with_lib_definitions(contents: &str, definitions: &str) -> String {
+    replace_lines(contents, &lib_links(contents), definitions, |lines| {
+        // Directly after the comment block at the top of the file
+        lines
+            .iter()
+            .position(|line| !line.trim_start().starts_with("//"))
+            .unwrap_or(lines.len())
+    })
+}
+
+/// README.md (with `contents`) with its docs.rs links replaced by links to
+/// `items`, each with its label.
+fn with_readme_definitions(
+    root: &Path,
+    contents: &str,
+    items: &[(String, &Item)],
+) -> Result<String> {
+    let name = Document::read(&root.join("Cargo.toml"))?
+        .get_str("package", "name")
+        .ok_or_else(|| Error::malformed("Cargo.toml", "`package.name` is missing"))?;
+    let existing = readme_links(contents);
+    // Keep whatever version the links are currently pinned to
+    let version = existing
+        .iter()
+        .find_map(|link| url_segments(&link.target).map(|segments| segments[1].to_owned()))
+        .unwrap_or_else(|| VERSION_PLACEHOLDER.to_owned());
+
+    let mut definitions = String::new();
+    for (label, item) in items {
+        let _ = writeln!(
+            definitions,
+            "[{label}]: {}",
+            docs_rs_url(&name, &version, item)
+        );
+    }
+    Ok(replace_lines(contents, &existing, &definitions, |lines| {
+        lines.len()
+    }))
+}
+
+/// Parses the docs.rs link reference definitions in README.md.
+fn readme_links(contents: &str) -> Vec<Link> {
+    let mut in_fence = false;
+    let mut links = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence {
+            continue;
+        }
+        let definition = parse_definition(line).filter(|(_, target)| target.contains("docs.rs/"));
+        if let Some((label, target)) = definition {
+            links.push(Link {
+                label: label.to_owned(),
+                target: target.to_owned(),
+                line: index + 1,
+            });
+        }
+    }
+
+    links
+}
+
+/// Parses the `//!` link reference definitions in src/lib.rs.
+fn lib_links(contents: &str) -> Vec<Link> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let (label, target) = parse_definition(line.trim_start().strip_prefix("//!")?.trim())?;
+            Some(Link {
+                label: label.to_owned(),
+                target: target.to_owned(),
+                line: index + 1,
+            })
+        })
+        .collect()
+}
+
+/// Parses a `[label]: target` line.
+fn parse_definition(line: &str) -> Option<(&str, &str)> {
+    let (label, target) = line.strip_prefix('[')?.split_once("]:")?;
+    let target = target.trim();
+    (!label.is_empty() && !target.is_empty() && !target.contains(' ')).then_some((label, target))
+}
+
+/// Splits a docs.rs URL into its path segments (crate, version, crate path,
+/// then modules and the item page).
+fn url_segments(url: &str) -> Option<Vec<&str>> {
+    let (_, path) = url.split_once("docs.rs/")?;
+    let path = path.split('#').next()?;
+    let segments: Vec<&str> = path.split('/').collect();
+    (segments.len() >= 4).then_some(segments)
+}
+
+/// Converts a docs.rs URL into the path of the item it links to.
+///
+/// For example, `.../crate_name/module/struct.Item.html#method.new` becomes
+/// `module::Item::new`.
+fn item_path_from_url(url: &str) -> Option<String> {
+    let segments = url_segments(url)?;
+    let (page, modules) = segments[3..].split_last()?;
+    let mut path: Vec<&str> = modules.to_vec();
+
+    let page = page.strip_suffix(".html")?;
+    if page != "index" {
+        let (_, name) = page.split_once('.')?;
+        path.push(name);
+    }
+    if let Some((_, fragment)) = url.split_once('#') {
+        path.push(fragment.split_once('.')?.1);
+    }
+
+    (!path.is_empty()).then(|| path.join("::"))
+}
+
+/// Builds the docs.rs URL of `item`.
+fn docs_rs_url(name: &str, version: &str, item: &Item) -> String {
+    let crate_path = name.replace('-', "_");
+    let segments: Vec<&str> = item.path.split("::").collect();
+    let page = match item.kind {
+        ItemKind::Module => format!("{}/index.html", item.path.replace("::", "/")),
+        kind => {
+            let (item_name, modules) = segments.split_last().unwrap_or((&"", &[]));
+            let mut page = modules.join("/");
+            if !page.is_empty() {
+                page.push('/');
+            }
+            let _ = write!(page, "{}.{item_name}.html", kind.url_prefix());
+            page
+        }
+    };
+
+    format!("https://docs.rs/{name}/{version}/{crate_path}/{page}")
+}
+
+/// Replaces the lines of `links` in `contents` with `definitions`.
+///
+/// The new definitions go where the first old one was, or at the line chosen
+/// by `position` if there were none.
+fn replace_lines(
+    contents: &str,
+    links: &[Link],
+    definitions: &str,
+    position: impl FnOnce(&[&str]) -> usize,
+) -> String {
+    let all_lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let insert_at = links
+        .first()
+        .map_or_else(|| position(&all_lines), |link| link.line - 1);
+
+    let mut output = String::with_capacity(contents.len() + definitions.len());
+    for (index, line) in all_lines.iter().enumerate() {
+        if index == insert_at {
+            output.push_str(definitions);
+        }
+        if !links.iter().any(|link| link.line == index + 1) {
+            output.push_str(line);
+        }
+    }
+    if insert_at >= all_lines.len() {
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push_str(definitions);
+    }
+
+    output
+}
+
+/// Finds the public items of the crate at `root`, following `pub mod`
+/// declarations from src/lib.rs.
+///
+/// Only items declared directly in a module are found; re-exports and items
+/// of inline modules are not.
+///
+/// # Errors
+///
+/// Returns an error if a source file cannot be read.
+pub fn public_items(root: &Path) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    let mut pending: Vec<(PathBuf, Vec<String>)> = vec![(root.join(LIB), Vec::new())];
+
+    while let Some((file, module)) = pending.pop() {
+        let contents = fs::read(&file)?;
+        for item in module_items(&contents) {
+            let mut path = module.clone();
+            path.push(item.name.clone());
+
+            if item.kind == ItemKind::Module && !item.inline {
+                // `src/lib.rs` and `src/foo/mod.rs` own their directory, while
+                // `src/foo.rs` owns `src/foo/`
+                let directory = match file.file_stem().and_then(|stem| stem.to_str()) {
+                    Some("lib" | "mod") => file.parent().map(Path::to_owned),
+                    _ => file.with_extension("").into(),
+                }
+                .unwrap_or_default();
+                let flat = directory.join(format!("{}.rs", item.name));
+                let nested = directory.join(&item.name).join("mod.rs");
+                let child = if flat.is_file() { flat } else { nested };
+                if child.is_file() {
+                    pending.push((child, path.clone()));
+                }
+            }
+
+            items.push(Item {
+                path: path.join("::"),
+                kind: item.kind,
+            });
+        }
+    }
+
+    items.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(items)
+}
+
+/// An item declared in a module's source.
+#[derive(Debug)]
+struct Declaration {
+    name: String,
+    kind: ItemKind,
+    /// Whether a module's contents follow inline (in braces).
+    inline: bool,
+}
+
+/// Finds the public items declared at the top level of a module's source.
+fn module_items(contents: &str) -> Vec<Declaration> {
+    let mut items: BTreeMap<usize, Declaration> = BTreeMap::new();
+    let mut depth = 0_usize;
+    let mut macro_export = false;
+
+    for (index, line) in contents.lines().enumerate() {
+        let code = line.split("//").next().unwrap_or_default().trim();
+        if depth == 0 {
+            if code.starts_with("#[macro_export") {
+                macro_export = true;
+            } else if let Some(name) = code.strip_prefix("macro_rules!") {
+                let name = identifier(name.trim_start());
+                if macro_export && !name.is_empty() {
+                    items.insert(
+                        index,
+                        Declaration {
+                            name: name.to_owned(),
+                            kind: ItemKind::Macro,
+                            inline: false,
+                        },
+                    );
+                }
+                macro_export = false;
+            } else if let Some(declaration) = parse_declaration(code) {
+                items.insert(index, declaration);
+                macro_export = false;
+            } else if !code.is_empty() && !code.starts_with("#[") {
+                macro_export = false;
+            }
+        }
+
+        for c in code.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    items.into_values().collect()
+}
+
+/// Parses a `pub` item declaration, like `pub const fn name(..)`.
+fn parse_declaration(code: &str) -> Option<Declaration> {
+    let mut rest = code.strip_prefix("pub ")?.trim_start();
+    for qualifier in ["const ", "async ", "unsafe ", "extern \"C\" ", "extern "] {
+        // `const` is a qualifier only when followed by another keyword
+        if qualifier == "const " && !rest.starts_with("const fn ") {
+            continue;
+        }
+        rest = rest.strip_prefix(qualifier).unwrap_or(rest).trim_start();
+    }
+
+    let (keyword, rest) = rest.split_once(char::is_whitespace)?;
+    let kind = ItemKind::from_keyword(keyword)?;
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix("mut ").unwrap_or(rest);
+    let name = identifier(rest);
+
+    (!name.is_empty()).then(|| Declaration {
+        name: name.to_owned(),
+        kind,
+        inline: kind == ItemKind::Module && !code.ends_with(';'),
+    })
+}
+
+/// Returns the identifier at the start of `text`.
+fn identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Scratch;
+
+    const LIB_RS: &str = "\
+//! Docs.
+//!
+//! [`Widget`]: Widget
+//! [`shapes`]: shapes
+
+pub mod shapes;
+mod private;
+
+/// A widget.
+pub struct Widget;
+
+#[macro_export]
+macro_rules! make {
+    () => {};
+}
+";
+
+    const README_MD: &str = "\
+# demo-crate
+
+See [`Widget`].
+
+[`Widget`]: https://docs.rs/demo-crate/1.2.3/demo_crate/struct.Widget.html
+[`shapes`]: https://docs.rs/demo-crate/1.2.3/demo_crate/shapes/index.html
+";
+
+    fn crate_with(readme: &str, lib: &str) -> Scratch {
+        Scratch::new(&[
+            ("Cargo.toml", "[package]\nname = \"demo-crate\"\n"),
+            ("README.md", readme),
+            ("src/lib.rs", lib),
+            (
+                "src/shapes.rs",
+                "pub enum Shape {}\n\npub const fn area() -> u32 {\n    0\n}\n",
+            ),
+            ("src/private.rs", "pub struct Hidden;\n"),
+        ])
+    }
+
+    fn synced(scratch: &Scratch, source: Source) -> Vec<(PathBuf, String)> {
+        sync(scratch.path(), source)
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.path, change.updated))
+            .collect()
+    }
+
+    #[test]
+    fn public_items_follow_module_declarations() {
+        let scratch = crate_with(README_MD, LIB_RS);
+        let items: Vec<(String, ItemKind)> = public_items(scratch.path())
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.path, item.kind))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("Widget".to_owned(), ItemKind::Struct),
+                ("make".to_owned(), ItemKind::Macro),
+                ("shapes".to_owned(), ItemKind::Module),
+                ("shapes::Shape".to_owned(), ItemKind::Enum),
+                ("shapes::area".to_owned(), ItemKind::Function),
+            ]
+        );
+    }
+
+    #[test]
+    fn urls_and_item_paths() {
+        let item = Item {
+            path: "shapes::Shape".to_owned(),
+            kind: ItemKind::Enum,
+        };
+        let url = docs_rs_url("demo-crate", "1.2.3", &item);
+        assert_eq!(
+            url,
+            "https://docs.rs/demo-crate/1.2.3/demo_crate/shapes/enum.Shape.html"
+        );
+        assert_eq!(item_path_from_url(&url).as_deref(), Some("shapes::Shape"));
+        assert_eq!(
+            item_path_from_url(&format!("{url}#variant.Circle")).as_deref(),
+            Some("shapes::Shape::Circle")
+        );
+        assert_eq!(item_path_from_url("https://docs.rs/demo-crate"), None);
+    }
+
+    #[test]
+    fn check_reports_every_kind_of_problem() {
+        assert_eq!(check(crate_with(README_MD, LIB_RS).path()).unwrap(), []);
+
+        let readme = README_MD.replace("shapes/index.html", "shapes/enum.Shape.html")
+            + "[`Gone`]: https://docs.rs/demo-crate/1.2.3/demo_crate/struct.Gone.html\n";
+        let lib = LIB_RS.replace(
+            "//! [`shapes`]: shapes\n",
+            "//! [`shapes`]: shapes\n//! [`Nope`]: Nope\n",
+        );
+        let problems: Vec<String> = check(crate_with(&readme, &lib).path())
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "src/lib.rs:4: [`shapes`] points at `shapes`, but README.md:6 links to \
+                 `shapes::Shape`",
+                "README.md:7: [`Gone`] has no matching `//!` definition in src/lib.rs",
+                "src/lib.rs:5: [`Nope`] has no matching docs.rs link in README.md",
+                "src/lib.rs:5: [`Nope`] points at `Nope`, which is not a public item of the crate",
+            ]
+        );
+    }
+
+    #[test]
+    fn syncs_one_list_from_the_other() {
+        let bare_lib = LIB_RS.replace("//! [`Widget`]: Widget\n//! [`shapes`]: shapes\n", "");
+        let scratch = crate_with(README_MD, &bare_lib);
+        assert_eq!(
+            synced(&scratch, Source::Readme),
+            [(PathBuf::from(LIB), LIB_RS.to_owned())]
+        );
+
+        let bare_readme = "# demo-crate\n\nSee [`Widget`].\n";
+        let scratch = crate_with(bare_readme, LIB_RS);
+        assert_eq!(
+            synced(&scratch, Source::Lib),
+            [(
+                PathBuf::from(README),
+                format!(
+                    "{bare_readme}[`Widget`]: https://docs.rs/demo-crate/{VERSION_PLACEHOLDER}/\
+                     demo_crate/struct.Widget.html\n[`shapes`]: https://docs.rs/demo-crate/\
+                     {VERSION_PLACEHOLDER}/demo_crate/shapes/index.html\n"
+                )
+            )]
+        );
+
+        let unknown = LIB_RS.replace("[`Widget`]: Widget", "[`Widget`]: Gadget");
+        assert!(sync(crate_with(README_MD, &unknown).path(), Source::Lib).is_err());
+    }
+
+    #[test]
+    fn syncs_both_lists_from_the_public_items() {
+        let scratch = crate_with(README_MD, LIB_RS);
+        let url = "https://docs.rs/demo-crate/1.2.3/demo_crate";
+        assert_eq!(
+            synced(&scratch, Source::Items),
+            [
+                (
+                    PathBuf::from(README),
+                    format!(
+                        "# demo-crate\n\nSee [`Widget`].\n\n\
+                         [`Widget`]: {url}/struct.Widget.html\n\
+                         [`make`]: {url}/macro.make.html\n\
+                         [`shapes`]: {url}/shapes/index.html\n\
+                         [`shapes::Shape`]: {url}/shapes/enum.Shape.html\n\
+                         [`shapes::area`]: {url}/shapes/fn.area.html\n"
+                    )
+                ),
+                (
+                    PathBuf::from(LIB),
+                    LIB_RS.replace(
+                        "//! [`shapes`]: shapes\n",
+                        "//! [`make`]: make\n//! [`shapes`]: shapes\n\
+                         //! [`shapes::Shape`]: shapes::Shape\n//! [`shapes::area`]: shapes::area\n"
+                    )
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,870 @@
+//! Reporting how a derived crate has drifted from the template.
+//!
+//! Unlike a textual diff, [`compare`] understands what it is looking at: lint
+//! entries are compared by level (so a lint going from `deny` to `warn` is
+//! reported as relaxed), CI stages and jobs by name, and README.md by its
+//! headings. Differences every derived crate is expected to have, like a
+//! filled-in package name, are left out, while placeholders that were never
+//! filled in are reported, in every file the template's manifest (see
+//! [`template`]) says to render.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, iter,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Result, changes, ci_script, fs, git, instantiate, json,
+    lints::{self, Tool, profile},
+    placeholder,
+    template::{self, Rule, Template},
+    toml::Document,
+    upgrade,
+    workflow::{self, Step, Workflow},
+};
+
+/// The kind of a [`Difference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// Template placeholders that were never filled in.
+    Placeholders,
+    /// The lint profile and the entries of the lint tables.
+    Lints,
+    /// Cargo.toml's other tables.
+    Manifest,
+    /// The `CI_STAGES` of `scripts/ci.py`.
+    CiStages,
+    /// The jobs of the CI workflow.
+    CiJobs,
+    /// The headings of README.md.
+    Readme,
+}
+
+impl Category {
+    /// Every category, in report order.
+    pub const ALL: [Self; 6] = [
+        Self::Placeholders,
+        Self::Lints,
+        Self::Manifest,
+        Self::CiStages,
+        Self::CiJobs,
+        Self::Readme,
+    ];
+
+    /// The category's identifier in JSON output.
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Placeholders => "placeholders",
+            Self::Lints => "lints",
+            Self::Manifest => "manifest",
+            Self::CiStages => "ci-stages",
+            Self::CiJobs => "ci-jobs",
+            Self::Readme => "readme",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Placeholders => "Unfilled placeholders",
+            Self::Lints => "Lint tables",
+            Self::Manifest => "Cargo.toml",
+            Self::CiStages => "CI stages (scripts/ci.py)",
+            Self::CiJobs => "CI jobs (ci.yaml)",
+            Self::Readme => "README.md structure",
+        })
+    }
+}
+
+/// One way the crate differs from the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// The kind of difference.
+    pub category: Category,
+    /// The file (relative to the crate root) that differs.
+    pub file: PathBuf,
+    /// The (1-based) line in the crate's copy of the file, if there is one.
+    pub line: Option<usize>,
+    /// A description of the difference.
+    pub message: String,
+}
+
+impl Difference {
+    /// Converts the difference to JSON.
+    pub fn to_json(&self) -> json::Value {
+        json::Value::object([
+            ("category", self.category.id().into()),
+            ("file", self.file.display().to_string().into()),
+            ("line", self.line.into()),
+            ("message", self.message.clone().into()),
+        ])
+    }
+}
+
+impl fmt::Display for Difference {
+    /// Formats the difference as `file:line: message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The `[package]` keys holding placeholders that instantiating the template
+/// fills in.
+const PLACEHOLDER_KEYS: &[&str] = &[
+    "name",
+    "authors",
+    "description",
+    "documentation",
+    "repository",
+    "keywords",
+    "categories",
+];
+
+/// The `[package]` keys that releasing the crate changes.
+const RELEASE_KEYS: &[&str] = &["version", "publish"];
+
+/// The files compared, relative to the crate root.
+const FILES: &[&str] = &[
+    "Cargo.toml",
+    ci_script::PATH,
+    workflow::PATH,
+    "README.md",
+    "src/lib.rs",
+];
+
+/// Where [`report`] renders the template, relative to the crate root.
+const BASELINE_DIR: &str = "target/drift";
+
+/// Compares the crate at `root` against commit `rev` of the template clone at
+/// `template`, returning the differences sorted by category, file and line.
+///
+/// When the template has a manifest at `rev`, the crate is compared against
+/// the template as instantiating it with the crate's recorded answers and
+/// variants (see [`upgrade::replayed`]) would render it, in
+/// `target/drift`. Files the manifest skips when instantiating are left out,
+/// and the crate's other rendered files are searched for placeholders.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read, written or parsed, or the
+/// template cannot be rendered.
+pub fn report(root: &Path, template: &Path, rev: &str) -> Result<Vec<Difference>> {
+    let manifest = git::show(template, rev, Path::new(template::PATH))?
+        .map(|contents| Template::parse(Path::new(template::PATH), &contents))
+        .transpose()?;
+    let rule = |file: &Path| {
+        manifest
+            .as_ref()
+            .map_or(Rule::Render, |manifest| manifest.rule(file))
+    };
+    let mut files: Vec<String> = FILES.iter().map(|&file| file.to_owned()).collect();
+    if manifest.is_some() {
+        for path in fs::walk(root)? {
+            let file = path.to_string_lossy().replace('\\', "/");
+            if rule(&path) == Rule::Render && !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    let baseline_dir = match &manifest {
+        Some(manifest) => Some(render_baseline(root, template, rev, manifest)?),
+        None => None,
+    };
+
+    let mut local = BTreeMap::new();
+    let mut upstream = BTreeMap::new();
+    let mut baseline = BTreeMap::new();
+    for file in files
+        .iter()
+        .filter(|file| rule(Path::new(file)) != Rule::Skip)
+    {
+        let path = root.join(file);
+        if path.is_file() {
+            local.insert(file.as_str(), fs::read(&path)?);
+        }
+        if let Some(contents) = git::show(template, rev, Path::new(file))? {
+            upstream.insert(file.as_str(), contents);
+        }
+        let rendered = baseline_dir.as_ref().map(|dir| dir.join(file));
+        if let Some(path) = rendered.filter(|path| path.is_file()) {
+            baseline.insert(file.as_str(), fs::read(&path)?);
+        }
+    }
+
+    match &baseline_dir {
+        Some(_) => compare(&local, &upstream, &baseline),
+        None => compare(&local, &upstream, &upstream),
+    }
+}
+
+/// Writes commit `rev` of the template clone at `template` to
+/// [`BASELINE_DIR`] under `root`, instantiated with the answers the crate at
+/// `root` was instantiated with, and returns the directory.
+fn render_baseline(
+    root: &Path,
+    template: &Path,
+    rev: &str,
+    manifest: &Template,
+) -> Result<PathBuf> {
+    let dir = root.join(BASELINE_DIR);
+    fs::remove_dir_all(&dir)?;
+    for file in git::files(template, rev)? {
+        if let Some(contents) = git::show(template, rev, &file)? {
+            fs::write(&dir.join(&file), &contents)?;
+        }
+    }
+    let answers = upgrade::replayed(root, Some(manifest))?;
+    let changes = instantiate::plan(&dir, manifest, &answers)?;
+    changes::apply(&dir, &changes)?;
+    Ok(dir)
+}
+
+/// Compares the files of a crate against those of the template, all given as
+/// maps from a path (relative to the crate root) to its contents. Files
+/// missing from a map are treated as deleted.
+///
+/// `template` is the template as it is, which the crate's Cargo.toml is
+/// checked against for placeholders that were never filled in, and
+/// `baseline` the template as instantiating it would render it, which
+/// everything else is compared against. Cargo.toml, the CI files, README.md
+/// and src/lib.rs are compared as a whole, while every local file is
+/// searched for placeholders.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be parsed.
+pub fn compare(
+    local: &BTreeMap<&str, String>,
+    template: &BTreeMap<&str, String>,
+    baseline: &BTreeMap<&str, String>,
+) -> Result<Vec<Difference>> {
+    let mut differences = Differences(Vec::new());
+
+    for &file in FILES {
+        let (Some(local), Some(baseline)) = (local.get(file), baseline.get(file)) else {
+            let category = category_of(file).filter(|_| baseline.contains_key(file));
+            if let Some(category) = category {
+                differences.push(category, file, None, "deleted from the crate");
+            }
+            continue;
+        };
+        match file {
+            "Cargo.toml" => {
+                let template = template.get(file).unwrap_or(baseline);
+                compare_manifests(&mut differences, local, template, baseline)?;
+            }
+            ci_script::PATH => compare_stages(&mut differences, local, baseline),
+            workflow::PATH => {
+                let path = Path::new(file);
+                compare_workflows(
+                    &mut differences,
+                    &Workflow::parse(path, local)?,
+                    &Workflow::parse(path, baseline)?,
+                );
+            }
+            "README.md" => compare_readmes(&mut differences, local, baseline),
+            _ => {}
+        }
+    }
+    for (file, contents) in local {
+        find_placeholders(&mut differences, file, contents)?;
+    }
+
+    let mut differences = differences.0;
+    differences.sort_by(|a, b| (a.category, &a.file, a.line).cmp(&(b.category, &b.file, b.line)));
+    Ok(differences)
+}
+
+/// The category differences in `file` fall under, for files compared as a
+/// whole.
+fn category_of(file: &str) -> Option<Category> {
+    match file {
+        "Cargo.toml" => Some(Category::Manifest),
+        ci_script::PATH => Some(Category::CiStages),
+        workflow::PATH => Some(Category::CiJobs),
+        "README.md" => Some(Category::Readme),
+        _ => None,
+    }
+}
+
+/// The differences found so far.
+struct Differences(Vec<Difference>);
+
+impl Differences {
+    fn push(
+        &mut self,
+        category: Category,
+        file: &str,
+        line: Option<usize>,
+        message: impl Into<String>,
+    ) {
+        self.0.push(Difference {
+            category,
+            file: PathBuf::from(file),
+            line,
+            message: message.into(),
+        });
+    }
+}
+
+/// Compares Cargo.toml: placeholders still holding the `template`'s values,
+/// then the lint profile and tables, and every other table key by key, against
+/// the `baseline`.
+fn compare_manifests(
+    differences: &mut Differences,
+    local: &str,
+    template: &str,
+    baseline: &str,
+) -> Result<()> {
+    let path = Path::new("Cargo.toml");
+    let local_document = Document::parse(path, local)?;
+    let template_document = Document::parse(path, template)?;
+    let baseline_document = Document::parse(path, baseline)?;
+
+    for &key in PLACEHOLDER_KEYS {
+        let placeholder = template_document.get("package", key);
+        let Some(entry) = local_document.get("package", key) else {
+            continue;
+        };
+        if placeholder.is_some_and(|placeholder| placeholder.value == entry.value) {
+            differences.push(
+                Category::Placeholders,
+                "Cargo.toml",
+                Some(entry.line),
+                format!("`package.{key}` is still the template's `{}`", entry.value),
+            );
+        }
+    }
+
+    let local_profile = profile::marker(local);
+    let baseline_profile = profile::marker(baseline);
+    // Without a marker, the tables are the template's own strict-library ones
+    let local_name = local_profile.map_or(profile::DEFAULT, |(_, name, _)| name);
+    let baseline_name = baseline_profile.map_or(profile::DEFAULT, |(_, name, _)| name);
+    if local_name != baseline_name {
+        differences.push(
+            Category::Lints,
+            "Cargo.toml",
+            local_profile.map(|(line, ..)| line + 1),
+            format!("lint profile changed from `{baseline_name}` to `{local_name}`"),
+        );
+    }
+    for tool in Tool::ALL {
+        compare_lints(
+            differences,
+            tool,
+            &lints::entries(path, &local_document, tool)?,
+            &lints::entries(path, &baseline_document, tool)?,
+        );
+    }
+
+    compare_tables(differences, &local_document, &baseline_document);
+    Ok(())
+}
+
+/// Compares every table of Cargo.toml other than the lint tables, key by key.
+fn compare_tables(differences: &mut Differences, local: &Document, template: &Document) {
+    let tables = |document: &Document| -> BTreeSet<String> {
+        iter::once("")
+            .chain(document.tables())
+            .filter(|table| *table != "lints" && !table.starts_with("lints."))
+            .map(str::to_owned)
+            .collect()
+    };
+    let local_tables = tables(local);
+    let template_tables = tables(template);
+    for table in template_tables.difference(&local_tables) {
+        differences.push(
+            Category::Manifest,
+            "Cargo.toml",
+            None,
+            format!("table `[{table}]` removed"),
+        );
+    }
+    for table in &local_tables {
+        let local_entries = local.entries(table);
+        if !template_tables.contains(table) {
+            let line = local_entries.first().map(|entry| entry.line - 1);
+            differences.push(
+                Category::Manifest,
+                "Cargo.toml",
+                line,
+                format!("table `[{table}]` added"),
+            );
+            continue;
+        }
+
+        let template_entries = template.entries(table);
+        let expected = |key: &str| {
+            table == "package" && (PLACEHOLDER_KEYS.contains(&key) || RELEASE_KEYS.contains(&key))
+        };
+        let qualified = |key: &str| {
+            if table.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{table}.{key}")
+            }
+        };
+        for entry in &template_entries {
+            if expected(entry.key) {
+                continue;
+            }
+            match local_entries.iter().find(|local| local.key == entry.key) {
+                None => differences.push(
+                    Category::Manifest,
+                    "Cargo.toml",
+                    None,
+                    format!("`{}` removed (was `{}`)", qualified(entry.key), entry.value),
+                ),
+                Some(local) if local.value != entry.value => differences.push(
+                    Category::Manifest,
+                    "Cargo.toml",
+                    Some(local.line),
+                    format!(
+                        "`{}` changed from `{}` to `{}`",
+                        qualified(entry.key),
+                        entry.value,
+                        local.value
+                    ),
+                ),
+                Some(_) => {}
+            }
+        }
+        for entry in &local_entries {
+            if !expected(entry.key) && !template_entries.iter().any(|t| t.key == entry.key) {
+                differences.push(
+                    Category::Manifest,
+                    "Cargo.toml",
+                    Some(entry.line),
+                    format!("`{}` added (`{}`)", qualified(entry.key), entry.value),
+                );
+            }
+        }
+    }
+}
+
+/// How strict a lint level is, from `allow` (0) to `forbid`.
+fn strictness(level: &str) -> Option<u8> {
+    match level {
+        "allow" => Some(0),
+        "expect" => Some(1),
+        "warn" => Some(2),
+        "deny" => Some(3),
+        "forbid" => Some(4),
+        _ => None,
+    }
+}
+
+/// Compares the entries of one lint table.
+fn compare_lints(
+    differences: &mut Differences,
+    tool: Tool,
+    local: &[lints::Entry],
+    template: &[lints::Entry],
+) {
+    let mut push = |line: Option<usize>, message: String| {
+        differences.push(Category::Lints, "Cargo.toml", line, message);
+    };
+    let table = tool.table();
+
+    for entry in template {
+        let name = &entry.name;
+        let Some(local) = local.iter().find(|local| local.name == *name) else {
+            push(
+                None,
+                format!("[{table}] `{name}` removed (was `{}`)", entry.level),
+            );
+            continue;
+        };
+        if local.level != entry.level {
+            let direction = match (strictness(&local.level), strictness(&entry.level)) {
+                (Some(local), Some(template)) if local < template => "relaxed",
+                (Some(local), Some(template)) if local > template => "tightened",
+                _ => "changed",
+            };
+            push(
+                Some(local.line),
+                format!(
+                    "[{table}] `{name}` {direction} from `{}` to `{}`",
+                    entry.level, local.level
+                ),
+            );
+        }
+        if local.priority != entry.priority {
+            push(
+                Some(local.line),
+                format!(
+                    "[{table}] `{name}` priority changed from {} to {}",
+                    entry.priority.unwrap_or_default(),
+                    local.priority.unwrap_or_default()
+                ),
+            );
+        }
+    }
+    for entry in local {
+        if !template.iter().any(|template| template.name == entry.name) {
+            push(
+                Some(entry.line),
+                format!("[{table}] `{}` added at `{}`", entry.name, entry.level),
+            );
+        }
+    }
+}
+
+/// Compares the `CI_STAGES` lists of two copies of `scripts/ci.py`.
+fn compare_stages(differences: &mut Differences, local: &str, template: &str) {
+    let (Some(local), Some(template)) = (ci_script::stages(local), ci_script::stages(template))
+    else {
+        return;
+    };
+    let mut push = |line: Option<usize>, message: String| {
+        differences.push(Category::CiStages, ci_script::PATH, line, message);
+    };
+
+    for stage in &template {
+        let name = &stage.name;
+        match local.iter().find(|local| local.name == *name) {
+            None if stage.enabled => push(None, format!("stage `{name}` removed")),
+            None => push(None, format!("disabled stage `{name}` removed")),
+            Some(local) if local.enabled && !stage.enabled => {
+                push(Some(local.line), format!("stage `{name}` enabled"));
+            }
+            Some(local) if !local.enabled && stage.enabled => {
+                push(Some(local.line), format!("stage `{name}` disabled"));
+            }
+            Some(_) => {}
+        }
+    }
+    for stage in &local {
+        if !template.iter().any(|template| template.name == stage.name) {
+            let state = if stage.enabled { "" } else { " (disabled)" };
+            push(
+                Some(stage.line),
+                format!("stage `{}` added{state}", stage.name),
+            );
+        }
+    }
+}
+
+/// What identifies a step across versions of a workflow: its action (without
+/// the version) or its command.
+fn step_key(step: &Step) -> String {
+    match (step.action(), &step.uses, &step.run) {
+        (Some((action, _)), ..) => action.to_owned(),
+        (None, Some(uses), _) => uses.clone(),
+        (None, None, Some(run)) => format!("run: {run}"),
+        (None, None, None) => String::new(),
+    }
+}
+
+/// Compares the jobs of two versions of the CI workflow.
+fn compare_workflows(differences: &mut Differences, local: &Workflow, template: &Workflow) {
+    let mut push = |line: Option<usize>, message: String| {
+        differences.push(Category::CiJobs, workflow::PATH, line, message);
+    };
+
+    for (key, value) in &template.env {
+        match local.env.iter().find(|(local, _)| local == key) {
+            None => push(None, format!("env `{key}` removed (was `{value}`)")),
+            Some((_, local)) if local != value => {
+                push(
+                    None,
+                    format!("env `{key}` changed from `{value}` to `{local}`"),
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, value) in &local.env {
+        if !template.env.iter().any(|(template, _)| template == key) {
+            push(None, format!("env `{key}` added (`{value}`)"));
+        }
+    }
+
+    for job in &template.jobs {
+        let id = &job.id;
+        let Some(local) = local.jobs.iter().find(|local| local.id == *id) else {
+            push(None, format!("job `{id}` removed"));
+            continue;
+        };
+        if local.runs_on != job.runs_on {
+            push(
+                Some(local.line),
+                format!(
+                    "job `{id}` runs on `{}` instead of `{}`",
+                    local.runs_on.as_deref().unwrap_or_default(),
+                    job.runs_on.as_deref().unwrap_or_default()
+                ),
+            );
+        }
+
+        let mut unmatched: Vec<&Step> = local.steps.iter().collect();
+        for step in &job.steps {
+            let key = step_key(step);
+            let Some(index) = unmatched.iter().position(|local| step_key(local) == key) else {
+                push(
+                    Some(local.line),
+                    format!("job `{id}`: step `{key}` removed"),
+                );
+                continue;
+            };
+            let local_step = unmatched.remove(index);
+            let version = |step: &Step| step.action().map(|(_, version)| version.to_owned());
+            if version(local_step) != version(step) {
+                push(
+                    Some(local_step.line),
+                    format!(
+                        "job `{id}`: `{key}` version changed from `{}` to `{}`",
+                        version(step).unwrap_or_default(),
+                        version(local_step).unwrap_or_default()
+                    ),
+                );
+            }
+            if local_step.with != step.with {
+                push(
+                    Some(local_step.line),
+                    format!("job `{id}`: inputs of `{key}` changed"),
+                );
+            }
+        }
+        for step in unmatched {
+            push(
+                Some(step.line),
+                format!("job `{id}`: step `{}` added", step_key(step)),
+            );
+        }
+    }
+    for job in &local.jobs {
+        if !template.jobs.iter().any(|template| template.id == job.id) {
+            push(Some(job.line), format!("job `{}` added", job.id));
+        }
+    }
+}
+
+/// The headings of a Markdown document, with their (1-based) lines, skipping
+/// code blocks and the title (the first heading).
+fn headings(contents: &str) -> Vec<(usize, String)> {
+    let mut headings = Vec::new();
+    let mut in_code = false;
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        let text = trimmed.trim_start_matches('#');
+        if !in_code && text.len() < trimmed.len() && text.starts_with(' ') {
+            headings.push((index + 1, trimmed.to_owned()));
+        }
+    }
+    headings.into_iter().skip(1).collect()
+}
+
+/// Compares the headings of two versions of README.md.
+fn compare_readmes(differences: &mut Differences, local: &str, template: &str) {
+    let local = headings(local);
+    let template = headings(template);
+
+    for (_, heading) in &template {
+        if !local.iter().any(|(_, local)| local == heading) {
+            differences.push(
+                Category::Readme,
+                "README.md",
+                None,
+                format!("section `{heading}` removed"),
+            );
+        }
+    }
+    for (line, heading) in &local {
+        if !template.iter().any(|(_, template)| template == heading) {
+            differences.push(
+                Category::Readme,
+                "README.md",
+                Some(*line),
+                format!("section `{heading}` added"),
+            );
+        }
+    }
+}
+
+/// Finds the placeholders (see [`placeholder`]) left in `file`, and the
+/// template's `TODO:` prose in README.md and src/lib.rs, skipping comments,
+/// which are left to the marker scanner.
+fn find_placeholders(differences: &mut Differences, file: &str, contents: &str) -> Result<()> {
+    for directive in placeholder::directives(Path::new(file), contents)? {
+        differences.push(
+            Category::Placeholders,
+            file,
+            Some(directive.line + 1),
+            format!("placeholder never filled in: `{}`", directive.text.trim()),
+        );
+    }
+    if !["README.md", "src/lib.rs"].contains(&file) {
+        return Ok(());
+    }
+
+    let mut in_comment = false;
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("<!--") {
+            in_comment = !trimmed.contains("-->");
+            continue;
+        }
+        if in_comment {
+            in_comment = !trimmed.contains("-->");
+            continue;
+        }
+        if trimmed.starts_with("//") && !trimmed.starts_with("//!") {
+            continue;
+        }
+
+        let prose = trimmed.trim_start_matches(['#', '/', '!']).trim_start();
+        if prose.starts_with("TODO:") {
+            differences.push(
+                Category::Placeholders,
+                file,
+                Some(index + 1),
+                format!("placeholder text `{prose}`"),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        answers::Answers,
+        fs::Scratch,
+        placeholder::{Values, Variable},
+        process,
+    };
+
+    /// The template's files, as committed.
+    const TEMPLATE: &[(&str, &str)] = &[
+        (
+            ".cargo/config.toml",
+            include_str!("../../.cargo/config.toml"),
+        ),
+        (".gitattributes", include_str!("../../.gitattributes")),
+        (
+            ".github/workflows/ci.yaml",
+            include_str!("../../.github/workflows/ci.yaml"),
+        ),
+        (".gitignore", include_str!("../../.gitignore")),
+        ("CHANGELOG.md", include_str!("../../CHANGELOG.md")),
+        ("Cargo.toml", include_str!("../../Cargo.toml")),
+        ("LICENSE-APACHE", include_str!("../../LICENSE-APACHE")),
+        ("LICENSE-MIT", include_str!("../../LICENSE-MIT")),
+        ("README.md", include_str!("../../README.md")),
+        ("scripts/ci.py", include_str!("../../scripts/ci.py")),
+        ("src/lib.rs", include_str!("../../src/lib.rs")),
+        ("template.toml", include_str!("../../template.toml")),
+    ];
+
+    /// A git repository of the template, and a crate instantiated from it with
+    /// `variants`.
+    fn instantiated(variants: &[&str]) -> (Scratch, Scratch) {
+        let template = Scratch::new(TEMPLATE);
+        for args in [
+            &["init", "--quiet"][..],
+            &["add", "--all"],
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "--message=Template",
+            ],
+        ] {
+            assert!(
+                process::status(git::command(template.path()).args(args))
+                    .unwrap()
+                    .success()
+            );
+        }
+
+        let derived = Scratch::new(TEMPLATE);
+        let manifest = Template::parse(
+            Path::new(template::PATH),
+            include_str!("../../template.toml"),
+        )
+        .unwrap();
+        let mut values = Values::new();
+        for (variable, value) in [
+            (Variable::Name, "demo-crate"),
+            (Variable::Owner, "someone"),
+            (Variable::Author, "Some One"),
+            (Variable::Description, "A demo crate"),
+        ] {
+            values.set(variable, value).unwrap();
+        }
+        let answers = Answers {
+            values: manifest.values(derived.path(), values).unwrap(),
+            keywords: vec!["demo".to_owned()],
+            categories: vec!["development-tools".to_owned()],
+            variants: variants.iter().map(|&variant| variant.to_owned()).collect(),
+            ..Answers::default()
+        };
+        let changes = instantiate::plan(derived.path(), &manifest, &answers).unwrap();
+        changes::apply(derived.path(), &changes).unwrap();
+        (template, derived)
+    }
+
+    /// The differences, leaving out README.md's prose placeholders, which
+    /// instantiating leaves for the author to write.
+    fn drift(derived: &Scratch, template: &Scratch) -> Vec<String> {
+        report(derived.path(), template.path(), "HEAD")
+            .unwrap()
+            .iter()
+            .filter(|difference| !difference.message.starts_with("placeholder text `TODO"))
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn fresh_instantiations_have_not_drifted() {
+        for variants in [&[][..], &["no_std"], &["binary"]] {
+            let (template, derived) = instantiated(variants);
+            assert_eq!(
+                drift(&derived, &template),
+                Vec::<String>::new(),
+                "{variants:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_changes_to_the_rendered_template() {
+        let (template, derived) = instantiated(&[]);
+        let path = derived.path().join("Cargo.toml");
+        let manifest = fs::read(&path).unwrap();
+        let line = manifest
+            .lines()
+            .position(|line| line.starts_with("unsafe_code = "))
+            .unwrap()
+            + 1;
+        fs::write(
+            &path,
+            &manifest.replace("unsafe_code = \"forbid\"", "unsafe_code = \"warn\""),
+        )
+        .unwrap();
+
+        assert_eq!(
+            drift(&derived, &template),
+            [format!(
+                "Cargo.toml:{line}: [lints.rust] `unsafe_code` relaxed from `forbid` to `warn`"
+            )]
+        );
+    }
+}
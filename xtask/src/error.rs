@@ -0,0 +1,66 @@
+//! The error type shared by all of the tooling.
+
+use std::{fmt, io, path::PathBuf};
+
+/// A specialized [`Result`](std::result::Result) for the tooling's [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Something that went wrong while running a tooling command.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io {
+        /// The file being read or written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        source: io::Error,
+    },
+    /// Running an external program (like `cargo` or `rustup`) failed.
+    Command {
+        /// The program being run.
+        program: String,
+        /// The underlying I/O error.
+        source: io::Error,
+    },
+    /// A file did not have the structure the tooling expected.
+    Malformed {
+        /// The offending file.
+        path: PathBuf,
+        /// A description of what was wrong with it.
+        message: String,
+    },
+    /// A value supplied by the user (on the command line or otherwise) was
+    /// invalid.
+    InvalidInput(String),
+}
+
+impl Error {
+    /// Creates an [`Error::Malformed`] for the file at `path`.
+    pub(crate) fn malformed(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        Self::Malformed {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Command { program, source } => write!(f, "failed to run `{program}`: {source}"),
+            Self::Malformed { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::InvalidInput(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } | Self::Command { source, .. } => Some(source),
+            Self::Malformed { .. } | Self::InvalidInput(_) => None,
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Auditing many crates derived from the template at once.
+//!
+//! [`audit`] looks at every repository directly inside a directory, picks out
+//! the ones derived from the template (see [`Signal`]) and summarizes each:
+//! MSRV, edition, lint profile and overrides, outstanding markers and template
+//! baseline. Problems with one crate are recorded against it rather than
+//! stopping the audit.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Error, Result, ci_script, fs, json,
+    lints::{self, Tool, profile},
+    markers::{self, MarkerKind},
+    toml::Document,
+    upgrade,
+};
+
+/// Evidence that a crate was created from the template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Cargo.toml has the template's `# # #` section banners.
+    Banner,
+    /// `scripts/ci.py` is the template's CI script (its docstring starts with
+    /// `Name: CI`).
+    CiScript,
+    /// The crate records its template baseline.
+    Stamp,
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Banner => "banner",
+            Self::CiScript => "ci.py",
+            Self::Stamp => "stamp",
+        })
+    }
+}
+
+/// Looks for evidence that the crate at `root` was created from the template.
+///
+/// # Errors
+///
+/// Returns an error if a file exists but cannot be read.
+pub fn detect(root: &Path) -> Result<Vec<Signal>> {
+    let mut signals = Vec::new();
+
+    let manifest = root.join("Cargo.toml");
+    if manifest.is_file() {
+        let is_banner = |line: &str| {
+            line.starts_with('#')
+                && line.trim_end().ends_with('#')
+                && line.trim_matches(['#', ' ']) == "PACKAGE"
+        };
+        if fs::read(&manifest)?.lines().any(is_banner) {
+            signals.push(Signal::Banner);
+        }
+    }
+
+    let script = root.join(ci_script::PATH);
+    if script.is_file()
+        && fs::read(&script)?
+            .lines()
+            .any(|line| line.trim() == "Name: CI")
+    {
+        signals.push(Signal::CiScript);
+    }
+
+    if root.join(upgrade::STAMP_PATH).is_file() {
+        signals.push(Signal::Stamp);
+    }
+
+    Ok(signals)
+}
+
+/// The summary of one template-derived crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crate {
+    /// The crate's directory.
+    pub path: PathBuf,
+    /// The evidence that the crate was created from the template.
+    pub signals: Vec<Signal>,
+    /// The package name.
+    pub name: Option<String>,
+    /// The `rust-version`.
+    pub msrv: Option<String>,
+    /// The edition.
+    pub edition: Option<String>,
+    /// The lint profile.
+    pub profile: Option<String>,
+    /// The number of local overrides of the lint profile.
+    pub overrides: Option<usize>,
+    /// The number of `TODO` markers left.
+    pub todo: usize,
+    /// The number of `ON_RELEASE` markers left.
+    pub on_release: usize,
+    /// The template baseline commit.
+    pub template: Option<String>,
+    /// The level of each requested lint (`clippy::` prefixed for Clippy
+    /// lints), or [`None`] if the lint tables don't set it.
+    pub lints: Vec<(String, Option<String>)>,
+    /// Problems that prevented parts of the summary from being filled in.
+    pub errors: Vec<String>,
+}
+
+impl Crate {
+    /// Converts the summary to JSON.
+    pub fn to_json(&self) -> json::Value {
+        json::Value::object([
+            ("path", self.path.display().to_string().into()),
+            (
+                "signals",
+                self.signals
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            ("name", self.name.clone().into()),
+            ("msrv", self.msrv.clone().into()),
+            ("edition", self.edition.clone().into()),
+            ("profile", self.profile.clone().into()),
+            ("overrides", self.overrides.into()),
+            ("todo", self.todo.into()),
+            ("on_release", self.on_release.into()),
+            ("template", self.template.clone().into()),
+            (
+                "lints",
+                json::Value::Object(
+                    self.lints
+                        .iter()
+                        .map(|(lint, level)| (lint.clone(), level.clone().into()))
+                        .collect(),
+                ),
+            ),
+            ("errors", self.errors.clone().into()),
+        ])
+    }
+}
+
+/// Summarizes every template-derived crate directly inside `dir`, sorted by
+/// path. `lints` names lints (`clippy::` prefixed for Clippy lints) whose
+/// levels should be looked up.
+///
+/// # Errors
+///
+/// Returns an error if `dir` cannot be listed, or a crate's files can't be
+/// read while detecting whether it is template-derived.
+pub fn audit(dir: &Path, lints: &[String]) -> Result<Vec<Crate>> {
+    let io_error = |source| Error::Io {
+        path: dir.to_owned(),
+        source,
+    };
+    let mut directories = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        if entry.file_type().map_err(io_error)?.is_dir() {
+            directories.push(entry.path());
+        }
+    }
+    directories.sort();
+
+    let mut crates = Vec::new();
+    for path in directories {
+        let signals = detect(&path)?;
+        if !signals.is_empty() {
+            crates.push(summarize(path, signals, lints));
+        }
+    }
+
+    Ok(crates)
+}
+
+/// Summarizes one crate, recording any problems in [`Crate::errors`].
+fn summarize(path: PathBuf, signals: Vec<Signal>, lints: &[String]) -> Crate {
+    let mut summary = Crate {
+        path,
+        signals,
+        name: None,
+        msrv: None,
+        edition: None,
+        profile: None,
+        overrides: None,
+        todo: 0,
+        on_release: 0,
+        template: None,
+        lints: lints.iter().map(|lint| (lint.clone(), None)).collect(),
+        errors: Vec::new(),
+    };
+    let root = summary.path.clone();
+    let record = |errors: &mut Vec<String>, error: Error| errors.push(error.to_string());
+
+    match Document::read(&root.join("Cargo.toml")) {
+        Ok(manifest) => {
+            summary.name = manifest.get_str("package", "name");
+            summary.msrv = manifest.get_str("package", "rust-version");
+            summary.edition = manifest.get_str("package", "edition");
+            for (lint, level) in &mut summary.lints {
+                let (tool, name) = match lint.strip_prefix("clippy::") {
+                    Some(name) => (Tool::Clippy, name),
+                    None => (Tool::Rust, lint.as_str()),
+                };
+                match lints::entries(&root.join("Cargo.toml"), &manifest, tool) {
+                    Ok(entries) => {
+                        *level = entries
+                            .into_iter()
+                            .find(|entry| entry.name == name)
+                            .map(|entry| entry.level);
+                    }
+                    Err(error) => record(&mut summary.errors, error),
+                }
+            }
+        }
+        Err(error) => record(&mut summary.errors, error),
+    }
+
+    match profile::status(&root) {
+        Ok(status) => {
+            summary.profile = Some(status.profile.name);
+            summary.overrides = Some(status.overrides.len());
+        }
+        Err(error) => record(&mut summary.errors, error),
+    }
+
+    match markers::scan(&root) {
+        Ok(markers) => {
+            let count = |kind| markers.iter().filter(|marker| marker.kind == kind).count();
+            summary.todo = count(MarkerKind::Todo);
+            summary.on_release = count(MarkerKind::OnRelease);
+        }
+        Err(error) => record(&mut summary.errors, error),
+    }
+
+    match upgrade::baseline(&root) {
+        Ok(baseline) => summary.template = baseline,
+        Err(error) => record(&mut summary.errors, error),
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Scratch;
+
+    const MANIFEST: &str = "\
+#             #
+#   PACKAGE   #
+#             #
+[package]
+name = \"alpha\"
+version = \"0.1.0\"
+edition = \"2024\"
+rust-version = \"1.85\"
+# TODO: write a description
+# ON_RELEASE: publish to crates.io
+
+[lints.rust]
+unsafe_code = \"deny\"
+
+[lints.clippy]
+pedantic = { level = \"warn\", priority = -1 }
+";
+
+    const SCRIPT: &str = "\"\"\"\nName: CI\n\"\"\"\n\n# TODO: add a stage\n";
+
+    #[test]
+    fn audits_only_template_derived_crates() {
+        let stamp = format!(
+            "{}\n[template]\ncommit = \"abc123\"\n",
+            upgrade::STAMP_HEADER
+        );
+        let fleet = Scratch::new(&[
+            ("alpha/Cargo.toml", MANIFEST),
+            ("alpha/scripts/ci.py", SCRIPT),
+            (&format!("alpha/{}", upgrade::STAMP_PATH), &stamp),
+            (
+                "broken/Cargo.toml",
+                "#   PACKAGE   #\n[package]\nname = \"broken\"\n\n[lints.rust]\nunsafe_code = 1\n",
+            ),
+            (
+                "unrelated/Cargo.toml",
+                "[package]\nname = \"unrelated\"\n# TODO: not counted\n",
+            ),
+            ("notes.md", "Not a crate\n"),
+        ]);
+        let lints = ["unsafe_code".to_owned(), "clippy::pedantic".to_owned()];
+        let crates = audit(fleet.path(), &lints).unwrap();
+        let names: Vec<Option<&str>> = crates.iter().map(|c| c.name.as_deref()).collect();
+        assert_eq!(names, [Some("alpha"), Some("broken")]);
+
+        let alpha = &crates[0];
+        assert_eq!(alpha.path, fleet.path().join("alpha"));
+        assert_eq!(
+            alpha.signals,
+            [Signal::Banner, Signal::CiScript, Signal::Stamp]
+        );
+        assert_eq!(alpha.msrv.as_deref(), Some("1.85"));
+        assert_eq!(alpha.edition.as_deref(), Some("2024"));
+        assert_eq!(alpha.profile.as_deref(), Some(profile::DEFAULT));
+        assert!(alpha.overrides.is_some_and(|overrides| overrides > 0));
+        assert_eq!((alpha.todo, alpha.on_release), (2, 1));
+        assert_eq!(alpha.template.as_deref(), Some("abc123"));
+        assert_eq!(
+            alpha.lints,
+            [
+                ("unsafe_code".to_owned(), Some("deny".to_owned())),
+                ("clippy::pedantic".to_owned(), Some("warn".to_owned())),
+            ]
+        );
+        assert_eq!(alpha.errors, Vec::<String>::new());
+
+        // Problems are recorded against the crate, without stopping the audit
+        let broken = &crates[1];
+        assert_eq!(broken.signals, [Signal::Banner]);
+        assert_eq!(broken.profile, None);
+        assert_eq!(broken.lints[0], ("unsafe_code".to_owned(), None));
+        assert!(!broken.errors.is_empty());
+        assert!(
+            broken
+                .errors
+                .iter()
+                .all(|error| error.contains("invalid lint entry `unsafe_code`")),
+            "{:?}",
+            broken.errors
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Thin wrappers around [`std::fs`] that attach the path to any error.
+
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// Reads the file at `path` to a string.
+pub(crate) fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Reads the file at `path` as bytes.
+pub(crate) fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Writes `contents` to the file at `path`, replacing it if it exists.
+///
+/// Missing parent directories are created.
+pub(crate) fn write(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Creates the directory at `path`, along with any missing parents.
+pub(crate) fn create_dir_all(path: &Path) -> Result<()> {
+    std::fs::create_dir_all(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Deletes the file at `path`.
+pub(crate) fn remove(path: &Path) -> Result<()> {
+    std::fs::remove_file(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Deletes the directory at `path` and everything in it, if it exists.
+pub(crate) fn remove_dir_all(path: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(path) {
+        Err(source) if source.kind() != std::io::ErrorKind::NotFound => Err(Error::Io {
+            path: path.to_owned(),
+            source,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Directories that never contain files of interest.
+const SKIPPED_DIRECTORIES: &[&str] = &[".git", "target"];
+
+/// Lists every file under `root`, as sorted paths relative to `root`.
+///
+/// Version control metadata and build output directories are skipped.
+pub(crate) fn walk(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(directory) = pending.pop() {
+        let path = root.join(&directory);
+        let io_error = |source| Error::Io {
+            path: path.clone(),
+            source,
+        };
+
+        for entry in std::fs::read_dir(&path).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
+            let relative = directory.join(entry.file_name());
+            let file_type = entry.file_type().map_err(io_error)?;
+            if file_type.is_dir() {
+                let skipped = SKIPPED_DIRECTORIES
+                    .iter()
+                    .any(|&skipped| entry.file_name() == skipped);
+                if !skipped {
+                    pending.push(relative);
+                }
+            } else if file_type.is_file() {
+                files.push(relative);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// A directory of files for a test, deleted when dropped.
+#[cfg(test)]
+pub(crate) struct Scratch(PathBuf);
+
+#[cfg(test)]
+impl Scratch {
+    /// Creates an empty directory with the given `files` (relative paths and
+    /// contents) in it.
+    pub(crate) fn new(files: &[(&str, &str)]) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "xtask-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        for (file, contents) in files {
+            write(&path.join(file), contents).unwrap();
+        }
+        Self(path)
+    }
+
+    /// The path of the directory.
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,96 @@
+//! Queries against local git repositories, by running the `git` binary.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{Result, process};
+
+/// A `git` command operating on the repository containing `dir`.
+pub(crate) fn command(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir);
+    command
+}
+
+/// Resolves `rev` to the full hash of a commit, or [`None`] if it doesn't
+/// name one (or `dir` isn't in a repository).
+pub(crate) fn resolve(dir: &Path, rev: &str) -> Result<Option<String>> {
+    let stdout = process::stdout(
+        command(dir)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{rev}^{{commit}}")),
+    )?;
+    Ok(stdout.map(|hash| hash.trim().to_owned()))
+}
+
+/// The contents of `path` (relative to the repository root) at commit `rev`,
+/// or [`None`] if it doesn't exist there.
+pub(crate) fn show(dir: &Path, rev: &str, path: &Path) -> Result<Option<String>> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    process::stdout(command(dir).arg("show").arg(format!("{rev}:{path}")))
+}
+
+/// The files that differ between commits `from` and `to`, as sorted paths
+/// relative to the repository root. A renamed file counts as a deletion and
+/// an addition.
+pub(crate) fn changed_files(dir: &Path, from: &str, to: &str) -> Result<Vec<PathBuf>> {
+    let stdout = process::stdout(
+        command(dir)
+            .args(["diff", "--name-only", "--no-renames", "-z"])
+            .args([from, to]),
+    )?
+    .unwrap_or_default();
+    let mut files: Vec<PathBuf> = stdout
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The files of commit `rev`, as sorted paths relative to the repository
+/// root.
+pub(crate) fn files(dir: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+    let stdout = process::stdout(
+        command(dir)
+            .args(["ls-tree", "-r", "-z", "--name-only"])
+            .arg(rev),
+    )?
+    .unwrap_or_default();
+    let mut files: Vec<PathBuf> = stdout
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The number of commits reachable from `to` but not from `from`.
+pub(crate) fn count_commits(dir: &Path, from: &str, to: &str) -> Result<Option<usize>> {
+    let stdout = process::stdout(
+        command(dir)
+            .args(["rev-list", "--count"])
+            .arg(format!("{from}..{to}")),
+    )?;
+    Ok(stdout.and_then(|count| count.trim().parse().ok()))
+}
+
+/// Whether the work tree containing `dir` has uncommitted changes (including
+/// untracked files), or [`None`] if `dir` isn't in a work tree.
+pub(crate) fn is_dirty(dir: &Path) -> Result<Option<bool>> {
+    let stdout = process::stdout(command(dir).args(["status", "--porcelain"]))?;
+    Ok(stdout.map(|status| !status.trim().is_empty()))
+}
+
+/// The value of the git configuration `key` (like `user.name`) as seen from
+/// `dir`, or [`None`] if it isn't set.
+pub(crate) fn config(dir: &Path, key: &str) -> Result<Option<String>> {
+    let stdout = process::stdout(command(dir).args(["config", "--get", key]))?;
+    Ok(stdout
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty()))
+}
@@ -0,0 +1,403 @@
+//! Turning a fresh copy of the template into a named crate.
+//!
+//! [`plan`] follows the template manifest (see [`template`](crate::template)),
+//! working through the template in three passes: the chosen variants are
+//! layered over it, conditional blocks (see [`conditional`]) are resolved,
+//! using the answers given explicitly, then those implied by the variants,
+//! then the conditions' values in the template, and
+//! finally placeholders (see [`placeholder`]) are filled in, so that the
+//! package metadata, badges, docs.rs links and licenses all agree on one
+//! [`CrateName`](crate::crate_name::CrateName) and owner. Only the files the
+//! manifest says to render go through the last two passes, and files that
+//! only belong to the template (like the manifest itself) are deleted.
+//!
+//! The commit checked out at the time is recorded as the crate's template
+//! baseline (see [`upgrade`]), since a freshly instantiated
+//! crate is normally still a plain copy of the template's repository.
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use crate::{
+    Result,
+    answers::Answers,
+    changes::{FileChange, Files},
+    conditional::{self, Condition},
+    git, metadata, msrv,
+    placeholder::{self, Values, Variable},
+    template::{Rule, Template},
+    toml::Document,
+    upgrade, variant,
+};
+
+/// A license the crate can be released under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum License {
+    /// Dual-licensed under MIT and Apache-2.0, like the template.
+    MitOrApache,
+    /// The MIT license alone.
+    Mit,
+    /// The Apache License, Version 2.0 alone.
+    Apache,
+}
+
+/// README.md's license section, as the template words it.
+const DUAL_LICENSE: &str = "\
+Licensed under either of:
+
+- Apache License, Version 2.0 ([LICENSE-APACHE](LICENSE-APACHE) or
+  <http://www.apache.org/licenses/LICENSE-2.0>)
+- MIT license ([LICENSE-MIT](LICENSE-MIT) or
+  <http://opensource.org/licenses/MIT>)
+
+at your option.";
+
+/// The end of README.md's contribution section, as the template words it.
+const DUAL_CONTRIBUTION: &str = "\
+for inclusion in the work by you, as defined in the Apache-2.0 license, shall be
+dual licensed as above, without any additional terms or conditions.";
+
+impl License {
+    /// Every license, in the order they're offered.
+    pub const ALL: [Self; 3] = [Self::MitOrApache, Self::Mit, Self::Apache];
+
+    /// The license's SPDX expression, as written in Cargo.toml.
+    pub fn expression(self) -> &'static str {
+        match self {
+            Self::MitOrApache => "MIT OR Apache-2.0",
+            Self::Mit => "MIT",
+            Self::Apache => "Apache-2.0",
+        }
+    }
+
+    /// Looks up the license with the SPDX expression `expression` (in either
+    /// order, for the dual license).
+    pub fn from_expression(expression: &str) -> Option<Self> {
+        match expression.trim() {
+            "Apache-2.0 OR MIT" => Some(Self::MitOrApache),
+            expression => Self::ALL
+                .into_iter()
+                .find(|license| license.expression() == expression),
+        }
+    }
+
+    /// The license files (relative to the crate root) the crate keeps.
+    fn files(self) -> &'static [&'static str] {
+        match self {
+            Self::MitOrApache => &["LICENSE-APACHE", "LICENSE-MIT"],
+            Self::Mit => &["LICENSE-MIT"],
+            Self::Apache => &["LICENSE-APACHE"],
+        }
+    }
+
+    /// README.md's license section and the end of its contribution section.
+    fn readme(self) -> (&'static str, &'static str) {
+        match self {
+            Self::MitOrApache => (DUAL_LICENSE, DUAL_CONTRIBUTION),
+            Self::Mit => (
+                "Licensed under the MIT license ([LICENSE-MIT](LICENSE-MIT) or\n\
+                 <http://opensource.org/licenses/MIT>).",
+                "for inclusion in the work by you shall be licensed as above, without any\n\
+                 additional terms or conditions.",
+            ),
+            Self::Apache => (
+                "Licensed under the Apache License, Version 2.0\n\
+                 ([LICENSE-APACHE](LICENSE-APACHE) or\n\
+                 <http://www.apache.org/licenses/LICENSE-2.0>).",
+                "for inclusion in the work by you, as defined in the Apache-2.0 license, shall be\n\
+                 licensed as above, without any additional terms or conditions.",
+            ),
+        }
+    }
+}
+
+impl fmt::Display for License {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expression())
+    }
+}
+
+/// Computes the changes needed to instantiate the template at `root`, as
+/// described by its manifest, `template`, with `answers`.
+///
+/// Files that are already instantiated (or were deleted) are skipped, and the
+/// chosen variants are only applied where they haven't been already. The
+/// answers are recorded, with the value each condition took, and unless one
+/// is already recorded, the current git commit of `root` (if it's in a
+/// repository) is recorded as the template baseline.
+///
+/// # Errors
+///
+/// Returns an error if a variant or condition is unknown, the keywords or
+/// categories would be rejected by crates.io, a file cannot be read, or a
+/// file's conditional blocks or placeholders are malformed.
+pub fn plan(root: &Path, template: &Template, answers: &Answers) -> Result<Vec<FileChange>> {
+    for name in answers.conditions.keys() {
+        Condition::find(name)?;
+    }
+    metadata::validate(&answers.keywords, &answers.categories)?;
+    let variants = variant::select(&template.variants, &answers.variants)?;
+
+    let mut files = Files::new(root);
+    if !variants.is_empty() {
+        variant::apply(&mut files, &variants)?;
+    }
+    let mut conditions = variant::conditions(&template.variants, &variants);
+    conditions.extend(answers.conditions.clone());
+    let conditions = conditional::complete(&conditions);
+
+    for path in files.paths()? {
+        match template.rule(&path) {
+            Rule::Copy => continue,
+            Rule::Skip => {
+                files.remove(&path)?;
+                continue;
+            }
+            Rule::Render => {}
+        }
+        let Some(original) = files.get(&path)? else {
+            continue;
+        };
+        let rendered = render(&path, original, &conditions, &answers.values)?;
+        files.set(&path, rendered)?;
+    }
+    apply_answers(&mut files, answers)?;
+
+    let commit = match upgrade::baseline(root)? {
+        Some(_) => None,
+        None => git::resolve(root, "HEAD")?,
+    };
+    let stamp = match commit {
+        Some(commit) => upgrade::stamp(root, &commit)?,
+        None => None,
+    };
+    if let Some(change) = stamp {
+        files.set(upgrade::STAMP_PATH, change.updated)?;
+    }
+    let used = Answers {
+        conditions,
+        ..answers.clone()
+    };
+    used.record(&mut files)?;
+
+    Ok(files.into_changes())
+}
+
+/// Renders the contents of the file at `path`: resolves its conditional
+/// blocks with `conditions`, then fills in its placeholders with `values`.
+///
+/// # Errors
+///
+/// Returns an error if the file's conditional blocks or placeholders are
+/// malformed.
+pub fn render(
+    path: &Path,
+    contents: &str,
+    conditions: &BTreeMap<String, bool>,
+    values: &Values,
+) -> Result<String> {
+    let resolved = conditional::resolve(path, contents, conditions)?;
+    placeholder::render(path, &resolved, values)
+}
+
+/// Applies the answers that aren't placeholders: the keywords, categories,
+/// license and MSRV.
+fn apply_answers(files: &mut Files<'_>, answers: &Answers) -> Result<()> {
+    for (key, items) in [
+        ("keywords", &answers.keywords),
+        ("categories", &answers.categories),
+    ] {
+        let manifest = match files.get("Cargo.toml")? {
+            Some(manifest) if !items.is_empty() => Some(set_list(manifest, key, items)?),
+            _ => None,
+        };
+        if let Some(manifest) = manifest {
+            files.set("Cargo.toml", manifest)?;
+        }
+    }
+
+    if let Some(license) = answers
+        .license
+        .filter(|&license| license != License::MitOrApache)
+    {
+        for file in License::MitOrApache.files() {
+            if !license.files().contains(file) {
+                files.remove(file)?;
+            }
+        }
+        if let Some(manifest) = files.get("Cargo.toml")? {
+            let mut document = Document::parse(Path::new("Cargo.toml"), manifest)?;
+            document.set("package", "license", &license.expression().into());
+            files.set("Cargo.toml", document.to_string())?;
+        }
+        if let Some(readme) = files.get("README.md")? {
+            let (section, contribution) = license.readme();
+            let readme = readme.replacen(DUAL_LICENSE, section, 1).replacen(
+                DUAL_CONTRIBUTION,
+                contribution,
+                1,
+            );
+            files.set("README.md", readme)?;
+        }
+    }
+
+    if let Some(version) = answers.msrv {
+        msrv::set_files(files, version)?;
+        if let Some(manifest) = files.get("Cargo.toml")? {
+            let manifest = manifest.replacen("# TODO: update MSRV\n", "", 1);
+            files.set("Cargo.toml", manifest)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the list `key` (like `keywords`) of Cargo.toml's `[package]`,
+/// dropping the `TODO` comment asking for it.
+fn set_list(contents: &str, key: &str, items: &[String]) -> Result<String> {
+    let mut manifest = Document::parse(Path::new("Cargo.toml"), contents)?;
+    let Some(entry) = manifest.get("package", key) else {
+        return Ok(contents.to_owned());
+    };
+    let is_todo = entry.comment.is_some_and(|comment| {
+        comment
+            .trim_start_matches('#')
+            .trim_start()
+            .starts_with("TODO")
+    });
+    if is_todo {
+        manifest.set_comment("package", key, None);
+    }
+    manifest.set("package", key, &items.to_vec().into());
+    Ok(manifest.to_string())
+}
+
+/// The values of the variables for the crate at `root`, as far as they can be
+/// read back from its Cargo.toml: the name, owner (from the GitHub
+/// repository URL), first author, version and description.
+///
+/// # Errors
+///
+/// Returns an error if Cargo.toml cannot be read or parsed.
+pub fn values(root: &Path) -> Result<Values> {
+    let manifest = Document::read(&root.join("Cargo.toml"))?;
+    let string = |key| manifest.get_str("package", key);
+
+    let owner = string("repository").and_then(|url| {
+        let path = url.strip_prefix("https://github.com/")?;
+        Some(path.split('/').next()?.to_owned())
+    });
+    let author = manifest
+        .get("package", "authors")
+        .and_then(|entry| Some(entry.value.as_array()?.first()?.as_str()?.to_owned()));
+
+    // Anything that doesn't have the variable's type (like an empty
+    // description) is left without a value
+    let mut values = Values::new();
+    for (variable, value) in [
+        (Variable::Name, string("name")),
+        (Variable::Owner, owner),
+        (Variable::Author, author),
+        (Variable::Version, string("version")),
+        (Variable::Description, string("description")),
+    ] {
+        if let Some(value) = value {
+            let _ = values.set(variable, value);
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fs::Scratch, template};
+
+    const MANIFEST: &str = "\
+[variants.no_std]
+description = \"A no_std library\"
+conditions = [\"no_std\"]
+
+[files]
+render = [\"scripts/ci.py\"]
+skip = [\"template.toml\"]
+";
+
+    const SCRIPT: &str = "\
+CI_STAGES = [
+    # @if no_std
+    # \"build_nostd\",
+    # @endif
+    # @if unsafe
+    # \"run_tests_miri\",
+    # @endif
+    # @if loom
+    \"loom\",
+    # @endif
+]
+";
+
+    fn instantiate(scratch: &Scratch, answers: &Answers) -> Vec<FileChange> {
+        let template = Template::parse(Path::new(template::PATH), MANIFEST).unwrap();
+        plan(scratch.path(), &template, answers).unwrap()
+    }
+
+    fn updated<'a>(changes: &'a [FileChange], path: &str) -> &'a str {
+        &changes
+            .iter()
+            .find(|change| change.path == Path::new(path))
+            .unwrap()
+            .updated
+    }
+
+    #[test]
+    fn every_condition_is_resolved_and_recorded() {
+        let scratch = Scratch::new(&[("scripts/ci.py", SCRIPT), (template::PATH, MANIFEST)]);
+        let answers = Answers {
+            variants: vec!["no_std".to_owned()],
+            conditions: BTreeMap::from([("loom".to_owned(), false)]),
+            ..Answers::default()
+        };
+        let changes = instantiate(&scratch, &answers);
+
+        assert_eq!(
+            updated(&changes, "scripts/ci.py"),
+            "CI_STAGES = [\n    \"build_nostd\",\n]\n"
+        );
+        assert!(
+            changes
+                .iter()
+                .any(|change| change.path == Path::new(template::PATH) && change.deleted)
+        );
+        assert!(updated(&changes, upgrade::STAMP_PATH).ends_with(
+            "[answers]\nvariants = [\"no_std\"]\n\n[answers.conditions]\nloom = false\nno_std = \
+             true\nunsafe = false\n"
+        ));
+    }
+
+    #[test]
+    fn answers_are_recorded_next_to_an_existing_baseline() {
+        let stamp = format!("{}\n[template]\ncommit = \"abc\"\n", upgrade::STAMP_HEADER);
+        let scratch = Scratch::new(&[("scripts/ci.py", SCRIPT), (upgrade::STAMP_PATH, &stamp)]);
+        let changes = instantiate(&scratch, &Answers::default());
+
+        assert_eq!(
+            updated(&changes, upgrade::STAMP_PATH),
+            format!("{stamp}\n[answers.conditions]\nloom = true\nno_std = false\nunsafe = false\n")
+        );
+    }
+
+    #[test]
+    fn licenses() {
+        for license in License::ALL {
+            assert_eq!(
+                License::from_expression(license.expression()),
+                Some(license)
+            );
+        }
+        assert_eq!(
+            License::from_expression("Apache-2.0 OR MIT"),
+            Some(License::MitOrApache)
+        );
+        assert_eq!(License::from_expression("GPL-3.0"), None);
+    }
+}
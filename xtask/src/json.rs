@@ -0,0 +1,428 @@
+//! A minimal JSON document model, for machine-readable command output (and
+//! reading answers files).
+
+use std::{
+    fmt::{self, Write as _},
+    path::Path,
+};
+
+use crate::{Error, Result};
+
+/// A JSON value.
+///
+/// Objects keep their keys in insertion order so output is deterministic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// A number, stored as its JSON text so no precision is lost.
+    Number(String),
+    /// A string.
+    String(String),
+    /// An array.
+    Array(Vec<Self>),
+    /// An object, as key-value pairs in order.
+    Object(Vec<(String, Self)>),
+}
+
+impl Value {
+    /// Creates an object from key-value pairs.
+    pub fn object(entries: impl IntoIterator<Item = (&'static str, Self)>) -> Self {
+        Self::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// Parses `contents` as a single JSON value. `path` is only used for error
+    /// messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `contents` is not valid JSON.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text: contents,
+            pos: 0,
+        };
+        let value = parser
+            .value()
+            .and_then(|value| {
+                parser.skip_whitespace();
+                match parser.peek() {
+                    None => Ok(value),
+                    Some(_) => Err("trailing characters after the value".to_owned()),
+                }
+            })
+            .map_err(|message| {
+                let line = contents[..parser.pos].matches('\n').count() + 1;
+                Error::malformed(path, format!("line {line}: {message}"))
+            })?;
+        Ok(value)
+    }
+
+    /// Looks up `key`, if the value is an object.
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(entries) => entries
+                .iter()
+                .find(|(entry, _)| entry == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Formats the value across multiple lines, indented by two spaces per
+    /// level.
+    pub fn to_pretty_string(&self) -> String {
+        let mut output = String::new();
+        self.write_pretty(&mut output, 0);
+        output
+    }
+
+    fn write_pretty(&self, output: &mut String, depth: usize) {
+        let indent = |output: &mut String, depth: usize| {
+            output.push('\n');
+            output.extend(std::iter::repeat_n("  ", depth));
+        };
+
+        match self {
+            Self::Array(items) if !items.is_empty() => {
+                output.push('[');
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        output.push(',');
+                    }
+                    indent(output, depth + 1);
+                    item.write_pretty(output, depth + 1);
+                }
+                indent(output, depth);
+                output.push(']');
+            }
+            Self::Object(entries) if !entries.is_empty() => {
+                output.push('{');
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        output.push(',');
+                    }
+                    indent(output, depth + 1);
+                    write_string(output, key);
+                    output.push_str(": ");
+                    value.write_pretty(output, depth + 1);
+                }
+                indent(output, depth);
+                output.push('}');
+            }
+            _ => {
+                let _ = write!(output, "{self}");
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value compactly, on a single line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Number(text) => f.write_str(text),
+            Self::String(text) => {
+                let mut output = String::with_capacity(text.len() + 2);
+                write_string(&mut output, text);
+                f.write_str(&output)
+            }
+            Self::Array(items) => {
+                f.write_char('[')?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(entries) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}:{value}", Self::String(key.clone()))?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Self::Number(value.to_string())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<Self>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T: Into<Self>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Self::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Appends `text` to `output` as a quoted and escaped JSON string.
+fn write_string(output: &mut String, text: &str) {
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(output, "\\u{:04x}", u32::from(c));
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+/// A recursive-descent JSON parser over `text`.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += c.len_utf8();
+                Ok(())
+            }
+            Some(c) => Err(format!("expected `{expected}`, found `{c}`")),
+            None => Err(format!("expected `{expected}`, found the end of the input")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        let rest = &self.text[self.pos..];
+        for (literal, value) in [
+            ("null", Value::Null),
+            ("true", Value::Bool(true)),
+            ("false", Value::Bool(false)),
+        ] {
+            if rest.starts_with(literal) {
+                self.pos += literal.len();
+                return Ok(value);
+            }
+        }
+
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    if self.peek() == Some(',') {
+                        self.pos += 1;
+                    } else {
+                        self.expect(']')?;
+                        return Ok(Value::Array(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    if self.peek() == Some(',') {
+                        self.pos += 1;
+                    } else {
+                        self.expect('}')?;
+                        return Ok(Value::Object(entries));
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                    .unwrap_or(rest.len());
+                let number = &rest[..len];
+                if number.parse::<f64>().is_err() {
+                    return Err(format!("invalid number `{number}`"));
+                }
+                self.pos += len;
+                Ok(Value::Number(number.to_owned()))
+            }
+            Some(c) => Err(format!("unexpected `{c}`")),
+            None => Err("unexpected end of the input".to_owned()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            return Err("expected a string".to_owned());
+        }
+        self.pos += 1;
+        let mut output = String::new();
+        let mut chars = self.text[self.pos..].char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += index + 1;
+                    return Ok(output);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| format!("invalid escape `\\u{hex}`"))?
+                        }
+                        _ => return Err("invalid escape in a string".to_owned()),
+                    };
+                    output.push(escaped);
+                }
+                c if c.is_control() => {
+                    return Err("unescaped control character in a string".to_owned());
+                }
+                c => output.push(c),
+            }
+        }
+        Err("unterminated string".to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Value> {
+        Value::parse(Path::new("test.json"), contents)
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = Value::object([
+            ("name", "demo \"crate\"\n\t\u{1}".into()),
+            ("count", 3_usize.into()),
+            ("ok", true.into()),
+            ("missing", Option::<&str>::None.into()),
+            ("items", vec!["a", "é"].into()),
+            ("empty", Value::object([])),
+            ("none", Vec::<&str>::new().into()),
+            (
+                "nested",
+                Value::object([("x", Value::Number("-1.5e3".into()))]),
+            ),
+        ]);
+        assert_eq!(parse(&value.to_string()).unwrap(), value);
+        assert_eq!(parse(&value.to_pretty_string()).unwrap(), value);
+        assert_eq!(
+            value.get("nested").unwrap().to_pretty_string(),
+            "{\n  \"x\": -1.5e3\n}"
+        );
+    }
+
+    #[test]
+    fn parses() {
+        let value = parse(" {\"a\": [1, null, \"\\u00e9\\/\"], \"b\": {}} ").unwrap();
+        assert_eq!(
+            value.get("a"),
+            Some(&Value::Array(vec![
+                Value::Number("1".into()),
+                Value::Null,
+                Value::String("é/".into()),
+            ]))
+        );
+        assert_eq!(value.get("b"), Some(&Value::Object(Vec::new())));
+        assert_eq!(value.get("c"), None);
+        assert_eq!(Value::Null.get("a"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        for (contents, message) in [
+            ("{\"a\": 1,\n}", "line 2: expected a string"),
+            ("[1 2]", "line 1: expected `]`, found `2`"),
+            ("\"open", "line 1: unterminated string"),
+            ("1 2", "line 1: trailing characters after the value"),
+            ("-", "line 1: invalid number `-`"),
+            ("\"\\x\"", "line 1: invalid escape in a string"),
+            ("", "line 1: unexpected end of the input"),
+        ] {
+            assert_eq!(
+                parse(contents).unwrap_err().to_string(),
+                format!("test.json: {message}"),
+                "{contents:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Maintenance tooling for crates derived from this template.
+//!
+//! Everything here is exposed both as a library and through the `xtask`
+//! binary, which is normally run as `cargo xtask <COMMAND>` from the root of
+//! the repository (see `.cargo/config.toml`). Commands operate on the files of
+//! the crate at the repository root, never on this tooling crate itself.
+
+pub mod answers;
+pub mod changes;
+pub mod ci;
+pub mod ci_script;
+pub mod cli;
+pub mod conditional;
+pub mod crate_name;
+pub mod diff;
+pub mod doc_links;
+pub mod drift;
+pub mod error;
+pub mod fleet;
+pub mod instantiate;
+pub mod json;
+pub mod lints;
+pub mod markers;
+pub mod metadata;
+pub mod msrv;
+pub mod placeholder;
+pub mod release;
+pub mod template;
+pub mod toml;
+pub mod upgrade;
+pub mod variant;
+pub mod version;
+pub mod workflow;
+pub mod yaml;
+
+mod fs;
+mod git;
+mod process;
+
+pub use error::{Error, Result};
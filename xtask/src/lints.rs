@@ -0,0 +1,706 @@
+//! Auditing Cargo.toml's `[lints.rust]` and `[lints.clippy]` tables against
+//! the lints the installed toolchain actually has.
+//!
+//! Each table is preceded by a note recording the Rust version it was last
+//! reviewed against:
+//!
+//! ```toml
+//! # NOTE: This lint table was last updated during Rust version 1.85.0
+//! [lints.rust]
+//! ```
+//!
+//! [`audit`] lists the lints added since that version (by comparing the lint
+//! lists of the two toolchains, when the recorded one is installed), entries
+//! naming lints that are unknown, renamed or removed, and lint groups missing
+//! `priority = -1`. [`stamp`] then records the current version in the notes.
+//!
+//! The tables themselves can be generated from a named [`profile`].
+
+pub mod profile;
+
+use std::{collections::BTreeMap, fmt, path::Path, process::Command};
+
+use crate::{
+    Error, Result,
+    changes::FileChange,
+    fs,
+    msrv::{self, RustVersion},
+    process,
+    toml::{Document, Value},
+};
+
+/// The text before the version in a lint table's note.
+const NOTE_PREFIX: &str = "# NOTE: This lint table was last updated during Rust version ";
+
+/// A tool whose lints are configured in Cargo.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Tool {
+    /// The compiler's own lints, in `[lints.rust]`.
+    Rust,
+    /// Clippy's lints, in `[lints.clippy]`.
+    Clippy,
+}
+
+impl Tool {
+    /// Every tool, in the order their tables appear in the template.
+    pub const ALL: [Self; 2] = [Self::Rust, Self::Clippy];
+
+    /// The name of the tool's lint table, like `lints.rust`.
+    pub fn table(self) -> &'static str {
+        match self {
+            Self::Rust => "lints.rust",
+            Self::Clippy => "lints.clippy",
+        }
+    }
+
+    /// The driver that lists the tool's lints with `-W help`.
+    fn driver(self) -> &'static str {
+        match self {
+            Self::Rust => "rustc",
+            Self::Clippy => "clippy-driver",
+        }
+    }
+
+    /// The prefix of the tool's lint names on the command line.
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Rust => "",
+            Self::Clippy => "clippy::",
+        }
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rust => "rustc",
+            Self::Clippy => "clippy",
+        })
+    }
+}
+
+/// The lints (and lint groups) a toolchain knows about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintList {
+    /// Each lint, with its default level (`allow`, `warn`, ...).
+    pub lints: BTreeMap<String, String>,
+    /// Each lint group, with its lints.
+    pub groups: BTreeMap<String, Vec<String>>,
+}
+
+impl LintList {
+    /// Lists the lints of `tool` known to `toolchain` (or to the default
+    /// toolchain, if [`None`]), by parsing the output of `-W help`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the driver cannot be run, or fails.
+    pub fn query(tool: Tool, toolchain: Option<&str>) -> Result<Self> {
+        let mut command = Command::new(tool.driver());
+        if let Some(toolchain) = toolchain {
+            command.arg(format!("+{toolchain}"));
+        }
+        let output = process::stdout(command.args(["-W", "help"]))?
+            .ok_or_else(|| Error::InvalidInput(format!("`{} -W help` failed", tool.driver())))?;
+        Ok(Self::parse(tool, &output))
+    }
+
+    /// Parses the output of `-W help`, keeping only the lints of `tool`.
+    fn parse(tool: Tool, output: &str) -> Self {
+        let mut list = Self::default();
+        let mut in_groups = false;
+
+        for line in output.lines() {
+            let line = line.trim();
+            if line.starts_with("Lint checks ") {
+                in_groups = false;
+                continue;
+            }
+            if line.starts_with("Lint groups ") {
+                in_groups = true;
+                continue;
+            }
+
+            // Skip the `----` rulers under the column headers
+            let Some((name, rest)) = line
+                .split_once(char::is_whitespace)
+                .filter(|(name, _)| !name.starts_with('-'))
+            else {
+                continue;
+            };
+            let Some(name) = lint_name(tool, name) else {
+                continue;
+            };
+            if name == "name" || name == "warnings" {
+                continue;
+            }
+            let rest = rest.trim();
+
+            if in_groups {
+                let members = rest
+                    .split(',')
+                    .filter_map(|member| lint_name(tool, member.trim()))
+                    .collect();
+                list.groups.insert(name, members);
+            } else if let Some((level, _)) = rest.split_once(char::is_whitespace) {
+                list.lints.insert(name, level.to_owned());
+            }
+        }
+
+        list
+    }
+
+    /// Whether `name` is a lint or lint group.
+    pub fn contains(&self, name: &str) -> bool {
+        self.lints.contains_key(name) || self.groups.contains_key(name)
+    }
+
+    /// The groups `lint` belongs to.
+    pub fn groups_of(&self, lint: &str) -> impl Iterator<Item = &str> {
+        self.groups
+            .iter()
+            .filter(move |(_, members)| members.iter().any(|member| member == lint))
+            .map(|(group, _)| group.as_str())
+    }
+}
+
+/// Normalizes a lint name as printed by `-W help` (like `clippy::use-self`) to
+/// how Cargo.toml spells it (`use_self`), if it belongs to `tool`.
+fn lint_name(tool: Tool, name: &str) -> Option<String> {
+    let name = match tool {
+        Tool::Rust if name.contains("::") => return None,
+        Tool::Rust => name,
+        Tool::Clippy => name.strip_prefix(tool.prefix())?,
+    };
+    Some(name.replace('-', "_"))
+}
+
+/// One entry of a lint table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The (1-based) line in Cargo.toml.
+    pub line: usize,
+    /// The lint (or group) name.
+    pub name: String,
+    /// The level, like `warn`.
+    pub level: String,
+    /// The priority, if one is given.
+    pub priority: Option<i64>,
+}
+
+/// Reads the entries of `tool`'s lint table. `path` is only used for error
+/// messages.
+///
+/// # Errors
+///
+/// Returns an error if an entry is neither a level string nor an inline table
+/// with a `level`.
+pub fn entries(path: &Path, manifest: &Document, tool: Tool) -> Result<Vec<Entry>> {
+    manifest
+        .entries(tool.table())
+        .into_iter()
+        .map(|entry| {
+            let (level, priority) = parse_entry(&entry.value).ok_or_else(|| {
+                Error::malformed(
+                    path,
+                    format!("line {}: invalid lint entry `{}`", entry.line, entry.key),
+                )
+            })?;
+
+            Ok(Entry {
+                line: entry.line,
+                name: entry.key.to_owned(),
+                level,
+                priority,
+            })
+        })
+        .collect()
+}
+
+/// Parses the value of a lint table entry: either a level (`"warn"`) or an
+/// inline table (`{ level = "warn", priority = -1 }`).
+fn parse_entry(value: &Value) -> Option<(String, Option<i64>)> {
+    match value {
+        Value::String(level) => Some((level.clone(), None)),
+        Value::Table(entries) => {
+            let mut level = None;
+            let mut priority = None;
+            for (key, value) in entries {
+                match (key.as_str(), value) {
+                    ("level", Value::String(value)) => level = Some(value.clone()),
+                    ("priority", Value::Integer(value)) => priority = Some(*value),
+                    _ => return None,
+                }
+            }
+            Some((level?, priority))
+        }
+        _ => None,
+    }
+}
+
+/// A problem with an entry of a lint table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The entry names a lint the toolchain doesn't know.
+    Unknown {
+        /// The entry.
+        entry: Entry,
+    },
+    /// The entry names a lint that has been renamed.
+    Renamed {
+        /// The entry.
+        entry: Entry,
+        /// The lint's new name.
+        to: String,
+    },
+    /// The entry names a lint that has been removed.
+    Removed {
+        /// The entry.
+        entry: Entry,
+        /// Why the lint was removed.
+        reason: String,
+    },
+    /// The entry configures a lint group without `priority = -1`, so it may
+    /// override the individual lints configured after it.
+    GroupPriority {
+        /// The entry.
+        entry: Entry,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown { entry } => {
+                write!(
+                    f,
+                    "Cargo.toml:{}: unknown lint `{}`",
+                    entry.line, entry.name
+                )
+            }
+            Self::Renamed { entry, to } => write!(
+                f,
+                "Cargo.toml:{}: lint `{}` has been renamed to `{to}`",
+                entry.line, entry.name
+            ),
+            Self::Removed { entry, reason } => write!(
+                f,
+                "Cargo.toml:{}: lint `{}` has been removed: {reason}",
+                entry.line, entry.name
+            ),
+            Self::GroupPriority { entry } => write!(
+                f,
+                "Cargo.toml:{}: lint group `{}` should have `priority = -1`",
+                entry.line, entry.name
+            ),
+        }
+    }
+}
+
+/// Asks `tool`'s driver what has become of the lints named by `entries`, which
+/// it doesn't list: whether they've been renamed or removed, or are unknown.
+fn probe(tool: Tool, entries: &[&Entry]) -> Result<Vec<Problem>> {
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut command = Command::new(tool.driver());
+    command.args(["-", "--crate-type", "lib", "--emit", "metadata", "-o"]);
+    command.arg(if cfg!(windows) { "NUL" } else { "/dev/null" });
+    for entry in entries {
+        command
+            .arg("-W")
+            .arg(format!("{}{}", tool.prefix(), entry.name));
+    }
+    // Compile an empty crate, just to get the warnings about the lints
+    let output = process::output_with_input(&mut command, "")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    Ok(entries
+        .iter()
+        .map(|&entry| {
+            let name = format!("`{}{}`", tool.prefix(), entry.name);
+            let entry = entry.clone();
+            let message = stderr
+                .lines()
+                .filter_map(|line| line.strip_prefix("warning"))
+                .find(|line| line.contains(&name));
+
+            let renamed = message
+                .and_then(|message| message.split_once(" has been renamed to `"))
+                .and_then(|(_, to)| to.strip_suffix('`'));
+            let removed = message
+                .and_then(|message| message.split_once(" has been removed"))
+                .map(|(_, reason)| reason.trim_start_matches(':').trim());
+            match (renamed, removed) {
+                (Some(to), _) => Problem::Renamed {
+                    entry,
+                    to: to.trim_start_matches(tool.prefix()).to_owned(),
+                },
+                (None, Some(reason)) => Problem::Removed {
+                    entry,
+                    reason: reason.to_owned(),
+                },
+                (None, None) => Problem::Unknown { entry },
+            }
+        })
+        .collect())
+}
+
+/// A lint added since the table was last reviewed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewLint {
+    /// The lint's name.
+    pub name: String,
+    /// The lint's default level.
+    pub level: String,
+    /// Whether the table already configures the lint, by name or through one
+    /// of its groups.
+    pub configured: bool,
+}
+
+/// The audit of one lint table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Audit {
+    /// The tool whose table was audited.
+    pub tool: Tool,
+    /// The (1-based) line of the table's version note, if it has one.
+    pub note_line: Option<usize>,
+    /// The version in the note, or [`None`] if it is missing or a
+    /// placeholder like `1.X.Y`.
+    pub recorded: Option<RustVersion>,
+    /// The version of the toolchain audited against.
+    pub current: RustVersion,
+    /// The lints added since the recorded version, or [`None`] if the
+    /// recorded version's toolchain isn't installed (so they can't be told
+    /// apart).
+    pub new_lints: Option<Vec<NewLint>>,
+    /// Lints that are allowed by default and not enabled by the table, which
+    /// are worth reviewing when the new lints can't be determined.
+    pub unconfigured: Vec<NewLint>,
+    /// Problems with the table's entries.
+    pub problems: Vec<Problem>,
+}
+
+/// Audits the lint tables of the crate at `root` against the default
+/// toolchain.
+///
+/// # Errors
+///
+/// Returns an error if Cargo.toml cannot be read or parsed, or a toolchain
+/// cannot be queried.
+pub fn audit(root: &Path) -> Result<Vec<Audit>> {
+    let path = root.join("Cargo.toml");
+    let manifest = fs::read(&path)?;
+    let document = Document::parse(&path, &manifest)?;
+    let current = current_version()?;
+    let installed = msrv::toolchains()?;
+
+    let mut audits = Vec::new();
+    for tool in Tool::ALL {
+        let entries = entries(&path, &document, tool)?;
+        let list = LintList::query(tool, None)?;
+        let (note_line, recorded) = match note(&manifest, tool) {
+            Some((line, version)) => (Some(line), version.parse().ok()),
+            None => (None, None),
+        };
+
+        let configured = |lint: &str| {
+            entries.iter().any(|entry| {
+                entry.name == lint || list.groups_of(lint).any(|group| group == entry.name)
+            })
+        };
+        let describe = |(name, level): (&String, &String)| NewLint {
+            name: name.clone(),
+            level: level.clone(),
+            configured: configured(name),
+        };
+
+        let baseline = recorded
+            .and_then(|recorded| {
+                installed
+                    .iter()
+                    .find(|toolchain| toolchain.version.same_release(recorded))
+            })
+            .map(|toolchain| LintList::query(tool, Some(&toolchain.name)))
+            .transpose()?;
+        let new_lints = baseline.map(|baseline| {
+            list.lints
+                .iter()
+                .filter(|(name, _)| !baseline.lints.contains_key(*name))
+                .map(describe)
+                .collect()
+        });
+        let unconfigured = list
+            .lints
+            .iter()
+            .filter(|(name, level)| *level == "allow" && !configured(name))
+            .map(describe)
+            .collect();
+
+        let mut problems: Vec<Problem> = entries
+            .iter()
+            .filter(|entry| {
+                list.groups.contains_key(&entry.name)
+                    && entry.priority.is_none_or(|priority| priority >= 0)
+            })
+            .map(|entry| Problem::GroupPriority {
+                entry: entry.clone(),
+            })
+            .collect();
+        let unknown: Vec<&Entry> = entries
+            .iter()
+            .filter(|entry| !list.contains(&entry.name))
+            .collect();
+        problems.extend(probe(tool, &unknown)?);
+        problems.sort_by_key(|problem| match problem {
+            Problem::Unknown { entry }
+            | Problem::Renamed { entry, .. }
+            | Problem::Removed { entry, .. }
+            | Problem::GroupPriority { entry } => entry.line,
+        });
+
+        audits.push(Audit {
+            tool,
+            note_line,
+            recorded,
+            current,
+            new_lints,
+            unconfigured,
+            problems,
+        });
+    }
+
+    Ok(audits)
+}
+
+/// Records the version of the default toolchain in the notes of both lint
+/// tables of the crate at `root`.
+///
+/// # Errors
+///
+/// Returns an error if Cargo.toml cannot be read, a table has no note, or the
+/// toolchain cannot be queried.
+pub fn stamp(root: &Path) -> Result<(RustVersion, Option<FileChange>)> {
+    let path = root.join("Cargo.toml");
+    let original = fs::read(&path)?;
+    let current = current_version()?;
+
+    let mut lines: Vec<String> = original.split_inclusive('\n').map(str::to_owned).collect();
+    for tool in Tool::ALL {
+        let (line, version) = note(&original, tool).ok_or_else(|| {
+            Error::malformed(
+                &path,
+                format!(
+                    "`[{}]` has no \"{}\" note",
+                    tool.table(),
+                    NOTE_PREFIX.trim_start_matches("# ").trim_end()
+                ),
+            )
+        })?;
+        let text = &mut lines[line - 1];
+        *text = text.replacen(
+            &format!("{NOTE_PREFIX}{version}"),
+            &format!("{NOTE_PREFIX}{current}"),
+            1,
+        );
+    }
+
+    Ok((
+        current,
+        FileChange::new("Cargo.toml", original, lines.concat()),
+    ))
+}
+
+/// Finds the version note in the comments directly above `tool`'s table,
+/// returning its (1-based) line and the recorded version.
+fn note(manifest: &str, tool: Tool) -> Option<(usize, &str)> {
+    let header = format!("[{}]", tool.table());
+    let lines: Vec<&str> = manifest.lines().collect();
+    let table = lines.iter().position(|line| line.trim() == header)?;
+
+    lines[..table]
+        .iter()
+        .enumerate()
+        .rev()
+        .take_while(|(_, line)| line.trim_start().starts_with('#'))
+        .find_map(|(index, line)| {
+            let version = line.trim().strip_prefix(NOTE_PREFIX)?;
+            Some((index + 1, version.split_whitespace().next()?))
+        })
+}
+
+/// The version of the default toolchain.
+fn current_version() -> Result<RustVersion> {
+    let output = process::stdout(Command::new("rustc").arg("--version"))?
+        .ok_or_else(|| Error::InvalidInput("`rustc --version` failed".to_owned()))?;
+    output
+        .split_whitespace()
+        .nth(1)
+        .and_then(|version| version.split('-').next())
+        .ok_or_else(|| {
+            Error::InvalidInput(format!("unexpected `rustc --version` output `{output}`"))
+        })?
+        .parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Scratch;
+
+    const MANIFEST: &str = "\
+[package]
+name = \"demo\"
+
+# NOTE: This lint table was last updated during Rust version 1.X.Y
+[lints.rust]
+unsafe_code = \"forbid\"
+rust_2018_idioms = \"warn\"
+bare_trait_object = \"warn\"
+raw_pointer_derive = \"warn\"
+no_such_lint = \"warn\"
+
+# Pedantic, with a few exceptions
+# NOTE: This lint table was last updated during Rust version 1.85.0
+[lints.clippy]
+pedantic = { level = \"warn\", priority = -1 }
+stutter = \"warn\"
+";
+
+    #[test]
+    fn parses_lint_help() {
+        let help = "\
+Lint checks provided by rustc:
+
+                    name  default  meaning
+                    ----  -------  -------
+              dead-code  warn     detects unused items
+
+Lint groups provided by rustc:
+
+                    name  sub-lints
+                    ----  ---------
+                warnings  all lints that are set to issue warnings
+                  unused  dead-code, unused-imports
+
+Lint checks provided by plugins loaded by this crate:
+
+      clippy::use-self  allow  unnecessary structure name repetition
+ clippy::doc-markdown  allow  missing backticks in doc comments
+
+Lint groups provided by plugins loaded by this crate:
+
+      clippy::pedantic  clippy::use-self, clippy::doc-markdown
+";
+        let rust = LintList::parse(Tool::Rust, help);
+        assert_eq!(
+            rust.lints,
+            BTreeMap::from([("dead_code".to_owned(), "warn".to_owned())])
+        );
+        assert_eq!(rust.groups_of("dead_code").collect::<Vec<_>>(), ["unused"]);
+
+        let clippy = LintList::parse(Tool::Clippy, help);
+        assert!(clippy.contains("use_self") && clippy.contains("pedantic"));
+        assert!(!clippy.contains("dead_code"));
+        assert_eq!(
+            clippy.groups_of("doc_markdown").collect::<Vec<_>>(),
+            ["pedantic"]
+        );
+    }
+
+    #[test]
+    fn entries_and_notes() {
+        let path = Path::new("Cargo.toml");
+        let document = Document::parse(path, MANIFEST).unwrap();
+        let clippy = entries(path, &document, Tool::Clippy).unwrap();
+        assert_eq!(
+            clippy,
+            [
+                Entry {
+                    line: 15,
+                    name: "pedantic".to_owned(),
+                    level: "warn".to_owned(),
+                    priority: Some(-1),
+                },
+                Entry {
+                    line: 16,
+                    name: "stutter".to_owned(),
+                    level: "warn".to_owned(),
+                    priority: None,
+                },
+            ]
+        );
+        let invalid = Document::parse(path, "[lints.rust]\nunsafe_code = 1\n").unwrap();
+        assert!(entries(path, &invalid, Tool::Rust).is_err());
+
+        assert_eq!(note(MANIFEST, Tool::Rust), Some((4, "1.X.Y")));
+        assert_eq!(note(MANIFEST, Tool::Clippy), Some((13, "1.85.0")));
+        assert_eq!(note("[lints.rust]\n", Tool::Rust), None);
+    }
+
+    #[test]
+    fn audit_reports_renamed_removed_and_unknown_lints() {
+        let scratch = Scratch::new(&[("Cargo.toml", MANIFEST)]);
+        let audits = audit(scratch.path()).unwrap();
+        let problems: Vec<(Tool, String)> = audits
+            .iter()
+            .flat_map(|audit| {
+                audit
+                    .problems
+                    .iter()
+                    .map(|problem| (audit.tool, problem.to_string()))
+            })
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                (
+                    Tool::Rust,
+                    "Cargo.toml:7: lint group `rust_2018_idioms` should have `priority = -1`"
+                        .to_owned()
+                ),
+                (
+                    Tool::Rust,
+                    "Cargo.toml:8: lint `bare_trait_object` has been renamed to \
+                     `bare_trait_objects`"
+                        .to_owned()
+                ),
+                (
+                    Tool::Rust,
+                    "Cargo.toml:9: lint `raw_pointer_derive` has been removed: using derive \
+                     with raw pointers is ok"
+                        .to_owned()
+                ),
+                (
+                    Tool::Rust,
+                    "Cargo.toml:10: unknown lint `no_such_lint`".to_owned()
+                ),
+                (
+                    Tool::Clippy,
+                    "Cargo.toml:16: lint `stutter` has been renamed to \
+                     `module_name_repetitions`"
+                        .to_owned()
+                ),
+            ]
+        );
+        // A placeholder version can't be compared against
+        assert_eq!(audits[0].note_line, Some(4));
+        assert_eq!(audits[0].recorded, None);
+        assert_eq!(audits[0].new_lints, None);
+        assert_eq!(audits[1].recorded, Some("1.85.0".parse().unwrap()));
+    }
+
+    #[test]
+    fn stamp_records_the_current_version() {
+        let scratch = Scratch::new(&[("Cargo.toml", MANIFEST)]);
+        let (current, change) = stamp(scratch.path()).unwrap();
+        let updated = change.unwrap().updated;
+        let current = current.to_string();
+        assert_eq!(note(&updated, Tool::Rust), Some((4, current.as_str())));
+        assert_eq!(note(&updated, Tool::Clippy), Some((13, current.as_str())));
+
+        let unnoted = Scratch::new(&[("Cargo.toml", "[lints.rust]\n[lints.clippy]\n")]);
+        assert!(stamp(unnoted.path()).is_err());
+    }
+}
@@ -0,0 +1,569 @@
+//! Named, versioned lint policy profiles.
+//!
+//! A profile is a complete pair of `[lints.rust]` and `[lints.clippy]` tables,
+//! defined in `xtask/lint-profiles.toml`. The profile a crate's tables were
+//! generated from is recorded in a comment above them:
+//!
+//! ```toml
+//! # Lint profile: strict-library (version 1)
+//! ```
+//!
+//! Any entry that differs from that profile is a local override, which
+//! [`switch`] carries over to the new profile.
+
+use std::{fmt, path::Path};
+
+use super::{Entry, NOTE_PREFIX, Tool, entries};
+use crate::{
+    Error, Result,
+    changes::FileChange,
+    fs,
+    toml::{Document, Value},
+};
+
+/// The profile definitions.
+const PROFILES: &str = include_str!("../../lint-profiles.toml");
+
+/// The path of the profile definitions, for error messages.
+const PROFILES_PATH: &str = "xtask/lint-profiles.toml";
+
+/// The profile assumed for crates that don't record one, since it's the one
+/// the template ships with.
+pub const DEFAULT: &str = "strict-library";
+
+/// The text before the name in the comment recording a crate's profile.
+const MARKER_PREFIX: &str = "# Lint profile: ";
+
+/// The title of the Cargo.toml banner above the lint tables.
+const BANNER: &str = "LINTS";
+
+/// A lint policy profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The profile's name, like `strict-library`.
+    pub name: String,
+    /// The profile's version, bumped whenever its lints change.
+    pub version: u32,
+    /// What the profile is for.
+    pub description: String,
+    /// The body of the profile's `[lints.rust]` table.
+    pub rust: String,
+    /// The body of the profile's `[lints.clippy]` table.
+    pub clippy: String,
+}
+
+impl Profile {
+    /// Lists every profile, in the order they're defined.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the profile definitions are malformed.
+    pub fn all() -> Result<Vec<Self>> {
+        let definitions = Document::parse(Path::new(PROFILES_PATH), PROFILES)?;
+        let names = PROFILES.lines().filter_map(|line| {
+            let name = line.strip_prefix('[')?.strip_suffix(']')?;
+            (!name.contains('.')).then_some(name)
+        });
+
+        names
+            .map(|name| {
+                let malformed =
+                    |message: &str| Error::malformed(PROFILES_PATH, format!("`{name}`: {message}"));
+                let version = definitions
+                    .get(name, "version")
+                    .and_then(|entry| match entry.value {
+                        Value::Integer(version) => u32::try_from(version).ok(),
+                        _ => None,
+                    })
+                    .ok_or_else(|| malformed("missing or invalid `version`"))?;
+                let description = definitions
+                    .get_str(name, "description")
+                    .ok_or_else(|| malformed("missing or invalid `description`"))?;
+                let body = |tool: &str| {
+                    table_body(PROFILES, &format!("{name}.{tool}"))
+                        .ok_or_else(|| malformed(&format!("missing `[{name}.{tool}]` table")))
+                };
+
+                Ok(Self {
+                    name: name.to_owned(),
+                    version,
+                    description,
+                    rust: body("rust")?,
+                    clippy: body("clippy")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Looks up the profile named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such profile, or the profile
+    /// definitions are malformed.
+    pub fn find(name: &str) -> Result<Self> {
+        let profiles = Self::all()?;
+        let names = profiles
+            .iter()
+            .map(|profile| profile.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        profiles
+            .into_iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "unknown lint profile `{name}` (expected one of {names})"
+                ))
+            })
+    }
+
+    /// The body of the profile's table for `tool`.
+    pub fn body(&self, tool: Tool) -> &str {
+        match tool {
+            Tool::Rust => &self.rust,
+            Tool::Clippy => &self.clippy,
+        }
+    }
+
+    /// The entries of the profile's table for `tool`.
+    fn entries(&self, tool: Tool) -> Result<Vec<Entry>> {
+        let path = Path::new(PROFILES_PATH);
+        let table = format!("[{}]\n{}", tool.table(), self.body(tool));
+        entries(path, &Document::parse(path, &table)?, tool)
+    }
+
+    /// The comment recording that a crate's lint tables use this profile.
+    fn marker(&self) -> String {
+        format!("{MARKER_PREFIX}{} (version {})", self.name, self.version)
+    }
+
+    /// Renders the profile as the lint section of a Cargo.toml, banner
+    /// included.
+    pub fn render(&self) -> String {
+        let mut output = banner(BANNER);
+        output.push('\n');
+        output.push_str(&self.marker());
+        output.push('\n');
+        for (index, tool) in Tool::ALL.into_iter().enumerate() {
+            output.push_str(if index == 0 { "\n" } else { "\n\n\n\n" });
+            output.push_str(NOTE_PREFIX);
+            output.push_str("1.X.Y\n[");
+            output.push_str(tool.table());
+            output.push_str("]\n\n");
+            output.push_str(self.body(tool));
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// Returns the body of the table named `table`: its lines up to the comments
+/// and blank lines before the next table, without leading or trailing blank
+/// lines.
+fn table_body(contents: &str, table: &str) -> Option<String> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let (start, end) = body_range(&lines, table)?;
+    Some(lines[start..end].join("\n"))
+}
+
+/// Finds the (0-based, half-open) range of lines of the body of `table`,
+/// trimmed of blank lines.
+fn body_range(lines: &[&str], table: &str) -> Option<(usize, usize)> {
+    let header = format!("[{table}]");
+    let start = lines.iter().position(|line| line.trim() == header)? + 1;
+    let next = lines[start..]
+        .iter()
+        .position(|line| line.starts_with('[') || line.starts_with("# # #"))
+        .map_or(lines.len(), |offset| start + offset);
+
+    // The comments right above the next table belong to it
+    let mut end = next;
+    if lines.get(next).is_some_and(|line| line.starts_with('[')) {
+        while end > start && lines[end - 1].starts_with('#') {
+            end -= 1;
+        }
+    }
+    while end > start && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+    let start = start
+        + lines[start..end]
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+
+    Some((start, end))
+}
+
+/// A difference between a crate's lint table and its profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Override {
+    /// The crate sets a lint differently from the profile, or sets a lint the
+    /// profile doesn't.
+    Set {
+        /// The table.
+        tool: Tool,
+        /// The crate's entry.
+        entry: Entry,
+        /// The entry's line, as written in Cargo.toml.
+        text: String,
+    },
+    /// The crate doesn't set a lint the profile does.
+    Removed {
+        /// The table.
+        tool: Tool,
+        /// The profile's entry.
+        entry: Entry,
+    },
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Set { tool, entry, text } => write!(
+                f,
+                "Cargo.toml:{}: [{}] {}",
+                entry.line,
+                tool.table(),
+                text.trim()
+            ),
+            Self::Removed { tool, entry } => write!(
+                f,
+                "[{}] {} is not set (the profile sets it to \"{}\")",
+                tool.table(),
+                entry.name,
+                entry.level
+            ),
+        }
+    }
+}
+
+/// The lint profile a crate uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    /// The profile.
+    pub profile: Profile,
+    /// The version of the profile the crate's tables were generated from, or
+    /// [`None`] if the crate doesn't record its profile (so [`DEFAULT`] is
+    /// assumed).
+    pub recorded_version: Option<u32>,
+    /// The crate's local overrides of the profile.
+    pub overrides: Vec<Override>,
+}
+
+/// Determines the lint profile of the crate at `root`, and its local
+/// overrides.
+///
+/// # Errors
+///
+/// Returns an error if Cargo.toml cannot be read or parsed, or it records an
+/// unknown profile.
+pub fn status(root: &Path) -> Result<Status> {
+    let path = root.join("Cargo.toml");
+    status_of(&path, &fs::read(&path)?)
+}
+
+/// Like [`status`], but for the given contents of Cargo.toml. `path` is only
+/// used for error messages.
+///
+/// # Errors
+///
+/// Returns an error if `manifest` cannot be parsed, or it records an unknown
+/// profile.
+pub fn status_of(path: &Path, manifest: &str) -> Result<Status> {
+    let document = Document::parse(path, manifest)?;
+
+    let (profile, recorded_version) = match marker(manifest) {
+        Some((_, name, version)) => (Profile::find(name)?, version),
+        None => (Profile::find(DEFAULT)?, None),
+    };
+
+    let lines: Vec<&str> = manifest.lines().collect();
+    let mut overrides = Vec::new();
+    for tool in Tool::ALL {
+        let local = entries(path, &document, tool)?;
+        let defined = profile.entries(tool)?;
+        let same = |a: &Entry, b: &Entry| (&a.level, a.priority) == (&b.level, b.priority);
+
+        for entry in &local {
+            if !defined
+                .iter()
+                .any(|defined| defined.name == entry.name && same(defined, entry))
+            {
+                overrides.push(Override::Set {
+                    tool,
+                    entry: entry.clone(),
+                    text: lines[entry.line - 1].to_owned(),
+                });
+            }
+        }
+        for entry in defined {
+            if !local.iter().any(|local| local.name == entry.name) {
+                overrides.push(Override::Removed { tool, entry });
+            }
+        }
+    }
+
+    Ok(Status {
+        profile,
+        recorded_version,
+        overrides,
+    })
+}
+
+/// Regenerates the lint tables of the crate at `root` from the profile named
+/// `name`, keeping the crate's local overrides of its current profile.
+///
+/// Returns the crate's previous status along with the change.
+///
+/// # Errors
+///
+/// Returns an error if Cargo.toml cannot be read or parsed, has no lint
+/// tables, or a profile is unknown.
+pub fn switch(root: &Path, name: &str) -> Result<(Status, Option<FileChange>)> {
+    let path = root.join("Cargo.toml");
+    let original = fs::read(&path)?;
+    let (status, updated) = switch_contents(&path, &original, name)?;
+    Ok((status, FileChange::new("Cargo.toml", original, updated)))
+}
+
+/// Like [`switch`], but for the given contents of Cargo.toml, returning the
+/// updated contents. `path` is only used for error messages.
+///
+/// # Errors
+///
+/// Returns an error if `original` cannot be parsed or has no lint tables, or a
+/// profile is unknown.
+pub fn switch_contents(path: &Path, original: &str, name: &str) -> Result<(Status, String)> {
+    let status = status_of(path, original)?;
+    let profile = Profile::find(name)?;
+
+    let mut lines: Vec<String> = original.lines().map(str::to_owned).collect();
+    // Replace the later table first, so the earlier one's lines don't move
+    for tool in Tool::ALL.into_iter().rev() {
+        let borrowed: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let (start, end) = body_range(&borrowed, tool.table())
+            .ok_or_else(|| Error::malformed(path, format!("no `[{}]` table", tool.table())))?;
+        let body = apply_overrides(profile.body(tool), tool, &status.overrides);
+        lines.splice(start..end, body.lines().map(str::to_owned));
+    }
+
+    // Record the new profile above the first table, replacing the old record
+    let borrowed: Vec<&str> = lines.iter().map(String::as_str).collect();
+    if let Some((index, _, _)) = marker(&borrowed.join("\n")) {
+        lines[index] = profile.marker();
+    } else {
+        let header = format!("[{}]", Tool::Rust.table());
+        let mut index = borrowed
+            .iter()
+            .position(|line| line.trim() == header)
+            .unwrap_or_default();
+        while index > 0
+            && borrowed[index - 1].starts_with('#')
+            && !borrowed[index - 1].starts_with("# # #")
+        {
+            index -= 1;
+        }
+        lines.splice(index..index, [profile.marker(), String::new()]);
+    }
+
+    let mut updated = lines.join("\n");
+    if original.ends_with('\n') {
+        updated.push('\n');
+    }
+    Ok((status, updated))
+}
+
+/// Applies the `overrides` of `tool`'s table to a profile's `body`: lints the
+/// crate sets differently replace the profile's lines, lints the crate doesn't
+/// set are dropped, and lints the profile doesn't mention are added at the end.
+fn apply_overrides(body: &str, tool: Tool, overrides: &[Override]) -> String {
+    let mut remaining: Vec<(&Entry, &str)> = overrides
+        .iter()
+        .filter_map(|o| match o {
+            Override::Set {
+                tool: table,
+                entry,
+                text,
+            } if *table == tool => Some((entry, text.as_str())),
+            _ => None,
+        })
+        .collect();
+    let removed = |key: &str| {
+        overrides.iter().any(|o| {
+            matches!(o, Override::Removed { tool: table, entry } if *table == tool && entry.name == key)
+        })
+    };
+
+    let mut output: Vec<String> = Vec::new();
+    for line in body.lines() {
+        let Some(key) = entry_key(line) else {
+            output.push(line.to_owned());
+            continue;
+        };
+        if removed(&key) {
+            continue;
+        }
+        match remaining.iter().position(|(entry, _)| entry.name == key) {
+            Some(index) => output.push(remaining.remove(index).1.to_owned()),
+            None => output.push(line.to_owned()),
+        }
+    }
+
+    if !remaining.is_empty() {
+        output.push(String::new());
+        output.push("# Local overrides".to_owned());
+        output.extend(remaining.into_iter().map(|(_, text)| text.to_owned()));
+    }
+
+    output.join("\n")
+}
+
+/// Returns the key of a `key = value` line, if it is one.
+fn entry_key(line: &str) -> Option<String> {
+    let document = Document::parse(Path::new(PROFILES_PATH), line).ok()?;
+    document
+        .entries("")
+        .first()
+        .map(|entry| entry.key.to_owned())
+}
+
+/// The width of the `# # # ...` banners separating Cargo.toml's sections.
+const BANNER_WIDTH: usize = 37;
+
+/// Formats a section banner in the style of the template's Cargo.toml, like
+///
+/// ```text
+/// # # # # # # # # # # # # # # # # # # # #
+/// #                                     #
+/// #                LINTS                #
+/// #                                     #
+/// # # # # # # # # # # # # # # # # # # # #
+/// ```
+fn banner(title: &str) -> String {
+    let border = format!("#{}\n", " #".repeat(BANNER_WIDTH / 2 + 1));
+    let blank = format!("#{}#\n", " ".repeat(BANNER_WIDTH));
+    let padding = BANNER_WIDTH.saturating_sub(title.len());
+    let left = padding / 2 + padding % 2;
+    let title = format!(
+        "#{}{title}{}#\n",
+        " ".repeat(left),
+        " ".repeat(padding - left)
+    );
+    [border.as_str(), &blank, &title, &blank, &border].concat()
+}
+
+/// Finds the comment recording a crate's lint profile, returning its (0-based)
+/// line, the profile's name and its version.
+pub fn marker(manifest: &str) -> Option<(usize, &str, Option<u32>)> {
+    manifest.lines().enumerate().find_map(|(index, line)| {
+        let rest = line.trim().strip_prefix(MARKER_PREFIX)?;
+        let (name, version) = match rest.split_once(" (version ") {
+            Some((name, version)) => (name, version.strip_suffix(')')?.parse().ok()),
+            None => (rest, None),
+        };
+        Some((index, name.trim(), version))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Scratch;
+
+    const TEMPLATE: &str = include_str!("../../../Cargo.toml");
+
+    /// The overridden lints and their levels, wherever they are.
+    fn lints(status: &Status) -> Vec<(Tool, String, String)> {
+        status
+            .overrides
+            .iter()
+            .map(|o| match o {
+                Override::Set { tool, entry, .. } | Override::Removed { tool, entry } => {
+                    (*tool, entry.name.clone(), entry.level.clone())
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn profiles_are_well_formed() {
+        let profiles = Profile::all().unwrap();
+        assert!(profiles.iter().any(|profile| profile.name == DEFAULT));
+        for profile in &profiles {
+            for tool in Tool::ALL {
+                assert!(
+                    !profile.entries(tool).unwrap().is_empty(),
+                    "{}",
+                    profile.name
+                );
+            }
+            assert_eq!(Profile::find(&profile.name).unwrap(), *profile);
+        }
+        assert!(Profile::find("lenient").is_err());
+    }
+
+    #[test]
+    fn markers() {
+        assert_eq!(
+            marker("a = 1\n# Lint profile: application (version 2)\n"),
+            Some((1, "application", Some(2)))
+        );
+        assert_eq!(
+            marker("# Lint profile: no_std\n"),
+            Some((0, "no_std", None))
+        );
+        assert_eq!(marker("[lints.rust]\n"), None);
+    }
+
+    #[test]
+    fn the_template_has_the_default_profile() {
+        let status = status_of(Path::new("Cargo.toml"), TEMPLATE).unwrap();
+        assert_eq!(status.profile.name, DEFAULT);
+        assert_eq!(status.recorded_version, None);
+        assert_eq!(status.overrides, []);
+    }
+
+    #[test]
+    fn switching_keeps_local_overrides() {
+        let edited = TEMPLATE
+            .replace("unsafe_code = \"forbid\"", "unsafe_code = \"deny\"")
+            .replace(
+                "missing_docs = \"warn\" # all public items should be documented\n",
+                "",
+            );
+        let scratch = Scratch::new(&[("Cargo.toml", &edited)]);
+        let (before, change) = switch(scratch.path(), "application").unwrap();
+        let overrides: Vec<String> = before.overrides.iter().map(ToString::to_string).collect();
+        assert_eq!(overrides.len(), 2, "{overrides:?}");
+        assert!(
+            overrides[0]
+                .ends_with("[lints.rust] unsafe_code = \"deny\" # do not permit unsafe code")
+        );
+        assert_eq!(
+            overrides[1],
+            "[lints.rust] missing_docs is not set (the profile sets it to \"warn\")"
+        );
+
+        let switched = change.unwrap().updated;
+        let after = status_of(Path::new("Cargo.toml"), &switched).unwrap();
+        assert_eq!(after.profile.name, "application");
+        assert_eq!(after.recorded_version, Some(after.profile.version));
+        assert!(switched.contains(&after.profile.marker()));
+        let overrides: Vec<String> = after.overrides.iter().map(ToString::to_string).collect();
+        assert!(
+            overrides
+                .iter()
+                .any(|o| o.contains("unsafe_code = \"deny\""))
+        );
+
+        // Switching back keeps the changed level, but `missing_docs` comes
+        // back: the application profile doesn't set it, so nothing recorded
+        // its removal
+        let (_, restored) = switch_contents(Path::new("Cargo.toml"), &switched, DEFAULT).unwrap();
+        let status = status_of(Path::new("Cargo.toml"), &restored).unwrap();
+        assert_eq!(status.profile.name, DEFAULT);
+        assert_eq!(lints(&status), lints(&before)[..1]);
+    }
+}
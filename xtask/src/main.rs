@@ -0,0 +1,7 @@
+//! The `xtask` binary. See [`xtask::cli`] for the available commands.
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    xtask::cli::run(std::env::args().skip(1))
+}
@@ -0,0 +1,680 @@
+//! Finding the `TODO`, `ON_RELEASE` and `NOTE` markers left in the template.
+//!
+//! A marker is a comment whose text starts with a marker keyword, optionally
+//! followed by an owner in parentheses, and then a colon:
+//!
+//! ```text
+//! # TODO: update package name
+//! // TODO(ijchen): go through all Rust lints
+//! <!-- ON_RELEASE: the below link(s) should be updated -->
+//! ```
+//!
+//! Each supported [`Language`] has its own comment syntax, and strings are
+//! skipped so that a `#` or `//` inside a literal is not mistaken for a
+//! comment. In Markdown, lines of prose that start with a marker (like
+//! `TODO: basic description`) count too, since those are visible placeholders.
+//!
+//! A marker's message continues onto the following lines while they are
+//! comments of the same kind at the same indentation that read like prose
+//! (one space after the comment leader, and not ending in `:`), so that
+//! commented-out code directly below a marker is not swallowed into it. Nor
+//! are directives like `# @render ...`.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{Result, fs, json};
+
+/// The kind of a [`Marker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MarkerKind {
+    /// `TODO`: something to do when deriving a crate from the template.
+    Todo,
+    /// `ON_RELEASE`: something to do when publishing a release.
+    OnRelease,
+    /// `NOTE`: information for whoever edits the surrounding code.
+    Note,
+}
+
+impl MarkerKind {
+    /// Every marker kind.
+    pub const ALL: [Self; 3] = [Self::Todo, Self::OnRelease, Self::Note];
+
+    /// The keyword that introduces the marker, like `ON_RELEASE`.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Self::Todo => "TODO",
+            Self::OnRelease => "ON_RELEASE",
+            Self::Note => "NOTE",
+        }
+    }
+
+    /// Parses a marker keyword, like `ON_RELEASE`.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.keyword() == keyword)
+    }
+}
+
+impl fmt::Display for MarkerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.keyword())
+    }
+}
+
+/// A marker comment found in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// The kind of marker.
+    pub kind: MarkerKind,
+    /// The owner named in parentheses after the keyword, if any.
+    pub owner: Option<String>,
+    /// The file containing the marker, relative to the scanned root.
+    pub file: PathBuf,
+    /// The (1-based) line the marker starts on.
+    pub line: usize,
+    /// The (1-based) last line of the marker's message.
+    pub end_line: usize,
+    /// Whether the marker trails code on its line, rather than being on a
+    /// line of its own.
+    pub inline: bool,
+    /// The text following the colon, with continuation lines joined by
+    /// spaces.
+    pub message: String,
+}
+
+impl Marker {
+    /// Converts the marker to JSON.
+    pub fn to_json(&self) -> json::Value {
+        json::Value::object([
+            ("kind", self.kind.keyword().into()),
+            ("owner", self.owner.clone().into()),
+            ("file", self.file.display().to_string().into()),
+            ("line", self.line.into()),
+            ("end_line", self.end_line.into()),
+            ("inline", self.inline.into()),
+            ("message", self.message.clone().into()),
+        ])
+    }
+}
+
+impl fmt::Display for Marker {
+    /// Formats the marker as `file:line: KIND(owner): message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.kind)?;
+        if let Some(owner) = &self.owner {
+            write!(f, "({owner})")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A file format whose comments can be scanned for markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    /// TOML, with `#` comments.
+    Toml,
+    /// Rust, with `//` (including doc comments) and `/* */` comments.
+    Rust,
+    /// Markdown, with `<!-- -->` comments and prose markers.
+    Markdown,
+    /// YAML, with `#` comments.
+    Yaml,
+    /// Python, with `#` comments.
+    Python,
+}
+
+impl Language {
+    /// Determines the language of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Self::Toml),
+            "rs" => Some(Self::Rust),
+            "md" => Some(Self::Markdown),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "py" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    /// The delimiters of a comment taking up a whole line, like `#` and an
+    /// empty closing delimiter, or `<!--` and `-->`.
+    pub fn line_comment(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Toml | Self::Yaml | Self::Python => ("#", ""),
+            Self::Rust => ("//", ""),
+            Self::Markdown => ("<!--", "-->"),
+        }
+    }
+}
+
+/// Scans every supported file under `root` for markers.
+///
+/// # Errors
+///
+/// Returns an error if the directory tree or a file cannot be read.
+pub fn scan(root: &Path) -> Result<Vec<Marker>> {
+    let mut markers = Vec::new();
+    for file in fs::walk(root)? {
+        if let Some(language) = Language::from_path(&file) {
+            let contents = fs::read(&root.join(&file))?;
+            markers.extend(scan_file(&file, language, &contents));
+        }
+    }
+
+    Ok(markers)
+}
+
+/// Scans the contents of a single file for markers.
+///
+/// `file` is only used to fill in [`Marker::file`].
+pub fn scan_file(file: &Path, language: Language, contents: &str) -> Vec<Marker> {
+    let comments = comments(language, contents);
+    let mut markers = Vec::new();
+    let mut index = 0;
+
+    while index < comments.len() {
+        let comment = &comments[index];
+        index += 1;
+        let Some((kind, owner, message)) = parse_marker(comment.text) else {
+            continue;
+        };
+
+        let mut marker = Marker {
+            kind,
+            owner: owner.map(str::to_owned),
+            file: file.to_owned(),
+            line: comment.line,
+            end_line: comment.line,
+            inline: comment.inline,
+            message: message.to_owned(),
+        };
+        while let Some(next) = comments.get(index) {
+            if !continues(&comments[index - 1], next) {
+                break;
+            }
+            marker.message.push(' ');
+            marker.message.push_str(next.text.trim());
+            marker.end_line = next.line;
+            index += 1;
+        }
+        markers.push(marker);
+    }
+
+    markers
+}
+
+/// Parses comment text as a marker, returning its kind, owner and message.
+fn parse_marker(text: &str) -> Option<(MarkerKind, Option<&str>, &str)> {
+    let text = text.trim_start();
+    let keyword_end = text
+        .find(|c: char| !(c.is_ascii_uppercase() || c == '_'))
+        .unwrap_or(text.len());
+    let kind = MarkerKind::from_keyword(&text[..keyword_end])?;
+
+    let rest = &text[keyword_end..];
+    let (owner, rest) = match rest.strip_prefix('(') {
+        Some(rest) => {
+            let (owner, rest) = rest.split_once(')')?;
+            (Some(owner.trim()), rest)
+        }
+        None => (None, rest),
+    };
+
+    Some((kind, owner, rest.strip_prefix(':')?.trim()))
+}
+
+/// Whether `next` continues the message of the marker ending with `previous`.
+fn continues(previous: &Comment<'_>, next: &Comment<'_>) -> bool {
+    if next.line != previous.line + 1 || next.inline || previous.inline {
+        return false;
+    }
+    // Neither is a directive for the tooling, like `@render` or `@if`
+    if next.text.trim().is_empty()
+        || parse_marker(next.text).is_some()
+        || next.text.trim_start().starts_with('@')
+    {
+        return false;
+    }
+
+    match (previous.block, next.block) {
+        (Some(previous), Some(next)) => previous == next,
+        (None, None) => {
+            let prose = next
+                .text
+                .strip_prefix(' ')
+                .is_some_and(|text| !text.starts_with(char::is_whitespace));
+            !previous.leader.is_empty()
+                && previous.leader == next.leader
+                && previous.indent == next.indent
+                && prose
+                && !next.text.trim_end().ends_with(':')
+        }
+        _ => false,
+    }
+}
+
+/// One line's worth of comment text.
+#[derive(Debug)]
+struct Comment<'a> {
+    /// The (1-based) line number.
+    line: usize,
+    /// Whether code precedes the comment on this line.
+    inline: bool,
+    /// The column the comment starts at.
+    indent: usize,
+    /// The comment leader, like `#` or `///`. Empty for block comments and
+    /// Markdown prose.
+    leader: &'a str,
+    /// The text after the leader (or delimiter), up to the end of the line or
+    /// the end of the comment.
+    text: &'a str,
+    /// For block comments spanning several lines, an identifier shared by all
+    /// of their lines.
+    block: Option<usize>,
+}
+
+/// Extracts the comments of a file, one entry per line of comment text.
+fn comments(language: Language, contents: &str) -> Vec<Comment<'_>> {
+    let hash = Syntax {
+        leader: "#",
+        block: None,
+        string_end: Some(quoted_string_end),
+        leader_needs_space: false,
+    };
+
+    match language {
+        Language::Markdown => markdown_comments(contents),
+        Language::Rust => code_comments(
+            contents,
+            &Syntax {
+                leader: "//",
+                block: Some(("/*", "*/")),
+                string_end: Some(rust_string_end),
+                leader_needs_space: false,
+            },
+        ),
+        Language::Toml | Language::Python => code_comments(contents, &hash),
+        // YAML quotes only start a string at the beginning of a value, but a
+        // `#` glued to preceding text (like a URL fragment) is never a comment
+        Language::Yaml => code_comments(
+            contents,
+            &Syntax {
+                string_end: None,
+                leader_needs_space: true,
+                ..hash
+            },
+        ),
+    }
+}
+
+/// The comment syntax of a programming language.
+#[derive(Debug)]
+struct Syntax {
+    /// The leader of line comments.
+    leader: &'static str,
+    /// The delimiters of block comments, if the language has them.
+    block: Option<(&'static str, &'static str)>,
+    /// Given the text starting at a possible string literal, returns the
+    /// length of the literal (which may span lines), or [`None`] if it isn't
+    /// one.
+    string_end: Option<fn(&str) -> Option<usize>>,
+    /// Whether a line comment must be preceded by whitespace (or start the
+    /// line).
+    leader_needs_space: bool,
+}
+
+/// Scans source code for comments, skipping string literals.
+fn code_comments<'a>(contents: &'a str, syntax: &Syntax) -> Vec<Comment<'a>> {
+    let mut comments = Vec::new();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut position = 0;
+    let mut blocks = 0;
+
+    while position < contents.len() {
+        let rest = &contents[position..];
+        let preceded_by_code = !contents[line_start..position].trim().is_empty();
+
+        if rest.starts_with('\n') {
+            line += 1;
+            position += 1;
+            line_start = position;
+        } else if rest.starts_with(syntax.leader)
+            && !(syntax.leader_needs_space
+                && contents[..position].ends_with(|c: char| !c.is_whitespace()))
+        {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            let comment = rest[..end].trim_end_matches('\r');
+            let leader_len = comment
+                .find(|c: char| !(syntax.leader.contains(c) || c == '!'))
+                .unwrap_or(comment.len());
+            comments.push(Comment {
+                line,
+                inline: preceded_by_code,
+                indent: position - line_start,
+                leader: &comment[..leader_len],
+                text: &comment[leader_len..],
+                block: None,
+            });
+            position += end;
+        } else if let Some((open, close)) = syntax.block.filter(|(open, _)| rest.starts_with(open))
+        {
+            let body = &rest[open.len()..];
+            let body = &body[..body.find(close).unwrap_or(body.len())];
+            blocks += 1;
+            for (offset, text) in body.split('\n').enumerate() {
+                comments.push(Comment {
+                    line: line + offset,
+                    inline: offset == 0 && preceded_by_code,
+                    indent: if offset == 0 {
+                        position - line_start
+                    } else {
+                        0
+                    },
+                    leader: "",
+                    text: text.trim_end_matches('\r').trim_start_matches('*'),
+                    block: Some(blocks),
+                });
+            }
+            let length = (open.len() + body.len() + close.len()).min(rest.len());
+            advance(contents, &mut position, &mut line, &mut line_start, length);
+        } else if let Some(length) = syntax.string_end.and_then(|string_end| string_end(rest)) {
+            advance(contents, &mut position, &mut line, &mut line_start, length);
+        } else {
+            position += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    comments
+}
+
+/// Moves `position` forward by `length` bytes, keeping track of lines.
+fn advance(
+    contents: &str,
+    position: &mut usize,
+    line: &mut usize,
+    line_start: &mut usize,
+    length: usize,
+) {
+    let skipped = &contents[*position..*position + length];
+    if let Some(last_newline) = skipped.rfind('\n') {
+        *line += skipped.matches('\n').count();
+        *line_start = *position + last_newline + 1;
+    }
+    *position += length;
+}
+
+/// Recognizes Rust string, raw string and character literals.
+fn rust_string_end(text: &str) -> Option<usize> {
+    // Raw strings: r"..", r#".."#, br#".."#, ...
+    let raw = text.strip_prefix("br").or_else(|| text.strip_prefix('r'));
+    if let Some(after_r) = raw {
+        let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+        if after_r[hashes..].starts_with('"') {
+            let terminator = format!("\"{}", "#".repeat(hashes));
+            let body_start = text.len() - after_r.len() + hashes + 1;
+            return Some(
+                text[body_start..]
+                    .find(&terminator)
+                    .map_or(text.len(), |end| body_start + end + terminator.len()),
+            );
+        }
+    }
+
+    if text.starts_with('"') {
+        return Some(escaped_string_end(text, '"', true));
+    }
+
+    // Character literals, as opposed to lifetimes
+    let mut chars = text.strip_prefix('\'')?.char_indices();
+    match chars.next()? {
+        (_, '\\') => Some(escaped_string_end(text, '\'', false)),
+        (_, c) => {
+            let after = 1 + c.len_utf8();
+            text[after..].starts_with('\'').then_some(after + 1)
+        }
+    }
+}
+
+/// Recognizes TOML and Python strings, including triple-quoted ones.
+fn quoted_string_end(text: &str) -> Option<usize> {
+    for triple in ["\"\"\"", "'''"] {
+        if let Some(body) = text.strip_prefix(triple) {
+            return Some(body.find(triple).map_or(text.len(), |end| end + 6));
+        }
+    }
+
+    let quote = text.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    Some(escaped_string_end(text, quote, false))
+}
+
+/// Returns the length of a string starting with `quote` in which `\` escapes
+/// the next character. Unless the string is `multiline` (like Rust's, which
+/// run to the closing quote), unterminated strings run to the end of the
+/// line.
+fn escaped_string_end(text: &str, quote: char, multiline: bool) -> usize {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' if !multiline => return index,
+            c if c == quote => return index + 1,
+            _ => {}
+        }
+    }
+
+    text.len()
+}
+
+/// Scans Markdown for `<!-- -->` comments and lines of prose starting with a
+/// marker, skipping fenced code blocks.
+fn markdown_comments(contents: &str) -> Vec<Comment<'_>> {
+    let mut comments = Vec::new();
+    let mut in_fence = false;
+    let mut block = None;
+    let mut blocks = 0;
+
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+
+        if let Some(id) = block {
+            let (text, closed) = match trimmed.split_once("-->") {
+                Some((text, _)) => (text, true),
+                None => (trimmed, false),
+            };
+            comments.push(Comment {
+                line: number,
+                inline: false,
+                indent: 0,
+                leader: "",
+                text,
+                block: Some(id),
+            });
+            if closed {
+                block = None;
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        if let Some(start) = line.find("<!--") {
+            let body = &line[start + 4..];
+            let (text, closed) = match body.split_once("-->") {
+                Some((text, _)) => (text, true),
+                None => (body, false),
+            };
+            blocks += 1;
+            comments.push(Comment {
+                line: number,
+                inline: !line[..start].trim().is_empty(),
+                indent: start,
+                leader: "",
+                text,
+                block: Some(blocks),
+            });
+            if !closed {
+                block = Some(blocks);
+            }
+        } else {
+            let text = trimmed.trim_start_matches('#');
+            if parse_marker(text).is_some() {
+                comments.push(Comment {
+                    line: number,
+                    inline: false,
+                    indent: 0,
+                    leader: "",
+                    text,
+                    block: None,
+                });
+            }
+        }
+    }
+
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The markers of `contents`, as `(kind, owner, line, end_line, inline,
+    /// message)`.
+    fn scan(
+        language: Language,
+        contents: &str,
+    ) -> Vec<(MarkerKind, Option<String>, usize, usize, bool, String)> {
+        scan_file(Path::new("file"), language, contents)
+            .into_iter()
+            .map(|marker| {
+                (
+                    marker.kind,
+                    marker.owner,
+                    marker.line,
+                    marker.end_line,
+                    marker.inline,
+                    marker.message,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rust_markers() {
+        let contents = "\
+// TODO(ijchen): go through all Rust lints
+// and update them
+//     let commented = \"out code\";
+let url = \"// TODO: not a comment\"; // NOTE: inline
+/* ON_RELEASE: bump
+   the version */
+// TODONT: not a marker
+";
+        assert_eq!(
+            scan(Language::Rust, contents),
+            [
+                (
+                    MarkerKind::Todo,
+                    Some("ijchen".to_owned()),
+                    1,
+                    2,
+                    false,
+                    "go through all Rust lints and update them".to_owned()
+                ),
+                (MarkerKind::Note, None, 4, 4, true, "inline".to_owned()),
+                (
+                    MarkerKind::OnRelease,
+                    None,
+                    5,
+                    6,
+                    false,
+                    "bump the version".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_rust_strings() {
+        let contents = "let s = \"line one\n// TODO: inside the string\n\\\"// NOTE: still inside\";\n// NOTE: outside\n";
+        assert_eq!(
+            scan(Language::Rust, contents),
+            [(MarkerKind::Note, None, 4, 4, false, "outside".to_owned())]
+        );
+        // Unlike TOML's and Python's, which end at the line
+        assert_eq!(scan(Language::Toml, "a = \"open\n# TODO: after\n").len(), 1);
+    }
+
+    #[test]
+    fn other_languages() {
+        assert_eq!(
+            scan(
+                Language::Toml,
+                "name = \"a # TODO: no\" # TODO: update package name\n"
+            ),
+            [(
+                MarkerKind::Todo,
+                None,
+                1,
+                1,
+                true,
+                "update package name".to_owned()
+            )]
+        );
+        assert_eq!(
+            scan(
+                Language::Yaml,
+                "url: https://x.dev/#TODO: no\n# NOTE: yes\n"
+            ),
+            [(MarkerKind::Note, None, 2, 2, false, "yes".to_owned())]
+        );
+        assert_eq!(
+            scan(
+                Language::Markdown,
+                "# Title\n\nTODO: basic description\n\n<!-- ON_RELEASE: update the link -->\n"
+            ),
+            [
+                (
+                    MarkerKind::Todo,
+                    None,
+                    3,
+                    3,
+                    false,
+                    "basic description".to_owned()
+                ),
+                (
+                    MarkerKind::OnRelease,
+                    None,
+                    5,
+                    5,
+                    false,
+                    "update the link".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn languages_by_extension() {
+        for (path, language) in [
+            ("Cargo.toml", Some(Language::Toml)),
+            ("src/lib.rs", Some(Language::Rust)),
+            ("README.md", Some(Language::Markdown)),
+            (".github/workflows/ci.yaml", Some(Language::Yaml)),
+            ("scripts/ci.py", Some(Language::Python)),
+            ("LICENSE-MIT", None),
+        ] {
+            assert_eq!(Language::from_path(Path::new(path)), language, "{path}");
+        }
+    }
+}
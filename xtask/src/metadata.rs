@@ -0,0 +1,528 @@
+//! Validating Cargo.toml's `keywords` and `categories` offline.
+//!
+//! crates.io rejects a publish whose keywords or categories it doesn't
+//! accept, which is a poor time to find out. Keywords follow fixed rules, and
+//! categories are checked against a snapshot of crates.io's category slugs,
+//! kept in `xtask/category-slugs.toml` and embedded in the tooling, so no
+//! network access is needed. A category that isn't in the snapshot comes with
+//! suggestions of the slugs it was most likely meant to be.
+//!
+//! The snapshot is refreshed with [`Categories::from_api`], from a download of
+//! crates.io's list.
+
+use std::{
+    fmt::{self, Write as _},
+    path::Path,
+};
+
+use crate::{
+    Error, Result, json, placeholder,
+    release::Diagnostic,
+    toml::{Document, Value},
+};
+
+/// The embedded snapshot of crates.io's category slugs.
+const SNAPSHOT: &str = include_str!("../category-slugs.toml");
+
+/// The path of the snapshot, relative to the crate root.
+pub const SNAPSHOT_PATH: &str = "xtask/category-slugs.toml";
+
+/// The comment at the top of the snapshot.
+const SNAPSHOT_HEADER: &str = "\
+# A snapshot of the category slugs crates.io accepts in Cargo.toml's
+# `categories`, used to check them offline (see `cargo xtask metadata`).
+#
+# To refresh it, download https://crates.io/api/v1/category_slugs and run
+# `cargo xtask metadata update-categories FILE` on the download, which
+# rewrites this file.
+";
+
+/// The most keywords, and the most categories, crates.io accepts.
+pub const MAX_ITEMS: usize = 5;
+
+/// The longest keyword crates.io accepts, in characters.
+pub const MAX_KEYWORD_LENGTH: usize = 20;
+
+/// The most suggestions given for an unknown category.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The shortest unknown category compared with the start of known slugs, as
+/// shorter ones would be close to too many of them.
+const MIN_PREFIX_LENGTH: usize = 4;
+
+/// A snapshot of crates.io's category slugs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Categories {
+    /// The date the snapshot was taken, as `YYYY-MM-DD`.
+    pub updated: String,
+    /// Every slug, sorted.
+    pub slugs: Vec<String>,
+}
+
+impl Categories {
+    /// The snapshot embedded in the tooling.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot is malformed.
+    pub fn snapshot() -> Result<Self> {
+        Self::parse(Path::new(SNAPSHOT_PATH), SNAPSHOT)
+    }
+
+    /// Parses a snapshot file's `contents`. `path` is only used for error
+    /// messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `contents` isn't valid TOML, or lacks the
+    /// `updated` date or the `slugs` array of strings.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let document = Document::parse(path, contents)?;
+        let updated = document
+            .get_str("", "updated")
+            .ok_or_else(|| Error::malformed(path, "missing or invalid `updated`"))?;
+        let mut slugs: Vec<String> = document
+            .get("", "slugs")
+            .and_then(|entry| {
+                entry
+                    .value
+                    .as_array()?
+                    .iter()
+                    .map(|slug| slug.as_str().map(str::to_owned))
+                    .collect()
+            })
+            .ok_or_else(|| Error::malformed(path, "missing or invalid `slugs`"))?;
+        slugs.sort();
+        Ok(Self { updated, slugs })
+    }
+
+    /// Builds a snapshot, dated today, from the response of crates.io's
+    /// `/api/v1/category_slugs` endpoint. `path` is only used for error
+    /// messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `response` doesn't have a `category_slugs` array
+    /// of objects with a `slug`.
+    pub fn from_api(path: &Path, response: &json::Value) -> Result<Self> {
+        let invalid = || {
+            Error::malformed(
+                path,
+                "expected a `category_slugs` array of objects with a `slug`, as returned by \
+                 https://crates.io/api/v1/category_slugs",
+            )
+        };
+        let Some(json::Value::Array(categories)) = response.get("category_slugs") else {
+            return Err(invalid());
+        };
+        let mut slugs = categories
+            .iter()
+            .map(|category| match category.get("slug") {
+                Some(json::Value::String(slug)) => Some(slug.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        if slugs.is_empty() {
+            return Err(invalid());
+        }
+        slugs.sort();
+        slugs.dedup();
+
+        let (year, month, day) = placeholder::today();
+        Ok(Self {
+            updated: format!("{year:04}-{month:02}-{day:02}"),
+            slugs,
+        })
+    }
+
+    /// Whether `slug` is a known category.
+    pub fn contains(&self, slug: &str) -> bool {
+        self.slugs
+            .binary_search_by(|known| known.as_str().cmp(slug))
+            .is_ok()
+    }
+
+    /// The known slugs closest to `slug`, best first.
+    ///
+    /// A slug is close if few edits turn `slug` into it (or into one of its
+    /// `::`-separated parts, so `http-server` suggests
+    /// `web-programming::http-server`) or into the start of one of them,
+    /// ignoring case and `_` versus `-`.
+    pub fn suggest(&self, slug: &str) -> Vec<&str> {
+        let slug = slug.to_ascii_lowercase().replace('_', "-");
+        let length = slug.chars().count();
+        let threshold = (length / 3).max(1);
+
+        let mut close: Vec<(usize, &str)> = self
+            .slugs
+            .iter()
+            .filter_map(|known| {
+                let distance = known
+                    .split("::")
+                    .chain([known.as_str()])
+                    .map(|part| {
+                        // Also compare with the start of longer parts, at a
+                        // cost of one edit, so `parser` suggests
+                        // `parser-implementations`
+                        let prefix = part
+                            .char_indices()
+                            .nth(length)
+                            .filter(|_| length >= MIN_PREFIX_LENGTH)
+                            .map_or(usize::MAX, |(end, _)| {
+                                edit_distance(&slug, &part[..end]) + 1
+                            });
+                        edit_distance(&slug, part).min(prefix)
+                    })
+                    .min()?;
+                (distance <= threshold).then_some((distance, known.as_str()))
+            })
+            .collect();
+        close.sort_unstable();
+        close
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, known)| known)
+            .collect()
+    }
+
+    /// Checks the `categories` of a crate, returning everything wrong with
+    /// them (an empty list if crates.io would accept them).
+    pub fn check(&self, categories: &[String]) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if categories.len() > MAX_ITEMS {
+            problems.push(Problem::TooMany("categories", categories.len()));
+        }
+        for (index, category) in categories.iter().enumerate() {
+            if categories[..index].contains(category) {
+                problems.push(Problem::Duplicate("categories", category.clone()));
+            } else if !self.contains(category) {
+                problems.push(Problem::UnknownCategory {
+                    slug: category.clone(),
+                    suggestions: self
+                        .suggest(category)
+                        .into_iter()
+                        .map(str::to_owned)
+                        .collect(),
+                });
+            }
+        }
+        problems
+    }
+
+    /// Formats the snapshot as the contents of [`SNAPSHOT_PATH`].
+    pub fn to_toml(&self) -> String {
+        let mut output = format!(
+            "{SNAPSHOT_HEADER}\nupdated = {}\nslugs = [\n",
+            Value::from(self.updated.as_str())
+        );
+        for slug in &self.slugs {
+            let _ = writeln!(output, "    {},", Value::from(slug.as_str()));
+        }
+        output.push_str("]\n");
+        output
+    }
+}
+
+/// Something crates.io would reject in the keywords or categories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// There are more than [`MAX_ITEMS`] of the field (`keywords` or
+    /// `categories`), whose length is given.
+    TooMany(&'static str, usize),
+    /// An item of the field is given more than once.
+    Duplicate(&'static str, String),
+    /// A keyword doesn't follow crates.io's rules, for the given reason.
+    InvalidKeyword(String, String),
+    /// A category isn't in the snapshot.
+    UnknownCategory {
+        /// The unknown slug.
+        slug: String,
+        /// The known slugs it was most likely meant to be, best first.
+        suggestions: Vec<String>,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooMany(field, count) => write!(
+                f,
+                "{count} {field} given, but crates.io accepts at most {MAX_ITEMS}"
+            ),
+            Self::Duplicate(field, item) => write!(f, "`{item}` is in `{field}` more than once"),
+            Self::InvalidKeyword(keyword, reason) => {
+                write!(f, "invalid keyword `{keyword}`: {reason}")
+            }
+            Self::UnknownCategory { slug, suggestions } => {
+                write!(f, "unknown category `{slug}`")?;
+                if !suggestions.is_empty() {
+                    let suggestions: Vec<String> = suggestions
+                        .iter()
+                        .map(|suggestion| format!("`{suggestion}`"))
+                        .collect();
+                    write!(f, " (did you mean {}?)", suggestions.join(" or "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Checks the `keywords` of a crate, returning everything wrong with them (an
+/// empty list if crates.io would accept them).
+///
+/// A keyword must be at most [`MAX_KEYWORD_LENGTH`] ASCII letters, digits,
+/// `_`, `-` and `+`, starting with a letter or digit. crates.io ignores case,
+/// so `Parser` and `parser` are the same keyword.
+pub fn check_keywords(keywords: &[String]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if keywords.len() > MAX_ITEMS {
+        problems.push(Problem::TooMany("keywords", keywords.len()));
+    }
+    for (index, keyword) in keywords.iter().enumerate() {
+        let invalid = |reason: String| Problem::InvalidKeyword(keyword.clone(), reason);
+        let length = keyword.chars().count();
+        if keyword.is_empty() {
+            problems.push(invalid("must not be empty".to_owned()));
+        } else if !keyword.is_ascii() {
+            problems.push(invalid("must be ASCII".to_owned()));
+        } else if length > MAX_KEYWORD_LENGTH {
+            problems.push(invalid(format!(
+                "is {length} characters long (crates.io allows at most {MAX_KEYWORD_LENGTH})"
+            )));
+        } else if !keyword.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            problems.push(invalid("must start with a letter or digit".to_owned()));
+        } else if let Some(c) = keyword
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || "_-+".contains(c)))
+        {
+            problems.push(invalid(format!(
+                "contains `{c}` (only ASCII letters, digits, `_`, `-` and `+` are allowed)"
+            )));
+        } else if keywords[..index]
+            .iter()
+            .any(|other| other.eq_ignore_ascii_case(keyword))
+        {
+            problems.push(Problem::Duplicate("keywords", keyword.clone()));
+        }
+    }
+    problems
+}
+
+/// Checks the `keywords` and `categories` of Cargo.toml's `[package]`, its
+/// parsed `manifest`, against the embedded snapshot. Missing or empty lists
+/// are fine, as far as crates.io is concerned.
+///
+/// # Errors
+///
+/// Returns an error if the snapshot is malformed.
+pub fn check(manifest: &Document) -> Result<Vec<Diagnostic>> {
+    let categories = Categories::snapshot()?;
+    let mut diagnostics = Vec::new();
+    for key in ["keywords", "categories"] {
+        let Some(entry) = manifest.get("package", key) else {
+            continue;
+        };
+        let items: Option<Vec<String>> = entry.value.as_array().and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(str::to_owned))
+                .collect()
+        });
+        let problems = match items {
+            None => {
+                diagnostics.push(Diagnostic::new(
+                    "Cargo.toml",
+                    Some(entry.line),
+                    format!("`{key}` is not an array of strings"),
+                ));
+                continue;
+            }
+            Some(items) if key == "keywords" => check_keywords(&items),
+            Some(items) => categories.check(&items),
+        };
+        diagnostics.extend(
+            problems.into_iter().map(|problem| {
+                Diagnostic::new("Cargo.toml", Some(entry.line), problem.to_string())
+            }),
+        );
+    }
+    Ok(diagnostics)
+}
+
+/// Checks `keywords` and `categories` against the embedded snapshot.
+///
+/// # Errors
+///
+/// Returns an error listing every problem, or if the snapshot is malformed.
+pub fn validate(keywords: &[String], categories: &[String]) -> Result<()> {
+    let mut problems = check_keywords(keywords);
+    problems.extend(Categories::snapshot()?.check(categories));
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let problems: Vec<String> = problems.iter().map(Problem::to_string).collect();
+    Err(Error::InvalidInput(problems.join("; ")))
+}
+
+/// The number of single-character insertions, deletions and substitutions
+/// that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|&item| item.to_owned()).collect()
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let snapshot = Categories::snapshot().unwrap();
+        assert!(snapshot.contains("no-std"));
+        assert!(!snapshot.contains("no_std"));
+        assert_eq!(snapshot.to_toml(), SNAPSHOT);
+        assert_eq!(
+            Categories::parse(Path::new(SNAPSHOT_PATH), &snapshot.to_toml()).unwrap(),
+            snapshot
+        );
+        assert!(Categories::parse(Path::new(SNAPSHOT_PATH), "slugs = []\n").is_err());
+    }
+
+    #[test]
+    fn snapshots_from_the_api() {
+        let path = Path::new("category_slugs.json");
+        let response = json::Value::parse(
+            path,
+            r#"{"category_slugs": [{"slug": "b", "id": "b"}, {"slug": "a"}, {"slug": "a"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Categories::from_api(path, &response).unwrap().slugs,
+            ["a", "b"]
+        );
+        for invalid in [
+            r#"{"category_slugs": []}"#,
+            r#"{"category_slugs": [{}]}"#,
+            "[]",
+        ] {
+            let response = json::Value::parse(path, invalid).unwrap();
+            assert!(Categories::from_api(path, &response).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn suggestions() {
+        let snapshot = Categories::snapshot().unwrap();
+        assert_eq!(snapshot.suggest("no_std"), ["no-std", "no-std::no-alloc"]);
+        assert_eq!(
+            snapshot.suggest("http-server"),
+            ["web-programming::http-server"]
+        );
+        assert!(
+            snapshot
+                .suggest("parser")
+                .contains(&"parser-implementations")
+        );
+        assert_eq!(snapshot.suggest("zzzzzzzz"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(check_keywords(&strings(&["parser", "no-std", "c++"])), []);
+        let problems: Vec<String> = check_keywords(&strings(&[
+            "",
+            "café",
+            "a-very-long-keyword-indeed",
+            "-dash",
+            "has space",
+            "Parser",
+            "parser",
+        ]))
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(
+            problems,
+            [
+                "7 keywords given, but crates.io accepts at most 5",
+                "invalid keyword ``: must not be empty",
+                "invalid keyword `café`: must be ASCII",
+                "invalid keyword `a-very-long-keyword-indeed`: is 26 characters long (crates.io \
+                 allows at most 20)",
+                "invalid keyword `-dash`: must start with a letter or digit",
+                "invalid keyword `has space`: contains ` ` (only ASCII letters, digits, `_`, `-` \
+                 and `+` are allowed)",
+                "`parser` is in `keywords` more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn manifests() {
+        let path = Path::new("Cargo.toml");
+        let manifest = Document::parse(
+            path,
+            "[package]\nname = \"demo\"\nkeywords = [\"ok\", \"not ok\"]\ncategories = \
+             [\"no_std\", \"no-std\", \"no-std\"]\n",
+        )
+        .unwrap();
+        let diagnostics: Vec<String> = check(&manifest)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                "Cargo.toml:3: invalid keyword `not ok`: contains ` ` (only ASCII letters, \
+                 digits, `_`, `-` and `+` are allowed)",
+                "Cargo.toml:4: unknown category `no_std` (did you mean `no-std` or `no-std::no-alloc`?)",
+                "Cargo.toml:4: `no-std` is in `categories` more than once",
+            ]
+        );
+
+        let manifest = Document::parse(path, "[package]\nkeywords = \"parser\"\n").unwrap();
+        assert_eq!(
+            check(&manifest).unwrap()[0].to_string(),
+            "Cargo.toml:2: `keywords` is not an array of strings"
+        );
+        assert_eq!(
+            check(&Document::parse(path, "[package]\n").unwrap()).unwrap(),
+            []
+        );
+    }
+
+    #[test]
+    fn validates_answers() {
+        assert!(validate(&strings(&["parser"]), &strings(&["no-std"])).is_ok());
+        let error = validate(&strings(&["a b"]), &strings(&["no_std"])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid keyword `a b`: contains ` ` (only ASCII letters, digits, `_`, `-` and `+` \
+             are allowed); unknown category `no_std` (did you mean `no-std` or `no-std::no-alloc`?)"
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("no_std", "no-std"), 1);
+    }
+}
@@ -0,0 +1,561 @@
+//! Keeping the minimum supported Rust version (MSRV) consistent.
+//!
+//! The MSRV is written in three places: `rust-version` in Cargo.toml (the
+//! source of truth), the toolchain of the MSRV job in
+//! `.github/workflows/ci.yaml`, and the `MSRV` constant in `scripts/ci.py`.
+//! [`check`] finds the copies that have drifted from Cargo.toml, and [`set`]
+//! rewrites all of them at once.
+//!
+//! [`discover`] finds what the MSRV actually is, by bisecting over the
+//! toolchains installed with rustup.
+
+use std::{fmt, path::Path, process::Command, str::FromStr};
+
+use crate::{
+    Error, Result,
+    changes::{FileChange, Files},
+    ci_script, process,
+    toml::Document,
+    workflow::{self, Workflow},
+};
+
+/// A Rust release, like `1.85.0` (or `1.85`, as `rust-version` allows).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RustVersion {
+    /// The major version (always 1, in practice).
+    pub major: u64,
+    /// The minor version.
+    pub minor: u64,
+    /// The patch version, if specified.
+    pub patch: Option<u64>,
+}
+
+impl RustVersion {
+    /// Whether `self` and `other` name the same release, treating a missing
+    /// patch version as 0.
+    pub fn same_release(self, other: Self) -> bool {
+        (self.major, self.minor, self.patch.unwrap_or(0))
+            == (other.major, other.minor, other.patch.unwrap_or(0))
+    }
+}
+
+impl fmt::Display for RustVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if let Some(patch) = self.patch {
+            write!(f, ".{patch}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RustVersion {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || Error::InvalidInput(format!("invalid Rust version `{text}`"));
+        let mut parts = text
+            .split('.')
+            .map(|part| part.parse::<u64>().map_err(|_| invalid()));
+
+        let major = parts.next().ok_or_else(invalid)??;
+        let minor = parts.next().ok_or_else(invalid)??;
+        let patch = parts.next().transpose()?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+/// The first Rust release supporting each edition.
+const EDITIONS: &[(&str, RustVersion)] = &[
+    (
+        "2015",
+        RustVersion {
+            major: 1,
+            minor: 0,
+            patch: Some(0),
+        },
+    ),
+    (
+        "2018",
+        RustVersion {
+            major: 1,
+            minor: 31,
+            patch: Some(0),
+        },
+    ),
+    (
+        "2021",
+        RustVersion {
+            major: 1,
+            minor: 56,
+            patch: Some(0),
+        },
+    ),
+    (
+        "2024",
+        RustVersion {
+            major: 1,
+            minor: 85,
+            patch: Some(0),
+        },
+    ),
+];
+
+/// Returns the first Rust release supporting `edition`, if it is known.
+pub fn edition_minimum(edition: &str) -> Option<RustVersion> {
+    EDITIONS
+        .iter()
+        .find(|(known, _)| *known == edition)
+        .map(|&(_, version)| version)
+}
+
+/// A place other than Cargo.toml where the MSRV is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The file, relative to the crate root.
+    pub file: &'static str,
+    /// The (1-based) line.
+    pub line: usize,
+    /// The version written there.
+    pub version: RustVersion,
+}
+
+/// The MSRV of a crate and everywhere it is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The `rust-version` in Cargo.toml.
+    pub msrv: RustVersion,
+    /// The (1-based) line of `rust-version` in Cargo.toml.
+    pub line: usize,
+    /// The crate's `edition`.
+    pub edition: String,
+    /// The other places the MSRV is written.
+    pub locations: Vec<Location>,
+}
+
+impl Report {
+    /// The locations whose version differs from Cargo.toml's.
+    pub fn drifted(&self) -> impl Iterator<Item = &Location> {
+        self.locations
+            .iter()
+            .filter(|location| !location.version.same_release(self.msrv))
+    }
+
+    /// Describes the incompatibility between the MSRV and the edition, if any.
+    pub fn edition_problem(&self) -> Option<String> {
+        edition_problem(self.msrv, &self.edition)
+    }
+}
+
+/// Describes why `msrv` cannot compile crates of `edition`, if it can't.
+fn edition_problem(msrv: RustVersion, edition: &str) -> Option<String> {
+    match edition_minimum(edition) {
+        None => Some(format!("unknown edition `{edition}`")),
+        Some(minimum) if msrv < minimum => Some(format!(
+            "edition {edition} requires Rust {minimum} or newer, but the MSRV is {msrv}"
+        )),
+        Some(_) => None,
+    }
+}
+
+/// Reads the MSRV of the crate at `root`, and finds its other copies.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or parsed, or Cargo.toml has no
+/// valid `rust-version`.
+pub fn check(root: &Path) -> Result<Report> {
+    check_files(&mut Files::new(root))
+}
+
+/// Like [`check`], but reads the crate's files from `files`.
+pub(crate) fn check_files(files: &mut Files<'_>) -> Result<Report> {
+    let manifest_path = Path::new("Cargo.toml");
+    let manifest = Document::parse(
+        manifest_path,
+        files
+            .get(manifest_path)?
+            .ok_or_else(|| Error::malformed(manifest_path, "the file is missing"))?,
+    )?;
+    let field = manifest
+        .get("package", "rust-version")
+        .ok_or_else(|| Error::malformed(manifest_path, "`package.rust-version` is missing"))?;
+    let msrv = field
+        .value
+        .as_str()
+        .ok_or_else(|| Error::malformed(manifest_path, "`package.rust-version` is not a string"))?
+        .parse()?;
+    let edition = manifest
+        .get_str("package", "edition")
+        .unwrap_or_else(|| "2015".to_owned());
+
+    let mut locations = Vec::new();
+    if let Some(contents) = files.get(workflow::PATH)? {
+        let workflow = Workflow::parse(Path::new(workflow::PATH), contents)?;
+        for step in workflow.jobs.iter().flat_map(|job| &job.steps) {
+            let toolchain = step
+                .action()
+                .filter(|(action, _)| *action == "dtolnay/rust-toolchain")
+                .and_then(|(_, toolchain)| toolchain.parse().ok());
+            if let (Some(version), Some(line)) = (toolchain, step.uses_line) {
+                locations.push(Location {
+                    file: workflow::PATH,
+                    line,
+                    version,
+                });
+            }
+        }
+    }
+    let assignment = files
+        .get(ci_script::PATH)?
+        .and_then(|script| ci_script::string_constant(script, "MSRV"));
+    if let Some(assignment) = assignment {
+        locations.push(Location {
+            file: ci_script::PATH,
+            line: assignment.line,
+            version: assignment.value.parse()?,
+        });
+    }
+
+    Ok(Report {
+        msrv,
+        line: field.line,
+        edition,
+        locations,
+    })
+}
+
+/// Sets the MSRV of the crate at `root` to `msrv` everywhere it is written.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or parsed, or `msrv` is too old
+/// for the crate's edition.
+pub fn set(root: &Path, msrv: RustVersion) -> Result<Vec<FileChange>> {
+    let mut files = Files::new(root);
+    set_files(&mut files, msrv)?;
+    Ok(files.into_changes())
+}
+
+/// Like [`set`], but edits the crate's files in `files`.
+pub(crate) fn set_files(files: &mut Files<'_>, msrv: RustVersion) -> Result<()> {
+    let report = check_files(files)?;
+    if let Some(problem) = edition_problem(msrv, &report.edition) {
+        return Err(Error::InvalidInput(problem));
+    }
+
+    let manifest_path = Path::new("Cargo.toml");
+    let original = files.get(manifest_path)?.unwrap_or_default();
+    let mut manifest = Document::parse(manifest_path, original)?;
+    manifest.set("package", "rust-version", &msrv.to_string().into());
+    files.set(manifest_path, manifest.to_string())?;
+
+    for file in [workflow::PATH, ci_script::PATH] {
+        let lines: Vec<&Location> = report
+            .locations
+            .iter()
+            .filter(|location| location.file == file)
+            .collect();
+        let Some(original) = files.get(file)?.filter(|_| !lines.is_empty()) else {
+            continue;
+        };
+        let updated = original
+            .split_inclusive('\n')
+            .enumerate()
+            .map(
+                |(index, line)| match lines.iter().find(|location| location.line == index + 1) {
+                    Some(location) => replace_version(line, location.version, msrv),
+                    None => line.to_owned(),
+                },
+            )
+            .collect();
+        files.set(file, updated)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the version `old` in `line` with `new`.
+fn replace_version(line: &str, old: RustVersion, new: RustVersion) -> String {
+    // Prefer an exact textual match, then fall back to the normalized form
+    // (`1.85` written where `1.85.0` was parsed, or vice versa)
+    let old_text = old.to_string();
+    if line.contains(&old_text) {
+        return line.replacen(&old_text, &new.to_string(), 1);
+    }
+    let full = format!("{}.{}.{}", old.major, old.minor, old.patch.unwrap_or(0));
+    line.replacen(&full, &new.to_string(), 1)
+}
+
+/// A toolchain installed with rustup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    /// The toolchain's rustup name, like `stable-x86_64-unknown-linux-gnu`.
+    pub name: String,
+    /// The release of Rust the toolchain provides.
+    pub version: RustVersion,
+}
+
+/// Lists the installed stable toolchains, oldest first.
+///
+/// Beta and nightly toolchains are left out, since an MSRV must name a stable
+/// release. When several toolchains provide the same release, only the first
+/// one is kept.
+///
+/// # Errors
+///
+/// Returns an error if `rustup` cannot be run.
+pub fn toolchains() -> Result<Vec<Toolchain>> {
+    let list = process::stdout(Command::new("rustup").args(["toolchain", "list"]))?
+        .ok_or_else(|| Error::InvalidInput("`rustup toolchain list` failed".to_owned()))?;
+
+    let mut toolchains = Vec::new();
+    for name in list
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+    {
+        // `rustc 1.85.0 (4d91de4e4 2025-02-17)`, or `rustc 1.87.0-nightly (...)`
+        let Some(version) = process::stdout(
+            Command::new("rustc")
+                .arg(format!("+{name}"))
+                .arg("--version"),
+        )?
+        else {
+            continue;
+        };
+        let version = version
+            .split_whitespace()
+            .nth(1)
+            .and_then(|version| version.parse().ok());
+        if let Some(version) = version {
+            toolchains.push(Toolchain {
+                name: name.to_owned(),
+                version,
+            });
+        }
+    }
+
+    toolchains.sort_by_key(|toolchain| toolchain.version);
+    toolchains.dedup_by_key(|toolchain| toolchain.version);
+    Ok(toolchains)
+}
+
+/// Checks whether `toolchain` compiles the crate at `root` with all features.
+///
+/// The crate's current `rust-version` is ignored (it's what's being
+/// determined), and the build goes to a separate target directory so as not to
+/// disturb regular builds.
+///
+/// # Errors
+///
+/// Returns an error if `cargo` cannot be run.
+pub fn compiles(root: &Path, toolchain: &Toolchain) -> Result<bool> {
+    let output = process::output(
+        Command::new("cargo")
+            .arg(format!("+{}", toolchain.name))
+            .args([
+                "check",
+                "--all-features",
+                "--all-targets",
+                "--ignore-rust-version",
+            ])
+            .arg("--target-dir")
+            .arg(root.join("target").join("msrv"))
+            .arg("--manifest-path")
+            .arg(root.join("Cargo.toml"))
+            .env_remove("RUSTUP_TOOLCHAIN"),
+    )?;
+    Ok(output.status.success())
+}
+
+/// The outcome of [`discover`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovery {
+    /// The oldest toolchain that compiles the crate, if any does.
+    pub oldest: Option<Toolchain>,
+    /// Each toolchain tried, and whether it compiled the crate.
+    pub tried: Vec<(Toolchain, bool)>,
+}
+
+/// Finds the oldest of `toolchains` (sorted oldest first) that compiles the
+/// crate, by bisection: it is assumed that once a release compiles the crate,
+/// every later one does too.
+///
+/// `check_toolchain` is called for each toolchain tried (see [`compiles`]), and is
+/// never called for toolchains too old for the crate's edition.
+///
+/// # Errors
+///
+/// Returns an error if Cargo.toml cannot be read, or `check_toolchain` fails.
+pub fn discover(
+    root: &Path,
+    toolchains: &[Toolchain],
+    mut check_toolchain: impl FnMut(&Toolchain) -> Result<bool>,
+) -> Result<Discovery> {
+    let edition = check(root)?.edition;
+    let minimum = edition_minimum(&edition).unwrap_or(RustVersion {
+        major: 1,
+        minor: 0,
+        patch: Some(0),
+    });
+    let candidates: Vec<&Toolchain> = toolchains
+        .iter()
+        .filter(|toolchain| toolchain.version >= minimum)
+        .collect();
+
+    let mut tried = Vec::new();
+    let mut try_one = |toolchain: &Toolchain| -> Result<bool> {
+        let compiled = check_toolchain(toolchain)?;
+        tried.push((toolchain.clone(), compiled));
+        Ok(compiled)
+    };
+
+    let Some(&newest) = candidates.last() else {
+        return Ok(Discovery {
+            oldest: None,
+            tried,
+        });
+    };
+    if !try_one(newest)? {
+        return Ok(Discovery {
+            oldest: None,
+            tried,
+        });
+    }
+    // Invariant: candidates[high] compiles, and everything before low doesn't
+    let (mut low, mut high) = (0, candidates.len() - 1);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if try_one(candidates[middle])? {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    Ok(Discovery {
+        oldest: Some(candidates[high].clone()),
+        tried,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{changes, fs::Scratch};
+
+    fn version(text: &str) -> RustVersion {
+        text.parse().unwrap()
+    }
+
+    /// The template's files that write the MSRV, with `MSRV` in ci.py drifted
+    /// to 1.84.
+    fn drifted_crate() -> Scratch {
+        Scratch::new(&[
+            ("Cargo.toml", include_str!("../../Cargo.toml")),
+            (
+                workflow::PATH,
+                include_str!("../../.github/workflows/ci.yaml"),
+            ),
+            (
+                ci_script::PATH,
+                &include_str!("../../scripts/ci.py")
+                    .replace("MSRV = \"1.85.0\"", "MSRV = \"1.84\""),
+            ),
+        ])
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(
+            version("1.85"),
+            RustVersion {
+                major: 1,
+                minor: 85,
+                patch: None
+            }
+        );
+        assert_eq!(version("1.85.1").to_string(), "1.85.1");
+        assert!(version("1.85").same_release(version("1.85.0")));
+        assert!(!version("1.85").same_release(version("1.85.1")));
+        for invalid in ["", "1", "1.x", "1.85.0.0", "v1.85"] {
+            assert!(invalid.parse::<RustVersion>().is_err(), "{invalid}");
+        }
+        assert_eq!(edition_minimum("2021"), Some(version("1.56.0")));
+        assert_eq!(edition_minimum("2027"), None);
+    }
+
+    #[test]
+    fn check_finds_drifted_copies() {
+        let scratch = drifted_crate();
+        let report = check(scratch.path()).unwrap();
+        assert_eq!(report.msrv, version("1.85.0"));
+        assert_eq!(report.edition, "2024");
+        assert_eq!(report.edition_problem(), None);
+        assert_eq!(report.locations.len(), 2);
+        let drifted: Vec<&Location> = report.drifted().collect();
+        assert_eq!(
+            drifted,
+            [&Location {
+                file: ci_script::PATH,
+                line: 34,
+                version: version("1.84"),
+            }]
+        );
+    }
+
+    #[test]
+    fn set_and_check_round_trip() {
+        let scratch = drifted_crate();
+        let changes = set(scratch.path(), version("1.88")).unwrap();
+        assert_eq!(changes.len(), 3);
+        changes::apply(scratch.path(), &changes).unwrap();
+
+        let report = check(scratch.path()).unwrap();
+        assert_eq!(report.msrv, version("1.88"));
+        assert_eq!(report.drifted().count(), 0);
+        let script = crate::fs::read(&scratch.path().join(ci_script::PATH)).unwrap();
+        assert!(script.contains("\nMSRV = \"1.88\"\n"));
+        let workflow = crate::fs::read(&scratch.path().join(workflow::PATH)).unwrap();
+        assert!(workflow.contains("dtolnay/rust-toolchain@1.88\n"));
+
+        // The edition needs at least 1.85
+        assert!(set(scratch.path(), version("1.84")).is_err());
+        assert_eq!(set(scratch.path(), version("1.88")).unwrap(), []);
+    }
+
+    #[test]
+    fn discover_bisects_down_to_the_oldest_compiling_toolchain() {
+        let scratch = drifted_crate();
+        let toolchains: Vec<Toolchain> = (80..=90)
+            .map(|minor| Toolchain {
+                name: format!("1.{minor}-x86_64-unknown-linux-gnu"),
+                version: version(&format!("1.{minor}.0")),
+            })
+            .collect();
+
+        let discovery = discover(scratch.path(), &toolchains, |toolchain| {
+            // Toolchains too old for edition 2024 are never tried
+            assert!(toolchain.version >= version("1.85.0"));
+            Ok(toolchain.version >= version("1.87.0"))
+        })
+        .unwrap();
+        assert_eq!(
+            discovery.oldest.map(|toolchain| toolchain.version),
+            Some(version("1.87.0"))
+        );
+        assert!(discovery.tried.len() <= 4, "{:?}", discovery.tried);
+
+        let discovery = discover(scratch.path(), &toolchains, |_| Ok(false)).unwrap();
+        assert_eq!(discovery.oldest, None);
+        assert_eq!(discovery.tried.len(), 1);
+    }
+}
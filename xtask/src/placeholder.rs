@@ -0,0 +1,620 @@
+//! Placeholders: the parts of the template filled in for each derived crate.
+//!
+//! The template has to stay a working crate, so its files hold sample values
+//! (`rust-template`, `crate-name`, `ijchen`, ...) rather than placeholder
+//! syntax. Instead, every line that gets filled in is preceded by a `@render`
+//! directive, in the comment syntax of the file's [`Language`], giving the
+//! line's replacement:
+//!
+//! ```text
+//! # @render name = "{{ name }}"
+//! name = "rust-template" # TODO: update package name
+//! ```
+//!
+//! Between `{{` and `}}` is a [`Variable`], optionally followed by
+//! [`Filter`]s, each introduced by `|`. [`render`] replaces the directive and
+//! the line below it with the rendered text, at the directive's indentation,
+//! unless a variable it uses has no value, in which case both are left alone.
+//!
+//! Files without comments (the licenses) can't hold directives, and neither
+//! can lines where a comment wouldn't be one (like in the docstring of
+//! `scripts/ci.py`), so those placeholder lines are listed in [`PLAIN_TEXT`]
+//! instead.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write as _},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Error, Result, crate_name::CrateName, markers::Language, version::Version};
+
+/// A variable that placeholders can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variable {
+    /// The crate name, as given.
+    Name,
+    /// The GitHub user or organization that owns the repository.
+    Owner,
+    /// The author, as named in Cargo.toml and the licenses.
+    Author,
+    /// The crate's version.
+    Version,
+    /// The one-line description of the crate.
+    Description,
+    /// The year of the copyright notices.
+    Year,
+}
+
+impl Variable {
+    /// Every variable.
+    pub const ALL: [Self; 6] = [
+        Self::Name,
+        Self::Owner,
+        Self::Author,
+        Self::Version,
+        Self::Description,
+        Self::Year,
+    ];
+
+    /// The variable's name, as used in placeholders.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Owner => "owner",
+            Self::Author => "author",
+            Self::Version => "version",
+            Self::Description => "description",
+            Self::Year => "year",
+        }
+    }
+
+    /// Parses a variable name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|variable| variable.name() == name)
+    }
+
+    /// Checks that `value` has the variable's type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing what is wrong with `value`.
+    pub fn validate(self, value: &str) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(Error::InvalidInput(format!(
+                "invalid {} `{value}`: {reason}",
+                self.name()
+            )))
+        };
+
+        match self {
+            Self::Name => CrateName::new(value).map(drop),
+            Self::Owner
+                if value.is_empty()
+                    || value.starts_with('-')
+                    || !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') =>
+            {
+                invalid("GitHub names consist of ASCII letters, digits and non-leading `-`")
+            }
+            Self::Version => value.parse::<Version>().map(drop),
+            Self::Year if value.len() != 4 || !value.bytes().all(|b| b.is_ascii_digit()) => {
+                invalid("must be a four-digit year")
+            }
+            Self::Author | Self::Description if value.trim().is_empty() => {
+                invalid("must not be empty")
+            }
+            Self::Author | Self::Description if value.contains(char::is_control) => {
+                invalid("must be a single line of text")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A transformation applied to a variable's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Replaces every `-` with `_`, as in Rust paths.
+    SnakeCase,
+    /// Replaces every `_` with `-`.
+    KebabCase,
+    /// Escapes the message of a shields.io static badge, where `-` and `_`
+    /// are separators (so `crate-name` becomes `crate--name`).
+    ShieldsEscape,
+    /// Percent-encodes everything but unreserved URL characters.
+    UrlEncode,
+    /// Escapes the contents of a TOML basic string.
+    TomlEscape,
+}
+
+impl Filter {
+    /// Every filter.
+    pub const ALL: [Self; 5] = [
+        Self::SnakeCase,
+        Self::KebabCase,
+        Self::ShieldsEscape,
+        Self::UrlEncode,
+        Self::TomlEscape,
+    ];
+
+    /// The filter's name, as used in placeholders.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SnakeCase => "snake_case",
+            Self::KebabCase => "kebab_case",
+            Self::ShieldsEscape => "shields_escape",
+            Self::UrlEncode => "url_encode",
+            Self::TomlEscape => "toml_escape",
+        }
+    }
+
+    /// Parses a filter name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|filter| filter.name() == name)
+    }
+
+    /// Applies the filter to `text`.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Self::SnakeCase => text.replace('-', "_"),
+            Self::KebabCase => text.replace('_', "-"),
+            Self::ShieldsEscape => text.replace('-', "--").replace('_', "__").replace(' ', "_"),
+            Self::UrlEncode => {
+                let mut encoded = String::with_capacity(text.len());
+                for byte in text.bytes() {
+                    if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                        encoded.push(char::from(byte));
+                    } else {
+                        let _ = write!(encoded, "%{byte:02X}");
+                    }
+                }
+                encoded
+            }
+            Self::TomlEscape => {
+                let mut escaped = String::with_capacity(text.len());
+                for c in text.chars() {
+                    match c {
+                        '"' | '\\' => {
+                            escaped.push('\\');
+                            escaped.push(c);
+                        }
+                        c if c.is_control() => {
+                            let _ = write!(escaped, "\\u{:04X}", u32::from(c));
+                        }
+                        c => escaped.push(c),
+                    }
+                }
+                escaped
+            }
+        }
+    }
+}
+
+/// The values of the variables, each checked against its type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Values(BTreeMap<Variable, String>);
+
+impl Values {
+    /// Creates an empty set of values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of `variable`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` doesn't have the variable's type.
+    pub fn set(&mut self, variable: Variable, value: impl Into<String>) -> Result<()> {
+        let value = value.into();
+        variable.validate(&value)?;
+        self.0.insert(variable, value);
+        Ok(())
+    }
+
+    /// The value of `variable`, if it has one.
+    pub fn get(&self, variable: Variable) -> Option<&str> {
+        self.0.get(&variable).map(String::as_str)
+    }
+
+    /// Lists the variables that have values, with their values, in the order
+    /// of [`Variable::ALL`].
+    pub fn iter(&self) -> impl Iterator<Item = (Variable, &str)> {
+        self.0
+            .iter()
+            .map(|(&variable, value)| (variable, value.as_str()))
+    }
+}
+
+/// The current year (in UTC), the default for [`Variable::Year`].
+pub fn current_year() -> u64 {
+    today().0
+}
+
+/// The current date (in UTC), as the year, month and day of the month.
+pub fn today() -> (u64, u64, u64) {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400);
+
+    // Howard Hinnant's `civil_from_days`, with years starting in March
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    (
+        era * 400 + year_of_era + u64::from(month_index >= 10),
+        month,
+        day,
+    )
+}
+
+/// A placeholder line of a file without comments: the path of the file, the
+/// line in the template, and its replacement.
+pub type PlainText = (&'static str, &'static str, &'static str);
+
+/// The placeholder lines that can't be preceded by a directive.
+pub const PLAIN_TEXT: &[PlainText] = &[
+    (
+        "scripts/ci.py",
+        "Author(s): Isaac Chen",
+        "Author(s): {{ author }}",
+    ),
+    (
+        "LICENSE-MIT",
+        "Copyright (c) TODO_YEAR Isaac Chen",
+        "Copyright (c) {{ year }} {{ author }}",
+    ),
+    (
+        "LICENSE-APACHE",
+        "   Copyright TODO_YEAR Isaac Chen",
+        "   Copyright {{ year }} {{ author }}",
+    ),
+];
+
+/// One piece of a parsed replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece<'a> {
+    /// Literal text.
+    Text(&'a str),
+    /// A variable and the filters to apply to its value, in order.
+    Placeholder(Variable, Vec<Filter>),
+}
+
+/// Parses the replacement text of a directive, describing the first problem
+/// if it is invalid.
+fn parse(text: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            pieces.push(Piece::Text(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find("}}") else {
+            return Err("`{{` without `}}`".to_owned());
+        };
+        let mut parts = rest[start + 2..start + end].split('|').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let variable = Variable::from_name(name).ok_or_else(|| {
+            let known: Vec<&str> = Variable::ALL.iter().map(|v| v.name()).collect();
+            format!(
+                "unknown variable `{name}` (expected one of {})",
+                known.join(", ")
+            )
+        })?;
+        let filters = parts
+            .map(|name| {
+                Filter::from_name(name).ok_or_else(|| {
+                    let known: Vec<&str> = Filter::ALL.iter().map(|f| f.name()).collect();
+                    format!(
+                        "unknown filter `{name}` (expected one of {})",
+                        known.join(", ")
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        pieces.push(Piece::Placeholder(variable, filters));
+        rest = &rest[start + end + 2..];
+    }
+    if rest.contains("}}") {
+        return Err("`}}` without `{{`".to_owned());
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+/// Renders parsed `pieces`, or returns [`None`] if a variable they use has no
+/// value.
+fn render_pieces(pieces: &[Piece<'_>], values: &Values) -> Option<String> {
+    let mut rendered = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => rendered.push_str(text),
+            Piece::Placeholder(variable, filters) => {
+                let value = values.get(*variable)?;
+                let value = filters
+                    .iter()
+                    .fold(value.to_owned(), |value, filter| filter.apply(&value));
+                rendered.push_str(&value);
+            }
+        }
+    }
+    Some(rendered)
+}
+
+/// A `@render` directive (or [`PLAIN_TEXT`] entry) in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive {
+    /// The (1-based) line of the directive, or of the placeholder line for
+    /// [`PLAIN_TEXT`] entries.
+    pub line: usize,
+    /// The replacement text, including its indentation.
+    pub text: String,
+    /// The variables the replacement uses.
+    pub variables: BTreeSet<Variable>,
+    /// The (1-based) line the directive replaces.
+    target: usize,
+}
+
+/// The `@render` directive text on `line`, if it is one, along with its
+/// indentation.
+fn directive(language: Language, line: &str) -> Option<(&str, &str)> {
+    let (open, close) = language.line_comment();
+    let trimmed = line.trim_start();
+    let text = trimmed.strip_prefix(open)?.trim_end();
+    let text = text.strip_suffix(close)?.trim();
+    let text = text.strip_prefix("@render")?;
+    let text = if text.is_empty() {
+        text
+    } else {
+        text.strip_prefix(char::is_whitespace)?.trim()
+    };
+    Some((&line[..line.len() - trimmed.len()], text))
+}
+
+/// Finds the placeholders in the contents of the file at `path`.
+///
+/// # Errors
+///
+/// Returns an error if a directive is empty, isn't followed by the line it
+/// replaces, or uses an unknown variable or filter.
+pub fn directives(path: &Path, contents: &str) -> Result<Vec<Directive>> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let malformed =
+        |line: usize, message: &str| Error::malformed(path, format!("line {line}: {message}"));
+    let new_directive = |line: usize, target: usize, text: String| -> Result<Directive> {
+        let variables = parse(&text)
+            .map_err(|message| malformed(line, &message))?
+            .into_iter()
+            .filter_map(|piece| match piece {
+                Piece::Placeholder(variable, _) => Some(variable),
+                Piece::Text(_) => None,
+            })
+            .collect();
+        Ok(Directive {
+            line,
+            text,
+            variables,
+            target,
+        })
+    };
+
+    let mut found = Vec::new();
+    let plain_path = path.to_string_lossy().replace('\\', "/");
+    for &(_, template, text) in PLAIN_TEXT.iter().filter(|(file, ..)| *file == plain_path) {
+        let Some(index) = lines.iter().position(|&line| line == template) else {
+            continue;
+        };
+        found.push(new_directive(index + 1, index + 1, text.to_owned())?);
+    }
+    let Some(language) = Language::from_path(path) else {
+        return Ok(found);
+    };
+
+    for (index, &line) in lines.iter().enumerate() {
+        let Some((indent, text)) = directive(language, line) else {
+            continue;
+        };
+        if text.is_empty() {
+            return Err(malformed(index + 1, "`@render` without a replacement"));
+        }
+        let target = lines.get(index + 1).copied();
+        if target.is_none_or(|target| directive(language, target).is_some()) {
+            return Err(malformed(
+                index + 1,
+                "`@render` must be followed by the line it replaces",
+            ));
+        }
+        found.push(new_directive(
+            index + 1,
+            index + 2,
+            format!("{indent}{text}"),
+        )?);
+    }
+    found.sort_by_key(|directive| directive.line);
+    Ok(found)
+}
+
+/// Fills in every placeholder in the contents of the file at `path` whose
+/// variables all have values.
+///
+/// # Errors
+///
+/// Returns an error if the file's directives are malformed (see
+/// [`directives`]).
+pub fn render(path: &Path, contents: &str, values: &Values) -> Result<String> {
+    let directives = directives(path, contents)?;
+    let mut replacements = BTreeMap::new();
+    for directive in &directives {
+        let pieces = parse(&directive.text).map_err(|message| Error::malformed(path, message))?;
+        if let Some(rendered) = render_pieces(&pieces, values) {
+            replacements.insert(directive.target, (directive.line, rendered));
+        }
+    }
+    if replacements.is_empty() {
+        return Ok(contents.to_owned());
+    }
+
+    let mut output = String::with_capacity(contents.len());
+    let skipped: BTreeSet<usize> = replacements.values().map(|(line, _)| *line).collect();
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        let number = index + 1;
+        if let Some((_, rendered)) = replacements.get(&number) {
+            output.push_str(rendered);
+            output.push_str(&line[line.trim_end_matches(['\n', '\r']).len()..]);
+        } else if !skipped.contains(&number) {
+            output.push_str(line);
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[(Variable, &str)]) -> Values {
+        let mut set = Values::new();
+        for &(variable, value) in values {
+            set.set(variable, value).unwrap();
+        }
+        set
+    }
+
+    #[test]
+    fn plain_text_lines_in_files_with_comments() {
+        let script = "\"\"\"\nName: CI\nAuthor(s): Isaac Chen\n\"\"\"\n# @render OWNER = \"{{ owner }}\"\nOWNER = \"ijchen\"\n";
+        let rendered = render(
+            Path::new("scripts/ci.py"),
+            script,
+            &values(&[(Variable::Author, "Jo Doe"), (Variable::Owner, "jo")]),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "\"\"\"\nName: CI\nAuthor(s): Jo Doe\n\"\"\"\nOWNER = \"jo\"\n"
+        );
+    }
+
+    #[test]
+    fn filters() {
+        for (filter, text, expected) in [
+            (Filter::SnakeCase, "my-crate_name", "my_crate_name"),
+            (Filter::KebabCase, "my-crate_name", "my-crate-name"),
+            (
+                Filter::ShieldsEscape,
+                "my-crate_name v1",
+                "my--crate__name_v1",
+            ),
+            (Filter::UrlEncode, "a b/c~d-é", "a%20b%2Fc~d-%C3%A9"),
+            (
+                Filter::TomlEscape,
+                "say \"hi\"\\\n",
+                "say \\\"hi\\\"\\\\\\u000A",
+            ),
+        ] {
+            assert_eq!(filter.apply(text), expected, "{}", filter.name());
+        }
+        for filter in Filter::ALL {
+            assert_eq!(Filter::from_name(filter.name()), Some(filter));
+        }
+    }
+
+    #[test]
+    fn renders_directives() {
+        let manifest = "[package]\n  # @render name = \"{{ name }}\"\n  name = \"rust-template\"\n# @render doc = \"{{ name | snake_case }}: {{ description|toml_escape }}\"\ndoc = \"\"\n";
+        let rendered = render(
+            Path::new("Cargo.toml"),
+            manifest,
+            &values(&[(Variable::Name, "my-crate")]),
+        )
+        .unwrap();
+        // The second directive's description has no value, so it stays
+        assert_eq!(
+            rendered,
+            "[package]\n  name = \"my-crate\"\n# @render doc = \"{{ name | snake_case }}: {{ description|toml_escape }}\"\ndoc = \"\"\n"
+        );
+
+        let found = directives(Path::new("Cargo.toml"), manifest).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].line, 2);
+        assert_eq!(found[0].text, "  name = \"{{ name }}\"");
+        assert_eq!(
+            found[1].variables,
+            BTreeSet::from([Variable::Name, Variable::Description])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_directives() {
+        for (contents, message) in [
+            ("# @render\nx\n", "line 1: `@render` without a replacement"),
+            (
+                "x\n# @render {{ name }}\n",
+                "line 2: `@render` must be followed by the line it replaces",
+            ),
+            ("# @render {{ name\nx\n", "line 1: `{{` without `}}`"),
+            ("# @render name }}\nx\n", "line 1: `}}` without `{{`"),
+            (
+                "# @render {{ nmae }}\nx\n",
+                "line 1: unknown variable `nmae` (expected one of name, owner, author, version, description, year)",
+            ),
+            (
+                "# @render {{ name | upper }}\nx\n",
+                "line 1: unknown filter `upper` (expected one of snake_case, kebab_case, shields_escape, url_encode, toml_escape)",
+            ),
+        ] {
+            assert_eq!(
+                directives(Path::new("Cargo.toml"), contents)
+                    .unwrap_err()
+                    .to_string(),
+                format!("Cargo.toml: {message}"),
+                "{contents:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn validates_values() {
+        for (variable, value) in [
+            (Variable::Name, "my-crate"),
+            (Variable::Owner, "some-one"),
+            (Variable::Version, "1.2.3-rc.1"),
+            (Variable::Year, "2026"),
+            (Variable::Author, "Jo Doe <jo@example.com>"),
+        ] {
+            assert!(variable.validate(value).is_ok(), "{variable} `{value}`");
+        }
+        for (variable, value) in [
+            (Variable::Name, "my crate"),
+            (Variable::Owner, "-someone"),
+            (Variable::Owner, "some_one"),
+            (Variable::Version, "1.2"),
+            (Variable::Year, "26"),
+            (Variable::Author, " "),
+            (Variable::Description, "two\nlines"),
+        ] {
+            assert!(variable.validate(value).is_err(), "{variable} `{value}`");
+        }
+    }
+}
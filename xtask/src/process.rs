@@ -0,0 +1,101 @@
+//! Thin wrappers around [`std::process`] that attach the program to any error.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write as _},
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+use crate::{Error, Result};
+
+/// Runs `command` to completion, capturing its output.
+pub(crate) fn output(command: &mut Command) -> Result<Output> {
+    command.output().map_err(|source| Error::Command {
+        program: command.get_program().to_string_lossy().into_owned(),
+        source,
+    })
+}
+
+/// Runs `command` to completion, with its output going to ours.
+pub(crate) fn status(command: &mut Command) -> Result<ExitStatus> {
+    command.status().map_err(|source| Error::Command {
+        program: command.get_program().to_string_lossy().into_owned(),
+        source,
+    })
+}
+
+/// Runs `command` to completion with `input` on its stdin, capturing its
+/// output.
+pub(crate) fn output_with_input(command: &mut Command, input: &str) -> Result<Output> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let error = |source| Error::Command {
+        program: program.clone(),
+        source,
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(error)?;
+    // Dropping stdin closes it, so the child sees the end of the input
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(error)?;
+    }
+    child.wait_with_output().map_err(error)
+}
+
+/// Runs `command` to completion, returning its stdout if it succeeded.
+pub(crate) fn stdout(command: &mut Command) -> Result<Option<String>> {
+    let output = output(command)?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// Runs `command` to completion, capturing its stdout and stderr together,
+/// line by line in the order they were written.
+pub(crate) fn combined_output(command: &mut Command) -> Result<(ExitStatus, String)> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let error = |source| Error::Command {
+        program: program.clone(),
+        source,
+    };
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(error)?;
+
+    let output = Mutex::new(Vec::new());
+    let readers: [Option<Box<dyn Read + Send>>; 2] = [
+        child.stdout.take().map(|stdout| Box::new(stdout) as _),
+        child.stderr.take().map(|stderr| Box::new(stderr) as _),
+    ];
+    thread::scope(|scope| {
+        for reader in readers.into_iter().flatten() {
+            let output = &output;
+            scope.spawn(move || {
+                let mut reader = BufReader::new(reader);
+                let mut line = Vec::new();
+                while reader
+                    .read_until(b'\n', &mut line)
+                    .is_ok_and(|read| read > 0)
+                {
+                    output
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend_from_slice(&line);
+                    line.clear();
+                }
+            });
+        }
+    });
+
+    let status = child.wait().map_err(error)?;
+    let output = output.into_inner().unwrap_or_else(PoisonError::into_inner);
+    Ok((status, String::from_utf8_lossy(&output).into_owned()))
+}
@@ -0,0 +1,705 @@
+//! Checking that a crate is ready to be released, and preparing the release.
+//!
+//! The template leaves a trail of `TODO` and `ON_RELEASE` markers, a
+//! `publish = false`, placeholder package metadata and docs.rs links pinned to
+//! `__CRATE_VERSION_HERE__`. [`check`] finds every one of them that is still
+//! present, along with a package name, keywords or categories crates.io would
+//! reject (see [`crate_name`] and [`metadata`]), so a release can be refused
+//! until they're dealt with. Only the files the package ships (see
+//! [`packaged_files`]) are checked, so the tooling in `xtask/` doesn't count.
+//!
+//! [`prepare`] performs the mechanical part of the `ON_RELEASE` checklist:
+//! bumping the version, enabling publishing, pinning the crate's docs.rs
+//! links to the new version (replacing the placeholder the first time, and
+//! the previous version after that), adding a CHANGELOG.md section and deleting the one-shot
+//! `ON_RELEASE` comments (the publishing, README link and lib.rs notes). It
+//! only edits Cargo.toml, README.md, src/lib.rs and CHANGELOG.md. The
+//! comment on the `version` line of Cargo.toml is a reminder for every
+//! release, so it stays, and [`check`] doesn't count it as unresolved.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Error, Result,
+    changes::FileChange,
+    crate_name, fs,
+    markers::{self, Language, Marker, MarkerKind},
+    metadata, template,
+    toml::{Document, Value},
+    version::{Bump, Version},
+};
+
+/// The files [`prepare`] edits, relative to the crate root. `ON_RELEASE`
+/// comments anywhere else are left alone.
+const RELEASE_FILES: &[&str] = &["Cargo.toml", "README.md", "src/lib.rs", "CHANGELOG.md"];
+
+/// The placeholder standing in for the version in docs.rs links.
+pub const VERSION_PLACEHOLDER: &str = "__CRATE_VERSION_HERE__";
+
+/// A reason the crate is not ready to be released.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The file containing the problem, relative to the crate root.
+    pub file: PathBuf,
+    /// The (1-based) line of the problem, if it has one.
+    pub line: Option<usize>,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(
+        file: impl Into<PathBuf>,
+        line: Option<usize>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            file: file.into(),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Formats the diagnostic as `file:line: message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Finds everything that should block releasing the crate at `root`.
+///
+/// The diagnostics are sorted by file and line. An empty list means the crate
+/// is ready to release.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read.
+pub fn check(root: &Path) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+
+    let manifest = Document::read(&root.join("Cargo.toml"))?;
+    let files = packaged_files(root, &manifest)?;
+    let mut markers = Vec::new();
+    for file in &files {
+        if let Some(language) = Language::from_path(file) {
+            let contents = fs::read(&root.join(file))?;
+            markers.extend(markers::scan_file(file, language, &contents));
+        }
+    }
+    for marker in markers {
+        if matches!(marker.kind, MarkerKind::Todo | MarkerKind::OnRelease)
+            && !is_permanent(&marker, &manifest)
+        {
+            let owner = marker
+                .owner
+                .as_ref()
+                .map(|owner| format!(" (owned by {owner})"))
+                .unwrap_or_default();
+            let message = format!(
+                "unresolved {} marker{owner}: {}",
+                marker.kind, marker.message
+            );
+            diagnostics.push(Diagnostic::new(&marker.file, Some(marker.line), message));
+        }
+    }
+
+    check_manifest(&manifest, &mut diagnostics);
+    diagnostics.extend(metadata::check(&manifest)?);
+
+    for file in files {
+        if Language::from_path(&file) != Some(Language::Markdown) {
+            continue;
+        }
+        let contents = fs::read(&root.join(&file))?;
+        for (index, line) in contents.lines().enumerate() {
+            if line.contains(VERSION_PLACEHOLDER) {
+                diagnostics.push(Diagnostic::new(
+                    &file,
+                    Some(index + 1),
+                    format!("docs.rs link still contains the `{VERSION_PLACEHOLDER}` placeholder"),
+                ));
+            }
+        }
+    }
+
+    diagnostics.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(diagnostics)
+}
+
+/// The files of the crate at `root` that `cargo package` would ship, as
+/// sorted paths relative to `root`.
+///
+/// Those are the files the `include` list of its `manifest` matches (or,
+/// without one, those its `exclude` list doesn't), leaving out other
+/// packages in subdirectories (like `xtask/`). Like Cargo's, the patterns are
+/// gitignore-style.
+///
+/// # Errors
+///
+/// Returns an error if the directory tree cannot be read.
+pub fn packaged_files(root: &Path, manifest: &Document) -> Result<Vec<PathBuf>> {
+    let files = fs::walk(root)?;
+    let packages: Vec<&Path> = files
+        .iter()
+        .filter(|file| file.file_name().is_some_and(|name| name == "Cargo.toml"))
+        .filter_map(|file| file.parent())
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .collect();
+    let patterns = |key: &str| -> Option<Vec<String>> {
+        let entry = manifest.get("package", key)?;
+        let items = entry.value.as_array()?;
+        Some(
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_owned)
+                .collect(),
+        )
+    };
+    let (include, exclude) = (patterns("include"), patterns("exclude"));
+    let matches = |patterns: &[String], file: &Path| {
+        patterns
+            .iter()
+            .any(|pattern| ignore_pattern_matches(pattern, file))
+    };
+
+    Ok(files
+        .iter()
+        .filter(|file| !packages.iter().any(|package| file.starts_with(package)))
+        .filter(|file| match (&include, &exclude) {
+            (Some(include), _) => {
+                file.as_path() == Path::new("Cargo.toml") || matches(include, file)
+            }
+            (None, Some(exclude)) => !matches(exclude, file),
+            (None, None) => true,
+        })
+        .cloned()
+        .collect())
+}
+
+/// Whether the gitignore-style `pattern` matches the file at `path`
+/// (relative to the crate root) or a directory containing it. A pattern with
+/// a `/` (other than a trailing one) is relative to the root, and one without
+/// matches at any depth.
+fn ignore_pattern_matches(pattern: &str, path: &Path) -> bool {
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let (pattern, directory) = match pattern.trim_start_matches('/').strip_suffix('/') {
+        Some(pattern) => (pattern, true),
+        None => (pattern.trim_start_matches('/'), false),
+    };
+    let glob = if anchored {
+        pattern.to_owned()
+    } else {
+        format!("**/{pattern}")
+    };
+    (!directory && template::glob_matches(&glob, path))
+        || template::glob_matches(&format!("{glob}/*/**"), path)
+}
+
+/// Checks the `[package]` table of Cargo.toml for placeholder metadata.
+fn check_manifest(manifest: &Document, diagnostics: &mut Vec<Diagnostic>) {
+    let mut report = |line: Option<usize>, message: &str| {
+        diagnostics.push(Diagnostic::new("Cargo.toml", line, message));
+    };
+
+    match manifest.get("package", "name") {
+        Some(entry) => match entry.value.as_str() {
+            Some(name) => {
+                for problem in crate_name::check(name) {
+                    report(
+                        Some(entry.line),
+                        &format!("package name `{name}` {problem}"),
+                    );
+                }
+            }
+            None => report(Some(entry.line), "`name` is not a string"),
+        },
+        None => report(None, "`name` is missing"),
+    }
+
+    let publish = manifest.get("package", "publish");
+    if let Some(entry) = publish.filter(|entry| entry.value == Value::Boolean(false)) {
+        report(
+            Some(entry.line),
+            "publishing is disabled by `publish = false`",
+        );
+    }
+
+    match manifest.get("package", "version") {
+        Some(entry) if entry.value.as_str() == Some("0.0.0") => {
+            report(Some(entry.line), "`version` is still 0.0.0");
+        }
+        Some(_) => {}
+        None => report(None, "`version` is missing"),
+    }
+
+    match manifest.get("package", "description") {
+        Some(entry) if entry.value.as_str().is_some_and(|d| d.trim().is_empty()) => {
+            report(Some(entry.line), "`description` is empty");
+        }
+        Some(_) => {}
+        None => report(None, "`description` is missing"),
+    }
+
+    for key in ["keywords", "categories"] {
+        match manifest.get("package", key) {
+            Some(entry) if entry.value.as_array().is_some_and(<[Value]>::is_empty) => {
+                report(Some(entry.line), &format!("`{key}` is empty"));
+            }
+            Some(_) => {}
+            None => report(None, &format!("`{key}` is missing")),
+        }
+    }
+}
+
+/// The planned changes for a release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// The version before the release.
+    pub previous: Version,
+    /// The version being released.
+    pub version: Version,
+    /// The file changes that make up the release.
+    pub changes: Vec<FileChange>,
+}
+
+/// Plans a release of the crate at `root`, bumping its version by `bump`.
+///
+/// `notes` becomes the body of the new CHANGELOG.md section, unless the
+/// changelog already has an `## Unreleased` section, which is renamed instead.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read, or Cargo.toml has no valid
+/// `version`.
+pub fn prepare(root: &Path, bump: &Bump, notes: Option<&str>) -> Result<Release> {
+    let manifest_path = root.join("Cargo.toml");
+    let original_manifest = Document::read(&manifest_path)?;
+    let package_string = |key: &str| {
+        original_manifest
+            .get_str("package", key)
+            .ok_or_else(|| Error::malformed(&manifest_path, format!("`package.{key}` is missing")))
+    };
+    let previous: Version = package_string("version")?.parse()?;
+    let name = package_string("name")?;
+    let version = previous.bumped(bump);
+
+    // Original and updated contents of every file touched so far
+    let mut files: BTreeMap<PathBuf, (String, String)> = BTreeMap::new();
+    let load = |files: &mut BTreeMap<PathBuf, (String, String)>, file: &Path| -> Result<()> {
+        if !files.contains_key(file) && root.join(file).is_file() {
+            let contents = fs::read(&root.join(file))?;
+            files.insert(file.to_owned(), (contents.clone(), contents));
+        }
+        Ok(())
+    };
+
+    // The one-shot `ON_RELEASE` comments go first, while their line numbers
+    // are still accurate
+    for file in RELEASE_FILES {
+        let file = Path::new(file);
+        load(&mut files, file)?;
+        let Some((_, contents)) = files.get_mut(file) else {
+            continue;
+        };
+        let Some(language) = Language::from_path(file) else {
+            continue;
+        };
+        let one_shot: Vec<Marker> = markers::scan_file(file, language, contents)
+            .into_iter()
+            .filter(|marker| {
+                marker.kind == MarkerKind::OnRelease && !is_permanent(marker, &original_manifest)
+            })
+            .collect();
+        *contents = remove_markers(contents, &one_shot);
+    }
+    if let Some((_, contents)) = files.get_mut(Path::new("Cargo.toml")) {
+        let mut manifest = Document::parse(&manifest_path, contents)?;
+        manifest.set("package", "version", &version.to_string().into());
+        let publish = manifest.get("package", "publish");
+        if publish.is_some_and(|entry| entry.value == Value::Boolean(false)) {
+            manifest.remove("package", "publish");
+        }
+        *contents = manifest.to_string();
+    }
+    for file in ["README.md", "src/lib.rs"] {
+        if let Some((_, contents)) = files.get_mut(Path::new(file)) {
+            *contents = pin_docs_links(contents, &name, &version);
+        }
+    }
+    if let Some((_, contents)) = files.get_mut(Path::new("CHANGELOG.md")) {
+        *contents = add_changelog_section(contents, &version, notes);
+    }
+
+    let changes = files
+        .into_iter()
+        .filter_map(|(file, (original, updated))| FileChange::new(file, original, updated))
+        .collect();
+
+    Ok(Release {
+        previous,
+        version,
+        changes,
+    })
+}
+
+/// Pins the crate's own docs.rs links in `contents` to `version`, whether
+/// they have the [`VERSION_PLACEHOLDER`] or an earlier version. The crate's
+/// `name` is matched the way docs.rs does (see [`crate_name::canonical`]),
+/// and links to `latest` or to other crates are left alone.
+fn pin_docs_links(contents: &str, name: &str, version: &Version) -> String {
+    const PREFIX: &str = "docs.rs/";
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let is_version_char = |c: char| c.is_ascii_alphanumeric() || ".-+_".contains(c);
+
+    let mut output = String::with_capacity(contents.len());
+    let mut rest = contents;
+    while let Some(index) = rest.find(PREFIX) {
+        output.push_str(&rest[..index + PREFIX.len()]);
+        rest = &rest[index + PREFIX.len()..];
+
+        let name_end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+        let (linked, after) = rest.split_at(name_end);
+        let Some(after) = after.strip_prefix('/') else {
+            continue;
+        };
+        if crate_name::canonical(linked) != crate_name::canonical(name) {
+            continue;
+        }
+        let segment_end = after
+            .find(|c: char| !is_version_char(c))
+            .unwrap_or(after.len());
+        let segment = &after[..segment_end];
+        if segment == VERSION_PLACEHOLDER || segment.parse::<Version>().is_ok() {
+            output.push_str(linked);
+            output.push('/');
+            output.push_str(&version.to_string());
+            rest = &after[segment_end..];
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Whether `marker` is the `ON_RELEASE` reminder on the `version` line of
+/// `manifest` (the crate's Cargo.toml), which applies to every release.
+fn is_permanent(marker: &Marker, manifest: &Document) -> bool {
+    marker.kind == MarkerKind::OnRelease
+        && marker.inline
+        && marker.file == Path::new("Cargo.toml")
+        && manifest
+            .get("package", "version")
+            .is_some_and(|entry| entry.line == marker.line)
+}
+
+/// Deletes the given markers (all from the same file) from `contents`.
+///
+/// Markers on lines of their own are removed along with their lines, while
+/// markers trailing code only have their comment stripped.
+fn remove_markers(contents: &str, markers: &[Marker]) -> String {
+    let mut lines: Vec<Option<String>> = contents
+        .split_inclusive('\n')
+        .map(|line| Some(line.to_owned()))
+        .collect();
+
+    for marker in markers {
+        if marker.inline {
+            if let Some(Some(line)) = lines.get_mut(marker.line - 1) {
+                *line = strip_trailing_comment(line, marker.kind.keyword());
+            }
+        } else {
+            for line in lines.iter_mut().take(marker.end_line).skip(marker.line - 1) {
+                *line = None;
+            }
+        }
+    }
+
+    lines.into_iter().flatten().collect()
+}
+
+/// Removes the comment containing `keyword` from the end of `line`.
+fn strip_trailing_comment(line: &str, keyword: &str) -> String {
+    let ending = &line[line.trim_end().len()..];
+    let Some(keyword_start) = line.find(keyword) else {
+        return line.to_owned();
+    };
+
+    let comment_start = ["<!--", "//", "#"]
+        .iter()
+        .filter_map(|opener| line[..keyword_start].rfind(opener))
+        .max()
+        .unwrap_or(keyword_start);
+    format!("{}{ending}", line[..comment_start].trim_end())
+}
+
+/// Adds a `## vX.Y.Z` section for `version` under its `# Major Version N`
+/// heading, creating the heading if needed.
+fn add_changelog_section(contents: &str, version: &Version, notes: Option<&str>) -> String {
+    let section = format!("## v{version}");
+
+    let lines: Vec<&str> = contents.lines().collect();
+    if let Some(index) = lines
+        .iter()
+        .position(|line| line.trim().eq_ignore_ascii_case("## unreleased"))
+    {
+        let mut lines: Vec<String> = lines.iter().map(|&line| line.to_owned()).collect();
+        lines[index] = section;
+        return lines.join("\n") + "\n";
+    }
+
+    let body = notes.unwrap_or("No notable changes.");
+    let heading = format!("# Major Version {}", version.major);
+    let mut output: Vec<String> = Vec::with_capacity(lines.len() + 6);
+    let mut inserted = false;
+    for &line in &lines {
+        if !inserted && line.trim() == heading {
+            output.push(line.to_owned());
+            output.extend(["", &section, "", body].map(str::to_owned));
+            inserted = true;
+            continue;
+        }
+
+        // Newer major versions come first, so a missing heading goes before
+        // the first older one
+        let older = line
+            .strip_prefix("# Major Version ")
+            .and_then(|major| major.trim().parse::<u64>().ok())
+            .is_some_and(|major| major < version.major);
+        if !inserted && older {
+            output.extend([&heading, "", &section, "", body, ""].map(str::to_owned));
+            inserted = true;
+        }
+        output.push(line.to_owned());
+    }
+    if !inserted {
+        if output.last().is_some_and(|line| !line.is_empty()) {
+            output.push(String::new());
+        }
+        output.extend([&heading, "", &section, "", body].map(str::to_owned));
+    }
+
+    output.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Scratch;
+
+    const MANIFEST: &str = "\
+[package]
+name = \"example\"
+version = \"0.1.0\" # ON_RELEASE: Bump version. Also, do all \"ON_RELEASE\" tasks
+description = \"An example\"
+keywords = [\"example\"]
+categories = [\"development-tools\"]
+publish = false # ON_RELEASE: Remove publish = false
+";
+
+    const README: &str = "\
+# example
+
+<!-- ON_RELEASE: the below link(s) should be updated, and this comment removed -->
+[`Item`]: https://docs.rs/example/__CRATE_VERSION_HERE__/example/struct.Item.html
+";
+
+    const LIB: &str = "\
+// These links must be kept in sync with the readme.
+// ON_RELEASE: the below link(s) should be verified to match the readme, and
+// this \"on release\" comment removed (the above one should stay).
+//! [`Item`]: Item
+";
+
+    const CHANGELOG: &str = "# Major Version 0\n\n## v0.1.0\n\nInitial release.\n";
+
+    fn scratch() -> Scratch {
+        Scratch::new(&[
+            ("Cargo.toml", MANIFEST),
+            ("README.md", README),
+            ("src/lib.rs", LIB),
+            ("CHANGELOG.md", CHANGELOG),
+        ])
+    }
+
+    fn updated<'a>(release: &'a Release, file: &str) -> &'a str {
+        let change = release
+            .changes
+            .iter()
+            .find(|change| change.path == Path::new(file))
+            .unwrap();
+        &change.updated
+    }
+
+    #[test]
+    fn prepare_removes_only_one_shot_markers() {
+        let scratch = scratch();
+        let release = prepare(scratch.path(), &Bump::Minor, None).unwrap();
+        assert_eq!(release.version.to_string(), "0.2.0");
+
+        let manifest = updated(&release, "Cargo.toml");
+        assert!(manifest.contains(
+            "version = \"0.2.0\" # ON_RELEASE: Bump version. Also, do all \"ON_RELEASE\" tasks\n"
+        ));
+        assert!(!manifest.contains("publish"));
+
+        assert_eq!(
+            updated(&release, "README.md"),
+            "# example\n\n[`Item`]: https://docs.rs/example/0.2.0/example/struct.Item.html\n"
+        );
+        assert_eq!(
+            updated(&release, "src/lib.rs"),
+            "// These links must be kept in sync with the readme.\n//! [`Item`]: Item\n"
+        );
+        assert!(updated(&release, "CHANGELOG.md").contains("## v0.2.0\n\nNo notable changes."));
+    }
+
+    #[test]
+    fn prepare_only_edits_release_files() {
+        let scratch = Scratch::new(&[
+            ("Cargo.toml", MANIFEST),
+            ("README.md", README),
+            ("src/lib.rs", LIB),
+            ("CHANGELOG.md", CHANGELOG),
+            ("src/other.rs", "// ON_RELEASE: not for prepare\n"),
+            (
+                "xtask/src/fixture.rs",
+                "const FIXTURE: &str = r\"\n// ON_RELEASE: fixture\n\";\n// ON_RELEASE: tooling\n",
+            ),
+        ]);
+        let release = prepare(scratch.path(), &Bump::Patch, None).unwrap();
+        let mut files: Vec<&Path> = release
+            .changes
+            .iter()
+            .map(|change| change.path.as_path())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            ["CHANGELOG.md", "Cargo.toml", "README.md", "src/lib.rs"].map(Path::new)
+        );
+    }
+
+    #[test]
+    fn consecutive_releases_keep_docs_links_pinned() {
+        let readme = format!(
+            "{README}[`Other`]: https://docs.rs/Example/__CRATE_VERSION_HERE__/example/struct.Other.html\n\
+             [latest]: https://docs.rs/example/latest/example/\n\
+             [dependency]: https://docs.rs/example-core/1.0.0/example_core/\n"
+        );
+        let scratch = Scratch::new(&[
+            ("Cargo.toml", MANIFEST),
+            ("README.md", &readme),
+            ("src/lib.rs", LIB),
+            ("CHANGELOG.md", CHANGELOG),
+        ]);
+        for bump in [Bump::Patch, Bump::Minor] {
+            let release = prepare(scratch.path(), &bump, None).unwrap();
+            crate::changes::apply(scratch.path(), &release.changes).unwrap();
+        }
+
+        assert_eq!(
+            fs::read(&scratch.path().join("README.md")).unwrap(),
+            "# example\n\n\
+             [`Item`]: https://docs.rs/example/0.2.0/example/struct.Item.html\n\
+             [`Other`]: https://docs.rs/Example/0.2.0/example/struct.Other.html\n\
+             [latest]: https://docs.rs/example/latest/example/\n\
+             [dependency]: https://docs.rs/example-core/1.0.0/example_core/\n"
+        );
+        assert_eq!(check(scratch.path()).unwrap(), []);
+    }
+
+    #[test]
+    fn check_accepts_prepared_release() {
+        let scratch = scratch();
+        assert!(!check(scratch.path()).unwrap().is_empty());
+
+        let release = prepare(scratch.path(), &Bump::Patch, None).unwrap();
+        crate::changes::apply(scratch.path(), &release.changes).unwrap();
+        assert_eq!(check(scratch.path()).unwrap(), []);
+    }
+
+    #[test]
+    fn check_only_covers_packaged_files() {
+        let manifest = format!(
+            "{MANIFEST}include = [\"/src/\", \"/Cargo.toml\", \"/README.md\", \"/CHANGELOG.md\"]\n"
+        );
+        let scratch = Scratch::new(&[
+            ("Cargo.toml", &manifest),
+            ("README.md", README),
+            ("src/lib.rs", LIB),
+            ("src/todo.rs", "// TODO: shipped\n"),
+            ("CHANGELOG.md", CHANGELOG),
+            ("docs/notes.md", "TODO: unpublished notes\n"),
+            ("xtask/Cargo.toml", "[package]\nname = \"xtask\"\n"),
+            ("xtask/src/main.rs", "// TODO: tooling\n"),
+        ]);
+        let files = packaged_files(
+            scratch.path(),
+            &Document::parse(Path::new("Cargo.toml"), &manifest).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            files,
+            [
+                "CHANGELOG.md",
+                "Cargo.toml",
+                "README.md",
+                "src/lib.rs",
+                "src/todo.rs"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let release = prepare(scratch.path(), &Bump::Patch, None).unwrap();
+        crate::changes::apply(scratch.path(), &release.changes).unwrap();
+        let diagnostics: Vec<String> = check(scratch.path())
+            .unwrap()
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            ["src/todo.rs:1: unresolved TODO marker: shipped"]
+        );
+    }
+
+    #[test]
+    fn ignore_patterns() {
+        for (pattern, path, matches) in [
+            ("/src/", "src/lib.rs", true),
+            ("/src/", "src", false),
+            ("/src/", "xtask/src/main.rs", false),
+            ("/LICENSE-*", "LICENSE-MIT", true),
+            ("*.md", "docs/notes.md", true),
+            ("docs/*.md", "docs/notes.md", true),
+            ("docs", "docs/notes.md", true),
+            ("/README.md", "docs/README.md", false),
+        ] {
+            assert_eq!(
+                ignore_pattern_matches(pattern, Path::new(path)),
+                matches,
+                "{pattern} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn changelog_section_goes_under_its_major_version() {
+        let version = "1.0.0".parse().unwrap();
+        assert_eq!(
+            add_changelog_section(CHANGELOG, &version, Some("Stable.")),
+            "# Major Version 1\n\n## v1.0.0\n\nStable.\n\n# Major Version 0\n\n## v0.1.0\n\n\
+             Initial release.\n"
+        );
+    }
+}
@@ -0,0 +1,789 @@
+//! The template manifest, `template.toml`.
+//!
+//! The manifest is the one place that describes how the template becomes a
+//! crate: the [variables](Declaration) its placeholders use, the
+//! [variants](Variant) it can be instantiated as, a [`Rule`] for each file and
+//! the [checks](Check) a freshly instantiated crate has to pass. Instantiating,
+//! upgrading and drift reports are all driven by it, and [`Template::validate`]
+//! checks that it agrees with the template's files.
+//!
+//! The variables are the placeholder language's (see [`Variable`]), each
+//! with the type instantiating and upgrading rely on (like the crate name
+//! being a valid one), so the manifest declares how to ask for them and where
+//! their defaults come from, but can't add new ones.
+//!
+//! The manifest only belongs to the template, so instantiating deletes it.
+//! Upgrades and drift reports read it from the template's repository instead.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    Error, Result, conditional, fs, git,
+    markers::Language,
+    placeholder::{self, Values, Variable},
+    process,
+    release::Diagnostic,
+    toml::{Document, Value},
+    variant::{self, Variant},
+};
+
+/// The path of the manifest, relative to the template's root.
+pub const PATH: &str = "template.toml";
+
+/// The keys a variable's table may have.
+const VARIABLE_KEYS: &[&str] = &["prompt", "required", "default", "default-from"];
+
+/// The keys a check's table may have.
+const CHECK_KEYS: &[&str] = &["description", "command", "absent"];
+
+/// A parsed template manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    /// The variables placeholders can use, in the order they're declared.
+    pub variables: Vec<Declaration>,
+    /// Every variant, in the order they're defined.
+    pub variants: Vec<Variant>,
+    /// The file patterns of each rule, in the order they're listed.
+    pub files: Vec<Pattern>,
+    /// The checks to run after instantiating, in the order they're defined.
+    pub checks: Vec<Check>,
+}
+
+/// The declaration of a variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    /// The variable being declared.
+    pub variable: Variable,
+    /// What to ask for.
+    pub prompt: String,
+    /// Whether instantiating fails without a value.
+    pub required: bool,
+    /// Where the variable's value comes from if none is given.
+    pub default: Option<DefaultValue>,
+    /// The (1-based) line of the declaration's `prompt` in the manifest.
+    pub line: usize,
+}
+
+impl Declaration {
+    /// The variable's default value, for the template at `root`. A value from
+    /// git's configuration that doesn't have the variable's type is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if git cannot be run.
+    pub fn default_value(&self, root: &Path) -> Result<Option<String>> {
+        Ok(match &self.default {
+            None => None,
+            Some(DefaultValue::Value(value)) => Some(value.clone()),
+            Some(DefaultValue::CurrentYear) => Some(placeholder::current_year().to_string()),
+            Some(DefaultValue::GitConfig(key)) => {
+                git::config(root, key)?.filter(|value| self.variable.validate(value).is_ok())
+            }
+        })
+    }
+}
+
+/// Where a variable's default value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefaultValue {
+    /// A fixed value.
+    Value(String),
+    /// The current year.
+    CurrentYear,
+    /// A git configuration key, like `user.name`.
+    GitConfig(String),
+}
+
+/// What instantiating does with a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// The file is kept as it is.
+    Copy,
+    /// The file's conditional blocks are resolved and its placeholders filled
+    /// in.
+    Render,
+    /// The file is deleted, since it only belongs to the template.
+    Skip,
+}
+
+impl Rule {
+    /// The rule's key in the manifest's `[files]` table.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Copy => "copy",
+            Self::Render => "render",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+/// A pattern in the manifest's `[files]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    /// The rule for the files the pattern matches.
+    pub rule: Rule,
+    /// The pattern, where `*` matches within a path component and `**`
+    /// across them.
+    pub glob: String,
+    /// The (1-based) line of the pattern's list in the manifest.
+    pub line: usize,
+}
+
+impl Pattern {
+    /// Whether the pattern matches `path`, relative to the crate root.
+    pub fn matches(&self, path: &Path) -> bool {
+        glob_matches(&self.glob, path)
+    }
+}
+
+/// Whether `glob`, where `*` matches within a path component and `**` across
+/// them, matches `path`.
+pub(crate) fn glob_matches(glob: &str, path: &Path) -> bool {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let components: Vec<&str> = path.split('/').collect();
+    let glob: Vec<&str> = glob.split('/').collect();
+    matches_components(&glob, &components)
+}
+
+/// Whether the `glob` components match the path `components`.
+fn matches_components(glob: &[&str], components: &[&str]) -> bool {
+    match (glob.split_first(), components.split_first()) {
+        (None, _) => components.is_empty(),
+        (Some((&"**", rest)), _) => {
+            (0..=components.len()).any(|skip| matches_components(rest, &components[skip..]))
+        }
+        (Some((pattern, rest)), Some((component, remaining))) => {
+            matches_component(pattern, component) && matches_components(rest, remaining)
+        }
+        (Some(_), None) => false,
+    }
+}
+
+/// Whether `pattern`, where `*` matches any run of characters, matches
+/// `text`.
+fn matches_component(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// A check run after instantiating.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// The check's name.
+    pub name: String,
+    /// What the check makes sure of.
+    pub description: String,
+    /// What the check does.
+    pub kind: CheckKind,
+}
+
+/// What a [`Check`] does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckKind {
+    /// Runs a command (the program and its arguments) from the crate root,
+    /// which must succeed.
+    Command(Vec<String>),
+    /// Looks for text that must not appear in any rendered file.
+    Absent(Vec<String>),
+}
+
+impl Check {
+    /// Runs the check on the crate at `root`, whose rendered files are
+    /// `rendered`, returning what is wrong (nothing if the check passed).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command cannot be run, or a file cannot be
+    /// read.
+    pub fn run(&self, root: &Path, rendered: &[PathBuf]) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        match &self.kind {
+            CheckKind::Command(command) => {
+                let Some((program, arguments)) = command.split_first() else {
+                    return Ok(problems);
+                };
+                let output =
+                    process::output(Command::new(program).args(arguments).current_dir(root))?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    problems.push(match stderr.lines().rfind(|line| !line.trim().is_empty()) {
+                        Some(line) => format!("`{}` failed: {}", command.join(" "), line.trim()),
+                        None => format!("`{}` failed ({})", command.join(" "), output.status),
+                    });
+                }
+            }
+            CheckKind::Absent(texts) => {
+                for file in rendered {
+                    let contents = fs::read(&root.join(file))?;
+                    for (index, line) in contents.lines().enumerate() {
+                        for text in texts.iter().filter(|&text| line.contains(text.as_str())) {
+                            problems.push(format!(
+                                "{}:{}: contains `{text}`",
+                                file.display(),
+                                index + 1
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(problems)
+    }
+}
+
+impl Template {
+    /// Reads the manifest of the template at `root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no manifest (as in an instantiated
+    /// crate), or it cannot be read or is malformed.
+    pub fn read(root: &Path) -> Result<Self> {
+        let path = root.join(PATH);
+        if !path.is_file() {
+            return Err(Error::InvalidInput(format!(
+                "no template manifest at {} (instantiating deletes it, so this may already be an \
+                 instantiated crate)",
+                path.display()
+            )));
+        }
+        Self::parse(Path::new(PATH), &fs::read(&path)?)
+    }
+
+    /// Parses the manifest `contents`. `path` is only used for error messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest is malformed: not valid TOML, an
+    /// unknown table or key, an unknown or repeated variable, or a value of
+    /// the wrong type.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let manifest = Document::parse(path, contents)?;
+        let malformed = |message: String| Error::malformed(path, message);
+
+        if let Some(entry) = manifest.entries("").first() {
+            return Err(malformed(format!(
+                "line {}: unexpected top-level key `{}`",
+                entry.line, entry.key
+            )));
+        }
+        for table in manifest.tables() {
+            let known = table == "files"
+                || ["variables.", "variants.", "checks."]
+                    .iter()
+                    .any(|prefix| table.starts_with(prefix));
+            if !known {
+                return Err(malformed(format!("unknown table `[{table}]`")));
+            }
+        }
+
+        Ok(Self {
+            variables: parse_variables(path, &manifest)?,
+            variants: Variant::parse_all(path, &manifest)?,
+            files: parse_files(path, &manifest)?,
+            checks: parse_checks(path, &manifest)?,
+        })
+    }
+
+    /// The rule for the file at `path`, relative to the crate root: that of
+    /// the first pattern matching it, or [`Rule::Copy`] if none does.
+    pub fn rule(&self, path: &Path) -> Rule {
+        self.files
+            .iter()
+            .find(|pattern| pattern.matches(path))
+            .map_or(Rule::Copy, |pattern| pattern.rule)
+    }
+
+    /// Lists the files under `root` that are rendered, as sorted paths
+    /// relative to `root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be read.
+    pub fn rendered(&self, root: &Path) -> Result<Vec<PathBuf>> {
+        Ok(fs::walk(root)?
+            .into_iter()
+            .filter(|path| self.rule(path) == Rule::Render)
+            .collect())
+    }
+
+    /// Fills in the defaults of the variables missing from `given`, for the
+    /// template at `root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a required variable has no value, or git cannot be
+    /// run.
+    pub fn values(&self, root: &Path, mut given: Values) -> Result<Values> {
+        for declaration in &self.variables {
+            if given.get(declaration.variable).is_some() {
+                continue;
+            }
+            match declaration.default_value(root)? {
+                Some(value) => given.set(declaration.variable, value)?,
+                None if declaration.required => {
+                    return Err(Error::InvalidInput(format!(
+                        "missing a value for `{}` ({})",
+                        declaration.variable, declaration.prompt
+                    )));
+                }
+                None => {}
+            }
+        }
+        Ok(given)
+    }
+
+    /// Checks that the manifest agrees with the files of the template at
+    /// `root`.
+    ///
+    /// Reported are patterns that match no file, files that more than one
+    /// rule matches, conditional blocks and placeholders in files that aren't
+    /// rendered, variables that are declared but unused (or used but not
+    /// declared, or not always given a value), and `.gitignore` lines a combination of variants would
+    /// drop. An empty list means the manifest is consistent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read, or its conditional blocks or
+    /// placeholders are malformed.
+    pub fn validate(&self, root: &Path) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        let files = fs::walk(root)?;
+
+        for pattern in &self.files {
+            if !files.iter().any(|file| pattern.matches(file)) {
+                diagnostics.push(Diagnostic::new(
+                    PATH,
+                    Some(pattern.line),
+                    format!(
+                        "`{}` in `files.{}` doesn't match any file",
+                        pattern.glob,
+                        pattern.rule.name()
+                    ),
+                ));
+            }
+        }
+
+        let mut used = BTreeSet::new();
+        for file in &files {
+            let rules: BTreeSet<&str> = self
+                .files
+                .iter()
+                .filter(|pattern| pattern.matches(file))
+                .map(|pattern| pattern.rule.name())
+                .collect();
+            if rules.len() > 1 {
+                let rules: Vec<&str> = rules.into_iter().collect();
+                diagnostics.push(Diagnostic::new(
+                    file,
+                    None,
+                    format!("matched by more than one rule ({})", rules.join(", ")),
+                ));
+            }
+
+            let plain_text = placeholder::PLAIN_TEXT
+                .iter()
+                .any(|(path, ..)| Path::new(path) == file);
+            if Language::from_path(file).is_none() && !plain_text {
+                continue;
+            }
+            let contents = fs::read(&root.join(file))?;
+            let blocks = conditional::blocks(file, &contents)?;
+            let directives = placeholder::directives(file, &contents)?;
+            if self.rule(file) != Rule::Render {
+                let lines = blocks
+                    .iter()
+                    .map(|block| (block.line, "a conditional block"))
+                    .chain(
+                        directives
+                            .iter()
+                            .map(|directive| (directive.line, "a placeholder")),
+                    );
+                for (line, what) in lines {
+                    diagnostics.push(Diagnostic::new(
+                        file,
+                        Some(line),
+                        format!("{what} in a file that isn't rendered (add it to `files.render`)"),
+                    ));
+                }
+                continue;
+            }
+            for directive in directives {
+                for &variable in &directive.variables {
+                    used.insert(variable);
+                    if !self.variables.iter().any(|d| d.variable == variable) {
+                        diagnostics.push(Diagnostic::new(
+                            file,
+                            Some(directive.line),
+                            format!("placeholder uses `{variable}`, which isn't declared"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if files.iter().any(|file| file == Path::new(".gitignore")) {
+            let base = fs::read(&root.join(".gitignore"))?;
+            diagnostics.extend(self.check_gitignore(&base));
+        }
+
+        for declaration in &self.variables {
+            let always_set = declaration.required
+                || matches!(
+                    declaration.default,
+                    Some(DefaultValue::Value(_) | DefaultValue::CurrentYear)
+                );
+            let problem = if !used.contains(&declaration.variable) {
+                "isn't used by any rendered file"
+            } else if !always_set {
+                // Checked before anything is written, unlike the checks run
+                // after instantiating
+                "is used by rendered files, so it has to be `required` (or have a `default`) \
+                 for instantiating not to leave its placeholders behind"
+            } else {
+                continue;
+            };
+            diagnostics.push(Diagnostic::new(
+                PATH,
+                Some(declaration.line),
+                format!("variable `{}` {problem}", declaration.variable),
+            ));
+        }
+
+        diagnostics.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        Ok(diagnostics)
+    }
+
+    /// Checks that every combination of variants that can be chosen together
+    /// keeps every line of the template's `base` .gitignore, since variants
+    /// only add to it.
+    fn check_gitignore(&self, base: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let count = self.variants.len().min(usize::BITS as usize - 1);
+        for combination in 1..1_usize << count {
+            let names: Vec<String> = (0..count)
+                .filter(|index| combination & 1 << index != 0)
+                .map(|index| self.variants[index].name.clone())
+                .collect();
+            let Ok(chosen) = variant::select(&self.variants, &names) else {
+                continue;
+            };
+            let composed = variant::gitignore(base, &chosen);
+            for (index, line) in base.lines().enumerate() {
+                if !composed.lines().any(|composed| composed == line) {
+                    diagnostics.push(Diagnostic::new(
+                        ".gitignore",
+                        Some(index + 1),
+                        format!(
+                            "`{line}` is dropped when instantiating with --variant {}",
+                            names.join(" --variant ")
+                        ),
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Reads the array of strings `key` of `table`, if it's there.
+fn strings(
+    path: &Path,
+    manifest: &Document,
+    table: &str,
+    key: &str,
+) -> Result<Option<Vec<String>>> {
+    let Some(entry) = manifest.get(table, key) else {
+        return Ok(None);
+    };
+    entry
+        .value
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(str::to_owned))
+                .collect()
+        })
+        .map(Some)
+        .ok_or_else(|| {
+            Error::malformed(
+                path,
+                format!(
+                    "line {}: `{table}.{key}` is not an array of strings",
+                    entry.line
+                ),
+            )
+        })
+}
+
+/// Parses the `[variables.NAME]` tables.
+fn parse_variables(path: &Path, manifest: &Document) -> Result<Vec<Declaration>> {
+    let mut declarations: Vec<Declaration> = Vec::new();
+    for table in manifest.tables() {
+        let Some(name) = table.strip_prefix("variables.") else {
+            continue;
+        };
+        let malformed =
+            |message: &str| Error::malformed(path, format!("variable `{name}`: {message}"));
+        let variable = Variable::from_name(name).ok_or_else(|| {
+            let known: Vec<&str> = Variable::ALL.iter().map(|v| v.name()).collect();
+            malformed(&format!(
+                "unknown variable (expected one of {})",
+                known.join(", ")
+            ))
+        })?;
+        if declarations.iter().any(|d| d.variable == variable) {
+            return Err(malformed("declared more than once"));
+        }
+        let entries = manifest.entries(table);
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| !VARIABLE_KEYS.contains(&entry.key))
+        {
+            return Err(malformed(&format!("unknown key `{}`", entry.key)));
+        }
+
+        let prompt = manifest
+            .get(table, "prompt")
+            .ok_or_else(|| malformed("missing `prompt`"))?;
+        let required = match manifest.get(table, "required").map(|entry| entry.value) {
+            None => false,
+            Some(Value::Boolean(required)) => required,
+            Some(_) => return Err(malformed("`required` is not a boolean")),
+        };
+        let default = match (
+            manifest.get(table, "default"),
+            manifest.get_str(table, "default-from").as_deref(),
+        ) {
+            (Some(_), Some(_)) => {
+                return Err(malformed(
+                    "only one of `default` and `default-from` is allowed",
+                ));
+            }
+            (Some(entry), None) => {
+                let value = entry
+                    .value
+                    .as_str()
+                    .ok_or_else(|| malformed("`default` is not a string"))?;
+                variable
+                    .validate(value)
+                    .map_err(|error| malformed(&error.to_string()))?;
+                Some(DefaultValue::Value(value.to_owned()))
+            }
+            (None, Some("current-year")) => Some(DefaultValue::CurrentYear),
+            (None, Some(source)) => match source.strip_prefix("git-config:") {
+                Some(key) if !key.is_empty() => Some(DefaultValue::GitConfig(key.to_owned())),
+                _ => {
+                    return Err(malformed(&format!(
+                        "unknown `default-from` `{source}` (expected current-year or \
+                         git-config:KEY)"
+                    )));
+                }
+            },
+            (None, None) => None,
+        };
+
+        declarations.push(Declaration {
+            variable,
+            prompt: prompt
+                .value
+                .as_str()
+                .ok_or_else(|| malformed("`prompt` is not a string"))?
+                .to_owned(),
+            required,
+            default,
+            line: prompt.line,
+        });
+    }
+    Ok(declarations)
+}
+
+/// Parses the `[files]` table.
+fn parse_files(path: &Path, manifest: &Document) -> Result<Vec<Pattern>> {
+    let mut patterns = Vec::new();
+    for entry in manifest.entries("files") {
+        let rule = [Rule::Copy, Rule::Render, Rule::Skip]
+            .into_iter()
+            .find(|rule| rule.name() == entry.key)
+            .ok_or_else(|| {
+                Error::malformed(
+                    path,
+                    format!(
+                        "line {}: unknown rule `{}` (expected copy, render or skip)",
+                        entry.line, entry.key
+                    ),
+                )
+            })?;
+        let globs = strings(path, manifest, "files", entry.key)?.unwrap_or_default();
+        patterns.extend(globs.into_iter().map(|glob| Pattern {
+            rule,
+            glob,
+            line: entry.line,
+        }));
+    }
+    Ok(patterns)
+}
+
+/// Parses the `[checks.NAME]` tables.
+fn parse_checks(path: &Path, manifest: &Document) -> Result<Vec<Check>> {
+    manifest
+        .tables()
+        .filter_map(|table| Some((table, table.strip_prefix("checks.")?)))
+        .map(|(table, name)| {
+            let malformed =
+                |message: &str| Error::malformed(path, format!("check `{name}`: {message}"));
+            if let Some(entry) = manifest
+                .entries(table)
+                .into_iter()
+                .find(|entry| !CHECK_KEYS.contains(&entry.key))
+            {
+                return Err(malformed(&format!("unknown key `{}`", entry.key)));
+            }
+            let kind = match (
+                strings(path, manifest, table, "command")?,
+                strings(path, manifest, table, "absent")?,
+            ) {
+                (Some(command), None) if !command.is_empty() => CheckKind::Command(command),
+                (None, Some(texts)) => CheckKind::Absent(texts),
+                _ => {
+                    return Err(malformed(
+                        "needs exactly one of `command` (not empty) and `absent`",
+                    ));
+                }
+            };
+            Ok(Check {
+                name: name.to_owned(),
+                description: manifest
+                    .get_str(table, "description")
+                    .ok_or_else(|| malformed("missing or invalid `description`"))?,
+                kind,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Template {
+        Template::parse(Path::new(PATH), include_str!("../../template.toml")).unwrap()
+    }
+
+    #[test]
+    fn variants_keep_the_gitignore() {
+        assert_eq!(
+            template().check_gitignore(include_str!("../../.gitignore")),
+            []
+        );
+    }
+
+    #[test]
+    fn sample_values_are_caught() {
+        let scratch = fs::Scratch::new(&[
+            ("LICENSE-MIT", "Copyright (c) TODO_YEAR Isaac Chen\n"),
+            (
+                "Cargo.toml",
+                "# @render name = \"{{ name }}\"\nname = \"crate-name\"\n",
+            ),
+            ("README.md", "# demo\n"),
+        ]);
+        let check = template()
+            .checks
+            .into_iter()
+            .find(|check| check.name == "sample-name")
+            .unwrap();
+        let rendered = ["LICENSE-MIT", "Cargo.toml", "README.md"].map(PathBuf::from);
+        assert_eq!(
+            check.run(scratch.path(), &rendered).unwrap(),
+            [
+                "LICENSE-MIT:1: contains `Isaac Chen`",
+                "LICENSE-MIT:1: contains `TODO_YEAR`",
+                "Cargo.toml:1: contains `@render`",
+                "Cargo.toml:2: contains `crate-name`",
+            ]
+        );
+    }
+
+    #[test]
+    fn used_variables_always_get_a_value() {
+        let manifest = "\
+[variables.name]
+prompt = \"Crate name\"
+required = true
+
+[variables.author]
+prompt = \"Author\"
+default-from = \"git-config:user.name\"
+
+[variables.year]
+prompt = \"Year\"
+default-from = \"current-year\"
+
+[files]
+render = [\"Cargo.toml\"]
+";
+        let scratch = fs::Scratch::new(&[(
+            "Cargo.toml",
+            "# @render name = \"{{ name }}\"\nname = \"x\"\n# @render authors = [\"{{ author }}\"]\nauthors = []\n",
+        )]);
+        let diagnostics: Vec<String> = Template::parse(Path::new(PATH), manifest)
+            .unwrap()
+            .validate(scratch.path())
+            .unwrap()
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                "template.toml:6: variable `author` is used by rendered files, so it has to be \
+                 `required` (or have a `default`) for instantiating not to leave its placeholders \
+                 behind",
+                "template.toml:10: variable `year` isn't used by any rendered file",
+            ]
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let template = template();
+        assert_eq!(template.rule(Path::new("Cargo.toml")), Rule::Render);
+        assert_eq!(template.rule(Path::new("LICENSE-MIT")), Rule::Render);
+        assert_eq!(template.rule(Path::new("template.toml")), Rule::Skip);
+        assert_eq!(template.rule(Path::new("src/lib.rs")), Rule::Copy);
+    }
+
+    #[test]
+    fn patterns() {
+        let pattern = |glob: &str| Pattern {
+            glob: glob.to_owned(),
+            rule: Rule::Render,
+            line: 1,
+        };
+        assert!(pattern("src/*.rs").matches(Path::new("src/lib.rs")));
+        assert!(!pattern("src/*.rs").matches(Path::new("src/a/b.rs")));
+        assert!(pattern("src/**/*.rs").matches(Path::new("src/a/b.rs")));
+        assert!(pattern("**/*.rs").matches(Path::new("lib.rs")));
+        assert!(!pattern("*.md").matches(Path::new("docs/a.md")));
+    }
+}
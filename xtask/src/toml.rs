@@ -0,0 +1,981 @@
+//! A format-preserving TOML editor.
+//!
+//! A [`Document`] keeps every byte of the file it was parsed from: blank
+//! lines, comments (including the `# # # #` banners of the template's
+//! Cargo.toml), key order, the spacing around `=` and trailing comments. An
+//! unedited document prints back exactly as it was read, and an edit only
+//! touches the text of the value (or comment) being changed.
+//!
+//! Supported: tables and arrays of tables, bare, quoted and dotted keys, and
+//! every kind of value, including multi-line strings and arrays. Keys are
+//! matched as written, so `a.b = 1` under `[x]` is the key `a.b` of table `x`
+//! rather than key `b` of table `x.a`, which is all the template needs.
+
+use std::{fmt, path::Path};
+
+use crate::{Error, Result, fs};
+
+/// A parsed TOML value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A string.
+    String(String),
+    /// An integer.
+    Integer(i64),
+    /// A float, as written.
+    Float(String),
+    /// A boolean.
+    Boolean(bool),
+    /// A date and/or time, as written.
+    Datetime(String),
+    /// An array.
+    Array(Vec<Self>),
+    /// An inline table, as key-value pairs in order.
+    Table(Vec<(String, Self)>),
+}
+
+impl Value {
+    /// Parses the text of a single value.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parser = ValueParser { text, pos: 0 };
+        let value = parser.value().ok()?;
+        parser.skip_whitespace(true);
+        (parser.pos == text.len()).then_some(value)
+    }
+
+    /// The value's text, if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The value's items, if it is an array.
+    pub fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key`, if the value is an inline table.
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Table(entries) => entries
+                .iter()
+                .find(|(entry, _)| entry == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Self::String(text.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Self::String(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Boolean(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl<T: Into<Self>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Self::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value as TOML, on a single line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(text) => {
+                f.write_str("\"")?;
+                for c in text.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\r' => f.write_str("\\r")?,
+                        c if c.is_control() => write!(f, "\\u{:04X}", u32::from(c))?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Float(text) | Self::Datetime(text) => f.write_str(text),
+            Self::Boolean(value) => write!(f, "{value}"),
+            Self::Array(items) => {
+                f.write_str("[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Table(entries) if entries.is_empty() => f.write_str("{}"),
+            Self::Table(entries) => {
+                f.write_str("{ ")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{} = {value}", format_key(key))?;
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+/// Formats `key` bare if it can be, or quoted otherwise.
+fn format_key(key: &str) -> String {
+    if is_bare_key(key) {
+        key.to_owned()
+    } else {
+        Value::from(key).to_string()
+    }
+}
+
+/// Whether `key` can be written without quotes.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A `key = value` entry of a [`Document`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// The (1-based) line the entry starts on.
+    pub line: usize,
+    /// The key, as written (without quotes, for a simple quoted key).
+    pub key: &'a str,
+    /// The value.
+    pub value: Value,
+    /// The text of the value, as written.
+    pub raw: &'a str,
+    /// The trailing comment (including the `#`), if any.
+    pub comment: Option<&'a str>,
+}
+
+/// A TOML document that remembers how it was written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    items: Vec<Item>,
+}
+
+/// A piece of a [`Document`]: one or more whole lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    /// Blank lines and comments (and the line ending).
+    Trivia(String),
+    /// A `[table]` or `[[array]]` header line, with its table name.
+    Header { name: String, raw: String },
+    /// A `key = value` entry.
+    Entry {
+        key: String,
+        /// Everything before the value: indentation, the key as written and
+        /// the `=` with its surrounding whitespace.
+        prefix: String,
+        /// The value as written, which may span several lines.
+        value: String,
+        /// Everything after the value: whitespace, the trailing comment, and
+        /// the line ending.
+        suffix: String,
+    },
+}
+
+impl Item {
+    fn text(&self) -> [&str; 3] {
+        match self {
+            Self::Trivia(raw) | Self::Header { raw, .. } => [raw, "", ""],
+            Self::Entry {
+                prefix,
+                value,
+                suffix,
+                ..
+            } => [prefix, value, suffix],
+        }
+    }
+}
+
+impl Document {
+    /// Reads and parses the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or is not valid TOML.
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::parse(path, &fs::read(path)?)
+    }
+
+    /// Parses `contents`. `path` is only used for error messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `contents` is not valid TOML (within the supported
+    /// subset).
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let mut items = Vec::new();
+        let mut pos = 0;
+        let mut line = 1;
+
+        while pos < contents.len() {
+            let error = |message: &str| Error::malformed(path, format!("line {line}: {message}"));
+            let rest = &contents[pos..];
+            let end_of_line = rest.find('\n').map_or(rest.len(), |index| index + 1);
+            let code = strip_comment(&rest[..end_of_line]).trim();
+
+            let (item, len) = if code.is_empty() {
+                (Item::Trivia(rest[..end_of_line].to_owned()), end_of_line)
+            } else if code.starts_with('[') {
+                let name = code
+                    .strip_prefix("[[")
+                    .and_then(|name| name.strip_suffix("]]"))
+                    .or_else(|| code.strip_prefix('[')?.strip_suffix(']'))
+                    .ok_or_else(|| error("invalid table header"))?;
+                let name = normalize_key(name);
+                let item = Item::Header {
+                    name,
+                    raw: rest[..end_of_line].to_owned(),
+                };
+                (item, end_of_line)
+            } else {
+                let equals =
+                    find_unquoted(rest, '=').ok_or_else(|| error("expected `key = value`"))?;
+                let key = rest[..equals].trim();
+                if key.is_empty() || key.contains('\n') {
+                    return Err(error("expected `key = value`"));
+                }
+
+                let mut parser = ValueParser {
+                    text: rest,
+                    pos: equals + 1,
+                };
+                parser.skip_whitespace(false);
+                let value_start = parser.pos;
+                parser.value().map_err(|message| error(&message))?;
+                let value_end = parser.pos;
+
+                let after = &rest[value_end..];
+                let suffix_len = after.find('\n').map_or(after.len(), |index| index + 1);
+                let suffix = &after[..suffix_len];
+                let trailing = suffix.trim_start_matches([' ', '\t']);
+                if !(trailing.trim().is_empty() || trailing.starts_with('#')) {
+                    return Err(error("unexpected text after value"));
+                }
+
+                let item = Item::Entry {
+                    key: normalize_key(key),
+                    prefix: rest[..value_start].to_owned(),
+                    value: rest[value_start..value_end].to_owned(),
+                    suffix: suffix.to_owned(),
+                };
+                (item, value_end + suffix_len)
+            };
+
+            line += rest[..len].matches('\n').count();
+            pos += len;
+            items.push(item);
+        }
+
+        Ok(Self { items })
+    }
+
+    /// Lists the names of the document's tables, in order (repeating the
+    /// names of arrays of tables).
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Header { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Lists the entries of `table` (`""` for the top-level table), in order.
+    pub fn entries(&self, table: &str) -> Vec<Entry<'_>> {
+        let mut entries = Vec::new();
+        let mut line = 1;
+        let mut current = "";
+
+        for item in &self.items {
+            match item {
+                Item::Header { name, .. } => current = name,
+                Item::Entry {
+                    key, value, suffix, ..
+                } if current == table => {
+                    let comment = strip_comment_start(suffix);
+                    entries.push(Entry {
+                        line,
+                        key,
+                        value: Value::parse(value).unwrap_or_else(|| Value::String(value.clone())),
+                        raw: value,
+                        comment,
+                    });
+                }
+                _ => {}
+            }
+            line += item
+                .text()
+                .iter()
+                .map(|text| text.matches('\n').count())
+                .sum::<usize>();
+        }
+
+        entries
+    }
+
+    /// Looks up `key` in `table`.
+    pub fn get(&self, table: &str, key: &str) -> Option<Entry<'_>> {
+        self.entries(table)
+            .into_iter()
+            .find(|entry| entry.key == key)
+    }
+
+    /// Looks up the string value of `key` in `table`.
+    pub fn get_str(&self, table: &str, key: &str) -> Option<String> {
+        self.get(table, key)?.value.as_str().map(str::to_owned)
+    }
+
+    /// Sets `key` in `table` to `value`, keeping its trailing comment.
+    ///
+    /// A new key is added after the table's last entry (and a new table at the
+    /// end of the document).
+    pub fn set(&mut self, table: &str, key: &str, value: &Value) {
+        if let Some(Item::Entry { value: text, .. }) = self.find_mut(table, key) {
+            *text = value.to_string();
+            return;
+        }
+
+        let entry = Item::Entry {
+            key: key.to_owned(),
+            prefix: format!("{} = ", format_key(key)),
+            value: value.to_string(),
+            suffix: "\n".to_owned(),
+        };
+        if let Some(index) = self.insertion_point(table) {
+            // The entry (or header) before it may be the last line of a file
+            // without a final line ending
+            if let Some(previous) = index.checked_sub(1).map(|index| &mut self.items[index]) {
+                let text = match previous {
+                    Item::Trivia(text) | Item::Header { raw: text, .. } => text,
+                    Item::Entry { suffix, .. } => suffix,
+                };
+                if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+            self.items.insert(index, entry);
+            return;
+        }
+
+        if self
+            .items
+            .last()
+            .is_some_and(|item| !item.text().concat().ends_with('\n'))
+        {
+            self.items.push(Item::Trivia("\n".to_owned()));
+        }
+        if !self.items.is_empty() {
+            self.items.push(Item::Trivia("\n".to_owned()));
+        }
+        self.items.push(Item::Header {
+            name: table.to_owned(),
+            raw: format!("[{table}]\n"),
+        });
+        self.items.push(entry);
+    }
+
+    /// Sets (or, if [`None`], removes) the trailing comment of `key` in
+    /// `table`. `comment` should include the leading `#`.
+    ///
+    /// Returns whether the key exists.
+    pub fn set_comment(&mut self, table: &str, key: &str, comment: Option<&str>) -> bool {
+        let Some(Item::Entry { suffix, .. }) = self.find_mut(table, key) else {
+            return false;
+        };
+        let ending = if suffix.ends_with("\r\n") {
+            "\r\n"
+        } else if suffix.ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        };
+        *suffix = match comment {
+            Some(comment) => format!(" {comment}{ending}"),
+            None => ending.to_owned(),
+        };
+        true
+    }
+
+    /// Removes `key` from `table`, along with its trailing comment.
+    ///
+    /// Returns whether the key existed.
+    pub fn remove(&mut self, table: &str, key: &str) -> bool {
+        match self.position(table, key) {
+            Some(index) => {
+                self.items.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Finds the index of the item for `key` in `table`.
+    fn position(&self, table: &str, key: &str) -> Option<usize> {
+        let mut current = "";
+        self.items.iter().position(|item| match item {
+            Item::Header { name, .. } => {
+                current = name;
+                false
+            }
+            Item::Entry { key: entry, .. } => current == table && entry == key,
+            Item::Trivia(_) => false,
+        })
+    }
+
+    fn find_mut(&mut self, table: &str, key: &str) -> Option<&mut Item> {
+        let index = self.position(table, key)?;
+        self.items.get_mut(index)
+    }
+
+    /// Finds where a new entry of `table` goes: after its last entry, or right
+    /// after its header if it has none. Returns [`None`] if there is no such
+    /// table.
+    fn insertion_point(&self, table: &str) -> Option<usize> {
+        let mut current = "";
+        let mut found = table.is_empty().then_some(0);
+        for (index, item) in self.items.iter().enumerate() {
+            match item {
+                Item::Header { name, .. } => {
+                    current = name;
+                    if current == table && found.is_none() {
+                        found = Some(index + 1);
+                    }
+                }
+                Item::Entry { .. } if current == table => found = Some(index + 1),
+                _ => {}
+            }
+        }
+        found
+    }
+}
+
+impl fmt::Display for Document {
+    /// Prints the document, exactly as it was read apart from any edits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            for text in item.text() {
+                f.write_str(text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Normalizes a (possibly dotted) key as written to the form keys are looked
+/// up by: quotes removed from each part, and whitespace around the dots
+/// dropped.
+fn normalize_key(key: &str) -> String {
+    let mut parts = Vec::new();
+    let mut rest = key.trim();
+    loop {
+        let end = find_unquoted(rest, '.').unwrap_or(rest.len());
+        parts.push(unquote_key(rest[..end].trim()));
+        match rest.get(end + 1..) {
+            Some(next) => rest = next,
+            None => return parts.join("."),
+        }
+    }
+}
+
+/// Removes the quotes around a quoted key.
+fn unquote_key(key: &str) -> String {
+    let quoted = (key.len() >= 2 && key.starts_with('"') && key.ends_with('"'))
+        || (key.len() >= 2 && key.starts_with('\'') && key.ends_with('\''));
+    match Value::parse(key).filter(|_| quoted) {
+        Some(Value::String(key)) => key,
+        _ => key.to_owned(),
+    }
+}
+
+/// Finds the first `target` outside of quotes on the first line of `text`.
+fn find_unquoted(text: &str, target: char) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == target => return Some(index),
+            _ if c == '\n' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Removes the comment from a line, ignoring `#`s inside strings.
+fn strip_comment(line: &str) -> &str {
+    match find_unquoted(line, '#') {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+/// Returns the comment at the start of `text` (after whitespace), without the
+/// line ending.
+fn strip_comment_start(text: &str) -> Option<&str> {
+    let text = text.trim_start_matches([' ', '\t']);
+    text.starts_with('#')
+        .then(|| text.trim_end_matches(['\n', '\r']))
+}
+
+/// A recursive descent parser for values, over the text of a document (so
+/// values may span lines).
+#[derive(Debug)]
+struct ValueParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Skips spaces and tabs, and (if `newlines`) line endings and comments
+    /// too.
+    fn skip_whitespace(&mut self, newlines: bool) {
+        loop {
+            let rest = self.rest();
+            let trimmed = if newlines {
+                rest.trim_start_matches([' ', '\t', '\n', '\r'])
+            } else {
+                rest.trim_start_matches([' ', '\t'])
+            };
+            self.pos += rest.len() - trimmed.len();
+            if newlines && trimmed.starts_with('#') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let rest = self.rest();
+        if rest.starts_with("\"\"\"") {
+            self.multiline_string("\"\"\"", true)
+        } else if rest.starts_with("'''") {
+            self.multiline_string("'''", false)
+        } else if rest.starts_with('"') {
+            self.basic_string()
+        } else if let Some(literal) = rest.strip_prefix('\'') {
+            let end = literal
+                .find(['\'', '\n'])
+                .filter(|&end| literal[end..].starts_with('\''))
+                .ok_or("unterminated string")?;
+            self.pos += end + 2;
+            Ok(Value::String(literal[..end].to_owned()))
+        } else if rest.starts_with('[') {
+            self.array()
+        } else if rest.starts_with('{') {
+            self.inline_table()
+        } else {
+            self.scalar()
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut output = String::new();
+        loop {
+            let mut chars = self.rest().chars();
+            match chars.next() {
+                None | Some('\n') => return Err("unterminated string".to_owned()),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(Value::String(output));
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    self.escape(&mut output)?;
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn multiline_string(&mut self, delimiter: &str, escapes: bool) -> Result<Value, String> {
+        self.pos += delimiter.len();
+        // A newline right after the opening delimiter is trimmed
+        if self.rest().starts_with("\r\n") {
+            self.pos += 2;
+        } else if self.rest().starts_with('\n') {
+            self.pos += 1;
+        }
+
+        let mut output = String::new();
+        loop {
+            let rest = self.rest();
+            if let Some(after) = rest.strip_prefix(delimiter) {
+                // Up to two quotes may directly precede the closing delimiter
+                let extra = after
+                    .chars()
+                    .take(2)
+                    .take_while(|&c| delimiter.starts_with(c))
+                    .count();
+                output.push_str(&rest[..extra]);
+                self.pos += delimiter.len() + extra;
+                return Ok(Value::String(output));
+            }
+            match rest.chars().next() {
+                None => return Err("unterminated string".to_owned()),
+                Some('\\') if escapes => {
+                    self.pos += 1;
+                    let after = self.rest();
+                    let trimmed = after.trim_start_matches([' ', '\t']);
+                    if trimmed.starts_with('\n') || trimmed.starts_with("\r\n") {
+                        // A line-ending backslash trims the following whitespace
+                        let skipped = after.trim_start_matches([' ', '\t', '\n', '\r']);
+                        self.pos += after.len() - skipped.len();
+                    } else {
+                        self.escape(&mut output)?;
+                    }
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Parses the escape sequence after a `\`.
+    fn escape(&mut self, output: &mut String) -> Result<(), String> {
+        let c = self.rest().chars().next().ok_or("unterminated string")?;
+        self.pos += c.len_utf8();
+        let escaped = match c {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            'e' => '\u{1b}',
+            '"' => '"',
+            '\\' => '\\',
+            'u' | 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let code = self.rest().get(..len).ok_or("invalid escape")?;
+                self.pos += len;
+                u32::from_str_radix(code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or("invalid escape")?
+            }
+            _ => return Err(format!("invalid escape `\\{c}`")),
+        };
+        output.push(escaped);
+        Ok(())
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace(true);
+            if self.rest().starts_with(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_whitespace(true);
+            if self.rest().starts_with(',') {
+                self.pos += 1;
+            } else if !self.rest().starts_with(']') {
+                return Err("expected `,` or `]` in array".to_owned());
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace(false);
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+                return Ok(Value::Table(entries));
+            }
+
+            let rest = self.rest();
+            let equals =
+                find_unquoted(rest, '=').ok_or("expected `key = value` in inline table")?;
+            let key = unquote_key(rest[..equals].trim());
+            self.pos += equals + 1;
+            self.skip_whitespace(false);
+            entries.push((key, self.value()?));
+
+            self.skip_whitespace(false);
+            if self.rest().starts_with(',') {
+                self.pos += 1;
+            } else if !self.rest().starts_with('}') {
+                return Err("expected `,` or `}` in inline table".to_owned());
+            }
+        }
+    }
+
+    /// Parses a boolean, number or date-time.
+    fn scalar(&mut self) -> Result<Value, String> {
+        let rest = self.rest();
+        let token_len = |text: &str| {
+            text.find(|c: char| c.is_whitespace() || matches!(c, ',' | ']' | '}' | '#'))
+                .unwrap_or(text.len())
+        };
+        let mut len = token_len(rest);
+        // A date may be separated from its time by a space instead of a `T`
+        if is_date(&rest[..len]) && rest[len..].strip_prefix(' ').is_some_and(is_time) {
+            len += 1 + token_len(&rest[len + 1..]);
+        }
+        let token = &rest[..len];
+        let value = match token {
+            "" => return Err("missing value".to_owned()),
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ => {
+                let digits = token.replace('_', "");
+                if let Some(number) = integer(&digits) {
+                    Value::Integer(number)
+                } else if digits.parse::<f64>().is_ok()
+                    || matches!(token, "inf" | "+inf" | "-inf" | "nan" | "+nan" | "-nan")
+                {
+                    Value::Float(token.to_owned())
+                } else if token.get(..10).is_some_and(is_date) || is_time(token) {
+                    Value::Datetime(token.to_owned())
+                } else {
+                    return Err(format!("invalid value `{token}`"));
+                }
+            }
+        };
+        self.pos += len;
+        Ok(value)
+    }
+}
+
+/// Parses a decimal, or `0x` hexadecimal, `0o` octal or `0b` binary integer
+/// (with any underscores already removed).
+fn integer(digits: &str) -> Option<i64> {
+    let prefixed = [("0x", 16), ("0o", 8), ("0b", 2)]
+        .into_iter()
+        .find_map(|(prefix, radix)| Some((digits.strip_prefix(prefix)?, radix)));
+    match prefixed {
+        // Unlike decimal integers, these can't have a sign
+        Some((digits, radix)) if !digits.starts_with(['+', '-']) => {
+            i64::from_str_radix(digits, radix).ok()
+        }
+        Some(_) => None,
+        None => digits.parse().ok(),
+    }
+}
+
+/// Whether `text` starts like a time, like `07:32`.
+fn is_time(text: &str) -> bool {
+    text.get(2..3) == Some(":")
+        && text
+            .get(..2)
+            .is_some_and(|hour| hour.bytes().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether `text` is a full date, like `1979-05-27`.
+fn is_date(text: &str) -> bool {
+    text.len() == 10
+        && text.char_indices().all(|(index, c)| match index {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Document {
+        Document::parse(Path::new("test.toml"), contents).unwrap()
+    }
+
+    #[test]
+    fn unedited_documents_round_trip() {
+        for contents in [
+            include_str!("../../Cargo.toml"),
+            include_str!("../Cargo.toml"),
+            include_str!("../lint-profiles.toml"),
+            "a = 1",
+            "a = 1\r\n[b]\r\nc = 'd' # comment\r\n",
+            "  key   =   \"value\"   # spaced\n\n\n",
+            "list = [\n  1, # one\n  2,\n]\n[[bin]]\nname = \"x\"\n[[bin]]\nname = \"y\"\n",
+            "text = \"\"\"\nmany\nlines\"\"\"\nraw = '''\n'quoted'\n'''\n",
+        ] {
+            assert_eq!(parse(contents).to_string(), contents);
+        }
+    }
+
+    #[test]
+    fn values() {
+        let value = |text| Value::parse(text).unwrap();
+        assert_eq!(value("\"a\\tb\\u00E9\""), Value::from("a\tb\u{e9}"));
+        assert_eq!(value("'C:\\path'"), Value::from("C:\\path"));
+        assert_eq!(
+            value("\"\"\"\\\n   trimmed\"\"\"\""),
+            Value::from("trimmed\"")
+        );
+        assert_eq!(value("1_000"), Value::Integer(1000));
+        assert_eq!(value("-17"), Value::Integer(-17));
+        assert_eq!(value("0x1F"), Value::Integer(31));
+        assert_eq!(value("0xdead_beef"), Value::Integer(0xdead_beef));
+        assert_eq!(value("0o755"), Value::Integer(0o755));
+        assert_eq!(value("0b1010"), Value::Integer(10));
+        assert_eq!(value("6.626e-34"), Value::Float("6.626e-34".to_owned()));
+        assert_eq!(value("-inf"), Value::Float("-inf".to_owned()));
+        assert_eq!(value("true"), Value::Boolean(true));
+        for datetime in [
+            "1979-05-27",
+            "07:32:00",
+            "1979-05-27T07:32:00Z",
+            "1979-05-27 07:32:00Z",
+            "1979-05-27 07:32:00.999-07:00",
+        ] {
+            assert_eq!(value(datetime), Value::Datetime(datetime.to_owned()));
+        }
+        assert_eq!(
+            value("[1, [\"a\"], { b = false, \"c d\" = [] }]"),
+            Value::Array(vec![
+                Value::Integer(1),
+                Value::from(vec!["a"]),
+                Value::Table(vec![
+                    ("b".to_owned(), Value::Boolean(false)),
+                    ("c d".to_owned(), Value::Array(Vec::new())),
+                ]),
+            ])
+        );
+        for invalid in [
+            "",
+            "0x-1",
+            "+0x1",
+            "0b2",
+            "1979-5-27",
+            "\"open",
+            "[1 2]",
+            "nope",
+        ] {
+            assert_eq!(Value::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn datetime_entries_keep_their_comments() {
+        let document = parse("[a]\nwhen = 1979-05-27 07:32:00 # a comment\n");
+        let entry = document.get("a", "when").unwrap();
+        assert_eq!(
+            entry.value,
+            Value::Datetime("1979-05-27 07:32:00".to_owned())
+        );
+        assert_eq!(entry.comment, Some("# a comment"));
+    }
+
+    #[test]
+    fn entries_and_lookups() {
+        let document = parse(
+            "top = 1\n\n# comment\n[package]\nname = \"x\" # the name\n\"quoted key\" = \
+             [\n  1,\n  2,\n]\nlints.rust = {}\n[dependencies]\n",
+        );
+        assert_eq!(
+            document.tables().collect::<Vec<_>>(),
+            ["package", "dependencies"]
+        );
+        assert_eq!(document.get("", "top").unwrap().value, Value::Integer(1));
+        assert_eq!(document.get_str("package", "name").as_deref(), Some("x"));
+
+        let entries = document.entries("package");
+        let keys: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.key, entry.line))
+            .collect();
+        assert_eq!(keys, [("name", 5), ("quoted key", 6), ("lints.rust", 10)]);
+        assert_eq!(entries[0].comment, Some("# the name"));
+        assert_eq!(entries[1].raw, "[\n  1,\n  2,\n]");
+        assert!(document.entries("dependencies").is_empty());
+    }
+
+    #[test]
+    fn edits_touch_only_their_values() {
+        let mut document = parse(
+            "# # banner # #\n[package]\nname = \"x\"    # TODO: rename\nversion = \"0.0.0\"\n\
+             publish = false # ON_RELEASE: Remove publish = false\n\n[lints]\n",
+        );
+        document.set("package", "name", &"renamed".into());
+        document.set("package", "edition", &"2024".into());
+        assert!(document.remove("package", "publish"));
+        assert!(!document.remove("package", "publish"));
+        assert!(document.set_comment("package", "version", Some("# bumped")));
+        document.set("lints", "unsafe_code", &"forbid".into());
+        document.set("new", "list", &vec!["a", "b"].into());
+        assert_eq!(
+            document.to_string(),
+            "# # banner # #\n[package]\nname = \"renamed\"    # TODO: rename\nversion = \"0.0.0\" \
+             # bumped\nedition = \"2024\"\n\n[lints]\nunsafe_code = \"forbid\"\n\n[new]\nlist = \
+             [\"a\", \"b\"]\n"
+        );
+    }
+
+    #[test]
+    fn inserting_after_a_last_line_without_ending() {
+        let mut document = parse("[package]\nname = \"x\"");
+        document.set("package", "version", &"1.0.0".into());
+        assert_eq!(
+            document.to_string(),
+            "[package]\nname = \"x\"\nversion = \"1.0.0\"\n"
+        );
+
+        let mut document = parse("[package]");
+        document.set("package", "name", &"x".into());
+        assert_eq!(document.to_string(), "[package]\nname = \"x\"\n");
+
+        let mut document = parse("a = 1");
+        document.set("b", "c", &true.into());
+        assert_eq!(document.to_string(), "a = 1\n\n[b]\nc = true\n");
+    }
+
+    #[test]
+    fn invalid_documents() {
+        for contents in ["[package\n", "key\n", "a = 1 2\n", "= 1\n", "a = \"\n\"\n"] {
+            assert!(
+                Document::parse(Path::new("test.toml"), contents).is_err(),
+                "{contents:?}"
+            );
+        }
+    }
+}
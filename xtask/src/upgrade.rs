@@ -0,0 +1,523 @@
+//! Bringing later changes to the template into a crate derived from it.
+//!
+//! A derived crate records the template commit it was created from (its
+//! baseline) in [`STAMP_PATH`]. [`plan`] takes every file the template has
+//! changed since the baseline and three-way merges it into the crate with
+//! `git merge-file`, using the baseline version as the common ancestor. Local
+//! edits (like the filled-in placeholders) survive, and where both sides
+//! changed the same lines, the file is written with conflict markers for the
+//! user to resolve. Before merging, the files the template's manifest (see
+//! [`template`]) says to render are rendered the way instantiating rendered
+//! them, replaying the recorded answers (see [`answers`]) and the crate's own
+//! values (see [`instantiate::values`]), so lines that only differ by them
+//! don't conflict. Files the manifest skips when instantiating are left out.
+//!
+//! The template is read from a local clone, so nothing here touches the
+//! network.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    Error, Result,
+    answers::{self, Answers},
+    changes::FileChange,
+    fs, git, instantiate,
+    placeholder::Values,
+    process,
+    template::{self, Rule, Template},
+    toml::Document,
+    variant,
+};
+
+/// The file (relative to the crate root) recording the template baseline.
+pub const STAMP_PATH: &str = ".rust-template.toml";
+
+/// The comment at the top of a newly created [`STAMP_PATH`].
+pub(crate) const STAMP_HEADER: &str = "\
+# Records the version of the template this crate was created from (and the
+# answers it was instantiated with), so that `cargo xtask template upgrade`
+# can merge in later changes to the template.
+";
+
+/// Reads the baseline commit recorded in the crate at `root`, if any.
+///
+/// # Errors
+///
+/// Returns an error if [`STAMP_PATH`] exists but cannot be read or parsed.
+pub fn baseline(root: &Path) -> Result<Option<String>> {
+    let path = root.join(STAMP_PATH);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Document::read(&path)?.get_str("template", "commit"))
+}
+
+/// Computes the change recording `commit` as the baseline of the crate at
+/// `root`, or [`None`] if it is already recorded.
+///
+/// # Errors
+///
+/// Returns an error if [`STAMP_PATH`] exists but cannot be read or parsed.
+pub fn stamp(root: &Path, commit: &str) -> Result<Option<FileChange>> {
+    let path = root.join(STAMP_PATH);
+    let original = if path.is_file() {
+        fs::read(&path)?
+    } else {
+        String::new()
+    };
+    let mut document = if original.is_empty() {
+        Document::parse(&path, STAMP_HEADER)?
+    } else {
+        Document::parse(&path, &original)?
+    };
+    document.set("template", "commit", &commit.into());
+    Ok(FileChange::new(STAMP_PATH, original, document.to_string()))
+}
+
+/// What an upgrade does to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The file was unchanged locally, so it now matches the template.
+    Updated,
+    /// Both the crate and the template changed the file, and the changes
+    /// merged cleanly.
+    Merged,
+    /// Both the crate and the template changed the same parts of the file.
+    /// It contains this many regions of conflict markers.
+    Conflicted(usize),
+    /// The file is new in the template.
+    Added,
+    /// The template deleted the file, and it was unchanged locally.
+    Removed,
+    /// The file already matches the template.
+    UpToDate,
+    /// The file was left alone, for the given reason.
+    Skipped(&'static str),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Updated => f.write_str("updated"),
+            Self::Merged => f.write_str("merged"),
+            Self::Conflicted(1) => f.write_str("CONFLICT (1 region)"),
+            Self::Conflicted(regions) => write!(f, "CONFLICT ({regions} regions)"),
+            Self::Added => f.write_str("added"),
+            Self::Removed => f.write_str("removed"),
+            Self::UpToDate => f.write_str("already up to date"),
+            Self::Skipped(reason) => write!(f, "skipped, {reason}"),
+        }
+    }
+}
+
+/// The planned upgrade of a crate from one template commit to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upgrade {
+    /// The baseline commit the crate was at.
+    pub from: String,
+    /// The template commit being upgraded to.
+    pub to: String,
+    /// What happens to each file the template changed, sorted by path.
+    pub files: Vec<(PathBuf, Outcome)>,
+    /// The changes to files that are added, updated, merged (including
+    /// conflicted files) or removed, and to [`STAMP_PATH`].
+    pub changes: Vec<FileChange>,
+}
+
+impl Upgrade {
+    /// The total number of conflict regions across all files.
+    pub fn conflicts(&self) -> usize {
+        self.files
+            .iter()
+            .map(|(_, outcome)| match outcome {
+                Outcome::Conflicted(regions) => *regions,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// Plans upgrading the crate at `root` to commit `to` of the template clone
+/// at `template`.
+///
+/// # Errors
+///
+/// Returns an error if the crate has no recorded baseline, either commit
+/// isn't in the template repository, or a file cannot be read or merged.
+pub fn plan(root: &Path, template: &Path, to: &str) -> Result<Upgrade> {
+    let from = baseline(root)?.ok_or_else(|| {
+        Error::InvalidInput(format!(
+            "{STAMP_PATH} doesn't record a template commit (record the one the crate was \
+             created from with `cargo xtask template stamp`)"
+        ))
+    })?;
+    let resolved_from = git::resolve(template, &from)?.ok_or_else(|| {
+        Error::InvalidInput(format!(
+            "the baseline commit {from} is not in the template repository at {} (fetch it, or \
+             record the right baseline with `cargo xtask template stamp`)",
+            template.display()
+        ))
+    })?;
+    let to = git::resolve(template, to)?.ok_or_else(|| {
+        Error::InvalidInput(format!(
+            "`{to}` is not a commit in the template repository at {}",
+            template.display()
+        ))
+    })?;
+
+    let mut upgrade = Upgrade {
+        from: resolved_from,
+        to,
+        files: Vec::new(),
+        changes: Vec::new(),
+    };
+    let scratch = root.join("target").join("template-upgrade");
+    // Templates from before the manifest existed had every file rendered
+    let manifest = git::show(template, &upgrade.to, Path::new(template::PATH))?
+        .map(|contents| Template::parse(Path::new(template::PATH), &contents))
+        .transpose()?;
+    let rendering = rendering(root, manifest.as_ref())?;
+    for path in git::changed_files(template, &upgrade.from, &upgrade.to)? {
+        if path == Path::new(STAMP_PATH) {
+            continue;
+        }
+        let rule = manifest
+            .as_ref()
+            .map_or(Rule::Render, |manifest| manifest.rule(&path));
+        let outcome = match rule {
+            Rule::Skip => Outcome::Skipped("only part of the template"),
+            Rule::Render => plan_file(
+                root,
+                template,
+                &scratch,
+                Some(&rendering),
+                &mut upgrade,
+                &path,
+            )?,
+            Rule::Copy => plan_file(root, template, &scratch, None, &mut upgrade, &path)?,
+        };
+        upgrade.files.push((path, outcome));
+    }
+    upgrade.changes.extend(stamp(root, &upgrade.to)?);
+
+    Ok(upgrade)
+}
+
+/// What the template's files are rendered with before merging.
+struct Rendering {
+    /// The values of the variables.
+    values: Values,
+    /// The answers to the conditions.
+    conditions: BTreeMap<String, bool>,
+}
+
+/// How the crate at `root` was instantiated, as far as can be told: the
+/// recorded answers (see [`answers::recorded`]), except for the values that
+/// can be read back from Cargo.toml, which may have changed since. The
+/// recorded variants are looked up in the template's `manifest`, and dropped
+/// if it no longer has them.
+///
+/// # Errors
+///
+/// Returns an error if [`STAMP_PATH`] or Cargo.toml cannot be read or parsed.
+pub(crate) fn replayed(root: &Path, manifest: Option<&Template>) -> Result<Answers> {
+    let mut answers = answers::recorded(root)?.unwrap_or_default();
+    for (variable, value) in instantiate::values(root)?.iter() {
+        // The value was already validated, so this can't fail
+        let _ = answers.values.set(variable, value);
+    }
+    let known = manifest
+        .is_some_and(|manifest| variant::select(&manifest.variants, &answers.variants).is_ok());
+    if !known {
+        answers.variants.clear();
+    }
+    Ok(answers)
+}
+
+/// What to render the template's files with, for the crate at `root` (see
+/// [`replayed`]).
+fn rendering(root: &Path, manifest: Option<&Template>) -> Result<Rendering> {
+    let answers = replayed(root, manifest)?;
+    let chosen = match manifest {
+        Some(manifest) if !answers.variants.is_empty() => {
+            variant::select(&manifest.variants, &answers.variants)
+                .ok()
+                .map(|chosen| (manifest, chosen))
+        }
+        _ => None,
+    };
+    let mut conditions = chosen.map_or_else(BTreeMap::new, |(manifest, chosen)| {
+        variant::conditions(&manifest.variants, &chosen)
+    });
+    conditions.extend(answers.conditions);
+
+    Ok(Rendering {
+        values: answers.values,
+        conditions,
+    })
+}
+
+/// Plans the upgrade of one file changed by the template, recording its
+/// changes in `upgrade`. The template's versions of the file are rendered
+/// (see [`instantiate::render`]) first, if `rendering` is given.
+fn plan_file(
+    root: &Path,
+    template: &Path,
+    scratch: &Path,
+    rendering: Option<&Rendering>,
+    upgrade: &mut Upgrade,
+    path: &Path,
+) -> Result<Outcome> {
+    let render = |contents: Option<String>| -> Result<Option<String>> {
+        match (contents, rendering) {
+            (Some(contents), Some(rendering)) => {
+                instantiate::render(path, &contents, &rendering.conditions, &rendering.values)
+                    .map(Some)
+            }
+            (contents, _) => Ok(contents),
+        }
+    };
+    let base = render(git::show(template, &upgrade.from, path)?)?;
+    let theirs = render(git::show(template, &upgrade.to, path)?)?;
+    let local_path = root.join(path);
+    let ours = if local_path.is_file() {
+        Some(fs::read(&local_path)?)
+    } else {
+        None
+    };
+
+    Ok(match (ours, theirs) {
+        (None, None) => Outcome::UpToDate,
+        (None, Some(theirs)) if base.is_none() => {
+            upgrade
+                .changes
+                .extend(FileChange::new(path, String::new(), theirs));
+            Outcome::Added
+        }
+        (None, Some(_)) => Outcome::Skipped("deleted locally"),
+        (Some(ours), None) if base.as_ref() == Some(&ours) => {
+            upgrade.changes.push(FileChange::deletion(path, ours));
+            Outcome::Removed
+        }
+        (Some(_), None) => Outcome::Skipped("deleted in the template but changed locally"),
+        (Some(ours), Some(theirs)) if ours == theirs => Outcome::UpToDate,
+        (Some(ours), Some(theirs)) if base.as_ref() == Some(&ours) => {
+            upgrade.changes.extend(FileChange::new(path, ours, theirs));
+            Outcome::Updated
+        }
+        (Some(ours), Some(theirs)) => {
+            let labels = [
+                "local".to_owned(),
+                format!("template {}", short(&upgrade.from)),
+                format!("template {}", short(&upgrade.to)),
+            ];
+            let (merged, conflicts) = merge(
+                scratch,
+                [&ours, base.as_deref().unwrap_or_default(), &theirs],
+                &labels,
+            )?;
+            upgrade.changes.extend(FileChange::new(path, ours, merged));
+            if conflicts == 0 {
+                Outcome::Merged
+            } else {
+                Outcome::Conflicted(conflicts)
+            }
+        }
+    })
+}
+
+/// Three-way merges `[ours, base, theirs]` with `git merge-file`, returning
+/// the merged contents and the number of conflicts. `labels` name the three
+/// versions in conflict markers.
+fn merge(scratch: &Path, versions: [&str; 3], labels: &[String; 3]) -> Result<(String, usize)> {
+    fs::create_dir_all(scratch)?;
+    let paths = ["ours", "base", "theirs"].map(|name| scratch.join(name));
+    for (path, contents) in paths.iter().zip(versions) {
+        fs::write(path, contents)?;
+    }
+
+    let mut command = Command::new("git");
+    command.args(["merge-file", "--stdout"]);
+    for label in labels {
+        command.arg("-L").arg(label);
+    }
+    let output = process::output(command.args(&paths))?;
+    // The exit code is the number of conflicts (capped at 127), or negative
+    // (so 255) on error
+    match output.status.code() {
+        Some(conflicts @ 0..=127) => Ok((
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            usize::try_from(conflicts).unwrap_or_default(),
+        )),
+        _ => Err(Error::InvalidInput(format!(
+            "`git merge-file` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Abbreviates a commit hash for display.
+pub fn short(commit: &str) -> &str {
+    commit.get(..12).unwrap_or(commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{changes, fs::Scratch};
+
+    /// Writes (or, for [`None`], deletes) `files` in the repository at `dir`
+    /// and commits them, returning the commit's hash.
+    fn commit(dir: &Path, files: &[(&str, Option<&str>)]) -> String {
+        for &(file, contents) in files {
+            match contents {
+                Some(contents) => fs::write(&dir.join(file), contents).unwrap(),
+                None => std::fs::remove_file(dir.join(file)).unwrap(),
+            }
+        }
+        for args in [
+            &["add", "--all"][..],
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "--message=Change",
+            ],
+        ] {
+            assert!(
+                process::status(git::command(dir).args(args))
+                    .unwrap()
+                    .success()
+            );
+        }
+        git::resolve(dir, "HEAD").unwrap().unwrap()
+    }
+
+    #[test]
+    fn three_way_merges() {
+        let labels = ["ours", "base", "theirs"].map(str::to_owned);
+        let scratch = Scratch::new(&[]);
+        assert_eq!(
+            merge(
+                scratch.path(),
+                ["A\nb\nc\n", "a\nb\nc\n", "a\nb\nC\n"],
+                &labels
+            )
+            .unwrap(),
+            ("A\nb\nC\n".to_owned(), 0)
+        );
+        assert_eq!(
+            merge(scratch.path(), ["x\n", "a\n", "y\n"], &labels).unwrap(),
+            (
+                "<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\n".to_owned(),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn stamps() {
+        let scratch = Scratch::new(&[]);
+        assert_eq!(baseline(scratch.path()).unwrap(), None);
+
+        let change = stamp(scratch.path(), "abc").unwrap().unwrap();
+        changes::apply(scratch.path(), &[change]).unwrap();
+        assert_eq!(baseline(scratch.path()).unwrap().as_deref(), Some("abc"));
+        assert_eq!(stamp(scratch.path(), "abc").unwrap(), None);
+    }
+
+    #[test]
+    fn upgrades() {
+        let template = Scratch::new(&[]);
+        assert!(
+            process::status(git::command(template.path()).args(["init", "--quiet"]))
+                .unwrap()
+                .success()
+        );
+        let manifest = "[package]\n# @render name = \"{{ name }}\"\nname = \"rust-template\"\nversion = \"0.1.0\"\n";
+        let from = commit(
+            template.path(),
+            &[
+                ("Cargo.toml", Some(manifest)),
+                ("notes.md", Some("a\nb\nc\n")),
+                ("kept.md", Some("x\n")),
+                ("old.md", Some("old\n")),
+                ("edited.md", Some("old\n")),
+            ],
+        );
+        let to = commit(
+            template.path(),
+            &[
+                (
+                    "Cargo.toml",
+                    Some(&format!("{manifest}edition = \"2024\"\n")),
+                ),
+                ("notes.md", Some("a\nb\nC\n")),
+                ("kept.md", Some("y\n")),
+                ("old.md", None),
+                ("edited.md", None),
+                ("new.md", Some("new\n")),
+            ],
+        );
+
+        // The crate's Cargo.toml was rendered, and its other files edited
+        let derived = Scratch::new(&[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+            ),
+            ("notes.md", "A\nb\nc\n"),
+            ("kept.md", "z\n"),
+            ("old.md", "old\n"),
+            ("edited.md", "edited\n"),
+        ]);
+        let change = stamp(derived.path(), &from).unwrap().unwrap();
+        changes::apply(derived.path(), &[change]).unwrap();
+
+        let upgrade = plan(derived.path(), template.path(), "HEAD").unwrap();
+        assert_eq!(
+            (upgrade.from.as_str(), upgrade.to.as_str()),
+            (from.as_str(), to.as_str())
+        );
+        let outcomes: Vec<(&str, &Outcome)> = upgrade
+            .files
+            .iter()
+            .map(|(path, outcome)| (path.to_str().unwrap(), outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("Cargo.toml", &Outcome::Updated),
+                (
+                    "edited.md",
+                    &Outcome::Skipped("deleted in the template but changed locally")
+                ),
+                ("kept.md", &Outcome::Conflicted(1)),
+                ("new.md", &Outcome::Added),
+                ("notes.md", &Outcome::Merged),
+                ("old.md", &Outcome::Removed),
+            ]
+        );
+        assert_eq!(upgrade.conflicts(), 1);
+
+        changes::apply(derived.path(), &upgrade.changes).unwrap();
+        let read = |file: &str| fs::read(&derived.path().join(file)).unwrap();
+        assert_eq!(
+            read("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2024\"\n"
+        );
+        assert_eq!(read("notes.md"), "A\nb\nC\n");
+        assert!(read("kept.md").starts_with("<<<<<<< local\nz\n"));
+        assert_eq!(read("new.md"), "new\n");
+        assert!(!derived.path().join("old.md").exists());
+        assert_eq!(baseline(derived.path()).unwrap(), Some(to));
+    }
+}
@@ -0,0 +1,474 @@
+//! Template variants: composable overlays for different kinds of crate.
+//!
+//! The template is a plain library, with the pieces other kinds of crate need
+//! left as commented-out code. A variant, defined in the template manifest
+//! (see [`template`](crate::template)), describes everything that sets one
+//! kind of crate apart: files to add or delete, Cargo.toml keys, a lint
+//! profile, [conditions](crate::conditional), `.gitignore` lines and crate
+//! attributes. [`apply`] layers the chosen variants over the template, after
+//! [`select`] has checked that they can be combined.
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    Error, Result,
+    changes::Files,
+    conditional::Condition,
+    lints::profile,
+    toml::{Document, Value},
+};
+
+/// The keys a variant's table may have.
+const KEYS: &[&str] = &[
+    "description",
+    "conflicts",
+    "profile",
+    "conditions",
+    "gitignore",
+    "attributes",
+    "remove",
+];
+
+/// One variant of the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// The variant's name, like `no_std`.
+    pub name: String,
+    /// What the variant is for.
+    pub description: String,
+    /// The variants this one can't be combined with.
+    pub conflicts: Vec<String>,
+    /// The lint profile the variant switches to.
+    pub profile: Option<String>,
+    /// The conditions that hold for the variant, like `no_std`.
+    pub conditions: Vec<String>,
+    /// The `.gitignore` lines the variant needs.
+    pub gitignore: Vec<String>,
+    /// The inner attributes the variant adds to the crate root, like
+    /// `#![no_std]`.
+    pub attributes: Vec<String>,
+    /// The files (relative to the crate root) the variant deletes.
+    pub remove: Vec<String>,
+    /// The files the variant creates or replaces, with their contents.
+    pub files: Vec<(String, String)>,
+    /// The Cargo.toml keys the variant sets, as `(table, key, value)`.
+    pub manifest: Vec<(String, String, Value)>,
+}
+
+impl Variant {
+    /// Parses the `[variants.NAME]` tables of the template manifest at
+    /// `path`, in the order they're defined.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a variant is malformed or names an unknown
+    /// condition.
+    pub fn parse_all(path: &Path, manifest: &Document) -> Result<Vec<Self>> {
+        let names: Vec<&str> = manifest
+            .tables()
+            .filter_map(|table| table.strip_prefix("variants."))
+            .filter(|name| !name.contains('.'))
+            .collect();
+
+        names
+            .iter()
+            .map(|&name| {
+                let table = format!("variants.{name}");
+                let malformed =
+                    |message: &str| Error::malformed(path, format!("variant `{name}`: {message}"));
+                if let Some(entry) = manifest
+                    .entries(&table)
+                    .into_iter()
+                    .find(|entry| !KEYS.contains(&entry.key))
+                {
+                    return Err(malformed(&format!("unknown key `{}`", entry.key)));
+                }
+                let strings = |key: &str| -> Result<Vec<String>> {
+                    let Some(entry) = manifest.get(&table, key) else {
+                        return Ok(Vec::new());
+                    };
+                    entry
+                        .value
+                        .as_array()
+                        .and_then(|items| {
+                            items
+                                .iter()
+                                .map(|item| item.as_str().map(str::to_owned))
+                                .collect()
+                        })
+                        .ok_or_else(|| malformed(&format!("`{key}` is not an array of strings")))
+                };
+
+                let files = manifest
+                    .entries(&format!("{table}.files"))
+                    .into_iter()
+                    .map(|entry| match entry.value {
+                        Value::String(contents) => Ok((entry.key.to_owned(), contents)),
+                        _ => Err(malformed(&format!("file `{}` is not a string", entry.key))),
+                    })
+                    .collect::<Result<_>>()?;
+                let prefix = format!("{table}.manifest.");
+                let cargo_manifest = manifest
+                    .tables()
+                    .filter_map(|table| table.strip_prefix(&prefix))
+                    .flat_map(|table| {
+                        manifest
+                            .entries(&format!("{prefix}{table}"))
+                            .into_iter()
+                            .map(|entry| (table.to_owned(), entry.key.to_owned(), entry.value))
+                    })
+                    .collect();
+                let conditions = strings("conditions")?;
+                for condition in &conditions {
+                    Condition::find(condition).map_err(|error| malformed(&error.to_string()))?;
+                }
+
+                Ok(Self {
+                    name: name.to_owned(),
+                    description: manifest
+                        .get_str(&table, "description")
+                        .ok_or_else(|| malformed("missing or invalid `description`"))?,
+                    conflicts: strings("conflicts")?,
+                    profile: manifest.get_str(&table, "profile"),
+                    conditions,
+                    gitignore: strings("gitignore")?,
+                    attributes: strings("attributes")?,
+                    remove: strings("remove")?,
+                    files,
+                    manifest: cargo_manifest,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Looks up the variants named `names` among `all` and checks that they can be
+/// combined. Repeated names are only included once.
+///
+/// # Errors
+///
+/// Returns an error if a variant is unknown, or two of them conflict (or would
+/// set the same file, Cargo.toml key or lint profile differently).
+pub fn select(all: &[Variant], names: &[String]) -> Result<Vec<Variant>> {
+    let mut chosen: Vec<Variant> = Vec::new();
+    for name in names {
+        if chosen.iter().any(|variant| variant.name == *name) {
+            continue;
+        }
+        let Some(variant) = all.iter().find(|variant| variant.name == *name) else {
+            let known: Vec<&str> = all.iter().map(|variant| variant.name.as_str()).collect();
+            return Err(Error::InvalidInput(format!(
+                "unknown variant `{name}` (expected one of {})",
+                known.join(", ")
+            )));
+        };
+
+        for other in &chosen {
+            let combine_error = |reason: &str| {
+                Err(Error::InvalidInput(format!(
+                    "variants `{}` and `{name}` can't be combined: {reason}",
+                    other.name
+                )))
+            };
+            if variant.conflicts.contains(&other.name) || other.conflicts.contains(name) {
+                return combine_error("they are incompatible");
+            }
+            match (&other.profile, &variant.profile) {
+                (Some(a), Some(b)) if a != b => {
+                    return combine_error(&format!("they use lint profiles `{a}` and `{b}`"));
+                }
+                _ => {}
+            }
+            for (path, contents) in &variant.files {
+                let clash = other.files.iter().any(|(other_path, other_contents)| {
+                    other_path == path && other_contents != contents
+                });
+                if clash {
+                    return combine_error(&format!("they both provide `{path}`"));
+                }
+            }
+            for (table, key, value) in &variant.manifest {
+                let clash = other
+                    .manifest
+                    .iter()
+                    .any(|(t, k, v)| (t, k) == (table, key) && v != value);
+                if clash {
+                    return combine_error(&format!("they both set `{table}.{key}` in Cargo.toml"));
+                }
+            }
+        }
+        chosen.push(variant.clone());
+    }
+
+    Ok(chosen)
+}
+
+/// Layers the `chosen` variants over the crate's files.
+///
+/// `.gitignore` lines are only ever added, so the template's own lines stay
+/// whichever variants are chosen. Conditions are resolved separately, with
+/// the answers from [`conditions`].
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or parsed.
+pub fn apply(files: &mut Files<'_>, chosen: &[Variant]) -> Result<()> {
+    for variant in chosen {
+        for (path, contents) in &variant.files {
+            files.set(path, contents.clone())?;
+        }
+        for path in &variant.remove {
+            files.remove(path)?;
+        }
+    }
+
+    if let Some(manifest) = files.get("Cargo.toml")?.map(str::to_owned) {
+        let path = Path::new("Cargo.toml");
+        let mut manifest = match chosen.iter().find_map(|variant| variant.profile.as_deref()) {
+            Some(name) => profile::switch_contents(path, &manifest, name)?.1,
+            None => manifest,
+        };
+        let mut document = Document::parse(path, &manifest)?;
+        for (table, key, value) in chosen.iter().flat_map(|variant| &variant.manifest) {
+            document.set(table, key, value);
+        }
+        manifest = document.to_string();
+        files.set(path, manifest)?;
+    }
+
+    let base = files.get(".gitignore")?.map(str::to_owned);
+    if base.is_some() || chosen.iter().any(|variant| !variant.gitignore.is_empty()) {
+        let composed = gitignore(base.as_deref().unwrap_or_default(), chosen);
+        files.set(".gitignore", composed)?;
+    }
+
+    let attributes: Vec<&String> = chosen
+        .iter()
+        .flat_map(|variant| &variant.attributes)
+        .collect();
+    if !attributes.is_empty() {
+        let crate_root = if files.exists("src/lib.rs")? {
+            "src/lib.rs"
+        } else {
+            "src/main.rs"
+        };
+        edit_lines(files, crate_root, |lines| {
+            for attribute in attributes {
+                add_attribute(lines, attribute);
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The answers to the conditions claimed by any variant in `all`: those of
+/// the `chosen` variants hold, and the rest don't.
+pub fn conditions(all: &[Variant], chosen: &[Variant]) -> BTreeMap<String, bool> {
+    claimed(all, chosen, |variant| &variant.conditions)
+        .into_iter()
+        .collect()
+}
+
+/// The items (like conditions) `select` picks out of every variant in `all`,
+/// each with whether any of the `chosen` variants wants it.
+fn claimed(
+    all: &[Variant],
+    chosen: &[Variant],
+    select: fn(&Variant) -> &[String],
+) -> Vec<(String, bool)> {
+    let mut claimed: Vec<(String, bool)> = Vec::new();
+    for variant in all {
+        let is_chosen = chosen.iter().any(|chosen| chosen.name == variant.name);
+        for item in select(variant) {
+            match claimed.iter_mut().find(|(claimed, _)| claimed == item) {
+                Some((_, wanted)) => *wanted |= is_chosen,
+                None => claimed.push((item.clone(), is_chosen)),
+            }
+        }
+    }
+    claimed
+}
+
+/// The `.gitignore` with the lines of `base` followed by those the `chosen`
+/// variants need that it lacks.
+pub fn gitignore(base: &str, chosen: &[Variant]) -> String {
+    let mut lines: Vec<&str> = base.lines().collect();
+    for line in chosen.iter().flat_map(|variant| &variant.gitignore) {
+        if !lines.iter().any(|existing| existing.trim() == line) {
+            lines.push(line);
+        }
+    }
+
+    let mut composed = lines.join("\n");
+    if !composed.is_empty() && (base.is_empty() || base.ends_with('\n')) {
+        composed.push('\n');
+    }
+    composed
+}
+
+/// Edits the lines of `path`, if it exists, keeping its trailing newline.
+fn edit_lines(
+    files: &mut Files<'_>,
+    path: &str,
+    edit: impl FnOnce(&mut Vec<String>),
+) -> Result<()> {
+    let Some(contents) = files.get(path)? else {
+        return Ok(());
+    };
+    let trailing_newline = contents.is_empty() || contents.ends_with('\n');
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+    edit(&mut lines);
+
+    let mut updated = lines.join("\n");
+    if trailing_newline && !updated.is_empty() {
+        updated.push('\n');
+    }
+    files.set(path, updated)
+}
+
+/// Adds the inner `attribute` to a crate root's lines, after its other inner
+/// attributes (or its leading comments, if it has none), unless it's already
+/// there.
+fn add_attribute(lines: &mut Vec<String>, attribute: &str) {
+    if lines.iter().any(|line| line.trim() == attribute) {
+        return;
+    }
+    let index = match lines.iter().rposition(|line| line.starts_with("#![")) {
+        Some(last) => last + 1,
+        None => lines
+            .iter()
+            .take_while(|line| line.starts_with("//"))
+            .count(),
+    };
+    lines.insert(index, attribute.to_owned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fs::Scratch, template::Template};
+
+    fn variants() -> Vec<Variant> {
+        Template::parse(
+            Path::new("template.toml"),
+            include_str!("../../template.toml"),
+        )
+        .unwrap()
+        .variants
+    }
+
+    fn chosen(names: &[&str]) -> Result<Vec<Variant>> {
+        let names: Vec<String> = names.iter().map(|&name| name.to_owned()).collect();
+        select(&variants(), &names)
+    }
+
+    #[test]
+    fn select_rejects_conflicts() {
+        assert_eq!(chosen(&["no_std", "library", "no_std"]).unwrap().len(), 2);
+        assert!(chosen(&["binary", "library"]).is_err());
+        assert!(chosen(&["no_std", "binary"]).is_err());
+        assert!(chosen(&["unknown"]).is_err());
+    }
+
+    #[test]
+    fn combinations_follow_the_conflict_table() {
+        // The pairs of variants whose combination doesn't build
+        const INCOMPATIBLE: &[(&str, &str)] = &[
+            ("library", "binary"),
+            ("library", "proc-macro"),
+            ("binary", "proc-macro"),
+            ("binary", "no_std"),
+            ("binary", "cdylib"),
+            ("proc-macro", "no_std"),
+            ("proc-macro", "cdylib"),
+            ("no_std", "cdylib"),
+        ];
+        let variants = variants();
+        for a in &variants {
+            for b in variants.iter().filter(|b| b.name != a.name) {
+                let (a, b) = (a.name.as_str(), b.name.as_str());
+                let incompatible = INCOMPATIBLE.contains(&(a, b)) || INCOMPATIBLE.contains(&(b, a));
+                assert_eq!(chosen(&[a, b]).is_err(), incompatible, "{a} and {b}");
+            }
+        }
+        for variant in &variants {
+            for other in &variant.conflicts {
+                assert!(
+                    variants
+                        .iter()
+                        .any(|b| b.name == *other && b.conflicts.contains(&variant.name)),
+                    "`{}` conflicts with `{other}`, but not the other way around",
+                    variant.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gitignore_only_adds_lines() {
+        let base = include_str!("../../.gitignore");
+        for names in [
+            &[][..],
+            &["library"],
+            &["binary"],
+            &["no_std", "library"],
+            &["cdylib", "library"],
+        ] {
+            let composed = gitignore(base, &chosen(names).unwrap());
+            assert_eq!(composed, base, "{names:?}");
+        }
+
+        let cdylib = chosen(&["cdylib"]).unwrap();
+        assert_eq!(gitignore("target/\n", &cdylib), "target/\n*.so\n");
+        assert_eq!(gitignore("target/", &cdylib), "target/\n*.so");
+        assert_eq!(gitignore("", &cdylib), "*.so\n");
+    }
+
+    #[test]
+    fn apply_overlays_files_manifest_and_attributes() {
+        let scratch = Scratch::new(&[
+            ("Cargo.toml", include_str!("../../Cargo.toml")),
+            (
+                "src/lib.rs",
+                "//! Docs.\n#![doc = \"x\"]\n\npub fn f() {}\n",
+            ),
+            (".gitignore", "target/\n*.rlib\n"),
+        ]);
+        let mut files = Files::new(scratch.path());
+        apply(&mut files, &chosen(&["no_std"]).unwrap()).unwrap();
+        assert_eq!(
+            files.get("src/lib.rs").unwrap(),
+            Some("//! Docs.\n#![doc = \"x\"]\n#![no_std]\n\npub fn f() {}\n")
+        );
+        let manifest = files.get("Cargo.toml").unwrap().unwrap().to_owned();
+        let status = profile::status_of(Path::new("Cargo.toml"), &manifest).unwrap();
+        assert_eq!(status.profile.name, "no_std");
+
+        let mut files = Files::new(scratch.path());
+        apply(&mut files, &chosen(&["cdylib"]).unwrap()).unwrap();
+        assert_eq!(
+            files.get(".gitignore").unwrap(),
+            Some("target/\n*.rlib\n*.so\n")
+        );
+        let manifest = files.get("Cargo.toml").unwrap().unwrap().to_owned();
+        let document = Document::parse(Path::new("Cargo.toml"), &manifest).unwrap();
+        assert_eq!(
+            document.get("lib", "crate-type").unwrap().value,
+            vec!["cdylib", "rlib"].into()
+        );
+    }
+
+    #[test]
+    fn conditions_of_unchosen_variants_dont_hold() {
+        let all = variants();
+        assert_eq!(
+            conditions(&all, &chosen(&["no_std"]).unwrap()),
+            BTreeMap::from([("no_std".to_owned(), true)])
+        );
+        assert_eq!(
+            conditions(&all, &chosen(&["library"]).unwrap()),
+            BTreeMap::from([("no_std".to_owned(), false)])
+        );
+    }
+}
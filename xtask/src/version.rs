@@ -0,0 +1,174 @@
+//! Semantic versions of the crate, and how they are bumped for a release.
+
+use std::{fmt, str::FromStr};
+
+use crate::Error;
+
+/// A semantic version, like `1.2.3` or `1.2.3-alpha.1`.
+///
+/// Build metadata (`+...`) is not supported, since the template never uses it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Version {
+    /// The major version.
+    pub major: u64,
+    /// The minor version.
+    pub minor: u64,
+    /// The patch version.
+    pub patch: u64,
+    /// The pre-release identifier (without the leading `-`), if any.
+    pub pre: Option<String>,
+}
+
+impl Version {
+    /// Returns the version that follows this one after `bump`.
+    ///
+    /// Bumping a pre-release to the release it precedes drops the pre-release
+    /// identifier (so a patch bump of `1.2.3-alpha.1` gives `1.2.3`), matching
+    /// the conventions of `cargo release`.
+    #[must_use]
+    pub fn bumped(&self, bump: &Bump) -> Self {
+        let (major, minor, patch) = (self.major, self.minor, self.patch);
+        let released = |major, minor, patch| Self {
+            major,
+            minor,
+            patch,
+            pre: None,
+        };
+
+        match bump {
+            Bump::Major if self.pre.is_some() && minor == 0 && patch == 0 => released(major, 0, 0),
+            Bump::Major => released(major + 1, 0, 0),
+            Bump::Minor if self.pre.is_some() && patch == 0 => released(major, minor, 0),
+            Bump::Minor => released(major, minor + 1, 0),
+            Bump::Patch if self.pre.is_some() => released(major, minor, patch),
+            Bump::Patch => released(major, minor, patch + 1),
+            Bump::Pre(label) => {
+                let next = self
+                    .pre
+                    .as_deref()
+                    .and_then(|pre| pre.strip_prefix(label.as_str())?.strip_prefix('.'))
+                    .and_then(|number| number.parse::<u64>().ok());
+                match next {
+                    Some(number) => Self {
+                        pre: Some(format!("{label}.{}", number + 1)),
+                        ..self.clone()
+                    },
+                    None => Self {
+                        pre: Some(format!("{label}.1")),
+                        ..if self.pre.is_some() {
+                            self.clone()
+                        } else {
+                            released(major, minor, patch + 1)
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{pre}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidInput(format!("invalid version `{text}`"));
+
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, Some(pre.to_owned())),
+            Some(_) => return Err(invalid()),
+            None => (text, None),
+        };
+        let mut numbers = core.split('.').map(|number| {
+            let leading_zero = number.len() > 1 && number.starts_with('0');
+            match number.parse::<u64>() {
+                Ok(number) if !leading_zero => Ok(number),
+                _ => Err(invalid()),
+            }
+        });
+        let (Some(major), Some(minor), Some(patch), None) = (
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+        ) else {
+            return Err(invalid());
+        };
+
+        Ok(Self {
+            major: major?,
+            minor: minor?,
+            patch: patch?,
+            pre,
+        })
+    }
+}
+
+/// Which part of a [`Version`] to increment for a release.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Bump {
+    /// `X.0.0`
+    Major,
+    /// `x.Y.0`
+    Minor,
+    /// `x.y.Z`
+    Patch,
+    /// A pre-release with the given label, like `x.y.z-alpha.N`.
+    Pre(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bumped(version: &str, bump: &Bump) -> String {
+        version.parse::<Version>().unwrap().bumped(bump).to_string()
+    }
+
+    #[test]
+    fn parse_round_trips() {
+        for version in ["0.0.0", "1.2.3", "10.20.30-alpha.1", "1.0.0-rc"] {
+            assert_eq!(version.parse::<Version>().unwrap().to_string(), version);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_versions() {
+        for version in ["", "1", "1.2", "1.2.3.4", "01.2.3", "1.2.3-", "a.b.c"] {
+            assert!(version.parse::<Version>().is_err(), "{version}");
+        }
+    }
+
+    #[test]
+    fn release_bumps() {
+        assert_eq!(bumped("1.2.3", &Bump::Major), "2.0.0");
+        assert_eq!(bumped("1.2.3", &Bump::Minor), "1.3.0");
+        assert_eq!(bumped("1.2.3", &Bump::Patch), "1.2.4");
+    }
+
+    #[test]
+    fn release_bumps_finish_pre_releases() {
+        assert_eq!(bumped("2.0.0-rc.1", &Bump::Major), "2.0.0");
+        assert_eq!(bumped("1.2.0-rc.1", &Bump::Major), "2.0.0");
+        assert_eq!(bumped("1.3.0-rc.1", &Bump::Minor), "1.3.0");
+        assert_eq!(bumped("1.3.1-rc.1", &Bump::Minor), "1.4.0");
+        assert_eq!(bumped("1.2.3-rc.1", &Bump::Patch), "1.2.3");
+    }
+
+    #[test]
+    fn pre_release_bumps() {
+        let alpha = Bump::Pre("alpha".to_owned());
+        assert_eq!(bumped("1.2.3", &alpha), "1.2.4-alpha.1");
+        assert_eq!(bumped("1.2.4-alpha.1", &alpha), "1.2.4-alpha.2");
+        assert_eq!(bumped("1.2.4-beta.3", &alpha), "1.2.4-alpha.1");
+    }
+}
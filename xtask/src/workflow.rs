@@ -0,0 +1,164 @@
+//! The GitHub Actions workflow at `.github/workflows/ci.yaml`.
+
+use std::path::Path;
+
+use crate::{Error, Result, fs, yaml};
+
+/// The path of the CI workflow, relative to the crate root.
+pub const PATH: &str = ".github/workflows/ci.yaml";
+
+/// A parsed GitHub Actions workflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workflow {
+    /// The workflow's display name.
+    pub name: Option<String>,
+    /// Environment variables set for every job.
+    pub env: Vec<(String, String)>,
+    /// The jobs, in the order they're defined.
+    pub jobs: Vec<Job>,
+}
+
+/// One job of a [`Workflow`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// The job's key in the `jobs` mapping.
+    pub id: String,
+    /// The job's display name.
+    pub name: Option<String>,
+    /// The runner the job runs on, like `ubuntu-latest` (or an expression
+    /// like `${{ matrix.os }}`).
+    pub runs_on: Option<String>,
+    /// The job's `strategy.matrix`: each key with its values, in order.
+    /// `include` and `exclude` are not supported.
+    pub matrix: Vec<(String, Vec<String>)>,
+    /// Environment variables set for every step of the job.
+    pub env: Vec<(String, String)>,
+    /// The job's steps.
+    pub steps: Vec<Step>,
+    /// The (1-based) line the job starts on.
+    pub line: usize,
+}
+
+/// One step of a [`Job`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The action used by the step, like `dtolnay/rust-toolchain@stable`.
+    pub uses: Option<String>,
+    /// The inputs of the action.
+    pub with: Vec<(String, String)>,
+    /// The shell command run by the step.
+    pub run: Option<String>,
+    /// Environment variables set for the step.
+    pub env: Vec<(String, String)>,
+    /// The (1-based) line the step starts on.
+    pub line: usize,
+    /// The (1-based) line of the `uses:` key, if any.
+    pub uses_line: Option<usize>,
+}
+
+impl Step {
+    /// Splits [`Step::uses`] into the action and its version (the part after
+    /// `@`).
+    pub fn action(&self) -> Option<(&str, &str)> {
+        self.uses.as_deref()?.split_once('@')
+    }
+
+    /// Looks up an input of the action.
+    pub fn input(&self, key: &str) -> Option<&str> {
+        self.with
+            .iter()
+            .find(|(input, _)| input == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Workflow {
+    /// Reads the CI workflow of the crate at `root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workflow cannot be read or parsed.
+    pub fn read(root: &Path) -> Result<Self> {
+        let path = root.join(PATH);
+        Self::parse(&path, &fs::read(&path)?)
+    }
+
+    /// Parses a workflow. `path` is only used for error messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workflow is not valid YAML, or its jobs or steps
+    /// are not mappings.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let root = yaml::parse(path, contents)?;
+        let string = |node: &yaml::Node, key: &str| node.get(key)?.as_str().map(str::to_owned);
+        let pairs = |node: Option<&yaml::Node>| -> Vec<(String, String)> {
+            node.map(yaml::Node::entries)
+                .unwrap_or_default()
+                .iter()
+                .map(|(key, value)| (key.clone(), value.as_str().unwrap_or_default().to_owned()))
+                .collect()
+        };
+
+        let mut jobs = Vec::new();
+        for (id, job) in root
+            .get("jobs")
+            .map(yaml::Node::entries)
+            .unwrap_or_default()
+        {
+            if !matches!(job.value, yaml::Value::Mapping(_)) {
+                return Err(Error::malformed(
+                    path,
+                    format!("line {}: job `{id}` is not a mapping", job.line),
+                ));
+            }
+
+            let mut steps = Vec::new();
+            for step in job.get("steps").map(yaml::Node::items).unwrap_or_default() {
+                if !matches!(step.value, yaml::Value::Mapping(_)) {
+                    return Err(Error::malformed(
+                        path,
+                        format!("line {}: step of job `{id}` is not a mapping", step.line),
+                    ));
+                }
+                steps.push(Step {
+                    uses: string(step, "uses"),
+                    with: pairs(step.get("with")),
+                    run: string(step, "run"),
+                    env: pairs(step.get("env")),
+                    line: step.line,
+                    uses_line: step.get("uses").map(|node| node.line),
+                });
+            }
+
+            let matrix = job
+                .get("strategy")
+                .and_then(|strategy| strategy.get("matrix"))
+                .map(yaml::Node::entries)
+                .unwrap_or_default()
+                .iter()
+                .filter(|(_, values)| matches!(values.value, yaml::Value::Sequence(_)))
+                .map(|(key, values)| {
+                    let values = values.items().iter().filter_map(yaml::Node::as_str);
+                    (key.clone(), values.map(str::to_owned).collect())
+                })
+                .collect();
+
+            jobs.push(Job {
+                id: id.clone(),
+                name: string(job, "name"),
+                runs_on: string(job, "runs-on"),
+                matrix,
+                env: pairs(job.get("env")),
+                steps,
+                line: job.line,
+            });
+        }
+
+        Ok(Self {
+            name: string(&root, "name"),
+            env: pairs(root.get("env")),
+            jobs,
+        })
+    }
+}
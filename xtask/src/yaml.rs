@@ -0,0 +1,508 @@
+//! A parser for the subset of YAML used by GitHub Actions workflows.
+//!
+//! Supported: block mappings and sequences, plain and quoted scalars, flow
+//! sequences of scalars (`[a, b]`), literal and folded block scalars (`|` and
+//! `>`), and comments. Anchors, aliases, tags, flow mappings and multiple
+//! documents are not, since the template's workflow doesn't use them.
+//!
+//! Every node remembers the line it starts on, so callers can point at (or
+//! surgically rewrite) the exact line a value came from.
+
+use std::path::Path;
+
+use crate::{Error, Result};
+
+/// A parsed YAML node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// The (1-based) line the node starts on.
+    pub line: usize,
+    /// The node's value.
+    pub value: Value,
+}
+
+/// The value of a YAML [`Node`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// An empty value.
+    Null,
+    /// A scalar, with quotes removed.
+    Scalar(String),
+    /// A sequence.
+    Sequence(Vec<Node>),
+    /// A mapping, as key-value pairs in order.
+    Mapping(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// Looks up `key`, if this node is a mapping.
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match &self.value {
+            Value::Mapping(entries) => entries
+                .iter()
+                .find(|(entry, _)| entry == key)
+                .map(|(_, node)| node),
+            _ => None,
+        }
+    }
+
+    /// The node's text, if it is a scalar.
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            Value::Scalar(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The node's items, if it is a sequence (or an empty list otherwise).
+    pub fn items(&self) -> &[Self] {
+        match &self.value {
+            Value::Sequence(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// The node's entries, if it is a mapping (or an empty list otherwise).
+    pub fn entries(&self) -> &[(String, Self)] {
+        match &self.value {
+            Value::Mapping(entries) => entries,
+            _ => &[],
+        }
+    }
+}
+
+/// Parses a YAML document. `path` is only used for error messages.
+///
+/// # Errors
+///
+/// Returns an error if the document is not valid YAML (within the supported
+/// subset).
+pub fn parse(path: &Path, contents: &str) -> Result<Node> {
+    let lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, raw)| Line {
+            number: index + 1,
+            indent: raw.len() - raw.trim_start_matches(' ').len(),
+            text: strip_comment(raw).trim().to_owned(),
+            raw: raw.to_owned(),
+        })
+        .collect();
+
+    let mut parser = Parser {
+        path,
+        lines,
+        next: 0,
+    };
+    parser.skip_blank();
+    let Some(first) = parser.peek() else {
+        return Ok(Node {
+            line: 1,
+            value: Value::Null,
+        });
+    };
+    let indent = first.indent;
+    let node = parser.block(indent)?;
+
+    parser.skip_blank();
+    match parser.peek() {
+        Some(line) => Err(parser.error(line.number, "unexpected indentation")),
+        None => Ok(node),
+    }
+}
+
+/// One line of the document.
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    /// The number of leading spaces.
+    indent: usize,
+    /// The content, without indentation or comments.
+    text: String,
+    /// The line as written.
+    raw: String,
+}
+
+#[derive(Debug)]
+struct Parser<'a> {
+    path: &'a Path,
+    lines: Vec<Line>,
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, line: usize, message: &str) -> Error {
+        Error::malformed(self.path, format!("line {line}: {message}"))
+    }
+
+    fn peek(&self) -> Option<&Line> {
+        self.lines.get(self.next)
+    }
+
+    fn skip_blank(&mut self) {
+        while self.peek().is_some_and(|line| line.text.is_empty()) {
+            self.next += 1;
+        }
+    }
+
+    /// Parses the block starting at the next line, which is at `indent`.
+    fn block(&mut self, indent: usize) -> Result<Node> {
+        self.skip_blank();
+        let Some(line) = self.peek() else {
+            return Ok(Node {
+                line: self.lines.last().map_or(1, |line| line.number),
+                value: Value::Null,
+            });
+        };
+
+        if is_sequence_item(&line.text) {
+            self.sequence(indent)
+        } else if split_key(&line.text).is_some() {
+            self.mapping(indent)
+        } else {
+            let node = Node {
+                line: line.number,
+                value: scalar(&line.text),
+            };
+            self.next += 1;
+            Ok(node)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Node> {
+        let start = self.peek().map_or(1, |line| line.number);
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_blank();
+            let Some(line) = self.peek() else { break };
+            if line.indent != indent || !is_sequence_item(&line.text) {
+                break;
+            }
+
+            let number = line.number;
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() {
+                self.next += 1;
+                self.skip_blank();
+                match self.peek() {
+                    Some(child) if child.indent > indent => {
+                        let child_indent = child.indent;
+                        items.push(self.block(child_indent)?);
+                    }
+                    _ => items.push(Node {
+                        line: number,
+                        value: Value::Null,
+                    }),
+                }
+            } else {
+                // Treat the item's content as if it started its own line, at
+                // the column it is written at
+                let offset = line.text.len() - rest.len();
+                let rest = rest.to_owned();
+                let line = &mut self.lines[self.next];
+                line.indent += offset;
+                line.text = rest;
+                let child_indent = line.indent;
+                items.push(self.block(child_indent)?);
+            }
+        }
+
+        Ok(Node {
+            line: start,
+            value: Value::Sequence(items),
+        })
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Node> {
+        let start = self.peek().map_or(1, |line| line.number);
+        let mut entries: Vec<(String, Node)> = Vec::new();
+
+        loop {
+            self.skip_blank();
+            let Some(line) = self.peek() else { break };
+            if line.indent != indent || is_sequence_item(&line.text) {
+                if line.indent > indent {
+                    return Err(self.error(line.number, "unexpected indentation"));
+                }
+                break;
+            }
+            let Some((key, value)) = split_key(&line.text) else {
+                return Err(self.error(line.number, "expected `key: value`"));
+            };
+            let (key, value, number) = (unquote(key), value.to_owned(), line.number);
+            if entries.iter().any(|(existing, _)| *existing == key) {
+                return Err(self.error(number, &format!("duplicate key `{key}`")));
+            }
+            self.next += 1;
+
+            let node = match value.as_str() {
+                "" => {
+                    self.skip_blank();
+                    match self.peek() {
+                        Some(child) if child.indent > indent => {
+                            let child_indent = child.indent;
+                            self.block(child_indent)?
+                        }
+                        // Sequences may be indented at the same level as their key
+                        Some(child) if child.indent == indent && is_sequence_item(&child.text) => {
+                            self.sequence(indent)?
+                        }
+                        _ => Node {
+                            line: number,
+                            value: Value::Null,
+                        },
+                    }
+                }
+                "|" | "|-" | ">" | ">-" => self.block_scalar(indent, number, &value),
+                _ => Node {
+                    line: number,
+                    value: scalar(&value),
+                },
+            };
+            entries.push((key, node));
+        }
+
+        Ok(Node {
+            line: start,
+            value: Value::Mapping(entries),
+        })
+    }
+
+    /// Parses the lines of a `|` or `>` block scalar belonging to a key at
+    /// `indent`.
+    fn block_scalar(&mut self, indent: usize, line: usize, style: &str) -> Node {
+        let mut lines = Vec::new();
+        let mut content_indent = None;
+
+        while let Some(next) = self.peek() {
+            let blank = next.raw.trim().is_empty();
+            if !blank && next.indent <= indent {
+                break;
+            }
+            let content_indent = *content_indent.get_or_insert(next.indent);
+            lines.push(if blank {
+                String::new()
+            } else {
+                next.raw
+                    .get(content_indent..)
+                    .unwrap_or_default()
+                    .to_owned()
+            });
+            self.next += 1;
+        }
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+
+        let mut text = if style.starts_with('>') {
+            lines.join(" ")
+        } else {
+            lines.join("\n")
+        };
+        if !style.ends_with('-') {
+            text.push('\n');
+        }
+
+        Node {
+            line,
+            value: Value::Scalar(text),
+        }
+    }
+}
+
+/// Whether a line is a sequence item (`- ...`).
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: value` (or `key:`), outside of quotes.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if index == 0 && (c == '"' || c == '\'') => quote = Some(c),
+            None if c == ':' => {
+                let rest = &text[index + 1..];
+                if rest.is_empty() || rest.starts_with(' ') {
+                    return Some((text[..index].trim(), rest.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Parses a scalar (or flow sequence of scalars) written on one line.
+fn scalar(text: &str) -> Value {
+    if let Some(inner) = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+    {
+        let items = inner
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| Node {
+                line: 0,
+                value: Value::Scalar(unquote(item)),
+            })
+            .collect();
+        return Value::Sequence(items);
+    }
+
+    match text {
+        "" | "~" | "null" => Value::Null,
+        _ => Value::Scalar(unquote(text)),
+    }
+}
+
+/// Removes the quotes around a quoted scalar.
+fn unquote(text: &str) -> String {
+    if let Some(inner) = text
+        .strip_prefix('\'')
+        .and_then(|text| text.strip_suffix('\''))
+    {
+        return inner.replace("''", "'");
+    }
+    if let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    {
+        return inner.replace("\\\"", "\"").replace("\\\\", "\\");
+    }
+
+    text.to_owned()
+}
+
+/// Removes a trailing comment: a `#` at the start of the line or after
+/// whitespace, outside of quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    // Whether `previous` closed a single-quoted scalar, so that another `'`
+    // makes it the escaped quote `''` instead
+    let mut closed = false;
+    for (index, c) in line.char_indices() {
+        let closing = quote == Some(c);
+        match quote {
+            Some(_) if closing => quote = None,
+            None if c == '\'' && closed => quote = Some(c),
+            None if (c == '"' || c == '\'') && (previous == ' ' || previous == ':') => {
+                quote = Some(c);
+            }
+            None if c == '#' && previous.is_whitespace() => return &line[..index],
+            _ => {}
+        }
+        closed = closing && c == '\'';
+        previous = c;
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Node> {
+        super::parse(Path::new("test.yaml"), contents)
+    }
+
+    #[test]
+    fn workflow() {
+        let document = parse(
+            "# comment\n\
+             name: CI # trailing comment\n\
+             on: [push, 'pull_request']\n\
+             \n\
+             jobs:\n\
+             \x20 lint:\n\
+             \x20   runs-on: \"ubuntu-latest\"\n\
+             \x20   steps:\n\
+             \x20   - uses: actions/checkout@v4\n\
+             \x20   - name: 'It''s # not a comment'\n\
+             \x20     run: |\n\
+             \x20       cargo fmt\n\
+             \n\
+             \x20       cargo clippy\n\
+             \x20   - run: >-\n\
+             \x20       cargo\n\
+             \x20       test\n\
+             \x20 empty:\n\
+             \x20 nothing: ~\n",
+        )
+        .unwrap();
+
+        assert_eq!(document.get("name").unwrap().as_str(), Some("CI"));
+        let on: Vec<_> = document
+            .get("on")
+            .unwrap()
+            .items()
+            .iter()
+            .map(|item| item.as_str().unwrap())
+            .collect();
+        assert_eq!(on, ["push", "pull_request"]);
+
+        let jobs = document.get("jobs").unwrap();
+        assert_eq!(jobs.line, 6);
+        let lint = jobs.get("lint").unwrap();
+        assert_eq!(lint.get("runs-on").unwrap().as_str(), Some("ubuntu-latest"));
+        assert_eq!(lint.get("runs-on").unwrap().line, 7);
+
+        let steps = lint.get("steps").unwrap().items();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            steps[0].get("uses").unwrap().as_str(),
+            Some("actions/checkout@v4")
+        );
+        assert_eq!(
+            steps[1].get("name").unwrap().as_str(),
+            Some("It's # not a comment")
+        );
+        assert_eq!(
+            steps[1].get("run").unwrap().as_str(),
+            Some("cargo fmt\n\ncargo clippy\n")
+        );
+        assert_eq!(steps[1].get("run").unwrap().line, 11);
+        assert_eq!(steps[2].get("run").unwrap().as_str(), Some("cargo test"));
+
+        let names: Vec<_> = jobs.entries().iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["lint", "empty", "nothing"]);
+        assert_eq!(jobs.get("empty").unwrap().value, Value::Null);
+        assert_eq!(jobs.get("nothing").unwrap().value, Value::Null);
+    }
+
+    #[test]
+    fn sequences_at_the_key_indentation() {
+        let document = parse("list:\n- a\n- \"b: c\"\n-\nafter: 1\n").unwrap();
+        let items = document.get("list").unwrap().items();
+        assert_eq!(items[0].as_str(), Some("a"));
+        assert_eq!(items[1].as_str(), Some("b: c"));
+        assert_eq!(items[2].value, Value::Null);
+        assert_eq!(document.get("after").unwrap().as_str(), Some("1"));
+    }
+
+    #[test]
+    fn empty_document() {
+        assert_eq!(parse("# only a comment\n").unwrap().value, Value::Null);
+    }
+
+    #[test]
+    fn rejects_invalid_yaml() {
+        for (contents, message) in [
+            ("a: 1\na: 2\n", "line 2: duplicate key `a`"),
+            ("a: 1\n  b: 2\n", "line 2: unexpected indentation"),
+            ("a:\n  b: 1\n  c\n", "line 3: expected `key: value`"),
+        ] {
+            assert_eq!(
+                parse(contents).unwrap_err().to_string(),
+                format!("test.yaml: {message}"),
+                "{contents:?}"
+            );
+        }
+    }
+}